@@ -0,0 +1,15 @@
+//! Generates the gRPC service from `proto/beeeees.proto` when the `grpc` feature is enabled.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    {
+        // use a bundled protoc, so building with gRPC doesn't need anything else installed
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("no bundled protoc available");
+        std::env::set_var("PROTOC", protoc);
+        tonic_build::configure()
+            .build_client(false)
+            .compile(&["proto/beeeees.proto"], &["proto"])
+            .expect("could not compile proto/beeeees.proto");
+    }
+}
@@ -3,14 +3,61 @@ from __future__ import annotations
 import asyncio
 import json
 import platform
+import time
 from collections.abc import Iterable, Mapping
 from enum import Enum, auto
-from typing import Union, Iterator, Literal, Callable, NewType
+from typing import Union, Iterator, Literal, Callable, NewType, NamedTuple, Optional
 
 
 BeeID = NewType("BeeID", int)
 PlayerID = NewType("PlayerID", int)
-Direction = Union[Literal["North", "East", "South", "West"], None]
+Direction = Union[
+    Literal[
+        "North", "NorthEast", "East", "SouthEast", "South", "SouthWest", "West", "NorthWest"
+    ],
+    None,
+]
+
+
+class Handoff(NamedTuple):
+    """An action giving all of a bee's pollen to another bee on the same tile.
+
+    The other bee only takes as much pollen as it can carry.
+    """
+
+    to: BeeID
+
+
+class Dance(NamedTuple):
+    """An action performing a waggle dance at the hive.
+
+    Shows teammates where the bee last collected pollen.
+    """
+
+
+class Plan(NamedTuple):
+    """An action moving a bee in each of the given directions in turn, one per tile.
+
+    Each tick the bee takes as many steps as its speed,
+    and it keeps following the plan while it's left out of later moves.
+    """
+
+    directions: list[Direction]
+
+
+class MoveTo(NamedTuple):
+    """An action sending a bee along the cheapest path to `target`.
+
+    The server works out the path, and the bee follows it like a `Plan`.
+    """
+
+    target: Position
+    avoid_roads: bool = False
+
+
+Action = Union[Direction, Handoff, Dance, Plan, MoveTo]
+Upgrade = Literal["spawn_rate", "energy", "capacity", "speed"]
+Role = Literal["worker", "scout", "guard"]
 Position = tuple[int, int]
 
 
@@ -28,6 +75,9 @@ class Tile(Enum):
     Road = auto()
     Block = auto()
     SpawnPoint = auto()
+    Meadow = auto()
+    Water = auto()
+    Crossing = auto()
 
     def __repr__(self) -> str:
         return f"<{self.__class__.__name__}.{self.name}>"
@@ -36,6 +86,24 @@ class Tile(Enum):
         """Whether or not `Bee`s can pass over this tile."""
         return self != Tile.Block
 
+    def movement_cost(self) -> int:
+        """The energy a `Bee` uses to move onto this tile."""
+        if self == Tile.Garden:
+            return 0
+        if self == Tile.Neutral:
+            return 2
+        if self == Tile.Water:
+            return 3
+        return 1
+
+    def is_road(self) -> bool:
+        """Whether or not cars can drive on this tile."""
+        return self in (Tile.Road, Tile.Crossing)
+
+    def pollen_yield(self) -> int:
+        """The pollen a `Bee` gains for each unit collected from a flower on this tile."""
+        return 2 if self == Tile.Meadow else 1
+
 
 class World(Mapping[Position, Tile]):
     """The world map, a mapping from `Position` to `Tile`.
@@ -43,6 +111,10 @@ class World(Mapping[Position, Tile]):
     Attributes:
     - `width`: the width of the map.
     - `height`: the height of the map.
+    - `grid`: the shape of the tiles, either "square" or "hex".
+    - `wrap_edges`: whether moving off one edge arrives on the opposite edge.
+    - `lanes`: a dictionary from the `Position` of road tiles with a lane
+      to the only `Direction` cars drive in from there.
     - `[x, y]`: the `Tile` at position `(x, y)`.
     """
 
@@ -50,7 +122,22 @@ class World(Mapping[Position, Tile]):
         """Initialize the world from the given dictionary."""
         self.width = int(data["width"])
         self.height = int(data["height"])
-        self._tiles = list(Tile[t] for t in data["map"])
+        self.grid: Literal["square", "hex"] = data.get("grid", "square")
+        self.wrap_edges = bool(data.get("wrap_edges", False))
+        self.lanes: dict[Position, Direction] = {
+            make_position(lane): lane["direction"] for lane in data.get("lanes", [])
+        }
+        if data["map"] is None:
+            # large maps are sent separately in chunks
+            self._tiles = [Tile.Block] * (self.width * self.height)
+        else:
+            self._tiles = list(Tile[t] for t in data["map"])
+
+    def add_chunk(self, data) -> None:
+        """Fill in part of the map from a "chunk" message."""
+        (x, y, width) = (int(data["x"]), int(data["y"]), int(data["width"]))
+        for (i, t) in enumerate(data["map"]):
+            self._tiles[x + i % width + (y + i // width) * self.width] = Tile[t]
 
     def __getitem__(self, key: Position) -> Tile:
         """Get the `Tile` at the given `Position`."""
@@ -78,6 +165,19 @@ class Bee(object):
     - `player`: Who owns this bee
     - `energy`: The remaining energy for the bee. Dies when reaches 0.
     - `pollen`: The amount of pollen held by this bee.
+    - `nectar`: The amount of nectar held by this bee, if playing with honey.
+    - `max_energy`: The most energy the bee can have.
+    - `max_pollen`: The most pollen the bee can carry, or `None` if unlimited.
+    - `speed`: How many tiles the bee flies each time it moves.
+    - `age`: The number of ticks since the bee spawned.
+    - `max_age`: The age at which the bee dies, or `None` if unlimited.
+    - `stage`: "larva" while the bee is growing up in its hive and can't move, then "forager".
+    - `ticks_away`: The number of ticks since the bee last rested at a hive.
+    - `power_up`: The kind of item the bee's current power-up came from, or `None`.
+    - `power_up_ticks`: How many more ticks the bee's power-up lasts.
+    - `queen`: Whether the bee is escorting a new queen to one of its player's hives.
+    - `role`: The bee's `Role`: workers carry more, scouts see farther,
+      and guards count as three bees when chasing away birds.
     - `position`: a `Position` with the bee's current location in the world.
     """
 
@@ -87,6 +187,19 @@ class Bee(object):
         self.player = PlayerID(bee["player"])
         self.energy = int(bee["energy"])
         self.pollen = int(bee["pollen"])
+        self.nectar = int(bee.get("nectar", 0))
+        self.max_energy = int(bee["max_energy"])
+        self.max_pollen = bee.get("max_pollen")
+        self.speed = int(bee.get("speed", 1))
+        self.age = int(bee.get("age", 0))
+        self.max_age = bee.get("max_age")
+        self.stage: Literal["larva", "forager"] = bee.get("stage", "forager")
+        self.ticks_away = int(bee.get("ticks_away", 0))
+        power_up = bee.get("power_up") or {}
+        self.power_up = power_up.get("kind")
+        self.power_up_ticks = int(power_up.get("ticks", 0))
+        self.queen = bool(bee["queen"])
+        self.role = bee["role"]
         self.position = make_position(bee["position"])
 
 
@@ -94,14 +207,23 @@ class Flower(object):
     """A flower that makes pollen.
 
     Attributes:
+    - `species`: The kind of flower: "wildflower", "clover", "rose", or "sunflower".
+      Every flower is a wildflower unless the server is configured with species.
     - `pollen`: The amount of pollen that this flower can still make.
+    - `max_pollen`: The amount of pollen the flower regrows up to.
+    - `nectar`: The amount of nectar that this flower can still make.
+    - `age`: The number of turns since the flower spawned.
     - `is_pollinated`: Whether or not the flower's been pollinated yet.
     - `position`: a `Position` with the flower's location in the world.
     """
 
     def __init__(self, flower):
         """Initialize the flower from the given dictionary."""
+        self.species: Literal["wildflower", "clover", "rose", "sunflower"] = flower["species"]
         self.pollen = int(flower["pollen"])
+        self.max_pollen = int(flower["max_pollen"])
+        self.nectar = int(flower.get("nectar", 0))
+        self.age = int(flower["age"])
         self.is_pollinated = bool(flower["is_pollinated"])
         self.position = make_position(flower["position"])
 
@@ -112,12 +234,88 @@ class Hive(object):
     Attributes:
     - `player`: The player that this hive belongs to
     - `position`: a `Position` with the hive's location in the world.
+    - `color`: the hex colour chosen by the player, or `None`.
+    - `emoji`: the emoji chosen by the player, or `None`.
+    - `team`: the name of the player's team, or `None`.
+    - `destination`: where the hive is being relocated to, or `None`.
+      Bees can't deliver pollen to a hive while it's being relocated.
+    - `upgrades`: a dictionary from each `Upgrade` to the level bought for this hive.
+    - `queen`: whether the hive has a queen. Hives without a queen don't spawn bees;
+      one of the player's bees must escort a new queen from an unused spawn point.
+    - `nectar`: how much nectar is waiting to be made into honey, if playing with honey.
     """
 
     def __init__(self, hive):
         """Initialize the hive from the given dictionary."""
         self.player = PlayerID(hive["player"])
         self.position = make_position(hive["position"])
+        self.color = hive.get("color")
+        self.emoji = hive.get("emoji")
+        self.team = hive.get("team")
+        transit = hive.get("transit")
+        self.destination = make_position(transit["destination"]) if transit else None
+        self.upgrades = dict(hive["upgrades"])
+        self.queen = bool(hive["queen"])
+        self.nectar = int(hive.get("nectar", 0))
+
+
+class Car(object):
+    """A car driving along the roads, which kills any bees it drives over.
+
+    Attributes:
+    - `position`: a `Position` with the car's location in the world.
+    - `facing`: the `Direction` the car last drove in.
+    """
+
+    def __init__(self, car):
+        """Initialize the car from the given dictionary."""
+        self.position = make_position(car["position"])
+        self.facing: Direction = car["facing"]
+
+
+class Nest(object):
+    """A wasp nest, which sends out wasps to steal pollen from hives.
+
+    Attributes:
+    - `position`: a `Position` with the nest's location in the world.
+    - `health`: how much more damage the nest can take before it's destroyed.
+      Each bee on the nest does one damage each turn, and each guard three.
+    """
+
+    def __init__(self, nest):
+        """Initialize the nest from the given dictionary."""
+        self.position = make_position(nest["position"])
+        self.health = int(nest["health"])
+
+
+class Wasp(object):
+    """A wasp out raiding hives.
+
+    Attributes:
+    - `position`: a `Position` with the wasp's location in the world.
+    - `nest`: the `Position` of the nest the wasp returns to.
+    - `pollen`: how much pollen the wasp has stolen.
+    """
+
+    def __init__(self, wasp):
+        """Initialize the wasp from the given dictionary."""
+        self.position = make_position(wasp["position"])
+        self.nest = make_position(wasp["nest"])
+        self.pollen = int(wasp["pollen"])
+
+
+class Item(object):
+    """A power-up item lying on the map.
+
+    Attributes:
+    - `kind`: "energy_drink", "pollen_magnet", or "bird_repellent".
+    - `position`: a `Position` with the item's location in the world.
+    """
+
+    def __init__(self, item):
+        """Initialize the item from the given dictionary."""
+        self.kind = item["kind"]
+        self.position = make_position(item["position"])
 
 
 class Entities(object):
@@ -127,17 +325,39 @@ class Entities(object):
     - `bees`: A list of living `Bee`s.
     - `flowers`: A list of living `Flower`s.
     - `hives`: A list of player `Hive`s.
+    - `cars`: A list of `Car`s driving around the roads.
+    - `items`: A list of power-up `Item`s waiting to be collected.
+    - `nests`: A list of wasp `Nest`s.
+    - `wasps`: A list of `Wasp`s out raiding hives.
+    - `dances`: The positions of flowers shown by our or our teammates' waggle dances
+      on the last turn.
+    - `weather`: The weather for the next turn: "sunny", "rain", or "wind".
+    - `wind`: The direction the wind is blowing, or `None` if it isn't windy.
     """
 
     bees: list[Bee]
     flowers: list[Flower]
     hives: list[Hive]
+    cars: list[Car]
+    items: list[Item]
+    nests: list[Nest]
+    wasps: list[Wasp]
+    dances: list[Position]
+    weather: Literal["sunny", "rain", "wind"]
+    wind: Direction
 
     def __init__(self, data):
         """Initialize the entity collection from the given dictionary."""
         self.bees = [Bee(b) for b in data["bees"]]
         self.flowers = [Flower(f) for f in data["flowers"]]
         self.hives = [Hive(h) for h in data["hives"]]
+        self.cars = [Car(c) for c in data["cars"]]
+        self.items = [Item(i) for i in data.get("items", [])]
+        self.nests = [Nest(n) for n in data.get("nests", [])]
+        self.wasps = [Wasp(w) for w in data.get("wasps", [])]
+        self.dances = [make_position(d["position"]) for d in data["dances"]]
+        self.weather = data["weather"]["kind"]
+        self.wind = data["weather"].get("direction")
 
     def bees_for(self, player: PlayerID) -> Iterable[Bee]:
         """Get an iterable of bees, filtered for just the given player."""
@@ -146,9 +366,15 @@ class Entities(object):
                 yield bee
 
     def hive_for(self, player: PlayerID) -> Hive:
-        """Get the hive (spawn point) for the given player."""
+        """Get the first hive (spawn point) for the given player."""
         return next(h for h in self.hives if h.player == player)
 
+    def hives_for(self, player: PlayerID) -> Iterable[Hive]:
+        """Get an iterable of every hive owned by the given player."""
+        for hive in self.hives:
+            if hive.player == player:
+                yield hive
+
 
 class Error(Exception):
     """An error within the `beeees` module.
@@ -221,10 +447,24 @@ class Connection(object):
         return line.decode("utf-8")
 
 
-Moves = dict[BeeID, Direction]
+Moves = dict[BeeID, Action]
 StepFunc = Callable[[PlayerID, World, Entities], Moves]
 
 
+def make_move(bee: BeeID, action: Action) -> dict:
+    """Convert an action for `bee` into the format expected by the server."""
+    if isinstance(action, Handoff):
+        return {"bee": bee, "handoff": action.to}
+    if isinstance(action, Dance):
+        return {"bee": bee, "dance": True}
+    if isinstance(action, Plan):
+        return {"bee": bee, "plan": action.directions}
+    if isinstance(action, MoveTo):
+        (x, y) = action.target
+        return {"bee": bee, "target": {"x": x, "y": y}, "avoid_roads": action.avoid_roads}
+    return {"bee": bee, "direction": action}
+
+
 class Client(object):
     """Encapsulates a client program in the game.
 
@@ -232,24 +472,56 @@ class Client(object):
     - `conn`: The `Connection` to the server.
     - `id`: The client's player ID.
     - `world`: The (immutable) game world.
+    - `tick`: The number of the next tick the server will process, or `None` if not yet known.
+    - `deadline`: When the server will next process a tick, as a `time.monotonic()` value,
+      or `None` if not yet known.
     """
 
     conn: Connection
     id: PlayerID
     world: World
+    tick: Optional[int]
+    deadline: Optional[float]
 
     @classmethod
-    async def register(cls, conn: Connection, name: str) -> Client:
+    async def register(
+        cls,
+        conn: Connection,
+        name: str,
+        color: Optional[str] = None,
+        emoji: Optional[str] = None,
+        team: Optional[str] = None,
+        password: Optional[str] = None,
+        invite: Optional[str] = None,
+        token: Optional[str] = None,
+    ) -> Client:
         """Create a new client.
 
         Registers to the server over the provided connection `conn`.
         Specifies the player name as `name`;
         this can be used to reconnect to an existing session later on.
+        The hive may optionally be displayed with a hex `color` and an `emoji`,
+        and the player may ask to join a `team`.
+        Games with a password can only be joined by giving the right `password`,
+        and invite-only games by giving an `invite` code.
+        Servers that require API tokens can only be joined by giving a `token`.
         """
         self = cls()
         self.conn = conn
-
-        await self.conn.write_json({"type": "register", "name": name})
+        self.tick = None
+        self.deadline = None
+
+        data = {
+            "type": "register",
+            "name": name,
+            "team": team,
+            "color": color,
+            "emoji": emoji,
+            "password": password,
+            "invite": invite,
+            "token": token,
+        }
+        await self.conn.write_json(data)
         msg = await self.conn.read()
         packet = json.loads(msg)
         if packet["type"] == "done":
@@ -264,6 +536,79 @@ class Client(object):
 
         return self
 
+    async def chat(self, msg: str) -> None:
+        """Send a chat message to everyone in the game."""
+        await self.conn.write_json({"type": "chat", "msg": msg})
+
+    async def transfer(self, to: PlayerID, amount: int) -> None:
+        """Donate `amount` pollen from our hives to the player `to`.
+
+        Bees from both players must be together at one of their hives.
+        """
+        await self.conn.write_json({"type": "transfer", "to": to, "amount": amount})
+
+    async def build_hive(self, bee: BeeID) -> None:
+        """Spend pollen to build a new hive where the bee `bee` is.
+
+        The bee must be on an unused spawn point,
+        and the server must allow building hives.
+        """
+        await self.conn.write_json({"type": "build_hive", "bee": bee})
+
+    async def relocate_hive(self, hive: Position, to: Position) -> None:
+        """Spend pollen to move our hive at `hive` to the spawn point `to`.
+
+        The hive can't be used for several turns while it moves.
+        """
+        data = {
+            "type": "relocate_hive",
+            "hive": {"x": hive[0], "y": hive[1]},
+            "to": {"x": to[0], "y": to[1]},
+        }
+        await self.conn.write_json(data)
+
+    async def upgrade_hive(self, hive: Position, upgrade: Upgrade) -> None:
+        """Spend pollen to buy an `upgrade` for our hive at `hive`.
+
+        Only bees spawned by the hive after the upgrade benefit from it.
+        """
+        data = {
+            "type": "upgrade_hive",
+            "hive": {"x": hive[0], "y": hive[1]},
+            "upgrade": upgrade,
+        }
+        await self.conn.write_json(data)
+
+    async def assign_role(self, bee: BeeID, role: Role) -> None:
+        """Change the role of the bee `bee`.
+
+        The bee must be on one of our hives.
+        """
+        await self.conn.write_json({"type": "assign_role", "bee": bee, "role": role})
+
+    async def set_viewport(self, x: int, y: int, width: int, height: int) -> None:
+        """Only receive updates about the `width` by `height` area from `(x, y)`.
+
+        Our own bees and hives are always included.
+        """
+        rect = {"x": x, "y": y, "width": width, "height": height}
+        await self.conn.write_json({"type": "set_viewport", "rect": rect})
+
+    async def clear_viewport(self) -> None:
+        """Go back to receiving updates about the whole world."""
+        await self.conn.write_json({"type": "set_viewport", "rect": None})
+
+    async def rename(self, name: str) -> None:
+        """Change the name this client is registered under."""
+        await self.conn.write_json({"type": "rename", "name": name})
+
+    async def query_players(self) -> None:
+        """Request the list of players in the game.
+
+        The response is printed when it arrives.
+        """
+        await self.conn.write_json({"type": "query", "what": "players"})
+
     async def run(self, step: StepFunc) -> None:
         """Run the game.
 
@@ -277,6 +622,48 @@ class Client(object):
             if packet["type"] == "done":
                 print("Received finish signal")
                 return
+            elif packet["type"] == "registration":
+                print("Starting new game")
+                self.id = PlayerID(packet["player"])
+                self.world = World(packet["world"])
+                self.tick = None
+            elif packet["type"] == "chunk":
+                self.world.add_chunk(packet)
+            elif packet["type"] == "event":
+                print(f"Event: {packet['kind']}")
+            elif packet["type"] == "achievement":
+                print(f"Player {packet['player']} unlocked {packet['achievement']}")
+            elif packet["type"] == "chat":
+                print(f"[{packet['channel']}] {packet['name']}: {packet['msg']}")
+            elif packet["type"] == "transfer":
+                print(f"Player {packet['from']} gave {packet['amount']} pollen to {packet['to']}")
+            elif packet["type"] == "rename":
+                print(f"Player {packet['player']} is now called {packet['name']}")
+            elif packet["type"] == "removed":
+                print(f"Player {packet['player']} left with score {packet['score']}")
+            elif packet["type"] == "roster":
+                for p in packet["players"]:
+                    status = "connected" if p["connected"] else "disconnected"
+                    latency = "" if p["latency_ms"] is None else f", {p['latency_ms']}ms"
+                    print(
+                        f"Player {p['player']} ({p['name']}): {p['score']}, {status}{latency}"
+                    )
+            elif packet["type"] == "tick_rate":
+                print(f"Tick rate is now {packet['tick_rate']} seconds")
+            elif packet["type"] == "tick_schedule":
+                self.tick = packet["tick"]
+                self.deadline = time.monotonic() + packet["next_tick_in"]
+            elif packet["type"] == "ping":
+                await self.conn.write_json({"type": "pong", "id": packet["id"]})
+            elif packet["type"] == "ack":
+                if packet["accepted"] < packet["received"]:
+                    print(f"Only {packet['accepted']} of {packet['received']} moves were accepted")
+            elif packet["type"] == "blocked":
+                print(f"Bees {packet['bees']} were blocked by full tiles")
+            elif packet["type"] == "summary":
+                print(f"Finished with score {packet['score']}, rank {packet['rank']}")
+                if packet["team"] is not None:
+                    print(f"Team {packet['team']} scored {packet['team_score']}")
             elif packet["type"] == "warning":
                 print("Received warning:", packet["msg"])
             elif packet["type"] == "error":
@@ -287,21 +674,43 @@ class Client(object):
                 moves = step(self.id, self.world, entities)
                 data = {
                     "type": "moves",
-                    "moves": list(
-                        {"bee": k, "direction": v} for (k, v) in moves.items()
-                    ),
+                    "moves": list(make_move(k, v) for (k, v) in moves.items()),
+                    "tick": self.tick,
                 }
+                # send bees moving the same way as a group
+                groups: dict[str, list[BeeID]] = {}
+                for move in data["moves"]:
+                    if set(move) == {"bee", "direction"} and move["direction"] is not None:
+                        groups.setdefault(move["direction"], []).append(move["bee"])
+                data["moves"] = [m for m in data["moves"] if m.get("direction") not in groups]
+                data["groups"] = [{"bees": b, "direction": d} for (d, b) in groups.items()]
                 await self.conn.write_json(data)
             else:
                 raise Error(f"unknown message: {msg}")
 
 
-def play(name: str, host: str, port: Union[int, str], step: StepFunc) -> None:
+def play(
+    name: str,
+    host: str,
+    port: Union[int, str],
+    step: StepFunc,
+    color: Optional[str] = None,
+    emoji: Optional[str] = None,
+    team: Optional[str] = None,
+    password: Optional[str] = None,
+    invite: Optional[str] = None,
+    token: Optional[str] = None,
+) -> None:
     """Play the game as a new (or returning) client.
 
     Connects to the server with given `host` and `port`.
     Registers as the player identified by `name`:
     This is used to reconnect to an existing session if dropping out early.
+    The player's hive may optionally be displayed with a hex `color` and an `emoji`,
+    and the player may ask to join a `team`.
+    Games with a password can only be joined by giving the right `password`,
+    and invite-only games by giving an `invite` code.
+    Servers that require API tokens can only be joined by giving a `token`.
 
     The bulk of the work is handled by the provided `step` function.
     Each "round" the function will be provided with
@@ -310,8 +719,15 @@ def play(name: str, host: str, port: Union[int, str], step: StepFunc) -> None:
     by all bees owned by their player ID.
 
     The movements are specified by the four cardinal directions:
-    "North", "South", "East", or "West".
+    "North", "South", "East", or "West";
+    or by the diagonals "NorthEast", "SouthEast", "SouthWest", or "NorthWest".
     The return should be a dictionary from a given `BeeID` to a direction.
+    Instead of a direction, a bee can be given a `Handoff(other_bee)`
+    to give all of its pollen to another of the player's bees on the same tile,
+    or a `Dance()` to show teammates where the bee last collected pollen.
+    A `Plan([...])` gives a list of directions to follow over the next few turns;
+    leave the bee out of later moves to let it continue following the plan.
+    Similarly, `MoveTo((x, y))` has the server plan the cheapest route to a position.
     For example, a step function that always moves south might look like:
 
     ```python
@@ -327,7 +743,9 @@ def play(name: str, host: str, port: Union[int, str], step: StepFunc) -> None:
     async def main() -> None:
         try:
             conn = await Connection.create(host, port)
-            client = await Client.register(conn, name)
+            client = await Client.register(
+                conn, name, color, emoji, team, password, invite, token
+            )
             await client.run(step)
         except Error as e:
             print(f"Fatal error: {e.message}")
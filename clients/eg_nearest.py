@@ -16,15 +16,14 @@ def step_to_point(start: Position, end: Position) -> tuple[int, Direction]:
     """
     xdist = abs(end[0] - start[0])
     ydist = abs(end[1] - start[1])
-    total = xdist + ydist
-    if start[0] > end[0]:
-        return (total, "West")
-    elif start[0] < end[0]:
-        return (total, "East")
-    elif start[1] > end[1]:
-        return (total, "South")
-    elif start[1] < end[1]:
-        return (total, "North")
+    total = max(xdist, ydist)
+    vertical = "South" if start[1] > end[1] else "North" if start[1] < end[1] else ""
+    horizontal = "West" if start[0] > end[0] else "East" if start[0] < end[0] else ""
+    if vertical and horizontal:
+        # move diagonally, e.g. "NorthEast"
+        return (total, vertical + horizontal)
+    elif vertical or horizontal:
+        return (total, vertical or horizontal)
     else:
         # we must already be there
         return (total, None)
@@ -0,0 +1,223 @@
+//! A client for playing or watching the game over TCP, for writing bots in Rust.
+//!
+//! The [`Client`] takes care of the framing and the JSON,
+//! so bots only deal with the typed messages in [`ServerMessage`] and [`ClientMessage`].
+//! It also replies to the server's pings, and gathers large maps sent in chunks.
+//!
+//! The messages are made of the same types the game is played with,
+//! which are re-exported here for convenience.
+//!
+//! ```no_run
+//! use beeeees::client::{Client, Direction, Move, Register, ServerMessage};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let mut client = Client::connect("127.0.0.1:49998").await?;
+//! let registration = client.register(Register::new("buzz")).await?;
+//! while let Some(message) = client.recv().await? {
+//!     if let ServerMessage::Update { data } = message {
+//!         let moves = (data.bees.iter())
+//!             .filter(|bee| bee.player == registration.player)
+//!             .map(|bee| Move::new(bee.id, Direction::North))
+//!             .collect();
+//!         client.send_moves(moves, None).await?;
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod protocol;
+
+use std::collections::VecDeque;
+
+use anyhow::{bail, Context, Result};
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio_util::codec::{Decoder, Framed, LinesCodec};
+
+pub use self::protocol::*;
+pub use crate::game::{
+    achievements::Achievement,
+    events::Event,
+    world::{Chunk, Direction, Position, Rect, Tile, World},
+    Bee, BeeID, GroupMove, Hive, Move, Player, Role, Snapshot, Summary, Upgrade,
+};
+
+/// A connection to the server.
+#[derive(Debug)]
+pub struct Client {
+    /// The connection, one JSON message per line.
+    socket: Framed<TcpStream, LinesCodec>,
+    /// Messages that arrived while waiting for something else,
+    /// to be returned by [`Client::recv`] before any new messages.
+    pending: VecDeque<ServerMessage>,
+}
+
+impl Client {
+    /// Connect to the server's TCP port at `addr`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the server couldn't be connected to.
+    pub async fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+        let socket = TcpStream::connect(addr)
+            .await
+            .context("Could not connect to the server")?;
+        Ok(Client {
+            socket: LinesCodec::new().framed(socket),
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Register with the server, and wait for it to accept the registration.
+    ///
+    /// Register with an empty name to watch the game as an observer.
+    /// Any other messages that arrive first are kept to be returned by [`Client::recv`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the server refuses the registration, closes the connection,
+    /// or sends a message that can't be understood.
+    pub async fn register(&mut self, register: Register) -> Result<Registration> {
+        self.send(&ClientMessage::Register(register)).await?;
+        let mut skipped = VecDeque::new();
+        let result = loop {
+            match self.recv().await {
+                Ok(Some(ServerMessage::Registration(registration))) => break Ok(registration),
+                Ok(Some(ServerMessage::Error { msg, .. })) => {
+                    break Err(anyhow::anyhow!("The server refused to register: {}", msg))
+                }
+                Ok(Some(message)) => skipped.push_back(message),
+                Ok(None) => break Err(anyhow::anyhow!("The server closed the connection")),
+                Err(e) => break Err(e),
+            }
+        };
+        skipped.append(&mut self.pending);
+        self.pending = skipped;
+        result
+    }
+
+    /// Receive the next message from the server,
+    /// or `None` if the server has closed the connection.
+    ///
+    /// Pings are replied to as soon as they arrive, before they are returned.
+    /// When a map is sent in chunks, the chunks are gathered into the world of its registration,
+    /// which is only returned once the whole map has arrived.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the connection is lost, or the server sends a message that can't be understood.
+    pub async fn recv(&mut self) -> Result<Option<ServerMessage>> {
+        if let Some(message) = self.pending.pop_front() {
+            return Ok(Some(message));
+        }
+        let mut message = match self.read().await? {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+        // large maps are sent without their tiles, which follow in chunks
+        if message["type"] == "registration" && message["world"]["map"].is_null() {
+            if let Some(world) = message.get_mut("world") {
+                self.gather_map(world).await?;
+            }
+        }
+        parse(&message).map(Some)
+    }
+
+    /// Send a `message` to the server.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the connection is lost.
+    pub async fn send(&mut self, message: &ClientMessage) -> Result<()> {
+        let line = serde_json::to_string(message)?;
+        self.socket.send(line).await?;
+        Ok(())
+    }
+
+    /// Send a set of `moves` for the player's bees,
+    /// to be made on the given `tick` or on the next tick if `None`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the connection is lost.
+    pub async fn send_moves(&mut self, moves: Vec<Move>, tick: Option<u64>) -> Result<()> {
+        let groups = Vec::new();
+        self.send(&ClientMessage::Moves {
+            moves,
+            groups,
+            tick,
+        })
+        .await
+    }
+
+    /// Fill in the tiles of a `world` sent without them, from the chunks that follow it.
+    ///
+    /// Any other messages that arrive in the meantime are kept to be returned by [`Client::recv`].
+    async fn gather_map(&mut self, world: &mut Value) -> Result<()> {
+        let size = |field| {
+            (world.get(field).and_then(Value::as_i64))
+                .with_context(|| format!("The server sent a world without a {}", field))
+        };
+        let (width, height) = (size("width")?, size("height")?);
+        let mut map = vec![None; (width.max(0) * height.max(0)) as usize];
+        let mut missing = map.len();
+        while missing > 0 {
+            let message = match self.read().await? {
+                Some(message) => parse(&message)?,
+                None => bail!("The server closed the connection before sending the map"),
+            };
+            let chunk = match message {
+                ServerMessage::Chunk(chunk) => chunk,
+                message => {
+                    self.pending.push_back(message);
+                    continue;
+                }
+            };
+            for (position, tile) in chunk.tiles() {
+                let (x, y) = (i64::from(position.x), i64::from(position.y));
+                let index = ((0..width).contains(&x) && (0..height).contains(&y))
+                    .then(|| (y * width + x) as usize);
+                match index.and_then(|index| map.get_mut(index)) {
+                    Some(slot @ None) => *slot = Some(tile),
+                    _ => bail!(
+                        "The server sent an unexpected part of the map at {:?}",
+                        position
+                    ),
+                }
+                missing -= 1;
+            }
+        }
+        world["map"] = serde_json::to_value(map)?;
+        Ok(())
+    }
+
+    /// Read the next message from the connection, exactly as the server sent it.
+    ///
+    /// Pings are replied to as soon as they arrive,
+    /// even if they're kept in [`Client::pending`] to be returned later.
+    async fn read(&mut self) -> Result<Option<Value>> {
+        match self.socket.next().await {
+            Some(line) => {
+                let line = line.context("Could not read from the server")?;
+                let message: Value = serde_json::from_str(&line)
+                    .with_context(|| format!("Could not understand the server: {}", line))?;
+                if message["type"] == "ping" {
+                    if let ServerMessage::Ping { id } = parse(&message)? {
+                        self.send(&ClientMessage::Pong { id }).await?;
+                    }
+                }
+                Ok(Some(message))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Turn a `message` read from the connection into the message it represents.
+fn parse(message: &Value) -> Result<ServerMessage> {
+    ServerMessage::deserialize(message)
+        .with_context(|| format!("Could not understand the server: {}", message))
+}
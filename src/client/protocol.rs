@@ -0,0 +1,353 @@
+//! The messages sent between clients and the server.
+//!
+//! The game's own types are used wherever the server sends them;
+//! only the messages themselves, and the parts of them that come from the server rather than the game,
+//! are defined here. See `protocol.md` for when each message is sent.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::game::{
+    achievements::Achievement,
+    events::Event,
+    world::{Chunk, Position, Rect, World},
+    BeeID, GroupMove, Move, Player, Role, Snapshot, Summary, Upgrade,
+};
+
+/// Deserialize a duration from a single [`f64`] representing the number of seconds.
+fn deserialize_duration_from_f64<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let seconds = f64::deserialize(deserializer)?;
+    Duration::try_from_secs_f64(seconds).map_err(serde::de::Error::custom)
+}
+
+/// Messages sent from the server.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// Sent on initial handshake, and whenever a new game starts,
+    /// with any information about the game that won't change.
+    Registration(Registration),
+    /// Part of a large world map, sent following a [`ServerMessage::Registration`].
+    ///
+    /// [`Client::recv`](super::Client::recv) gathers these into the registration's world,
+    /// so never returns them.
+    Chunk(Chunk),
+    /// Sent every tick with the current game state.
+    Update {
+        /// The mutable game data.
+        data: Snapshot,
+    },
+    /// Sent when the server changes the tick rate of the current game.
+    TickRate {
+        /// The new expected tick rate of the server.
+        #[serde(deserialize_with = "deserialize_duration_from_f64")]
+        tick_rate: Duration,
+    },
+    /// Sent just before each [`ServerMessage::Update`],
+    /// saying when the server will next advance the game.
+    TickSchedule {
+        /// The number of the next tick, which moves sent now will apply to.
+        tick: u64,
+        /// How long until the next tick is processed.
+        #[serde(deserialize_with = "deserialize_duration_from_f64")]
+        next_tick_in: Duration,
+    },
+    /// A chat message sent by another client.
+    Chat {
+        /// The player that sent the message.
+        player: Player,
+        /// The name of the client that sent the message.
+        name: String,
+        /// The contents of the message.
+        msg: String,
+        /// Which channel the message was sent on.
+        channel: ChatChannel,
+    },
+    /// Sent in response to a [`Query::Players`],
+    /// listing every player taking part in the current game.
+    Roster {
+        /// Information about each player.
+        players: Vec<RosterEntry>,
+    },
+    /// Sent when a player donates pollen to another player.
+    Transfer {
+        /// The player that donated the pollen.
+        from: Player,
+        /// The player that received the pollen.
+        to: Player,
+        /// The amount of pollen transferred.
+        amount: i32,
+    },
+    /// Sent when a player changes their name.
+    Rename {
+        /// The player that changed their name.
+        player: Player,
+        /// The player's new name.
+        name: String,
+    },
+    /// Sent when a player is removed from the game after staying disconnected for too long.
+    Removed {
+        /// The player that was removed.
+        player: Player,
+        /// The player's score when they were removed.
+        score: i32,
+    },
+    /// Sent when the player unlocks an achievement.
+    Achievement {
+        /// The player that unlocked the achievement.
+        player: Player,
+        /// The achievement that was unlocked.
+        achievement: Achievement,
+    },
+    /// Sent when a random event happens in the world.
+    Event(Event),
+    /// Sent in reply to each [`ClientMessage::Moves`].
+    Ack {
+        /// The tick that the moves will apply to.
+        tick: u64,
+        /// The number of moves that were accepted.
+        accepted: usize,
+        /// The number of moves in the message, including those in groups.
+        received: usize,
+    },
+    /// Sent when some of the player's bees couldn't move because the tile was full.
+    Blocked {
+        /// The bees that stayed where they were.
+        bees: Vec<BeeID>,
+    },
+    /// Sent after each tick in which the player's score changed.
+    Score {
+        /// The player's total score over all of their hives.
+        score: i32,
+    },
+    /// Sent to players when the game finishes, summarising their performance.
+    Summary(Summary),
+    /// Sent when an ignorable issue has occurred.
+    Warning {
+        /// A human-readable description of the issue.
+        msg: String,
+    },
+    /// Sent as the last message before the server closes the connection because of an error.
+    Error {
+        /// A human-readable description of the error.
+        msg: String,
+        /// What went wrong, for errors that clients may want to handle specially.
+        #[serde(default)]
+        code: Option<ErrorCode>,
+    },
+    /// Sent to players every so often to measure their latency.
+    ///
+    /// [`Client::recv`](super::Client::recv) replies to these automatically.
+    Ping {
+        /// Identifies the ping, so that the reply can be matched up with it.
+        id: u64,
+    },
+    /// Sent as the last message before the server shuts down.
+    Done,
+    /// A message that this version of the client doesn't know about.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Information about a game that won't change while it's played.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Registration {
+    /// The world map.
+    pub world: World,
+    /// The client's player ID, or the observer ID for observers.
+    pub player: Player,
+    /// The expected time between ticks.
+    #[serde(deserialize_with = "deserialize_duration_from_f64")]
+    pub tick_rate: Duration,
+    /// The player's statistics from previously finished games, if any.
+    pub profile: Option<Profile>,
+}
+
+/// A player's statistics from previously finished games.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Profile {
+    /// The number of finished games the player has participated in.
+    pub games_played: usize,
+    /// The number of games where the player had the (possibly shared) highest score.
+    pub wins: usize,
+    /// The sum of the player's scores across all games.
+    pub total_score: i64,
+    /// The player's highest score in a single game.
+    pub best_score: i32,
+    /// The total amount of pollen the player has delivered across all games.
+    pub total_pollen: i64,
+    /// The player's current ELO-style rating.
+    pub rating: f64,
+}
+
+/// Machine-readable reasons for a [`ServerMessage::Error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The game requires a password, and the player gave the wrong one.
+    WrongPassword,
+    /// The game is invite-only, and the player's invite code was missing, wrong, or used up.
+    InvalidInvite,
+    /// The server requires API tokens, and the player's token was missing or unknown.
+    InvalidToken,
+    /// An admin kicked the player out of the game.
+    Kicked,
+}
+
+/// The audiences that chat messages can be sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatChannel {
+    /// Messages from players, seen by everyone.
+    Players,
+    /// Messages from observers.
+    Spectators,
+}
+
+/// Information about a single player in the current game.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RosterEntry {
+    /// The player's ID.
+    pub player: Player,
+    /// The name the player registered with.
+    pub name: String,
+    /// The player's current score.
+    pub score: i32,
+    /// The team the player is on, if any.
+    pub team: Option<String>,
+    /// Whether the player is currently connected.
+    pub connected: bool,
+    /// The player's most recently measured round-trip latency in milliseconds, if known.
+    pub latency_ms: Option<u64>,
+}
+
+/// Messages sent to the server.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Registers the player with the server; see [`Register`].
+    Register(Register),
+    /// A set of bee movements to be made on the next tick.
+    ///
+    /// Later sets sent before the tick replace earlier instructions for the same bees.
+    Moves {
+        /// The set of moves to perform.
+        moves: Vec<Move>,
+        /// Moves applying to several bees at once.
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        groups: Vec<GroupMove>,
+        /// The tick the moves are meant for, if any.
+        ///
+        /// Moves meant for any tick but the next one are rejected with a warning.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tick: Option<u64>,
+    },
+    /// A chat message to send to everyone in the game.
+    Chat {
+        /// The contents of the message.
+        msg: String,
+    },
+    /// Donate pollen from the player's hive to an allied player's hive.
+    Transfer {
+        /// The player receiving the pollen.
+        to: Player,
+        /// The amount of pollen to transfer.
+        amount: i32,
+    },
+    /// Spend pollen to build a new hive where one of the player's bees is.
+    BuildHive {
+        /// The bee marking where to build the hive.
+        bee: BeeID,
+    },
+    /// Spend pollen to move one of the player's hives to a new position.
+    RelocateHive {
+        /// The current position of the hive to move.
+        hive: Position,
+        /// The spawn point to move the hive to.
+        to: Position,
+    },
+    /// Spend pollen to upgrade one of the player's hives.
+    UpgradeHive {
+        /// The position of the hive to upgrade.
+        hive: Position,
+        /// The upgrade to buy.
+        upgrade: Upgrade,
+    },
+    /// Change the role of one of the player's bees, which must be at one of the player's hives.
+    AssignRole {
+        /// The bee to change.
+        bee: BeeID,
+        /// The bee's new role.
+        role: Role,
+    },
+    /// Only receive updates about entities within part of the world.
+    SetViewport {
+        /// The area to receive updates about, or `None` for the whole world.
+        rect: Option<Rect>,
+    },
+    /// Change the name the player is registered under.
+    Rename {
+        /// The player's new name.
+        name: String,
+    },
+    /// Request information about the game.
+    Query {
+        /// The information being requested.
+        what: Query,
+    },
+    /// A reply to a [`ServerMessage::Ping`].
+    Pong {
+        /// The `id` of the ping being replied to.
+        id: u64,
+    },
+}
+
+/// How to register with the server.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Register {
+    /// The player's name, or an empty name to watch the game as an observer.
+    ///
+    /// Reconnecting with the same name resumes an existing session.
+    pub name: String,
+    /// The team the player would like to join, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub team: Option<String>,
+    /// The colour of the player's hive and bees, as a hex code like `"#ffcc00"`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// An emoji shown on the player's hive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,
+    /// The password needed to join the game, if it has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// The invite code needed to join the game, if it is invite-only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invite: Option<String>,
+    /// The player's API token, if the server requires one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl Register {
+    /// Register as a player called `name`, with no other options.
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Register {
+            name: name.into(),
+            ..Register::default()
+        }
+    }
+}
+
+/// Information that clients can request from the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Query {
+    /// The players taking part in the current game, answered with [`ServerMessage::Roster`].
+    Players,
+}
@@ -0,0 +1,70 @@
+//! Notable feats that players can accomplish during a game.
+//!
+//! Achievements are derived from the [statistics][`Stats`] tracked for each player,
+//! and are unlocked at most once per player per game.
+
+use std::collections::HashSet;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{Player, Stats};
+
+/// A notable feat accomplished by a player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Achievement {
+    /// Pollinated a flower for the first time.
+    FirstPollination,
+    /// Delivered at least 100 pollen to the hive.
+    HundredPollen,
+    /// Had a bee dodge a bird that flew into the tile it just left.
+    SurvivedBirdStrike,
+}
+
+impl Achievement {
+    /// Every kind of achievement.
+    const ALL: [Achievement; 3] = [
+        Achievement::FirstPollination,
+        Achievement::HundredPollen,
+        Achievement::SurvivedBirdStrike,
+    ];
+
+    /// Whether a player with the given `stats` has accomplished this feat.
+    #[must_use]
+    fn is_achieved(self, stats: &Stats) -> bool {
+        match self {
+            Achievement::FirstPollination => stats.flowers_pollinated > 0,
+            Achievement::HundredPollen => stats.pollen_delivered >= 100,
+            Achievement::SurvivedBirdStrike => stats.bird_strikes_survived > 0,
+        }
+    }
+}
+
+/// Tracks the achievements unlocked by each player.
+#[derive(Debug, Default, Clone)]
+pub struct Achievements {
+    /// The achievements each player has unlocked so far.
+    unlocked: HashSet<(Player, Achievement)>,
+    /// Achievements unlocked since the last call to [`Achievements::drain`].
+    pending: Vec<(Player, Achievement)>,
+}
+
+impl Achievements {
+    /// Check each player's statistics for any newly accomplished feats.
+    pub fn update(&mut self, players: &[(Player, Stats)]) {
+        for (player, stats) in players {
+            for &achievement in &Achievement::ALL {
+                let key = (*player, achievement);
+                if achievement.is_achieved(stats) && self.unlocked.insert(key) {
+                    self.pending.push(key);
+                }
+            }
+        }
+    }
+
+    /// Take all achievements unlocked since this was last called.
+    pub fn drain(&mut self) -> impl Iterator<Item = (Player, Achievement)> + '_ {
+        self.pending.drain(..)
+    }
+}
@@ -1,36 +1,55 @@
 //! Implementations of entity actions.
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
+    ops::AddAssign,
     sync::atomic::{AtomicUsize, Ordering},
 };
 
-use rand::Rng;
+use anyhow::{bail, Result};
+use rand::{distributions::WeightedIndex, prelude::*};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use super::{
-    world::{Direction, Position, World},
+    deserialize_chance,
+    items::{ItemKind, PowerUp},
+    weather::Weather,
+    world::{Direction, Position, Tile, World},
     Config, Player,
 };
 
 /// Uniquely identifies a bee.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 #[serde(transparent)]
 pub struct BeeID(usize);
 
+/// The next ID handed out by [`BeeID::new`].
+static BEE_COUNTER: AtomicUsize = AtomicUsize::new(1);
+
 impl BeeID {
     /// Create a new bee identifier.
     ///
     /// The generated ID will be unique for this execution of the application.
     /// Note that IDs will be duplicated across different executions.
+    // every call makes a different ID, so there's no sensible default
+    #[allow(clippy::new_without_default)]
     #[must_use]
     pub fn new() -> Self {
-        static BEE_COUNTER: AtomicUsize = AtomicUsize::new(1);
         let id = BEE_COUNTER.fetch_add(1, Ordering::Relaxed);
         BeeID(id)
     }
+
+    /// Make sure that IDs created from now on differ from this one,
+    /// which came from another execution of the application.
+    pub(super) fn reserve(self) {
+        BEE_COUNTER.fetch_max(self.0 + 1, Ordering::Relaxed);
+    }
 }
 
+/// The most directions that can be given in a single [`Move::plan`].
+pub const MAX_PLAN_LENGTH: usize = 100;
+
 /// Represents a set of bee actions made on a given turn.
 ///
 /// Moves are indexed on pairs of the player who made the action,
@@ -39,15 +58,177 @@ impl BeeID {
 /// the player controlling the affected bee don't match
 /// are ignored.
 ///
-/// Each bee can only get one action each turn.
-///
-/// An action is simply the direction the bee should move
-/// for this game turn, if possible.
-pub type Moves = HashMap<(Player, BeeID), Direction>;
+/// Each bee can only get one [`Action`] each turn.
+pub type Moves = HashMap<(Player, BeeID), Action>;
+
+/// Something a bee can do on a game turn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Move in the given direction, if possible.
+    Move(Direction),
+    /// Move in each of the given directions in turn.
+    ///
+    /// Bees take as many steps of the plan each turn as their [speed][Bee::speed] allows;
+    /// see [`Action::advance`] for moving on to the rest.
+    Plan(VecDeque<Direction>),
+    /// Travel to the given position along the cheapest path.
+    ///
+    /// This is turned into a [plan][Action::Plan] by [`State::route`][super::State::route]
+    /// when the action is received.
+    MoveTo {
+        /// Where to travel to.
+        target: Position,
+        /// Whether to avoid travelling along roads.
+        avoid_roads: bool,
+    },
+    /// Give all carried pollen to another bee on the same tile, or as much as it can carry.
+    ///
+    /// The other bee must belong to the same player or to a teammate.
+    /// The bee doesn't move this turn.
+    Handoff(BeeID),
+    /// Perform a waggle dance, showing teammates where the bee last collected pollen.
+    ///
+    /// Only works while the bee is at one of its player's hives.
+    /// The bee doesn't move this turn.
+    Dance,
+}
+
+impl Action {
+    /// Move on to the next turn, after a bee took `steps` steps.
+    ///
+    /// Returns whether there is anything left to do on later turns.
+    /// Only [plans][Action::Plan] last for more than one turn.
+    pub fn advance(&mut self, steps: u32) -> bool {
+        match self {
+            Action::Plan(plan) => {
+                let steps = steps as usize;
+                plan.drain(..steps.min(plan.len()));
+                !plan.is_empty()
+            }
+            _ => false,
+        }
+    }
+
+    /// The directions to move in this turn, taking up to `steps` steps.
+    ///
+    /// Plain [moves][Action::Move] go the same way each step.
+    pub fn directions(&self, steps: u32) -> impl Iterator<Item = Direction> + '_ {
+        let steps = steps as usize;
+        let (dir, plan) = match self {
+            Action::Move(dir) => (Some(*dir), None),
+            Action::Plan(plan) => (None, Some(plan)),
+            _ => (None, None),
+        };
+        let repeated = dir.into_iter().flat_map(std::iter::repeat);
+        let planned = plan.into_iter().flatten().copied();
+        repeated.chain(planned).take(steps)
+    }
+}
+
+/// A single action for a bee, as sent by its player.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Move {
+    /// The bee that is acting.
+    pub bee: BeeID,
+    /// The direction the bee should move.
+    /// `None` indicates that no movement should be made.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direction: Option<Direction>,
+    /// Another bee to give all of this bee's pollen to, instead of moving.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handoff: Option<BeeID>,
+    /// Whether to perform a waggle dance instead of moving.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dance: bool,
+    /// A position to travel to over this and the following ticks.
+    ///
+    /// The server finds the path there costing the least energy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<Position>,
+    /// Whether the path to [`Move::target`] should avoid roads.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub avoid_roads: bool,
+    /// Directions to move in over this and the following ticks, one per tile,
+    /// as many each tick as the bee's speed.
+    ///
+    /// Only the first [`MAX_PLAN_LENGTH`] directions are used.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub plan: Vec<Direction>,
+}
+
+/// The same movement for a group of bees.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GroupMove {
+    /// The bees that should move.
+    pub bees: Vec<BeeID>,
+    /// The direction the bees should move.
+    /// `None` indicates that no movement should be made.
+    #[serde(default)]
+    pub direction: Option<Direction>,
+}
+
+impl GroupMove {
+    /// Expand the group into a separate move for each bee.
+    pub fn expand(&self) -> impl Iterator<Item = Move> + '_ {
+        self.bees.iter().map(move |&bee| Move {
+            direction: self.direction,
+            ..Move::stay(bee)
+        })
+    }
+}
+
+impl Move {
+    /// Move `bee` one tile in `direction`.
+    #[must_use]
+    pub fn new(bee: BeeID, direction: Direction) -> Self {
+        Move {
+            direction: Some(direction),
+            ..Move::stay(bee)
+        }
+    }
+
+    /// Keep `bee` where it is.
+    #[must_use]
+    pub fn stay(bee: BeeID) -> Self {
+        Move {
+            bee,
+            direction: None,
+            handoff: None,
+            dance: false,
+            target: None,
+            avoid_roads: false,
+            plan: Vec::new(),
+        }
+    }
+
+    /// The action the bee should perform, if any.
+    ///
+    /// A handoff takes priority over a dance, then a target, then a plan,
+    /// and finally any provided direction.
+    #[must_use]
+    pub fn action(&self) -> Option<Action> {
+        if let Some(target) = self.handoff {
+            Some(Action::Handoff(target))
+        } else if self.dance {
+            Some(Action::Dance)
+        } else if let Some(target) = self.target {
+            let avoid_roads = self.avoid_roads;
+            Some(Action::MoveTo {
+                target,
+                avoid_roads,
+            })
+        } else if !self.plan.is_empty() {
+            let plan = self.plan.iter().take(MAX_PLAN_LENGTH).copied().collect();
+            Some(Action::Plan(plan))
+        } else {
+            self.direction.map(Action::Move)
+        }
+    }
+}
 
 /// A bee controlled by a player. Moves around the map and collects pollen
 /// at the player's direction.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Bee {
     /// Uniquely identifies the bee.
     pub id: BeeID,
@@ -57,8 +238,30 @@ pub struct Bee {
     pub position: Position,
     /// How much pollen the bee currently has collected.
     pub pollen: i32,
+    /// How much nectar the bee is carrying, if the game is played with [honey][HoneyConfig].
+    pub nectar: i32,
     /// The amount of energy the bee has left to live.
     pub energy: i32,
+    /// The most energy the bee can have.
+    pub max_energy: i32,
+    /// The most pollen the bee can carry, if limited.
+    pub max_pollen: Option<i32>,
+    /// How many tiles the bee flies each time it moves.
+    pub speed: u32,
+    /// Whether the bee is escorting a new queen to one of its player's hives.
+    pub queen: bool,
+    /// The bee's job.
+    pub role: Role,
+    /// The number of turns since the bee was spawned.
+    pub age: u32,
+    /// The age at which the bee dies, if limited.
+    pub max_age: Option<u32>,
+    /// Whether the bee is still growing up in its hive.
+    pub stage: Stage,
+    /// The number of turns since the bee last rested at a hive.
+    pub ticks_away: u32,
+    /// The temporary effect from the last item the bee collected, if it hasn't worn off.
+    pub power_up: Option<PowerUp>,
     /// The last flower the bee collected pollen from was.
     #[serde(skip)]
     pub last_flower: Option<FlowerID>,
@@ -73,186 +276,1087 @@ impl Bee {
             player,
             position,
             pollen: 0,
-            energy: 50,
+            nectar: 0,
+            energy: BEE_ENERGY,
+            max_energy: BEE_ENERGY,
+            max_pollen: None,
+            speed: 1,
+            queen: false,
+            role: Role::Worker,
+            age: 0,
+            max_age: None,
+            stage: Stage::Forager,
+            ticks_away: 0,
+            power_up: None,
             last_flower: None,
         }
     }
 
     /// Find the direction for the bee to move and go there, if possible.
     ///
-    /// Regardless of success or not, expends one energy each turn.
-    pub fn step(&mut self, moves: &Moves, world: &World) {
-        if let Some(&dir) = moves.get(&(self.player, self.id)) {
-            let new_pos = self.position.step(dir);
-            match world.get(new_pos) {
-                Some(tile) if tile.is_passable() => self.position = new_pos,
-                _ => {}
+    /// Bees fly up to [`Bee::speed`] tiles each turn: that many tiles in the direction of a plain move,
+    /// or that many steps of a [plan][Action::Plan]. They stop early at impassable tiles.
+    /// [Exhausted][Config::exhaustion_threshold] bees can only move every other turn.
+    ///
+    /// Moving spends the [movement cost][super::world::Tile::movement_cost]
+    /// of each tile the bee moves onto; otherwise the bee spends one energy.
+    /// Either way, the bee also spends extra energy for any pollen carried
+    /// (see [`Config::laden_drain`]) and in bad [weather][Weather::energy_drain].
+    ///
+    /// Bees that have been away from a hive for too long also lose pollen;
+    /// see [`Config::pollen_decay_ticks`].
+    /// Bees with an [energy drink][ItemKind::EnergyDrink] spend no energy at all.
+    ///
+    /// Either way, the bee gets one tick older, running out of energy once it reaches its
+    /// [maximum age][Bee::max_age]. [Larvae][Stage::Larva] stay put until they grow up.
+    pub fn step(&mut self, moves: &Moves, config: &Config, weather: Weather) {
+        self.age = self.age.saturating_add(1);
+        if self.max_age.is_some_and(|max| self.age >= max) {
+            self.energy = 0;
+        }
+        if self.stage == Stage::Larva {
+            if self.age >= config.larva_ticks {
+                self.stage = Stage::Forager;
+            }
+            return;
+        }
+
+        let world = &config.world;
+        let action = moves.get(&(self.player, self.id));
+        let exhausted = config
+            .exhaustion_threshold
+            .is_some_and(|limit| self.energy < limit);
+        let resting = exhausted && self.age.is_multiple_of(2);
+        let steps = if resting { 0 } else { self.speed };
+        let mut cost = None;
+        for dir in action
+            .into_iter()
+            .flat_map(|action| action.directions(steps))
+        {
+            let new_pos = world.step(self.position, dir);
+            match new_pos.and_then(|pos| Some((pos, world.get(pos)?))) {
+                Some((pos, tile)) if tile.is_passable() => {
+                    self.position = pos;
+                    cost = Some(cost.unwrap_or(0) + tile.movement_cost());
+                }
+                _ => break,
+            }
+        }
+        if !self.has_power_up(ItemKind::EnergyDrink) {
+            let cost = cost.unwrap_or(1);
+            self.energy -= cost + config.laden_drain.extra(self.pollen) + weather.energy_drain();
+        }
+        self.ticks_away += 1;
+        if config
+            .pollen_decay_ticks
+            .is_some_and(|ticks| self.ticks_away > ticks)
+        {
+            self.pollen = (self.pollen - 1).max(0);
+        }
+        if let Some(power_up) = &mut self.power_up {
+            power_up.ticks = power_up.ticks.saturating_sub(1);
+            if power_up.ticks == 0 {
+                self.power_up = None;
+            }
+        }
+    }
+
+    /// Whether the bee currently has a power-up of the given kind.
+    #[must_use]
+    pub fn has_power_up(&self, kind: ItemKind) -> bool {
+        self.power_up.is_some_and(|p| p.kind == kind)
+    }
+
+    /// Push the bee one tile in the given direction, if possible, without spending energy.
+    pub fn blow(&mut self, world: &World, dir: Direction) {
+        if let Some(new_pos) = world.step(self.position, dir) {
+            if world.get(new_pos).is_some_and(|tile| tile.is_passable()) {
+                self.position = new_pos;
             }
         }
-        self.energy -= 1;
     }
 
     /// Rest the bee, while visiting a hive.
+    ///
+    /// The bee still remembers the last flower it visited until it [dances][Action::Dance] about it.
     pub fn rest(&mut self) {
         self.pollen = 0;
-        self.last_flower = None;
-        self.energy = (self.energy + 5).min(50);
+        self.nectar = 0;
+        self.energy = (self.energy + 5).min(self.max_energy);
+        self.ticks_away = 0;
+    }
+
+    /// Change the bee's job, adjusting how much pollen it can carry.
+    pub fn set_role(&mut self, role: Role) {
+        let bonus = role.capacity_bonus() - self.role.capacity_bonus();
+        self.max_pollen = self.max_pollen.map(|max| max + bonus);
+        self.role = role;
+    }
+
+    /// How much more pollen the bee can carry.
+    #[must_use]
+    pub fn space(&self) -> i32 {
+        self.max_pollen
+            .map_or(i32::MAX, |max| (max - self.pollen).max(0))
+    }
+
+    /// Whether the bee can't carry any more pollen.
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.space() == 0
     }
 
     /// Intermingle pollen with any flowers you're on.
     ///
-    /// Bees on flowers transfer one unit of pollen each turn;
+    /// Bees on flowers transfer one unit of pollen each turn,
+    /// or more if the flower is on a [meadow][Tile::pollen_yield];
     /// if the flower has not been pollinated, and the bee has pollen,
     /// instead pollinates the flower.
-    pub fn transfer_pollen(&mut self, flowers: &mut [Flower]) {
-        let on_living_flower = |f: &&mut Flower| f.position == self.position && f.pollen > 0;
-        if let Some(flower) = flowers.iter_mut().find(on_living_flower) {
-            let this = Some(flower.id);
-            if self.pollen > 0 && !flower.is_pollinated && self.last_flower != this {
-                self.pollen -= 1;
-                flower.is_pollinated = true;
-            } else {
+    /// Full bees can still pollinate flowers, but can't collect any more pollen.
+    /// Bees with a [pollen magnet][ItemKind::PollenMagnet] can also reach the nearest flower
+    /// within [`ItemConfig::magnet_range`][super::items::ItemConfig::magnet_range] tiles.
+    /// If the game is played with [honey][HoneyConfig],
+    /// bees also sip one nectar from the flower if they have room for it,
+    /// even if they're too full of pollen to collect any more.
+    ///
+    /// Returns how the bee interacted with the flower, if at all.
+    pub fn transfer_pollen(
+        &mut self,
+        flowers: &mut HashMap<Position, Flower>,
+        config: &Config,
+    ) -> Option<Interaction> {
+        let world = &config.world;
+        let reach = if self.has_power_up(ItemKind::PollenMagnet) {
+            config.items.magnet_range
+        } else {
+            0
+        };
+        let has_pollen = |pos: &Position| flowers.get(pos).is_some_and(|f| f.pollen > 0);
+        let position = if reach == 0 {
+            Some(self.position).filter(has_pollen)?
+        } else {
+            (world.within_range(self.position, reach))
+                .filter(has_pollen)
+                .min_by_key(|&pos| world.distance(self.position, pos))?
+        };
+        let flower = flowers.get_mut(&position)?;
+        let this = Some(flower.id);
+        if self.pollen > 0 && !flower.is_pollinated && self.last_flower != this {
+            self.pollen -= 1;
+            flower.is_pollinated = true;
+            Some(Interaction::Pollinated)
+        } else {
+            let max_nectar = config.honey.as_ref().map_or(0, |h| h.max_nectar);
+            let collect_pollen = !self.is_full();
+            let sip_nectar = flower.nectar > 0 && self.nectar < max_nectar;
+            if !collect_pollen && !sip_nectar {
+                return None;
+            }
+            if collect_pollen {
+                let gained = world.get(flower.position).map_or(1, |t| t.pollen_yield());
                 flower.pollen -= 1;
-                self.pollen += 1;
-                self.last_flower = this;
+                self.pollen += gained.min(self.space());
+            }
+            if sip_nectar {
+                flower.nectar -= 1;
+                self.nectar += 1;
             }
+            let visited = self.last_flower != this;
+            self.last_flower = this;
+            Some(if visited {
+                Interaction::Visited
+            } else {
+                Interaction::Collected
+            })
         }
     }
 
     /// Whether the bee is alive.
     ///
     /// If out of energy, or colliding with a bird or a car, the bee is dead.
+    /// Birds leave bees with [bird repellent][ItemKind::BirdRepellent] alone.
     #[must_use]
     pub fn is_alive(&self, birds: &[Bird], cars: &[Car]) -> bool {
+        let repellent = self.has_power_up(ItemKind::BirdRepellent);
         self.energy > 0
-            && birds.iter().all(|bird| bird.position != self.position)
+            && (repellent || birds.iter().all(|bird| bird.position != self.position))
             && cars.iter().all(|car| car.position != self.position)
     }
 }
 
-/// A player's hive. Each player will have exactly one hive.
+/// A bee's stage of life.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Still growing up in its hive, and can't move;
+    /// see [`Config::larva_ticks`].
+    Larva,
+    /// Fully grown, and out collecting pollen.
+    Forager,
+}
+
+/// A waggle dance performed at a hive, revealing where a flower is.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Dance {
+    /// The player whose bee performed the dance.
+    pub player: Player,
+    /// The bee that performed the dance.
+    pub bee: BeeID,
+    /// Where the flower the bee last collected pollen from is.
+    pub position: Position,
+}
+
+/// The job of a bee, changing what it's good at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Carries more pollen than other bees.
+    Worker,
+    /// Sees farther than other bees.
+    Scout,
+    /// Counts as several bees when fighting birds.
+    Guard,
+}
+
+impl Role {
+    /// Every kind of role.
+    const ALL: [Role; 3] = [Role::Worker, Role::Scout, Role::Guard];
+
+    /// How much extra pollen a bee with this role can carry,
+    /// if bees' carrying capacity is limited.
+    #[must_use]
+    pub fn capacity_bonus(self) -> i32 {
+        match self {
+            Role::Worker => 5,
+            Role::Scout | Role::Guard => 0,
+        }
+    }
+
+    /// How much a bee with this role contributes to chasing away a bird.
+    ///
+    /// See [`Config::bird_chase_strength`].
+    #[must_use]
+    pub fn strength(self) -> u32 {
+        match self {
+            Role::Guard => 3,
+            Role::Worker | Role::Scout => 1,
+        }
+    }
+
+    /// How many tiles away a bee with this role can see.
+    #[must_use]
+    pub fn sight_range(self) -> i32 {
+        match self {
+            Role::Scout => 4,
+            Role::Worker | Role::Guard => 2,
+        }
+    }
+}
+
+/// How likely a new bee is to be given each [`Role`].
+///
+/// Weights are relative to each other, and don't need to add up to anything.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoleWeights {
+    /// The weighting for [`Role::Worker`].
+    pub worker: f64,
+    /// The weighting for [`Role::Scout`].
+    pub scout: f64,
+    /// The weighting for [`Role::Guard`].
+    pub guard: f64,
+}
+
+impl Default for RoleWeights {
+    fn default() -> Self {
+        Self {
+            worker: 0.6,
+            scout: 0.2,
+            guard: 0.2,
+        }
+    }
+}
+
+impl RoleWeights {
+    /// Randomly choose a role according to the weights.
+    ///
+    /// Chooses [`Role::Worker`] if the weights are invalid, e.g. all zero.
+    pub fn choose<R: Rng + ?Sized>(&self, rng: &mut R) -> Role {
+        let weights = [self.worker, self.scout, self.guard];
+        match WeightedIndex::new(weights) {
+            Ok(dist) => Role::ALL[dist.sample(rng)],
+            Err(_) => Role::Worker,
+        }
+    }
+}
+
+/// How a bee interacted with a flower during [`Bee::transfer_pollen`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interaction {
+    /// The bee pollinated the flower.
+    Pollinated,
+    /// The bee started collecting pollen from a new flower.
+    Visited,
+    /// The bee continued collecting pollen from the same flower as last time.
+    Collected,
+}
+
+/// Counters tracking a player's activity over the course of a game.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Stats {
+    /// The number of bees that have been created for the player.
+    pub bees_spawned: u32,
+    /// The number of the player's bees that have died.
+    pub bees_lost: u32,
+    /// The number of times the player's bees started collecting pollen from a flower.
+    pub flowers_visited: u32,
+    /// The number of flowers the player's bees have pollinated.
+    pub flowers_pollinated: u32,
+    /// The number of times the player's bees dodged a bird flying into their previous tile.
+    pub bird_strikes_survived: u32,
+    /// The number of birds the player's bees have helped chase away.
+    pub birds_chased: u32,
+    /// The total amount of pollen the player's bees have delivered to their hive.
+    pub pollen_delivered: i32,
+    /// The number of wasp nests the player's bees have helped destroy.
+    pub nests_destroyed: u32,
+    /// The total amount of pollen wasps have stolen from the player's hives.
+    pub pollen_stolen: i32,
+    /// The total amount of honey the player's hives have made from nectar.
+    pub honey_made: i32,
+}
+
+impl AddAssign for Stats {
+    fn add_assign(&mut self, other: Self) {
+        self.bees_spawned += other.bees_spawned;
+        self.bees_lost += other.bees_lost;
+        self.flowers_visited += other.flowers_visited;
+        self.flowers_pollinated += other.flowers_pollinated;
+        self.bird_strikes_survived += other.bird_strikes_survived;
+        self.birds_chased += other.birds_chased;
+        self.pollen_delivered += other.pollen_delivered;
+        self.nests_destroyed += other.nests_destroyed;
+        self.pollen_stolen += other.pollen_stolen;
+        self.honey_made += other.honey_made;
+    }
+}
+
+/// The energy a bee starts with, before any upgrades.
+const BEE_ENERGY: i32 = 50;
+
+/// How much each [`Upgrade::SpawnRate`] level increases the chance of spawning a bee,
+/// as a fraction of [`Config::bee_spawn_chance`].
+const SPAWN_RATE_BONUS: f64 = 0.5;
+
+/// How much extra energy each [`Upgrade::Energy`] level gives new bees.
+const ENERGY_BONUS: i32 = 10;
+
+/// How much extra pollen each [`Upgrade::Capacity`] level lets new bees carry.
+const CAPACITY_BONUS: i32 = 5;
+
+/// How many extra tiles each [`Upgrade::Speed`] level lets new bees fly each move.
+const SPEED_BONUS: u32 = 1;
+
+/// How many tiles away a hive can see, when playing with [`Config::fog_of_war`].
+pub const HIVE_SIGHT_RANGE: i32 = 3;
+
+/// Improvements that can be bought for a hive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Upgrade {
+    /// Spawn new bees more often.
+    SpawnRate,
+    /// Spawn bees with more energy.
+    Energy,
+    /// Spawn bees that can carry more pollen.
+    ///
+    /// Has no effect if bees can carry unlimited pollen.
+    Capacity,
+    /// Spawn bees that fly farther each move.
+    Speed,
+}
+
+/// The level of each [`Upgrade`] bought for a hive, starting from zero.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Upgrades {
+    /// The level of [`Upgrade::SpawnRate`].
+    pub spawn_rate: u32,
+    /// The level of [`Upgrade::Energy`].
+    pub energy: u32,
+    /// The level of [`Upgrade::Capacity`].
+    pub capacity: u32,
+    /// The level of [`Upgrade::Speed`].
+    pub speed: u32,
+}
+
+impl Upgrades {
+    /// The current level of the given `upgrade`.
+    #[must_use]
+    pub fn level(&self, upgrade: Upgrade) -> u32 {
+        match upgrade {
+            Upgrade::SpawnRate => self.spawn_rate,
+            Upgrade::Energy => self.energy,
+            Upgrade::Capacity => self.capacity,
+            Upgrade::Speed => self.speed,
+        }
+    }
+
+    /// Increase the level of the given `upgrade` by one.
+    pub fn improve(&mut self, upgrade: Upgrade) {
+        match upgrade {
+            Upgrade::SpawnRate => self.spawn_rate += 1,
+            Upgrade::Energy => self.energy += 1,
+            Upgrade::Capacity => self.capacity += 1,
+            Upgrade::Speed => self.speed += 1,
+        }
+    }
+}
+
+/// The maximum length of a player's emoji, in bytes.
+///
+/// Allows for emoji made of several combined code points.
+const MAX_EMOJI_LENGTH: usize = 32;
+
+/// Purely visual customisations chosen by a player.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Cosmetics {
+    /// The colour of the player's hive and bees, as a hex code like `"#ffcc00"`.
+    #[serde(default)]
+    pub color: Option<String>,
+    /// An emoji shown on the player's hive.
+    #[serde(default)]
+    pub emoji: Option<String>,
+}
+
+impl Cosmetics {
+    /// Check that the cosmetics are sensible to display.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the colour isn't a hex colour code,
+    /// or if the emoji is too long or contains whitespace.
+    pub fn check(&self) -> Result<()> {
+        if let Some(color) = &self.color {
+            let digits = color.strip_prefix('#').unwrap_or("");
+            let valid_length = digits.len() == 3 || digits.len() == 6;
+            if !valid_length || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                bail!(
+                    "Invalid hive color {:?}: expected a hex code like \"#ffcc00\"",
+                    color
+                );
+            }
+        }
+        if let Some(emoji) = &self.emoji {
+            if emoji.is_empty() || emoji.len() > MAX_EMOJI_LENGTH {
+                bail!(
+                    "Invalid emoji: must be between 1 and {} bytes",
+                    MAX_EMOJI_LENGTH
+                );
+            }
+            if emoji.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                bail!("Invalid emoji: cannot contain whitespace");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A player's hive. Each player starts with one hive, and may build more.
 ///
-/// Also tracks unique per-player information.
-#[derive(Debug, Clone, Serialize)]
+/// Each hive tracks its own score and statistics;
+/// a player's totals are the sums over all of their hives.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Hive {
     /// The player owning this hive.
     pub player: Player,
     /// Where the hive is on the map.
     pub position: Position,
-    /// How much pollen this hive has collected so far.
+    /// How the player's hive and bees should be displayed.
+    #[serde(flatten)]
+    pub cosmetics: Cosmetics,
+    /// The team the player is on, if any.
+    pub team: Option<String>,
+    /// Where the hive is moving to, if it is being relocated.
+    pub transit: Option<Transit>,
+    /// The upgrades bought for this hive.
+    pub upgrades: Upgrades,
+    /// Whether the hive has a queen.
+    ///
+    /// Hives without a queen don't spawn bees.
+    pub queen: bool,
+    /// How much nectar is waiting to be made into honey.
+    pub nectar: i32,
+    /// How much pollen this hive has collected so far,
+    /// or how much honey it has made if the game is played with [honey][HoneyConfig].
     #[serde(skip)]
     score: i32,
+    /// Statistics about the player's activity so far.
+    #[serde(skip)]
+    pub stats: Stats,
 }
 
 impl Hive {
-    /// Spawn a new hive at the given position.
+    /// Spawn a new hive at the given position, displayed with the player's `cosmetics`.
     ///
     /// Returns a hive and any initial bees to be constructed at the hive.
-    pub fn new(player: Player, position: Position) -> (Self, impl Iterator<Item = Bee>) {
-        (
-            Hive {
-                player,
-                position,
-                score: 0,
+    pub fn new(
+        player: Player,
+        position: Position,
+        cosmetics: Cosmetics,
+        team: Option<String>,
+        config: &Config,
+        rng: &mut (impl Rng + ?Sized),
+    ) -> (Self, Vec<Bee>) {
+        let initial_bees = 3;
+        let hive = Hive {
+            player,
+            position,
+            cosmetics,
+            team,
+            transit: None,
+            upgrades: Upgrades::default(),
+            queen: true,
+            nectar: 0,
+            score: 0,
+            stats: Stats {
+                bees_spawned: initial_bees,
+                ..Stats::default()
             },
-            (0..3).map(move |_| Bee::new(BeeID::new(), player, position)),
-        )
+        };
+        let bees = (0..initial_bees)
+            .map(|_| Bee {
+                stage: Stage::Forager,
+                ..hive.make_bee(rng, config)
+            })
+            .collect();
+        (hive, bees)
     }
 
-    /// The current amount of pollen stored by this particular hive.
+    /// The current amount of pollen, or honey, stored by this particular hive.
     #[must_use]
     pub fn score(&self) -> i32 {
         self.score
     }
 
+    /// Found a new, empty hive for the same player at the given position.
+    ///
+    /// The new hive doesn't come with any bees.
+    #[must_use]
+    pub fn found(&self, position: Position) -> Self {
+        Hive {
+            player: self.player,
+            position,
+            cosmetics: self.cosmetics.clone(),
+            team: self.team.clone(),
+            transit: None,
+            upgrades: Upgrades::default(),
+            queen: true,
+            nectar: 0,
+            score: 0,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Whether the hive is currently being relocated.
+    #[must_use]
+    pub fn is_in_transit(&self) -> bool {
+        self.transit.is_some()
+    }
+
+    /// Start moving the hive to `destination`, arriving after `ticks` turns.
+    pub fn relocate(&mut self, destination: Position, ticks: u32) {
+        self.transit = Some(Transit {
+            destination,
+            ticks_remaining: ticks,
+        });
+    }
+
+    /// Continue relocating the hive, if it is in transit.
+    ///
+    /// Returns the hive's old position if it arrived at its destination this turn.
+    pub fn travel(&mut self) -> Option<Position> {
+        let transit = self.transit.as_mut()?;
+        transit.ticks_remaining = transit.ticks_remaining.saturating_sub(1);
+        if transit.ticks_remaining > 0 {
+            return None;
+        }
+        let destination = transit.destination;
+        self.transit = None;
+        Some(std::mem::replace(&mut self.position, destination))
+    }
+
+    /// Remove up to `amount` pollen from this hive.
+    ///
+    /// Returns how much pollen was actually removed.
+    pub fn withdraw(&mut self, amount: i32) -> i32 {
+        let taken = amount.clamp(0, self.score.max(0));
+        self.score -= taken;
+        taken
+    }
+
+    /// Add `amount` pollen to this hive.
+    pub fn deposit(&mut self, amount: i32) {
+        self.score += amount;
+    }
+
+    /// Create a new bee at this hive, improved by the hive's upgrades.
+    ///
+    /// The bee's role is chosen randomly using [`Config::role_weights`].
+    #[must_use]
+    fn make_bee<R: Rng + ?Sized>(&self, rng: &mut R, config: &Config) -> Bee {
+        let bonus = |level: u32, per_level: i32| per_level.saturating_mul(level as i32);
+        let max_energy = BEE_ENERGY + bonus(self.upgrades.energy, ENERGY_BONUS);
+        let capacity = bonus(self.upgrades.capacity, CAPACITY_BONUS);
+        let mut bee = Bee {
+            energy: max_energy,
+            max_energy,
+            max_pollen: config.bee_max_pollen.map(|max| max + capacity),
+            speed: 1 + SPEED_BONUS.saturating_mul(self.upgrades.speed),
+            max_age: config.bee_max_age,
+            stage: if config.larva_ticks > 0 {
+                Stage::Larva
+            } else {
+                Stage::Forager
+            },
+            ..Bee::new(BeeID::new(), self.player, self.position)
+        };
+        bee.set_role(config.role_weights.choose(rng));
+        bee
+    }
+
+    /// Check whether a bird has reached the hive while the queen is unguarded.
+    ///
+    /// The queen is guarded if any of the player's bees are on the hive.
+    /// An unguarded queen is lost if a bird reaches the hive.
+    pub fn protect_queen(&mut self, bees: &[Bee], birds: &[Bird]) {
+        let here = |position| position == self.position;
+        let attacked = birds.iter().any(|b| here(b.position));
+        let guarded = bees
+            .iter()
+            .any(|b| b.player == self.player && here(b.position));
+        if self.queen && attacked && !guarded {
+            self.queen = false;
+        }
+    }
+
     /// Maybe spawn a bee at this hive.
+    ///
+    /// Hives don't spawn bees while in transit or without a queen.
     #[must_use]
-    pub fn spawn_bee<R: Rng + ?Sized>(&self, rng: &mut R, config: &Config) -> Option<Bee> {
-        rng.gen_bool(config.bee_spawn_chance)
-            .then(|| Bee::new(BeeID::new(), self.player, self.position))
+    pub fn spawn_bee<R: Rng + ?Sized>(&mut self, rng: &mut R, config: &Config) -> Option<Bee> {
+        if self.is_in_transit() || !self.queen {
+            return None;
+        }
+        let bonus = 1.0 + SPAWN_RATE_BONUS * f64::from(self.upgrades.spawn_rate);
+        let chance = (config.bee_spawn_chance * bonus).min(1.0);
+        if !rng.gen_bool(chance) {
+            return None;
+        }
+        let bee = self.make_bee(rng, config);
+        self.stats.bees_spawned += 1;
+        Some(bee)
     }
 
     /// Find any of our bees on this hive.
     /// Transfer their pollen and increase our score.
-    pub fn handle_bees(&mut self, bees: &mut [Bee]) {
+    /// If the hive has lost its queen, a bee escorting a new queen will install her.
+    ///
+    /// If `honey` is set, bees deliver nectar to be [made into honey][Hive::make_honey] instead.
+    /// Their pollen is still counted as delivered in the hive's stats, but is otherwise discarded:
+    /// it doesn't count towards the score.
+    /// Bees can't deliver to a hive that is in transit.
+    ///
+    /// Only the bees on the hive's tile need to be given; any others are ignored.
+    pub fn handle_bees<'a>(&mut self, bees: impl IntoIterator<Item = &'a mut Bee>, honey: bool) {
+        if self.is_in_transit() {
+            return;
+        }
         for bee in bees {
             if (bee.position, bee.player) == (self.position, self.player) {
-                self.score += bee.pollen;
+                if !self.queen && bee.queen {
+                    bee.queen = false;
+                    self.queen = true;
+                }
+                if honey {
+                    // pollen is only good for pollinating flowers when playing with honey
+                    self.nectar += bee.nectar;
+                } else {
+                    self.score += bee.pollen;
+                }
+                self.stats.pollen_delivered += bee.pollen;
                 bee.rest();
             }
         }
     }
+
+    /// Turn up to `rate` of the hive's stored nectar into honey, increasing our score.
+    pub fn make_honey(&mut self, rate: i32) {
+        let made = self.nectar.min(rate.max(0));
+        self.nectar -= made;
+        self.score += made;
+        self.stats.honey_made += made;
+    }
+}
+
+/// Configure how nectar is collected and made into honey.
+///
+/// When playing with honey, hives score the honey they make rather than the pollen delivered to them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HoneyConfig {
+    /// The most nectar a bee can carry at once.
+    pub max_nectar: i32,
+    /// How much nectar each hive can make into honey each tick.
+    pub rate: i32,
+}
+
+impl Default for HoneyConfig {
+    fn default() -> Self {
+        Self {
+            max_nectar: 5,
+            rate: 1,
+        }
+    }
+}
+
+/// The progress of a hive being moved to a new position.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+pub struct Transit {
+    /// Where the hive is moving to.
+    pub destination: Position,
+    /// How many more turns until the hive arrives.
+    pub ticks_remaining: u32,
 }
 
 /// Uniquely identifies a flower.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(transparent)]
 pub struct FlowerID(usize);
 
+/// The next ID handed out by [`FlowerID::new`].
+static FLOWER_COUNTER: AtomicUsize = AtomicUsize::new(1);
+
 impl FlowerID {
     /// Create a new flower identifier.
     ///
     /// The generated ID will be unique for this execution of the application.
     /// Note that IDs will be duplicated across different executions.
+    // every call makes a different ID, so there's no sensible default
+    #[allow(clippy::new_without_default)]
     #[must_use]
     pub fn new() -> Self {
-        static FLOWER_COUNTER: AtomicUsize = AtomicUsize::new(1);
         let id = FLOWER_COUNTER.fetch_add(1, Ordering::Relaxed);
         FlowerID(id)
     }
+
+    /// Make sure that IDs created from now on differ from this one,
+    /// which came from another execution of the application.
+    pub(super) fn reserve(self) {
+        FLOWER_COUNTER.fetch_max(self.0 + 1, Ordering::Relaxed);
+    }
+}
+
+/// The kinds of flower that can grow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Species {
+    /// An ordinary flower, which never regrows.
+    ///
+    /// Every flower is a wildflower unless [`Config::flower_species`] is set.
+    Wildflower,
+    /// Common and quick to regrow, but with little pollen.
+    Clover,
+    /// Lots of pollen, but never regrows; mostly found in gardens.
+    Rose,
+    /// The most pollen of all, but slow to regrow.
+    Sunflower,
+}
+
+impl Species {
+    /// Every species of flower that can spawn when [`Config::flower_species`] is set.
+    pub const ALL: [Species; 3] = [Species::Clover, Species::Rose, Species::Sunflower];
+
+    /// How many times the usual amount of pollen a flower of this species starts with.
+    #[must_use]
+    pub fn pollen_factor(self) -> i32 {
+        match self {
+            Species::Wildflower | Species::Clover => 1,
+            Species::Rose => 2,
+            Species::Sunflower => 3,
+        }
+    }
+
+    /// How many ticks a flower of this species takes to regrow one pollen,
+    /// or `None` if it never regrows.
+    #[must_use]
+    pub fn regrowth_ticks(self) -> Option<u32> {
+        match self {
+            Species::Clover => Some(5),
+            Species::Wildflower | Species::Rose => None,
+            Species::Sunflower => Some(20),
+        }
+    }
+
+    /// How likely a flower spawning on the `tile` is to be this species, relative to the others.
+    #[must_use]
+    pub fn spawn_weight(self, tile: Tile) -> f64 {
+        match (self, tile) {
+            (Species::Wildflower, _) => 0.0,
+            (Species::Clover, Tile::Garden) => 0.2,
+            (Species::Clover, _) => 0.7,
+            (Species::Rose, Tile::Garden) => 0.5,
+            (Species::Rose, _) => 0.1,
+            (Species::Sunflower, Tile::Garden) => 0.3,
+            (Species::Sunflower, _) => 0.2,
+        }
+    }
+
+    /// Choose a random species for a flower spawning on the `tile`.
+    pub fn choose<R: Rng + ?Sized>(rng: &mut R, tile: Tile) -> Species {
+        Species::ALL
+            .choose_weighted(rng, |s| s.spawn_weight(tile))
+            .copied()
+            .unwrap_or(Species::Clover)
+    }
 }
 
 /// A flower which can be visited to collect pollen.
 ///
-/// When it runs out of pollen, the flower "dies".
-/// If the flower was previously pollinated when it dies,
-/// it will spawn a new flower nearby.
-#[derive(Debug, Clone, Serialize)]
+/// When it runs out of pollen, or gets too old (see [`Config::flower_max_age`]),
+/// the flower "dies".
+/// Until then, once the flower has been pollinated
+/// it occasionally seeds new flowers on neighbouring tiles.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Flower {
     /// Uniquely identifies a flower.
     pub id: FlowerID,
+    /// The kind of flower.
+    pub species: Species,
     /// The location of the flower on the map.
     pub position: Position,
     /// How much pollen the flower has remaining.
     pub pollen: i32,
+    /// The most pollen the flower can regrow to.
+    pub max_pollen: i32,
+    /// How much nectar the flower has remaining.
+    ///
+    /// Flowers start with as much nectar as pollen, and regrow both together.
+    pub nectar: i32,
     /// Whether this flower has been pollinated.
     pub is_pollinated: bool,
+    /// The number of ticks since the flower spawned.
+    pub age: u32,
+    /// The number of ticks spent regrowing the next pollen.
+    #[serde(skip)]
+    growth: u32,
 }
 
 impl Flower {
     /// Spawn a new flower at the given position, with an initial amount of pollen.
     #[must_use]
-    pub fn new(position: Position, pollen: i32) -> Self {
+    pub fn new(position: Position, species: Species, pollen: i32) -> Self {
         Self {
             id: FlowerID::new(),
+            species,
             position,
             pollen,
+            max_pollen: pollen,
+            nectar: pollen,
             is_pollinated: false,
+            age: 0,
+            growth: 0,
+        }
+    }
+
+    /// Regrow pollen over time, depending on the flower's [species][Species::regrowth_ticks].
+    ///
+    /// Flowers that have run out of pollen don't regrow.
+    /// Either way, the flower gets one tick older.
+    pub fn grow(&mut self) {
+        self.age = self.age.saturating_add(1);
+        let ticks = match self.species.regrowth_ticks() {
+            Some(ticks) if self.pollen > 0 && self.pollen < self.max_pollen => ticks,
+            _ => return,
+        };
+        self.growth += 1;
+        if self.growth >= ticks {
+            self.growth = 0;
+            self.pollen += 1;
+            self.nectar = (self.nectar + 1).min(self.max_pollen);
         }
     }
 }
 
 /// A bird that flies around and eats any bees it passes.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Bird {
+    /// The location of the bird on the map.
     pub position: Position,
+    /// The direction the bird is flying in.
+    pub heading: Direction,
+    /// The number of ticks until the bird flies away.
+    #[serde(skip)]
+    flight: u32,
 }
 
 impl Bird {
-    pub fn step(&mut self, _world: &World) {
-        todo!()
+    /// Create a bird flying in the given direction, which flies away after `flight` ticks.
+    #[must_use]
+    pub fn new(position: Position, heading: Direction, flight: u32) -> Self {
+        Self {
+            position,
+            heading,
+            flight,
+        }
+    }
+
+    /// Choose which way to fly this turn.
+    ///
+    /// With [`BirdConfig::aggression`] chance, a bird that can see any `bees`
+    /// heads for the nearest one.
+    /// Otherwise it lines up with the most common heading among the `birds` it can see,
+    /// keeping its own heading unless more of its flock are flying another way.
+    #[must_use]
+    pub fn heading<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        world: &World,
+        config: &BirdConfig,
+        bees: &[Bee],
+        birds: &[Bird],
+    ) -> Direction {
+        let in_sight = |pos| world.distance(self.position, pos) <= config.sight_range;
+        if rng.gen_bool(config.aggression) {
+            let prey = (bees.iter())
+                .filter(|b| in_sight(b.position))
+                .min_by_key(|b| world.distance(self.position, b.position));
+            if let Some(dir) = prey.and_then(|b| world.direction_towards(self.position, b.position))
+            {
+                return dir;
+            }
+        }
+        let flocking = |dir| {
+            (birds.iter())
+                .filter(|b| b.heading == dir && in_sight(b.position))
+                .count()
+        };
+        (world.grid.directions().iter().copied())
+            .max_by_key(|&dir| (flocking(dir), dir == self.heading))
+            .unwrap_or(self.heading)
+    }
+
+    /// Fly `speed` tiles onwards. Birds can fly over any kind of tile,
+    /// but turn as little as possible to steer around any tiles they `avoid`.
+    pub fn step(&mut self, world: &World, speed: u32, avoid: impl Fn(Position) -> bool) {
+        let directions = world.grid.directions();
+        let count = directions.len();
+        let ahead = (directions.iter())
+            .position(|&dir| dir == self.heading)
+            .unwrap_or_default();
+        for _ in 0..speed {
+            // try flying straight on first, then turning further and further either way
+            let turns = (0..=count / 2).flat_map(|turn| [ahead + turn, ahead + count - turn]);
+            let next = turns.map(|i| directions[i % count]).find_map(|dir| {
+                let next = world.step(self.position, dir)?;
+                (!avoid(next)).then_some((next, dir))
+            });
+            if let Some((next, dir)) = next {
+                self.position = next;
+                self.heading = dir;
+            }
+            if world.get(self.position).is_none() {
+                break;
+            }
+        }
+        self.flight = self.flight.saturating_sub(1);
+    }
+
+    /// Whether the bird has flown off the map or away.
+    #[must_use]
+    pub fn has_left(&self, world: &World) -> bool {
+        self.flight == 0 || world.get(self.position).is_none()
+    }
+}
+
+/// Configure the birds that fly around the map.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BirdConfig {
+    /// The number of birds to keep flying around the map, including any migrating flocks.
+    ///
+    /// While there are fewer, a new bird flies in from a random edge each turn.
+    pub count: usize,
+    /// How many tiles each bird flies each turn.
+    pub speed: u32,
+    /// Chance that a bird that can see a bee turns towards it each turn.
+    #[serde(deserialize_with = "deserialize_chance")]
+    pub aggression: f64,
+    /// How many tiles away birds can see bees and each other.
+    pub sight_range: i32,
+    /// Whether birds steer around hives.
+    ///
+    /// Birds can only take a hive's queen if they don't avoid hives.
+    pub avoid_hives: bool,
+}
+
+impl Default for BirdConfig {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            speed: 1,
+            aggression: 0.5,
+            sight_range: 3,
+            avoid_hives: true,
+        }
     }
 }
 
 /// A car that drives around on roads, killing any bees it crosses over.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Car {
+    /// The location of the car on the map.
     pub position: Position,
+    /// The direction the car last drove in.
     pub facing: Direction,
 }
 
 impl Car {
-    pub fn step(&mut self, _world: &World) {
-        todo!()
+    /// Create a car driving in the given direction.
+    #[must_use]
+    pub fn new(position: Position, facing: Direction) -> Self {
+        Self { position, facing }
+    }
+
+    /// Drive one tile along the [road network][World::road_exits].
+    ///
+    /// At junctions the car turns at random, but never turns back unless it's at a dead end.
+    /// If the next tile is `blocked` for a car driving in that direction,
+    /// the car waits where it is instead.
+    pub fn step<R: Rng + ?Sized>(
+        &mut self,
+        rng: &mut R,
+        world: &World,
+        blocked: impl Fn(Position, Direction) -> bool,
+    ) {
+        let exits = world.road_exits(self.position);
+        let onwards: Vec<_> = (exits.iter().copied())
+            .filter(|&dir| dir != self.facing.opposite())
+            .collect();
+        let dir = match onwards.choose(rng).or_else(|| exits.first()) {
+            Some(&dir) => dir,
+            None => return,
+        };
+        if let Some(next) = world.step(self.position, dir) {
+            if !blocked(next, dir) {
+                self.position = next;
+                self.facing = dir;
+            }
+        }
+    }
+
+    /// Whether the car has driven off the map.
+    #[must_use]
+    pub fn has_left(&self, world: &World) -> bool {
+        world.get(self.position).is_none()
     }
 }
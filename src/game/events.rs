@@ -0,0 +1,173 @@
+//! Random events that occasionally shake up the world.
+//!
+//! Each tick there is a [small chance][`EventConfig::chance`] of an event happening,
+//! such as a storm destroying flowers or a flock of birds migrating across the map.
+//! Events are announced to every client as they happen.
+
+use rand::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    deserialize_chance,
+    entity::Bird,
+    world::{Direction, Position, Tile, World},
+    Entities,
+};
+
+/// Something unusual that happened in the world.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Event {
+    /// A storm destroyed every flower near `center`.
+    Storm {
+        /// The middle of the storm.
+        center: Position,
+        /// How many tiles away from the center the storm reached.
+        radius: i32,
+        /// The number of flowers destroyed.
+        flowers: usize,
+    },
+    /// Flowers on garden tiles near `center` had their pollen doubled, up to their most pollen.
+    Bloom {
+        /// The middle of the bloom; always a garden tile.
+        center: Position,
+        /// How many tiles away from the center the bloom reached.
+        radius: i32,
+        /// The number of flowers that bloomed.
+        flowers: usize,
+    },
+    /// A flock of birds started flying across the map.
+    Migration {
+        /// The direction the birds are flying.
+        direction: Direction,
+        /// The number of birds in the flock.
+        birds: usize,
+    },
+}
+
+/// Configure how often random events happen, and how big they are.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventConfig {
+    /// Chance that an event happens each tick.
+    ///
+    /// If zero, no events happen.
+    #[serde(deserialize_with = "deserialize_chance")]
+    pub chance: f64,
+    /// How many tiles away from its center a storm reaches.
+    pub storm_radius: i32,
+    /// How many tiles away from its center a bloom reaches.
+    pub bloom_radius: i32,
+    /// The number of birds in a migrating flock.
+    pub migration_size: usize,
+}
+
+impl Default for EventConfig {
+    fn default() -> Self {
+        Self {
+            chance: 0.0,
+            storm_radius: 2,
+            bloom_radius: 3,
+            migration_size: 4,
+        }
+    }
+}
+
+/// Possibly make a random event happen to the `entities`, returning the event if one did.
+pub(super) fn happen<R: Rng + ?Sized>(
+    rng: &mut R,
+    entities: &mut Entities,
+    world: &World,
+    config: &EventConfig,
+) -> Option<Event> {
+    if !rng.gen_bool(config.chance) {
+        return None;
+    }
+    match rng.gen_range(0..3) {
+        0 => Some(storm(rng, entities, world, config.storm_radius)),
+        1 => bloom(rng, entities, world, config.bloom_radius),
+        _ => migration(rng, entities, world, config.migration_size),
+    }
+}
+
+/// Destroy every flower within `radius` of a random tile.
+fn storm<R: Rng + ?Sized>(
+    rng: &mut R,
+    entities: &mut Entities,
+    world: &World,
+    radius: i32,
+) -> Event {
+    let center = Position::new(
+        rng.gen_range(0..world.width),
+        rng.gen_range(0..world.height),
+    );
+    let before = entities.flower_changes.len();
+    let changes = &mut entities.flower_changes;
+    entities.flowers.retain(|&pos, _| {
+        let spared = world.distance(center, pos) > radius;
+        if !spared {
+            changes.push((pos, false));
+        }
+        spared
+    });
+    let flowers = changes.len() - before;
+    Event::Storm {
+        center,
+        radius,
+        flowers,
+    }
+}
+
+/// Double the pollen of flowers on garden tiles within `radius` of a random garden tile,
+/// up to the most pollen each flower can have.
+///
+/// Does nothing if there are no garden tiles.
+fn bloom<R: Rng + ?Sized>(
+    rng: &mut R,
+    entities: &mut Entities,
+    world: &World,
+    radius: i32,
+) -> Option<Event> {
+    let gardens: Vec<_> = (0..world.height)
+        .flat_map(|y| (0..world.width).map(move |x| Position::new(x, y)))
+        .filter(|&pos| matches!(world[pos], Tile::Garden))
+        .collect();
+    let center = *gardens.choose(rng)?;
+    let mut flowers = 0;
+    for flower in entities.flowers.values_mut() {
+        let in_garden = matches!(world[flower.position], Tile::Garden);
+        if in_garden && world.distance(center, flower.position) <= radius {
+            flower.pollen = flower.pollen.saturating_mul(2).min(flower.max_pollen);
+            flowers += 1;
+        }
+    }
+    Some(Event::Bloom {
+        center,
+        radius,
+        flowers,
+    })
+}
+
+/// Send a flock of `size` birds across the map in a random direction,
+/// starting from the edge they fly in from.
+fn migration<R: Rng + ?Sized>(
+    rng: &mut R,
+    entities: &mut Entities,
+    world: &World,
+    size: usize,
+) -> Option<Event> {
+    let direction = *world.grid.directions().choose(rng)?;
+    let edge = world.edge(direction);
+    let flight = world.width.max(world.height) as u32;
+    let starts = edge.choose_multiple(rng, size);
+    let birds: Vec<_> = starts
+        .map(|&pos| Bird::new(pos, direction, flight))
+        .collect();
+    let count = birds.len();
+    entities.birds.extend(birds);
+    Some(Event::Migration {
+        direction,
+        birds: count,
+    })
+}
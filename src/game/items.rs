@@ -0,0 +1,106 @@
+//! Power-up items that bees can collect.
+//!
+//! Items [occasionally][`ItemConfig::spawn_chance`] appear on passable tiles.
+//! The first bee to move onto an item collects it,
+//! gaining a temporary [power-up][`PowerUp`] for [a while][`ItemConfig::duration`].
+
+use rand::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    deserialize_chance,
+    world::{Position, World},
+};
+
+/// The kinds of item that can spawn on the map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    /// The bee uses no energy while the power-up lasts.
+    EnergyDrink,
+    /// The bee can collect pollen from flowers on neighbouring tiles.
+    PollenMagnet,
+    /// Birds can't eat the bee while the power-up lasts.
+    BirdRepellent,
+}
+
+impl ItemKind {
+    /// Every kind of item, in declaration order.
+    pub const ALL: [ItemKind; 3] = [
+        ItemKind::EnergyDrink,
+        ItemKind::PollenMagnet,
+        ItemKind::BirdRepellent,
+    ];
+}
+
+/// An item lying on the map, waiting to be collected.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Item {
+    /// What the item does.
+    pub kind: ItemKind,
+    /// Where the item is.
+    pub position: Position,
+}
+
+/// A temporary effect on a bee, granted by collecting an [`Item`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PowerUp {
+    /// What the power-up does.
+    pub kind: ItemKind,
+    /// The number of turns left before the power-up wears off.
+    pub ticks: u32,
+}
+
+/// Configure how often items spawn, and how long their effects last.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ItemConfig {
+    /// Chance that a new item spawns each tick.
+    ///
+    /// If zero, no items spawn.
+    #[serde(deserialize_with = "deserialize_chance")]
+    pub spawn_chance: f64,
+    /// The most items that can be on the map at once.
+    pub max_items: usize,
+    /// The number of turns a power-up lasts once collected.
+    pub duration: u32,
+    /// How many tiles away a bee with a pollen magnet can collect pollen from.
+    pub magnet_range: i32,
+}
+
+impl Default for ItemConfig {
+    fn default() -> Self {
+        Self {
+            spawn_chance: 0.0,
+            max_items: 3,
+            duration: 20,
+            magnet_range: 1,
+        }
+    }
+}
+
+/// Possibly spawn a new item of a random kind on a random free passable tile.
+///
+/// Does nothing if there are already [`ItemConfig::max_items`] items on the map,
+/// or if the chosen tile is impassable or already has an item on it.
+pub(super) fn spawn<R: Rng + ?Sized>(
+    rng: &mut R,
+    items: &[Item],
+    world: &World,
+    config: &ItemConfig,
+) -> Option<Item> {
+    if items.len() >= config.max_items || !rng.gen_bool(config.spawn_chance) {
+        return None;
+    }
+    let position = Position::new(
+        rng.gen_range(0..world.width),
+        rng.gen_range(0..world.height),
+    );
+    let passable = world.get(position).is_some_and(|t| t.is_passable());
+    if !passable || items.iter().any(|i| i.position == position) {
+        return None;
+    }
+    let kind = *ItemKind::ALL.choose(rng)?;
+    Some(Item { kind, position })
+}
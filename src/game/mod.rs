@@ -11,20 +11,59 @@
 //! See their documentation for more information.
 //!
 //! User input is provided by the [`Moves`] type.
-//! This is a map from the target bee to the desired action.
+//! This is a map from the target bee to the desired [`Action`].
 //! A specific bee is targeted using [`Player`] and [`BeeID`] values.
 //! Any moves which do not specify a valid target are ignored.
 //!
-//! The game never finishes.
+//! By default the game never finishes.
 //! In theory, as long as input is provided, a game could run forever.
-//! However, a driver may wish to set a "finish" point,
-//! for example a certain number of ticks.
-//! However, this is up to the driver to decide and implement.
+//! However, the [configuration][Config] may set a "finish" point,
+//! such as a certain number of ticks or a target score;
+//! a driver can check this with [`State::is_finished`].
+//! Actually stopping the game is up to the driver to implement.
+//! Likewise, the driver decides when a player has left for good;
+//! [removing][State::remove_player] them frees up their spawn points for new players.
+//!
+//! Players may be grouped into teams, either by the [configuration][Config::teams]
+//! or when they are [added to the game][State::add_player].
+//! Each team's score is the total score of its members' hives.
+//! Allied players can [donate pollen][State::transfer_pollen] to each other.
+//!
+//! If the [configuration][Config::hive_cost] allows it,
+//! players can also spend pollen to [build extra hives][State::build_hive],
+//! or to [relocate][State::relocate_hive] a hive to a better spot.
+//! Pollen can also be spent on [upgrades][State::upgrade_hive] for a hive,
+//! improving the bees it spawns from then on.
+//!
+//! Each hive has a queen, which is lost if a bird reaches the hive
+//! while none of the player's bees are there to guard it,
+//! though by default birds [steer around hives][BirdConfig::avoid_hives].
+//! A hive without a queen doesn't spawn bees until one of the player's bees
+//! escorts a new queen to it from an unused spawn point.
+//! A player's score and statistics are the totals over all of their hives.
+//!
+//! The [weather][`weather::Weather`] may also change as the game goes on,
+//! affecting flowers and bees until it changes again.
+//! Longer-term changes to how often things spawn can be scripted with [seasons][Config::seasons].
+//! Bees can also collect [power-up items][`items::Item`] that appear on the map from time to time,
+//! and may need to band together against [wasp nests][`wasps::Nest`] that raid their hives.
+//!
+//! Nothing here depends on the server or its networking,
+//! so the game can also be built for WebAssembly (`wasm32-unknown-unknown`),
+//! where every tick runs on a single thread.
+//! This lets clients in a browser run the game themselves,
+//! for example to predict what happens between ticks, or to try out moves ahead of time.
 
+pub mod achievements;
 mod entity;
+pub mod events;
+pub mod items;
+pub mod wasps;
+pub mod weather;
 pub mod world;
 
 use std::{
+    collections::{BTreeMap, HashMap, HashSet},
     fmt,
     ops::RangeInclusive,
     sync::{
@@ -33,17 +72,33 @@ use std::{
     },
 };
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use rand::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
 use serde::{Deserialize, Serialize};
 
-use entity::{Bee, Bird, Car, Flower, Hive};
-pub use entity::{BeeID, Moves};
+use achievements::{Achievement, Achievements};
+pub use entity::{
+    Action, Bee, BeeID, Bird, BirdConfig, Car, Cosmetics, Dance, Flower, FlowerID, GroupMove, Hive,
+    HoneyConfig, Move, Moves, Role, RoleWeights, Species, Stage, Stats, Transit, Upgrade, Upgrades,
+    MAX_PLAN_LENGTH,
+};
+use entity::{Interaction, HIVE_SIGHT_RANGE};
 
-use self::world::{Position, World};
+use self::{
+    events::{Event, EventConfig},
+    items::{Item, ItemConfig, PowerUp},
+    wasps::{Nest, Wasp, WaspConfig},
+    weather::{Weather, WeatherConfig},
+    world::{Direction, Position, Rect, SpawnWeights, World},
+};
 
 /// Uniquely identifies a player.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
 #[serde(transparent)]
 pub struct Player(usize);
 
@@ -74,6 +129,12 @@ impl Player {
     pub fn is_observer(self) -> bool {
         self.0 == 0
     }
+
+    /// The number identifying the player, as sent to clients.
+    #[must_use]
+    pub fn id(self) -> usize {
+        self.0
+    }
 }
 
 impl Default for Player {
@@ -93,19 +154,150 @@ impl fmt::Display for Player {
 }
 
 /// Configure game rules and constants.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Config {
     /// The tile map used by the game.
     pub world: World,
+    /// A list of maps to rotate between when playing multiple games.
+    ///
+    /// If not empty, each game is played on one of these maps instead of [`Config::world`];
+    /// see [`Config::select_map`].
+    pub maps: Vec<World>,
+    /// How to choose the next map from [`Config::maps`].
+    pub map_rotation: MapRotation,
+    /// How to choose the spawn point for each new player's hive.
+    pub spawn_strategy: SpawnStrategy,
     /// Chance that a flower will spawn each turn.
     #[serde(deserialize_with = "deserialize_chance")]
     pub flower_spawn_chance: f64,
     /// The initial pollen value for a newly spawned flower.
     pub flower_initial_pollen: RangeInclusive<i32>,
+    /// Whether flowers spawn as different [species][entity::Species],
+    /// with their own amounts of pollen and regrowth.
+    ///
+    /// If not, every flower is a [wildflower][entity::Species::Wildflower].
+    pub flower_species: bool,
+    /// Chance that each pollinated flower seeds a new flower on a neighbouring tile each turn.
+    ///
+    /// If zero, the default, flowers never seed new flowers.
+    #[serde(deserialize_with = "deserialize_chance")]
+    pub flower_seed_chance: f64,
+    /// The number of turns after which a flower wilts, even if it still has pollen.
+    ///
+    /// If `None`, flowers only die once they run out of pollen.
+    pub flower_max_age: Option<u32>,
     /// How likely a player is to spawn a new bee each turn.
     #[serde(deserialize_with = "deserialize_chance")]
     pub bee_spawn_chance: f64,
+    /// The most pollen a bee can carry at once, or `None` for no limit.
+    ///
+    /// Smaller limits make bees return to their hive more often.
+    pub bee_max_pollen: Option<i32>,
+    /// The number of turns a bee lives for, or `None` for no limit.
+    ///
+    /// Bees also die once they run out of energy, whatever their age.
+    pub bee_max_age: Option<u32>,
+    /// The number of turns new bees spend as larvae in their hive before they can move.
+    ///
+    /// Each hive's initial bees are always fully grown.
+    pub larva_ticks: u32,
+    /// How much extra energy bees use each turn for the pollen they carry.
+    pub laden_drain: LadenDrain,
+    /// The energy below which bees become exhausted, and can only move every other turn.
+    ///
+    /// If `None`, bees never get exhausted.
+    pub exhaustion_threshold: Option<i32>,
+    /// The number of turns a bee can spend away from a hive before its pollen starts to decay.
+    ///
+    /// After that, the bee loses one pollen each turn until it rests at a hive.
+    /// If `None`, pollen never decays.
+    pub pollen_decay_ticks: Option<u32>,
+    /// How likely new bees are to be given each role.
+    pub role_weights: RoleWeights,
+    /// How likely a new car is to drive onto the map each turn.
+    ///
+    /// Cars enter and leave at roads along the edges of the map.
+    #[serde(deserialize_with = "deserialize_chance")]
+    pub car_spawn_chance: f64,
+    /// The most cars that can be on the map at once.
+    pub max_cars: usize,
+    /// What happens when bees belonging to rival players meet on the same tile.
+    pub bee_contact: BeeContact,
+    /// The most bees that can be on one tile at once, or `None` for no limit.
+    ///
+    /// Bees that would move onto a full tile stay where they were instead,
+    /// and hives don't spawn bees while their tile is full.
+    pub max_bees_per_tile: Option<usize>,
+    /// How many birds fly around the map, and how they behave.
+    pub birds: BirdConfig,
+    /// The combined strength of bees needed on a bird's tile to chase it away.
+    ///
+    /// Most bees have a strength of one, but guards are stronger;
+    /// see [`Role::strength`].
+    pub bird_chase_strength: u32,
+    /// The number of ticks after which the game finishes, if any.
+    pub max_ticks: Option<u64>,
+    /// The total score at which the game finishes, if any.
+    pub target_score: Option<i32>,
+    /// The most players that can take part in the game, or `None` for no limit.
+    ///
+    /// Either way, no more players can join once every spawn point is taken.
+    pub max_players: Option<usize>,
+    /// The password players must give to join the game, if any.
+    ///
+    /// Observers can watch without a password.
+    pub password: Option<String>,
+    /// Invite codes that let players join the game,
+    /// mapping each code to the number of players that can use it.
+    ///
+    /// If not empty, new players must give one of these codes to join;
+    /// players who already used a code can rejoin without using it again.
+    /// Observers can watch without an invite.
+    pub invites: BTreeMap<String, u32>,
+    /// Teams of players, mapping each team's name to the names of its members.
+    ///
+    /// Players listed here are always put on their assigned team;
+    /// other players may choose a team when they register.
+    pub teams: BTreeMap<String, Vec<String>>,
+    /// The amount of pollen a player must spend to build an extra hive.
+    ///
+    /// If `None`, players cannot build extra hives.
+    pub hive_cost: Option<i32>,
+    /// The amount of pollen a player must spend to relocate a hive.
+    ///
+    /// If `None`, players cannot relocate their hives.
+    pub relocation_cost: Option<i32>,
+    /// The number of ticks a hive spends in transit while being relocated.
+    pub relocation_ticks: u32,
+    /// The amount of pollen a player must spend on the first level of a hive upgrade.
+    ///
+    /// Each later level costs this much more than the previous one.
+    /// If `None`, players cannot upgrade their hives.
+    pub upgrade_cost: Option<i32>,
+    /// The highest level each hive upgrade can reach.
+    pub max_upgrade_level: u32,
+    /// Whether players can only see what's near their bees and hives.
+    ///
+    /// How far a bee can see depends on its [role][`Role::sight_range`].
+    pub fog_of_war: bool,
+    /// How the weather changes over the course of the game.
+    pub weather: WeatherConfig,
+    /// Points during the game at which spawn chances and flower pollen reach new values.
+    ///
+    /// The values are interpolated between seasons, starting from the values above;
+    /// see [`Season`].
+    pub seasons: Vec<Season>,
+    /// How often random world events happen.
+    pub events: EventConfig,
+    /// How often power-up items spawn, and how strong they are.
+    pub items: ItemConfig,
+    /// How often wasp nests appear, and how dangerous they are.
+    pub wasps: WaspConfig,
+    /// Whether bees also collect nectar, which hives make into honey.
+    ///
+    /// If set, each hive's score is the honey it has made rather than the pollen delivered to it.
+    pub honey: Option<HoneyConfig>,
 }
 
 impl Default for Config {
@@ -113,8 +305,295 @@ impl Default for Config {
         Self {
             flower_spawn_chance: 0.05,
             flower_initial_pollen: 3..=5,
+            flower_species: false,
+            flower_seed_chance: 0.0,
+            flower_max_age: None,
             bee_spawn_chance: 0.03,
+            bee_max_pollen: None,
+            bee_max_age: None,
+            larva_ticks: 0,
+            laden_drain: LadenDrain::default(),
+            exhaustion_threshold: None,
+            pollen_decay_ticks: None,
+            role_weights: RoleWeights::default(),
+            car_spawn_chance: 0.0,
+            max_cars: 4,
+            bee_contact: BeeContact::Off,
+            max_bees_per_tile: None,
+            birds: BirdConfig::default(),
+            bird_chase_strength: 3,
+            max_ticks: None,
+            target_score: None,
+            max_players: None,
+            password: None,
+            invites: BTreeMap::new(),
+            teams: BTreeMap::new(),
+            hive_cost: None,
+            relocation_cost: None,
+            relocation_ticks: 10,
+            upgrade_cost: None,
+            max_upgrade_level: 3,
+            fog_of_war: false,
+            weather: WeatherConfig::default(),
+            seasons: Vec::new(),
+            events: EventConfig::default(),
+            items: ItemConfig::default(),
+            wasps: WaspConfig::default(),
+            honey: None,
             world: World::default(),
+            maps: Vec::new(),
+            map_rotation: MapRotation::Cycle,
+            spawn_strategy: SpawnStrategy::Spread,
+        }
+    }
+}
+
+impl Config {
+    /// Choose the map to use for the `game`th game played with this configuration,
+    /// counting from zero.
+    ///
+    /// Replaces [`Config::world`] with a map from [`Config::maps`], if there are any.
+    pub fn select_map<R: Rng + ?Sized>(&mut self, rng: &mut R, game: usize) {
+        let chosen = match self.map_rotation {
+            MapRotation::Cycle => self.maps.get(game % self.maps.len().max(1)),
+            MapRotation::Random => self.maps.choose(rng),
+        };
+        if let Some(world) = chosen {
+            self.world = world.clone();
+        }
+    }
+
+    /// The team that the player registered as `name` is assigned to, if any.
+    #[must_use]
+    pub fn team_for(&self, name: &str) -> Option<&str> {
+        self.teams
+            .iter()
+            .find(|(_, members)| members.iter().any(|m| m == name))
+            .map(|(team, _)| team.as_str())
+    }
+
+    /// Check that a game can be played with this configuration,
+    /// before it's used for a new game or to reload the current one.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a range of values is empty, or if a chance is not between 0 and 1.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut ranges = vec![(
+            String::from("flower_initial_pollen"),
+            &self.flower_initial_pollen,
+        )];
+        let mut chances = vec![
+            (
+                String::from("flower_spawn_chance"),
+                self.flower_spawn_chance,
+            ),
+            (String::from("flower_seed_chance"), self.flower_seed_chance),
+            (String::from("bee_spawn_chance"), self.bee_spawn_chance),
+            (String::from("car_spawn_chance"), self.car_spawn_chance),
+            (String::from("birds.aggression"), self.birds.aggression),
+            (
+                String::from("weather.change_chance"),
+                self.weather.change_chance,
+            ),
+            (
+                String::from("weather.wind_push_chance"),
+                self.weather.wind_push_chance,
+            ),
+            (String::from("events.chance"), self.events.chance),
+            (String::from("items.spawn_chance"), self.items.spawn_chance),
+            (
+                String::from("wasps.nest_spawn_chance"),
+                self.wasps.nest_spawn_chance,
+            ),
+        ];
+        for (i, season) in self.seasons.iter().enumerate() {
+            let name = |field| format!("seasons[{}].{}", i, field);
+            ranges.push((name("flower_initial_pollen"), &season.flower_initial_pollen));
+            chances.push((name("flower_spawn_chance"), season.flower_spawn_chance));
+            chances.push((name("bee_spawn_chance"), season.bee_spawn_chance));
+        }
+
+        for (name, range) in ranges {
+            if range.is_empty() {
+                bail!("{} must not be an empty range, but is {:?}", name, range);
+            }
+        }
+        for (name, chance) in chances {
+            if !(0.0..=1.0).contains(&chance) {
+                bail!("{} must be between 0 and 1, but is {}", name, chance);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How much extra energy bees use each turn for carrying pollen.
+///
+/// A bee carrying `pollen` uses an extra `scale * pollen ^ exponent` energy each turn,
+/// rounded down.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LadenDrain {
+    /// How much the extra drain is multiplied by.
+    ///
+    /// Zero, the default, turns off the extra drain.
+    pub scale: f64,
+    /// How steeply the extra drain grows with the amount of pollen carried.
+    pub exponent: f64,
+}
+
+impl Default for LadenDrain {
+    fn default() -> Self {
+        Self {
+            scale: 0.0,
+            exponent: 1.5,
+        }
+    }
+}
+
+impl LadenDrain {
+    /// The extra energy used by a bee carrying `pollen` this turn.
+    #[must_use]
+    pub fn extra(&self, pollen: i32) -> i32 {
+        if pollen <= 0 {
+            return 0;
+        }
+        let drain = self.scale * f64::from(pollen).powf(self.exponent);
+        // float to int casts saturate, so silly configurations can't overflow
+        drain.max(0.0).floor() as i32
+    }
+}
+
+/// Values that change gradually over the course of a game.
+///
+/// Each value changes linearly from the previous season (or the game's opening configuration)
+/// until it reaches the season's value on the season's tick.
+/// The values of the last season are kept for the rest of the game.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Season {
+    /// The tick on which the season's values are reached.
+    pub tick: u64,
+    /// See [`Config::flower_spawn_chance`].
+    #[serde(deserialize_with = "deserialize_chance")]
+    pub flower_spawn_chance: f64,
+    /// See [`Config::flower_initial_pollen`].
+    pub flower_initial_pollen: RangeInclusive<i32>,
+    /// See [`Config::bee_spawn_chance`].
+    #[serde(deserialize_with = "deserialize_chance")]
+    pub bee_spawn_chance: f64,
+}
+
+impl Season {
+    /// The values from a configuration, as a season starting on the first tick.
+    #[must_use]
+    fn opening(config: &Config) -> Self {
+        Self {
+            tick: 0,
+            flower_spawn_chance: config.flower_spawn_chance,
+            flower_initial_pollen: config.flower_initial_pollen.clone(),
+            bee_spawn_chance: config.bee_spawn_chance,
+        }
+    }
+
+    /// The values on `tick`, part of the way from this season to the `next` one.
+    #[must_use]
+    fn blend(&self, next: &Season, tick: u64) -> Season {
+        if next.tick <= self.tick {
+            return next.clone();
+        }
+        let t = (tick.saturating_sub(self.tick) as f64 / (next.tick - self.tick) as f64).min(1.0);
+        let lerp = |a: f64, b: f64| a + (b - a) * t;
+        let lerp_int = |a: i32, b: i32| lerp(f64::from(a), f64::from(b)).round() as i32;
+        let (prev_pollen, next_pollen) = (&self.flower_initial_pollen, &next.flower_initial_pollen);
+        Season {
+            tick,
+            flower_spawn_chance: lerp(self.flower_spawn_chance, next.flower_spawn_chance),
+            flower_initial_pollen: lerp_int(*prev_pollen.start(), *next_pollen.start())
+                ..=lerp_int(*prev_pollen.end(), *next_pollen.end()),
+            bee_spawn_chance: lerp(self.bee_spawn_chance, next.bee_spawn_chance),
+        }
+    }
+
+    /// Use the season's values in place of the configured ones.
+    fn apply(&self, config: &mut Config) {
+        config.flower_spawn_chance = self.flower_spawn_chance;
+        config.flower_initial_pollen = self.flower_initial_pollen.clone();
+        config.bee_spawn_chance = self.bee_spawn_chance;
+    }
+}
+
+/// What happens when bees belonging to rival players end up on the same tile.
+///
+/// Bees belonging to the same player or to teammates never interact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BeeContact {
+    /// Nothing; bees share tiles peacefully.
+    #[default]
+    Off,
+    /// Bees that moved onto a tile with a rival bee are bumped back to where they came from.
+    Bump,
+    /// Each bee steals one pollen from a rival bee on the same tile, if it has room.
+    ///
+    /// Two bees only meet once each tick, so a bee can't steal back what was just taken from it.
+    Steal,
+}
+
+/// How to choose between maps when playing multiple games.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MapRotation {
+    /// Use each map in order, starting again from the first map after the last.
+    Cycle,
+    /// Choose a random map for each game.
+    Random,
+}
+
+/// How to choose where each new player's hive goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpawnStrategy {
+    /// Take spawn points in reverse reading order, starting from the bottom right of the map.
+    Sequential,
+    /// Take the spawn point as far as possible from every existing hive.
+    #[default]
+    Spread,
+    /// Take turns between the four quarters of the map,
+    /// choosing the quarter with the fewest hives in it.
+    Regions,
+}
+
+impl SpawnStrategy {
+    /// Choose one of the `free` spawn points for a new hive,
+    /// given the positions of the existing `hives`.
+    ///
+    /// Returns the index of the chosen spawn point, or `None` if there are no free spawn points.
+    #[must_use]
+    fn choose(self, world: &World, free: &[Position], hives: &[Position]) -> Option<usize> {
+        let last = free.len().checked_sub(1)?;
+        let nearest = |pos| hives.iter().map(|&h| world.distance(pos, h)).min();
+        match self {
+            SpawnStrategy::Sequential => Some(last),
+            SpawnStrategy::Spread => {
+                // prefer later spawn points on ties, so the first player gets the same as before
+                (0..free.len()).max_by_key(|&i| nearest(free[i]))
+            }
+            SpawnStrategy::Regions => {
+                // opposite corners first, so the first two players are far apart
+                const ORDER: [(bool, bool); 4] =
+                    [(true, true), (false, false), (true, false), (false, true)];
+                let region = |pos: Position| {
+                    let quarter = (pos.x * 2 >= world.width, pos.y * 2 >= world.height);
+                    ORDER.iter().position(|&q| q == quarter).unwrap_or_default()
+                };
+                let crowding = |pos| hives.iter().filter(|&&h| region(h) == region(pos)).count();
+                // within a region, spread out as usual
+                (0..free.len()).rev().min_by_key(|&i| {
+                    let pos = free[i];
+                    (crowding(pos), region(pos), std::cmp::Reverse(nearest(pos)))
+                })
+            }
         }
     }
 }
@@ -134,6 +613,84 @@ fn deserialize_chance<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Resu
     }
 }
 
+/// The final summary of a player's performance in a game.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Summary {
+    /// The amount of pollen stored in the player's hives.
+    pub score: i32,
+    /// The player's position in the game, starting from 1.
+    ///
+    /// Players with equal scores share the same rank.
+    pub rank: usize,
+    /// The team the player was on, if any.
+    pub team: Option<String>,
+    /// The combined score of every player on the player's team, if any.
+    pub team_score: Option<i32>,
+    /// Statistics about the player's activity over the game.
+    #[serde(flatten)]
+    pub stats: Stats,
+}
+
+/// The number of entities at which independent phases of a tick are spread over several threads.
+///
+/// With fewer entities than this, the cost of splitting up the work outweighs the gain.
+#[cfg(not(target_arch = "wasm32"))]
+const PARALLEL_THRESHOLD: usize = 512;
+
+/// Call `f` on each of the `items`, in parallel if there are enough of them.
+///
+/// Always runs on the current thread on WebAssembly, which has no threads to spread the work over.
+fn for_each_mut<T, F>(items: &mut [T], f: F)
+where
+    T: Send,
+    F: Fn(&mut T) + Send + Sync,
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    if items.len() >= PARALLEL_THRESHOLD {
+        items.par_iter_mut().for_each(f);
+        return;
+    }
+    items.iter_mut().for_each(f);
+}
+
+/// Collect the result of calling `f` on each of the `items`, in parallel if there are enough of them.
+///
+/// Always runs on the current thread on WebAssembly, like [`for_each_mut`].
+fn map<T, U, F>(items: &[T], f: F) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> U + Send + Sync,
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    if items.len() >= PARALLEL_THRESHOLD {
+        return items.par_iter().map(f).collect();
+    }
+    items.iter().map(f).collect()
+}
+
+/// Whether the secret `given` matches the `expected` one, such as a game's password.
+///
+/// Takes just as long wherever the two differ, so secrets can't be guessed a byte at a time.
+/// Public so that the server checks its admin token the same way.
+#[must_use]
+pub fn same_secret(given: &[u8], expected: &[u8]) -> bool {
+    let diff = (given.iter().zip(expected)).fold(0, |diff, (a, b)| diff | (a ^ b));
+    given.len() == expected.len() && diff == 0
+}
+
+/// Whether players `a` and `b` are allies: either the same player,
+/// or on the same team according to their `hives`.
+///
+/// Players who aren't on a team are only allied with themselves.
+fn are_allies(hives: &[Hive], a: Player, b: Player) -> bool {
+    let team = |player| {
+        let hive = hives.iter().find(|h| h.player == player)?;
+        hive.team.as_deref()
+    };
+    a == b || team(a).is_some() && team(a) == team(b)
+}
+
 /// Manage mutable entities in the game.
 #[derive(Debug, Clone, Serialize)]
 struct Entities {
@@ -141,77 +698,533 @@ struct Entities {
     bees: Vec<Bee>,
     /// The active player hives.
     hives: Vec<Hive>,
-    /// The currently living flowers.
-    flowers: Vec<Flower>,
+    /// The currently living flowers, indexed by their position.
+    ///
+    /// There is at most one flower on each tile.
+    flowers: HashMap<Position, Flower>,
     /// All birds in the game.
     birds: Vec<Bird>,
     /// All cars in the game.
     cars: Vec<Car>,
+    /// Power-up items waiting to be collected.
+    items: Vec<Item>,
+    /// Wasp nests on the map.
+    nests: Vec<Nest>,
+    /// Wasps out raiding hives.
+    wasps: Vec<Wasp>,
+    /// The waggle dances performed on the last tick.
+    ///
+    /// Only shown to the dancing player and their teammates; see [`Serializer::view`].
+    #[serde(skip)]
+    dances: Vec<Dance>,
+    /// The tiles that gained (`true`) or lost (`false`) a flower
+    /// since the [spawn weights][SpawnWeights] were last brought up to date.
+    #[serde(skip)]
+    flower_changes: Vec<(Position, bool)>,
 }
 
 impl Entities {
     /// Create the set of entities for the game with given world.
     ///
-    /// # TODO
-    ///
-    /// Do more than just "create nothing"; in particular, should create birds and cars.
+    /// Games start out empty: birds, cars and flowers are spawned as the game is ticked.
     #[must_use]
     fn new<R: Rng + ?Sized>(_rng: &mut R, _world: &World) -> Self {
         Entities {
             bees: Vec::new(),
             hives: Vec::new(),
-            flowers: Vec::new(),
+            flowers: HashMap::new(),
             birds: Vec::new(),
             cars: Vec::new(),
+            items: Vec::new(),
+            nests: Vec::new(),
+            wasps: Vec::new(),
+            dances: Vec::new(),
+            flower_changes: Vec::new(),
+        }
+    }
+
+    /// Combine the hives of each player.
+    ///
+    /// Returns each player's first hive, along with their total score and statistics,
+    /// in the order the players joined the game.
+    fn totals(&self) -> Vec<(&Hive, i32, Stats)> {
+        let mut totals: Vec<(&Hive, i32, Stats)> = Vec::new();
+        let mut index = HashMap::new();
+        for hive in &self.hives {
+            match index.get(&hive.player) {
+                Some(&i) => {
+                    let (_, score, stats) = &mut totals[i];
+                    *score += hive.score();
+                    *stats += hive.stats;
+                }
+                None => {
+                    index.insert(hive.player, totals.len());
+                    totals.push((hive, hive.score(), hive.stats));
+                }
+            }
         }
+        totals
     }
 
     /// Perform one game tick. See also [`State::tick`].
-    fn tick<R: Rng + ?Sized>(&mut self, config: &Config, rng: &mut R, moves: &Moves) {
+    ///
+    /// New flowers spawn according to the `spawn_weights`, which are kept up to date,
+    /// as are the `owners` of each bee as bees spawn and die.
+    /// Returns the bees that couldn't move because their destination was full.
+    fn tick<R: Rng + ?Sized>(
+        &mut self,
+        config: &Config,
+        rng: &mut R,
+        moves: &Moves,
+        weather: Weather,
+        spawn_weights: &mut SpawnWeights,
+        owners: &mut HashMap<BeeID, Player>,
+    ) -> Vec<(Player, BeeID)> {
         let world = &config.world;
 
         // move animated entities
-        for bee in &mut self.bees {
-            bee.step(moves, world);
+        let previous: Vec<_> = self.bees.iter().map(|b| b.position).collect();
+        for_each_mut(&mut self.bees, |bee| bee.step(moves, config, weather));
+        if let Weather::Wind { direction } = weather {
+            for bee in &mut self.bees {
+                if rng.gen_bool(config.weather.wind_push_chance) {
+                    bee.blow(world, direction);
+                }
+            }
         }
-        for bird in &mut self.birds {
-            bird.step(world);
+        self.contact(config.bee_contact, &previous);
+        let blocked = self.limit_crowding(config.max_bees_per_tile, &previous);
+        self.spawn_bird(world, config, rng);
+        for index in 0..self.birds.len() {
+            let bird = &self.birds[index];
+            let heading = bird.heading(rng, world, &config.birds, &self.bees, &self.birds);
+            self.birds[index].heading = heading;
+        }
+        let hives = &self.hives;
+        let avoid = |pos| config.birds.avoid_hives && hives.iter().any(|h| h.position == pos);
+        for_each_mut(&mut self.birds, |bird| {
+            bird.step(world, config.birds.speed, avoid)
+        });
+        self.birds.retain(|b| !b.has_left(world));
+        for index in 0..self.cars.len() {
+            let mut car = self.cars[index].clone();
+            car.step(rng, world, |pos, dir| self.blocks_traffic(world, pos, dir));
+            self.cars[index] = car;
+        }
+        self.cars.retain(|c| !c.has_left(world));
+        self.spawn_car(world, config, rng);
+
+        self.handoff_pollen(moves);
+        self.dance(moves);
+
+        self.chase_birds(config);
+
+        // birds attack unguarded queens, and bees collect new queens for queenless hives
+        for hive in &mut self.hives {
+            hive.protect_queen(&self.bees, &self.birds);
         }
-        for car in &mut self.cars {
-            car.step(world);
+        self.collect_queens(world);
+
+        // note any bees that just dodged a bird
+        for (bee, &from) in self.bees.iter().zip(&previous) {
+            let dodged = bee.position != from && self.birds.iter().any(|b| b.position == from);
+            if dodged && bee.is_alive(&self.birds, &self.cars) {
+                if let Some(hive) = self.hives.iter_mut().find(|h| h.player == bee.player) {
+                    hive.stats.bird_strikes_survived += 1;
+                }
+            }
         }
 
         // bees on their own hives transfer pollen and increase score
+        let mut on_tile: HashMap<Position, Vec<&mut Bee>> = HashMap::new();
+        for bee in &mut self.bees {
+            on_tile.entry(bee.position).or_default().push(bee);
+        }
         for hive in &mut self.hives {
-            hive.handle_bees(&mut self.bees);
+            let here = on_tile.get_mut(&hive.position).into_iter().flatten();
+            hive.handle_bees(here.map(|b| &mut **b), config.honey.is_some());
+            if let Some(honey) = &config.honey {
+                hive.make_honey(honey.rate);
+            }
         }
 
         // filter dead bees
         let birds = &self.birds;
         let cars = &self.cars;
-        self.bees.retain(|b| b.is_alive(birds, cars));
+        let alive = map(&self.bees, |b| b.is_alive(birds, cars));
+        let mut alive = alive.into_iter();
+        let hives = &mut self.hives;
+        self.bees.retain(|b| {
+            let alive = alive.next().unwrap_or(true);
+            if !alive {
+                owners.remove(&b.id);
+                if let Some(hive) = hives.iter_mut().find(|h| h.player == b.player) {
+                    hive.stats.bees_lost += 1;
+                }
+            }
+            alive
+        });
+
+        self.collect_items(config);
+        wasps::tick(rng, self, world, &config.wasps);
 
         // transfer pollen between bees and flowers
+        // (one bee at a time, since bees on the same tile share a flower)
         for bee in &mut self.bees {
-            bee.transfer_pollen(&mut self.flowers);
+            if let Some(interaction) = bee.transfer_pollen(&mut self.flowers, config) {
+                if let Some(hive) = self.hives.iter_mut().find(|h| h.player == bee.player) {
+                    match interaction {
+                        Interaction::Pollinated => hive.stats.flowers_pollinated += 1,
+                        Interaction::Visited => hive.stats.flowers_visited += 1,
+                        Interaction::Collected => {}
+                    }
+                }
+            }
         }
 
         // spawn new flowers with small chance each turn
-        let new_flowers = world.spawn_flowers(rng, config, &self.flowers);
-        self.flowers.extend(new_flowers);
+        let chance = (config.flower_spawn_chance * weather.flower_spawn_factor()).min(1.0);
+        spawn_weights.update(world, self.flower_changes.drain(..));
+        let new_flowers = world.spawn_flowers(rng, config, chance, spawn_weights);
+        (self.flowers).extend(new_flowers.map(|f| (f.position, f)));
+        self.seed_flowers(world, config, rng);
+
+        for flower in self.flowers.values_mut() {
+            flower.grow();
+        }
+        self.items
+            .extend(items::spawn(rng, &self.items, world, &config.items));
 
-        // clean out any dead flowers
-        // TODO: handle pollination (use drain_filter)
-        self.flowers.retain(|f| f.pollen > 0);
+        // clean out any dead or wilted flowers
+        let max_age = config.flower_max_age;
+        let changes = &mut self.flower_changes;
+        self.flowers.retain(|&pos, f| {
+            let alive = f.pollen > 0 && max_age.is_none_or(|max| f.age < max);
+            if !alive {
+                changes.push((pos, false));
+            }
+            alive
+        });
 
-        // each hive has a small chance of creating a new bee
-        let new_bees = self.hives.iter().filter_map(|h| h.spawn_bee(rng, config));
+        // each hive has a small chance of creating a new bee, if there's room for it
+        let mut crowds: HashMap<Position, usize> = HashMap::new();
+        if config.max_bees_per_tile.is_some() {
+            crowds.extend(self.hives.iter().map(|h| (h.position, 0)));
+            for bee in &self.bees {
+                if let Some(crowd) = crowds.get_mut(&bee.position) {
+                    *crowd += 1;
+                }
+            }
+        }
+        let full = |pos| {
+            let crowd = crowds.get(&pos).copied().unwrap_or(0);
+            config.max_bees_per_tile.is_some_and(|limit| crowd >= limit)
+        };
+        let new_bees: Vec<_> = (self.hives.iter_mut())
+            .filter(|h| !full(h.position))
+            .filter_map(|h| h.spawn_bee(rng, config))
+            .collect();
+        owners.extend(new_bees.iter().map(|b| (b.id, b.player)));
         self.bees.extend(new_bees);
+        blocked
+    }
+
+    /// Send bees back to where they came from if they moved onto a tile
+    /// that already has `limit` bees on it.
+    ///
+    /// Every bee that didn't move keeps its place and counts towards the limit,
+    /// including any bumped back by [contact][BeeContact] or that spawned last turn;
+    /// otherwise bees get in in the order they spawned.
+    /// `previous` holds where each bee was before moving this turn.
+    /// Returns the bees that were sent back.
+    fn limit_crowding(
+        &mut self,
+        limit: Option<usize>,
+        previous: &[Position],
+    ) -> Vec<(Player, BeeID)> {
+        let mut blocked = Vec::new();
+        let limit = match limit {
+            Some(limit) => limit,
+            None => return blocked,
+        };
+        // sending a bee back can crowd the tile it came from, so repeat until nothing changes
+        loop {
+            let mut counts: HashMap<Position, usize> = HashMap::new();
+            for (bee, &from) in self.bees.iter().zip(previous) {
+                if bee.position == from {
+                    *counts.entry(from).or_default() += 1;
+                }
+            }
+            let mut changed = false;
+            for (bee, &from) in self.bees.iter_mut().zip(previous) {
+                if bee.position == from {
+                    continue;
+                }
+                let count = counts.entry(bee.position).or_default();
+                if *count < limit {
+                    *count += 1;
+                } else {
+                    bee.position = from;
+                    blocked.push((bee.player, bee.id));
+                    changed = true;
+                }
+            }
+            if !changed {
+                return blocked;
+            }
+        }
+    }
+
+    /// Record the waggle dances requested by any [`Action::Dance`]s in `moves`.
+    ///
+    /// Bees can only dance at their player's hives,
+    /// and only if the last flower they collected pollen from is still alive.
+    /// Dancing makes the bee forget the flower, so each trip is only danced about once.
+    fn dance(&mut self, moves: &Moves) {
+        self.dances.clear();
+        for bee in &mut self.bees {
+            if moves.get(&(bee.player, bee.id)) != Some(&Action::Dance) {
+                continue;
+            }
+            let at_hive = |h: &Hive| h.player == bee.player && h.position == bee.position;
+            if !self.hives.iter().any(at_hive) {
+                continue;
+            }
+            let last_flower = bee.last_flower.take();
+            let flower = self.flowers.values().find(|f| Some(f.id) == last_flower);
+            if let Some(flower) = flower {
+                self.dances.push(Dance {
+                    player: bee.player,
+                    bee: bee.id,
+                    position: flower.position,
+                });
+            }
+        }
+    }
+
+    /// Whether `viewer` can see dances performed by bees belonging to `dancer`.
+    ///
+    /// Dances are seen by the dancing player and their teammates.
+    /// Observers can see every dance.
+    fn sees_dances_of(&self, viewer: Option<Player>, dancer: Player) -> bool {
+        viewer.is_none_or(|viewer| are_allies(&self.hives, viewer, dancer))
+    }
+
+    /// Let pollinated flowers spread to neighbouring tiles.
+    ///
+    /// Each pollinated flower has a [chance][Config::flower_seed_chance]
+    /// of seeding a new flower of the same species on a random neighbouring tile,
+    /// as long as flowers can grow there and there isn't one already.
+    fn seed_flowers<R: Rng + ?Sized>(&mut self, world: &World, config: &Config, rng: &mut R) {
+        let mut seeded = Vec::new();
+        for flower in self.flowers.values().filter(|f| f.is_pollinated) {
+            if !rng.gen_bool(config.flower_seed_chance) {
+                continue;
+            }
+            let target = world.grid.directions().choose(rng);
+            let target = match target.and_then(|&dir| world.step(flower.position, dir)) {
+                Some(target) => target,
+                None => continue,
+            };
+            let fertile = world.get(target).is_some_and(|t| t.spawn_weight() > 0.0);
+            let occupied = |f: &Flower| f.position == target;
+            let taken = self.flowers.contains_key(&target) || seeded.iter().any(occupied);
+            if fertile && !taken {
+                let pollen = rng.gen_range(config.flower_initial_pollen.clone());
+                let pollen = pollen * flower.species.pollen_factor();
+                seeded.push(Flower::new(target, flower.species, pollen));
+            }
+        }
+        (self.flower_changes).extend(seeded.iter().map(|f| (f.position, true)));
+        (self.flowers).extend(seeded.into_iter().map(|f| (f.position, f)));
+    }
+
+    /// Chase away any birds sharing a tile with enough bees.
+    ///
+    /// Each player with bees on the tile is credited with chasing the bird.
+    fn chase_birds(&mut self, config: &Config) {
+        let bees = &self.bees;
+        let hives = &mut self.hives;
+        self.birds.retain(|bird| {
+            let here = || bees.iter().filter(|b| b.position == bird.position);
+            let strength: u32 = here().map(|b| b.role.strength()).sum();
+            if strength < config.bird_chase_strength {
+                return true;
+            }
+
+            let mut players = Vec::new();
+            for bee in here() {
+                if !players.contains(&bee.player) {
+                    players.push(bee.player);
+                }
+            }
+            for player in players {
+                if let Some(hive) = hives.iter_mut().find(|h| h.player == player) {
+                    hive.stats.birds_chased += 1;
+                }
+            }
+            false
+        });
+    }
+
+    /// Send a bird flying in from a random edge of the map,
+    /// if there are fewer than [`BirdConfig::count`] birds.
+    fn spawn_bird<R: Rng + ?Sized>(&mut self, world: &World, config: &Config, rng: &mut R) {
+        if self.birds.len() >= config.birds.count {
+            return;
+        }
+        if let Some(&heading) = world.grid.directions().choose(rng) {
+            if let Some(&position) = world.edge(heading).choose(rng) {
+                let flight = world.width.max(world.height) as u32;
+                self.birds.push(Bird::new(position, heading, flight));
+            }
+        }
+    }
+
+    /// Whether cars driving in direction `dir` must wait rather than drive onto the given position.
+    ///
+    /// Cars never drive into the back of each other, but can pass oncoming cars.
+    /// They also yield to any bees on [crossings][world::Tile::Crossing].
+    fn blocks_traffic(&self, world: &World, pos: Position, dir: Direction) -> bool {
+        let crossing = world.get(pos).is_some_and(|t| t.is_crossing());
+        let oncoming = dir.opposite();
+        self.cars
+            .iter()
+            .any(|c| c.position == pos && c.facing != oncoming)
+            || crossing && self.bees.iter().any(|b| b.position == pos)
+    }
+
+    /// Possibly send a new car onto the map at a random [road entrance][World::road_entrances].
+    ///
+    /// Cars only spawn while there are fewer than [`Config::max_cars`],
+    /// and never on top of another car.
+    fn spawn_car<R: Rng + ?Sized>(&mut self, world: &World, config: &Config, rng: &mut R) {
+        if self.cars.len() >= config.max_cars || !rng.gen_bool(config.car_spawn_chance) {
+            return;
+        }
+        if let Some(&(position, facing)) = world.road_entrances().choose(rng) {
+            if self.cars.iter().all(|c| c.position != position) {
+                self.cars.push(Car::new(position, facing));
+            }
+        }
+    }
+
+    /// Give any items to the first bee on their tile, which gains the item's power-up.
+    ///
+    /// A bee collecting a new item loses any power-up it already had.
+    fn collect_items(&mut self, config: &Config) {
+        let bees = &mut self.bees;
+        self.items.retain(|item| {
+            let bee = bees.iter_mut().find(|b| b.position == item.position);
+            match bee {
+                Some(bee) => {
+                    bee.power_up = Some(PowerUp {
+                        kind: item.kind,
+                        ticks: config.items.duration,
+                    });
+                    false
+                }
+                None => true,
+            }
+        });
+    }
+
+    /// Have bees on unused spawn points pick up a new queen,
+    /// if their player has a hive that needs one.
+    ///
+    /// Each player only escorts one queen at a time.
+    fn collect_queens(&mut self, world: &World) {
+        let hives = &self.hives;
+        for index in 0..self.bees.len() {
+            let bee = &self.bees[index];
+            let player = bee.player;
+            let on_spawn_point = world.get(bee.position).is_some_and(|t| t.is_spawn_point());
+            let unused = hives.iter().all(|h| h.position != bee.position);
+            let needed = hives.iter().any(|h| h.player == player && !h.queen);
+            let escorting = self.bees.iter().any(|b| b.player == player && b.queen);
+            if on_spawn_point && unused && needed && !escorting {
+                self.bees[index].queen = true;
+            }
+        }
+    }
+
+    /// Let bees belonging to rival players interact when they're on the same tile;
+    /// see [`BeeContact`].
+    ///
+    /// Bees are handled in the order they spawned, so the outcome is always the same.
+    /// `previous` holds where each bee was before moving this turn.
+    fn contact(&mut self, contact: BeeContact, previous: &[Position]) {
+        let hives = &self.hives;
+        let bees = &mut self.bees;
+        let rivals =
+            |a: &Bee, b: &Bee| a.position == b.position && !are_allies(hives, a.player, b.player);
+
+        match contact {
+            BeeContact::Off => {}
+            BeeContact::Bump => {
+                let bumped: Vec<_> = (0..bees.len())
+                    .filter(|&i| bees[i].position != previous[i])
+                    .filter(|&i| bees.iter().any(|other| rivals(&bees[i], other)))
+                    .collect();
+                for i in bumped {
+                    bees[i].position = previous[i];
+                }
+            }
+            BeeContact::Steal => {
+                // each pair of bees only meets once, so pollen isn't just stolen straight back
+                let mut met = HashSet::new();
+                for thief in 0..bees.len() {
+                    if bees[thief].is_full() {
+                        continue;
+                    }
+                    let victim = (0..bees.len()).find(|&v| {
+                        let pair = (thief.min(v), thief.max(v));
+                        bees[v].pollen > 0 && rivals(&bees[thief], &bees[v]) && !met.contains(&pair)
+                    });
+                    if let Some(victim) = victim {
+                        met.insert((thief.min(victim), thief.max(victim)));
+                        bees[victim].pollen -= 1;
+                        bees[thief].pollen += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Transfer pollen between bees as requested by any [`Action::Handoff`]s in `moves`.
+    ///
+    /// Handoffs only happen between bees on the same tile
+    /// that belong to the same player or to teammates.
+    /// The receiving bee only takes as much pollen as it can carry.
+    /// Handoffs are processed in order, so pollen may be passed along a chain of bees.
+    fn handoff_pollen(&mut self, moves: &Moves) {
+        for giver in 0..self.bees.len() {
+            let bee = &self.bees[giver];
+            let target = match moves.get(&(bee.player, bee.id)) {
+                Some(&Action::Handoff(target)) => target,
+                _ => continue,
+            };
+            let receiver = self.bees.iter().position(|b| {
+                let allied = are_allies(&self.hives, b.player, bee.player);
+                b.id == target && b.id != bee.id && b.position == bee.position && allied
+            });
+
+            if let Some(receiver) = receiver {
+                let pollen = bee.pollen.min(self.bees[receiver].space());
+                self.bees[giver].pollen -= pollen;
+                self.bees[receiver].pollen += pollen;
+            }
+        }
     }
 }
 
 /// The current game state.
-#[derive(Debug)]
+///
+/// A state can be cloned to simulate the next few ticks without affecting the original game.
+/// The clone starts with the same random number generator,
+/// but the IDs of any bees or flowers it spawns are never the same as the original's,
+/// so the two soon stop making the same random choices.
+#[derive(Debug, Clone)]
 pub struct State {
     /// Configuration for parameters and chances.
     config: Config,
@@ -219,29 +1232,227 @@ pub struct State {
     spawn_points: Vec<Position>,
     /// This state's random number generator.
     rng: StdRng,
+    /// The number of ticks performed so far.
+    ticks: u64,
 
+    /// The flower spawn weights of each tile, leaving out tiles that already have a flower.
+    spawn_weights: SpawnWeights,
     /// The current entities alive in the game.
-    entities: Entities,
+    ///
+    /// Shared with any [`Serializer`]s still in use,
+    /// and only copied if it changes while one of them still needs the old entities.
+    entities: Arc<Entities>,
+    /// The achievements unlocked by players so far.
+    achievements: Achievements,
+    /// The current weather.
+    weather: Weather,
+    /// The configured values at the start of the game, before any [seasons][Config::seasons].
+    opening: Season,
+    /// Random events that happened since they were last [drained][State::drain_events].
+    events: Vec<Event>,
+    /// Bees that couldn't move onto a full tile since they were last [drained][State::drain_blocked].
+    blocked: Vec<(Player, BeeID)>,
+    /// The player owning each bee alive in the game.
+    ///
+    /// Kept alongside the entities so that checking each move a player sends is cheap.
+    owners: HashMap<BeeID, Player>,
+    /// What each player could see when the state was last [serialised][State::make_serializer],
+    /// if playing with [`Config::fog_of_war`].
+    ///
+    /// Shared with any [`Serializer`]s still in use, like the entities.
+    visibility: Arc<Visibility>,
 }
 
 impl State {
     /// Create a new game.
     #[must_use]
-    pub fn new(config: Config) -> State {
+    pub fn new(mut config: Config) -> State {
+        config.seasons.sort_by_key(|s| s.tick);
+        let opening = Season::opening(&config);
         let spawn_points = config.world.get_spawn_points();
         let mut rng = StdRng::from_entropy();
 
-        // TODO: generate a bunch of entities to start with
-        let entities = Entities::new(&mut rng, &config.world);
+        let entities = Arc::new(Entities::new(&mut rng, &config.world));
+        let spawn_weights = SpawnWeights::new(&config.world);
 
         State {
             config,
             spawn_points,
             rng,
+            ticks: 0,
+            spawn_weights,
             entities,
+            achievements: Achievements::default(),
+            weather: Weather::default(),
+            opening,
+            events: Vec::new(),
+            blocked: Vec::new(),
+            owners: HashMap::new(),
+            visibility: Arc::default(),
         }
     }
 
+    /// Rebuild a game from what a client has been sent:
+    /// the `world` from its registration, and the `snapshot` from its latest update.
+    ///
+    /// This lets clients predict the next few ticks for themselves, such as where their bees end up.
+    /// The predictions are only rough: the server's config isn't sent, so the defaults are used,
+    /// and anything hidden from the client, such as the scores of hives, starts out empty.
+    #[must_use]
+    pub fn from_snapshot(world: World, snapshot: Snapshot) -> State {
+        let mut state = State::new(Config {
+            world,
+            ..Config::default()
+        });
+        let Snapshot {
+            bees,
+            hives,
+            flowers,
+            birds,
+            cars,
+            items,
+            nests,
+            wasps,
+            dances,
+            weather,
+        } = snapshot;
+        // the IDs came from the server, so new bees and flowers mustn't reuse them
+        bees.iter().for_each(|b| b.id.reserve());
+        flowers.iter().for_each(|f| f.id.reserve());
+        let positions = flowers.iter().map(|f| (f.position, true));
+        state.spawn_weights.update(&state.config.world, positions);
+        state.owners = bees.iter().map(|b| (b.id, b.player)).collect();
+        state.weather = weather;
+        state.entities = Arc::new(Entities {
+            bees,
+            hives,
+            flowers: flowers.into_iter().map(|f| (f.position, f)).collect(),
+            birds,
+            cars,
+            items,
+            nests,
+            wasps,
+            dances,
+            flower_changes: Vec::new(),
+        });
+        let spawn_points = std::mem::take(&mut state.spawn_points);
+        state.spawn_points = (spawn_points.into_iter())
+            .filter(|&p| state.is_free_spawn_point(p))
+            .collect();
+        state
+    }
+
+    /// Take on the tunable values of a new `config`, such as spawn chances and flower pollen,
+    /// from the next tick onwards.
+    ///
+    /// Anything that shapes the game as a whole, such as the map, who may join,
+    /// and when the game finishes, is kept as it was when the game started.
+    pub fn reload(&mut self, mut config: Config) {
+        config.seasons.sort_by_key(|s| s.tick);
+        self.opening = Season::opening(&config);
+        let Config {
+            flower_spawn_chance,
+            flower_initial_pollen,
+            flower_species,
+            flower_seed_chance,
+            flower_max_age,
+            bee_spawn_chance,
+            bee_max_pollen,
+            bee_max_age,
+            larva_ticks,
+            laden_drain,
+            exhaustion_threshold,
+            pollen_decay_ticks,
+            role_weights,
+            car_spawn_chance,
+            max_cars,
+            bee_contact,
+            max_bees_per_tile,
+            birds,
+            bird_chase_strength,
+            hive_cost,
+            relocation_cost,
+            relocation_ticks,
+            upgrade_cost,
+            max_upgrade_level,
+            weather,
+            seasons,
+            events,
+            items,
+            wasps,
+            world: _,
+            maps: _,
+            map_rotation: _,
+            spawn_strategy: _,
+            max_ticks: _,
+            target_score: _,
+            max_players: _,
+            password: _,
+            invites: _,
+            teams: _,
+            fog_of_war: _,
+            honey: _,
+        } = config;
+        self.config = Config {
+            flower_spawn_chance,
+            flower_initial_pollen,
+            flower_species,
+            flower_seed_chance,
+            flower_max_age,
+            bee_spawn_chance,
+            bee_max_pollen,
+            bee_max_age,
+            larva_ticks,
+            laden_drain,
+            exhaustion_threshold,
+            pollen_decay_ticks,
+            role_weights,
+            car_spawn_chance,
+            max_cars,
+            bee_contact,
+            max_bees_per_tile,
+            birds,
+            bird_chase_strength,
+            hive_cost,
+            relocation_cost,
+            relocation_ticks,
+            upgrade_cost,
+            max_upgrade_level,
+            weather,
+            seasons,
+            events,
+            items,
+            wasps,
+            ..std::mem::take(&mut self.config)
+        };
+    }
+
+    /// Update the configured spawn chances and flower pollen for the current season.
+    fn change_season(&mut self) {
+        if self.config.seasons.is_empty() {
+            return;
+        }
+        let mut previous = &self.opening;
+        for season in &self.config.seasons {
+            if self.ticks < season.tick {
+                break;
+            }
+            previous = season;
+        }
+        let next = self.config.seasons.iter().find(|s| s.tick > self.ticks);
+        let current = match next {
+            Some(next) => previous.blend(next, self.ticks),
+            None => previous.clone(),
+        };
+        current.apply(&mut self.config);
+    }
+
+    /// The current weather.
+    #[must_use]
+    pub fn weather(&self) -> Weather {
+        self.weather
+    }
+
     /// View the state's world information.
     #[must_use]
     pub fn world(&self) -> &world::World {
@@ -254,50 +1465,644 @@ impl State {
         self.entities.hives.iter().map(Hive::score).sum()
     }
 
+    /// Get the current score of each player in the game.
+    pub fn scores(&self) -> impl Iterator<Item = (Player, i32)> + '_ {
+        let totals = self.entities.totals();
+        totals.into_iter().map(|(h, score, _)| (h.player, score))
+    }
+
+    /// Get the current combined score of each team in the game.
+    #[must_use]
+    pub fn team_scores(&self) -> HashMap<&str, i32> {
+        let mut scores = HashMap::new();
+        for hive in &self.entities.hives {
+            if let Some(team) = &hive.team {
+                *scores.entry(team.as_str()).or_insert(0) += hive.score();
+            }
+        }
+        scores
+    }
+
+    /// The team that `player` is on, if any.
+    #[must_use]
+    pub fn team(&self, player: Player) -> Option<&str> {
+        let hive = self.entities.hives.iter().find(|h| h.player == player)?;
+        hive.team.as_deref()
+    }
+
+    /// The team that the player registered as `name` is assigned to by the configuration.
+    #[must_use]
+    pub fn team_for(&self, name: &str) -> Option<&str> {
+        self.config.team_for(name)
+    }
+
+    /// The most players that can take part in the game, if limited.
+    #[must_use]
+    pub fn max_players(&self) -> Option<usize> {
+        self.config.max_players
+    }
+
+    /// Whether `password` lets a player join the game.
+    ///
+    /// Any password is accepted if the game doesn't have one.
+    /// Passwords are compared in constant time, so they can't be guessed from how long it takes.
+    #[must_use]
+    pub fn check_password(&self, password: Option<&str>) -> bool {
+        let required = self.config.password.as_deref();
+        required.is_none_or(|required| {
+            password.is_some_and(|password| same_secret(password.as_bytes(), required.as_bytes()))
+        })
+    }
+
+    /// The game's [invite codes][Config::invites], and how many players can use each.
+    ///
+    /// Keeping track of how the codes are used is up to the driver,
+    /// so that uses can carry over from one game to the next.
+    #[must_use]
+    pub fn invites(&self) -> &BTreeMap<String, u32> {
+        &self.config.invites
+    }
+
+    /// Whether `player` owns a bee with the ID `bee`.
+    #[must_use]
+    pub fn owns_bee(&self, player: Player, bee: BeeID) -> bool {
+        self.owners.get(&bee) == Some(&player)
+    }
+
+    /// Whether players `a` and `b` are allies: either the same player, or teammates.
+    #[must_use]
+    pub fn are_allies(&self, a: Player, b: Player) -> bool {
+        are_allies(&self.entities.hives, a, b)
+    }
+
+    /// Donate `amount` pollen from the hives of player `from` to the first hive of player `to`.
+    ///
+    /// The players must be allies,
+    /// and must have bees meeting on the same tile as one of their hives.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the transfer isn't allowed,
+    /// or if the donor doesn't have enough pollen.
+    pub fn transfer_pollen(&mut self, from: Player, to: Player, amount: i32) -> anyhow::Result<()> {
+        if amount <= 0 {
+            bail!("Must transfer a positive amount of pollen");
+        }
+        if from == to {
+            bail!("Cannot transfer pollen to yourself");
+        }
+        if !self.are_allies(from, to) {
+            bail!("Can only transfer pollen to allied players");
+        }
+
+        let hives = &self.entities.hives;
+        if hives.iter().all(|h| h.player != from) {
+            bail!("Not taking part in this game");
+        }
+        if hives.iter().all(|h| h.player != to) {
+            bail!("Recipient is not taking part in this game");
+        }
+
+        let bees = &self.entities.bees;
+        let ours = |h: &&Hive| h.player == from || h.player == to;
+        let meeting = hives.iter().filter(ours).any(|hive| {
+            let here = |player| {
+                let pos = hive.position;
+                bees.iter().any(|b| b.player == player && b.position == pos)
+            };
+            here(from) && here(to)
+        });
+        if !meeting {
+            bail!("Bees from both players must meet at one of their hives");
+        }
+        if self.score(from) < amount {
+            bail!("Not enough pollen to transfer");
+        }
+
+        self.spend_pollen(from, amount);
+        let hives = &mut Arc::make_mut(&mut self.entities).hives;
+        if let Some(recipient) = hives.iter_mut().find(|h| h.player == to) {
+            recipient.deposit(amount);
+        }
+        Ok(())
+    }
+
+    /// Spend `amount` pollen from a player's hives to build a new hive
+    /// where their bee `bee` currently is.
+    ///
+    /// The bee must be on a spawn point that isn't already used by a hive.
+    ///
+    /// # Errors
+    ///
+    /// Fails if building hives is disabled,
+    /// if the hive can't be built where the bee is,
+    /// or if the player doesn't have enough pollen.
+    pub fn build_hive(&mut self, player: Player, bee: BeeID) -> anyhow::Result<()> {
+        let cost = match self.config.hive_cost {
+            Some(cost) => cost,
+            None => bail!("Building hives is not allowed in this game"),
+        };
+        let hives = &self.entities.hives;
+        let first = hives
+            .iter()
+            .find(|h| h.player == player)
+            .context("Not taking part in this game")?;
+        let position = self
+            .entities
+            .bees
+            .iter()
+            .find(|b| b.id == bee && b.player == player)
+            .context("No such bee")?
+            .position;
+        if !self
+            .config
+            .world
+            .get(position)
+            .is_some_and(|t| t.is_spawn_point())
+        {
+            bail!("Hives can only be built on spawn points");
+        }
+        if !self.is_free_spawn_point(position) {
+            bail!("There is already a hive here");
+        }
+        if self.score(player) < cost {
+            bail!("Not enough pollen to build a hive");
+        }
+
+        let hive = first.found(position);
+        self.spend_pollen(player, cost);
+        self.spawn_points.retain(|&p| p != position);
+        Arc::make_mut(&mut self.entities).hives.push(hive);
+        Ok(())
+    }
+
+    /// Spend pollen to start moving `player`'s hive at `from` to the position `to`.
+    ///
+    /// The hive spends [`Config::relocation_ticks`] turns in transit,
+    /// during which bees can't deliver pollen to it and it doesn't spawn bees.
+    /// The destination must be a spawn point that isn't used by another hive.
+    ///
+    /// # Errors
+    ///
+    /// Fails if relocating hives is disabled,
+    /// if the player has no hive at `from` or it's already moving,
+    /// if the hive can't be moved to `to`,
+    /// or if the player doesn't have enough pollen.
+    pub fn relocate_hive(
+        &mut self,
+        player: Player,
+        from: Position,
+        to: Position,
+    ) -> anyhow::Result<()> {
+        let cost = match self.config.relocation_cost {
+            Some(cost) => cost,
+            None => bail!("Relocating hives is not allowed in this game"),
+        };
+        let hive = self
+            .entities
+            .hives
+            .iter()
+            .find(|h| h.player == player && h.position == from)
+            .context("No hive at that position")?;
+        if hive.is_in_transit() {
+            bail!("Hive is already being relocated");
+        }
+        if !self
+            .config
+            .world
+            .get(to)
+            .is_some_and(|t| t.is_spawn_point())
+        {
+            bail!("Hives can only be moved to spawn points");
+        }
+        if !self.is_free_spawn_point(to) {
+            bail!("There is already a hive there");
+        }
+        if self.score(player) < cost {
+            bail!("Not enough pollen to relocate the hive");
+        }
+
+        self.spend_pollen(player, cost);
+        self.spawn_points.retain(|&p| p != to);
+        let ticks = self.config.relocation_ticks;
+        let hives = &mut Arc::make_mut(&mut self.entities).hives;
+        if let Some(hive) = hives
+            .iter_mut()
+            .find(|h| h.player == player && h.position == from)
+        {
+            hive.relocate(to, ticks);
+        }
+        Ok(())
+    }
+
+    /// Spend pollen to improve `player`'s hive at `position` with an `upgrade`.
+    ///
+    /// Only bees spawned by the hive after the upgrade benefit from it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if upgrading hives is disabled,
+    /// if the player has no hive at `position`,
+    /// if the upgrade is already at its highest level,
+    /// or if the player doesn't have enough pollen.
+    pub fn upgrade_hive(
+        &mut self,
+        player: Player,
+        position: Position,
+        upgrade: Upgrade,
+    ) -> anyhow::Result<()> {
+        let base_cost = match self.config.upgrade_cost {
+            Some(cost) => cost,
+            None => bail!("Upgrading hives is not allowed in this game"),
+        };
+        let hive = self
+            .entities
+            .hives
+            .iter()
+            .find(|h| h.player == player && h.position == position)
+            .context("No hive at that position")?;
+        let level = hive.upgrades.level(upgrade);
+        if level >= self.config.max_upgrade_level {
+            bail!("Upgrade is already at its highest level");
+        }
+        let cost = base_cost.saturating_mul(level as i32 + 1);
+        if self.score(player) < cost {
+            bail!("Not enough pollen for this upgrade (costs {})", cost);
+        }
+
+        self.spend_pollen(player, cost);
+        let hives = &mut Arc::make_mut(&mut self.entities).hives;
+        if let Some(hive) = hives
+            .iter_mut()
+            .find(|h| h.player == player && h.position == position)
+        {
+            hive.upgrades.improve(upgrade);
+        }
+        Ok(())
+    }
+
+    /// Change the role of `player`'s bee `bee`.
+    ///
+    /// Bees can only change role while at one of their player's hives.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the player doesn't have a bee with that ID,
+    /// or if the bee isn't at one of the player's hives.
+    pub fn assign_role(&mut self, player: Player, bee: BeeID, role: Role) -> anyhow::Result<()> {
+        let entities = Arc::make_mut(&mut self.entities);
+        let hives = &entities.hives;
+        let bee = (entities.bees.iter_mut())
+            .find(|b| b.id == bee && b.player == player)
+            .context("No such bee")?;
+        if !hives
+            .iter()
+            .any(|h| h.player == player && h.position == bee.position)
+        {
+            bail!("Bees can only change role at one of their hives");
+        }
+        bee.set_role(role);
+        Ok(())
+    }
+
+    /// Turn an [`Action::MoveTo`] for `player`'s bee `bee` into a plan along the cheapest path.
+    ///
+    /// Returns `None` if the bee doesn't exist, is already at the target,
+    /// or can't reach the target.
+    /// Other actions are returned unchanged.
+    #[must_use]
+    pub fn route(&self, player: Player, bee: BeeID, action: Action) -> Option<Action> {
+        let (target, avoid_roads) = match action {
+            Action::MoveTo {
+                target,
+                avoid_roads,
+            } => (target, avoid_roads),
+            other => return Some(other),
+        };
+        let bees = &self.entities.bees;
+        let bee = bees.iter().find(|b| b.id == bee && b.player == player)?;
+        let path = self.world().find_path(bee.position, target, avoid_roads)?;
+        (!path.is_empty()).then_some(Action::Plan(path))
+    }
+
+    /// Set the actions `player`'s bees will take next tick from the `moves` they sent,
+    /// returning how many were accepted.
+    ///
+    /// Only the last move for each bee takes effect, so earlier ones aren't accepted.
+    /// Moves for bees the player doesn't own, or to targets that can't be reached, aren't accepted.
+    /// Unreachable targets still cancel any action the bee was already taking.
+    ///
+    /// Each move to a target uses up one of `paths_left`,
+    /// the number of paths the player may still have found this tick.
+    /// Once there are none left moves to targets aren't accepted,
+    /// and the bees carry on with whatever they were doing.
+    pub fn apply_moves(
+        &self,
+        next_moves: &mut Moves,
+        player: Player,
+        moves: impl IntoIterator<Item = Move>,
+        paths_left: &mut usize,
+    ) -> usize {
+        let moves: Vec<_> = moves.into_iter().collect();
+        // don't find paths for moves that are replaced straight away
+        let last: HashMap<BeeID, usize> = (moves.iter().enumerate())
+            .map(|(i, m)| (m.bee, i))
+            .collect();
+        let mut accepted = 0;
+        for (i, m) in moves.into_iter().enumerate() {
+            if last[&m.bee] != i || !self.owns_bee(player, m.bee) {
+                continue;
+            }
+            let action = m.action();
+            if let Some(Action::MoveTo { .. }) = action {
+                if *paths_left == 0 {
+                    continue;
+                }
+                *paths_left -= 1;
+            }
+            let stopping = action.is_none();
+            let action = action.and_then(|a| self.route(player, m.bee, a));
+            if action.is_some() || stopping {
+                accepted += 1;
+            }
+            if let Some(action) = action {
+                next_moves.insert((player, m.bee), action);
+            } else {
+                next_moves.remove(&(player, m.bee));
+            }
+        }
+        accepted
+    }
+
+    /// Move the `moves` made last turn on to the next turn, keeping any plans with steps left.
+    ///
+    /// Each bee took up to its [speed][Bee::speed] in steps of its plan,
+    /// and moves for bees that no longer exist are dropped.
+    pub fn advance_moves(&self, moves: &mut Moves) {
+        let speeds: HashMap<BeeID, u32> = (self.entities.bees.iter())
+            .map(|b| (b.id, b.speed))
+            .collect();
+        moves.retain(|(_, bee), action| speeds.get(bee).is_some_and(|&s| action.advance(s)));
+    }
+
+    /// Whether no hive is on, or moving to, `position`.
+    fn is_free_spawn_point(&self, position: Position) -> bool {
+        self.entities.hives.iter().all(|h| {
+            let destination = h.transit.map(|t| t.destination);
+            h.position != position && destination != Some(position)
+        })
+    }
+
+    /// The total score of `player`'s hives.
+    fn score(&self, player: Player) -> i32 {
+        let hives = self.entities.hives.iter().filter(|h| h.player == player);
+        hives.map(Hive::score).sum()
+    }
+
+    /// Remove `amount` pollen from `player`'s hives, starting with their first hive.
+    ///
+    /// The caller is responsible for checking that the player has enough pollen.
+    fn spend_pollen(&mut self, player: Player, mut amount: i32) {
+        for hive in &mut Arc::make_mut(&mut self.entities).hives {
+            if hive.player == player {
+                amount -= hive.withdraw(amount);
+            }
+        }
+    }
+
+    /// Summarise the performance of each player in the game so far.
+    ///
+    /// Returned in order of rank, best first.
+    #[must_use]
+    pub fn summaries(&self) -> Vec<(Player, Summary)> {
+        let team_scores = self.team_scores();
+        let mut totals = self.entities.totals();
+        totals.sort_by_key(|&(_, score, _)| std::cmp::Reverse(score));
+
+        let mut result: Vec<(Player, Summary)> = Vec::with_capacity(totals.len());
+        for (index, (hive, score, stats)) in totals.into_iter().enumerate() {
+            let rank = match result.last() {
+                Some((_, prev)) if prev.score == score => prev.rank,
+                _ => index + 1,
+            };
+            let summary = Summary {
+                score,
+                rank,
+                team: hive.team.clone(),
+                team_score: hive.team.as_deref().map(|t| team_scores[t]),
+                stats,
+            };
+            result.push((hive.player, summary));
+        }
+        result
+    }
+
+    /// The number of ticks that have been performed so far.
+    #[must_use]
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Whether the game has reached one of its configured finishing conditions.
+    ///
+    /// Games without a [`max_ticks`][Config::max_ticks] or
+    /// [`target_score`][Config::target_score] never finish.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        let out_of_time = self.config.max_ticks.is_some_and(|max| self.ticks >= max);
+        let target = self.config.target_score;
+        let hit_target = target.is_some_and(|t| self.total_score() >= t);
+        out_of_time || hit_target
+    }
+
     /// Get an independent serialisable view of the current state of the game.
     ///
     /// The returned serializer only represents
     /// the mutable members of the game.
-    /// In addition it is a new block of memory,
-    /// and is no longer tied to the game state;
-    /// as such, mutating the game will not change the result
-    /// of serialising the returned object.
+    /// It shares the game's entities rather than copying them,
+    /// but is no longer tied to the game state:
+    /// the entities are copied if the game changes while the serializer is still alive,
+    /// so mutating the game will not change the result of serialising the returned object.
     ///
     /// The returned object is safe to send across threads.
     #[must_use]
-    pub fn make_serializer(&self) -> Serializer {
-        Serializer(Arc::new(self.entities.clone()))
+    pub fn make_serializer(&mut self) -> Serializer {
+        let visible = if self.config.fog_of_war {
+            if Arc::get_mut(&mut self.visibility).is_none() {
+                // an old serializer still needs it, so start afresh rather than copying it
+                self.visibility = Arc::default();
+            }
+            if let Some(visibility) = Arc::get_mut(&mut self.visibility) {
+                visibility.update(&self.entities, &self.config.world);
+            }
+            Some(Arc::clone(&self.visibility))
+        } else {
+            None
+        };
+        Serializer {
+            entities: Arc::clone(&self.entities),
+            weather: self.weather,
+            viewer: None,
+            viewport: None,
+            visible,
+        }
     }
 
     /// Add a player to the game, starting them with a hive and some bees.
+    /// The hive is displayed using the player's chosen `cosmetics`,
+    /// and contributes to the score of their `team` if they are on one.
     ///
     /// Does nothing if the given player is already in the game.
     ///
     /// # Errors
     ///
     /// May fail if there are no more available spawn points.
-    pub fn add_player(&mut self, player: Player) -> anyhow::Result<()> {
+    pub fn add_player(
+        &mut self,
+        player: Player,
+        cosmetics: Cosmetics,
+        team: Option<String>,
+    ) -> anyhow::Result<()> {
         assert!(!player.is_observer());
         if self.players().all(|p| p != &player) {
-            let position = self
-                .spawn_points
-                .pop()
+            let hives: Vec<_> = self.entities.hives.iter().map(|h| h.position).collect();
+            let index = (self.config.spawn_strategy)
+                .choose(&self.config.world, &self.spawn_points, &hives)
                 .context("Could not add player: no more available spawn points")?;
-            let (hive, bees) = Hive::new(player, position);
-            self.entities.hives.push(hive);
-            self.entities.bees.extend(bees);
+            let position = self.spawn_points.remove(index);
+            let config = &self.config;
+            let (hive, bees) = Hive::new(player, position, cosmetics, team, config, &mut self.rng);
+            self.owners.extend(bees.iter().map(|b| (b.id, player)));
+            let entities = Arc::make_mut(&mut self.entities);
+            entities.hives.push(hive);
+            entities.bees.extend(bees);
         }
         Ok(())
     }
 
+    /// Remove a player from the game entirely, despawning their hives and bees.
+    ///
+    /// The spawn points their hives were on, or moving to, become free for new players.
+    /// Returns the player's final summary, or `None` if they weren't in the game.
+    pub fn remove_player(&mut self, player: Player) -> Option<Summary> {
+        let summaries = self.summaries();
+        let (_, summary) = summaries.into_iter().find(|(p, _)| *p == player)?;
+        for hive in self.entities.hives.iter().filter(|h| h.player == player) {
+            self.spawn_points.push(hive.position);
+            self.spawn_points
+                .extend(hive.transit.map(|t| t.destination));
+        }
+        let entities = Arc::make_mut(&mut self.entities);
+        entities.hives.retain(|h| h.player != player);
+        entities.bees.retain(|b| b.player != player);
+        entities.dances.retain(|d| d.player != player);
+        self.owners.retain(|_, &mut p| p != player);
+        Some(summary)
+    }
+
     /// List all players in the game.
     pub fn players(&self) -> impl Iterator<Item = &'_ Player> {
-        self.entities.hives.iter().map(|h| &h.player)
+        let totals = self.entities.totals();
+        totals.into_iter().map(|(h, _, _)| &h.player)
     }
 
     /// Perform one game tick. User input is taken in `moves`.
     pub fn tick(&mut self, moves: &Moves) {
-        self.entities.tick(&self.config, &mut self.rng, moves)
+        self.change_season();
+        let entities = Arc::make_mut(&mut self.entities);
+        let blocked = entities.tick(
+            &self.config,
+            &mut self.rng,
+            moves,
+            self.weather,
+            &mut self.spawn_weights,
+            &mut self.owners,
+        );
+        self.blocked.extend(blocked);
+        let config = &self.config;
+        let event = events::happen(&mut self.rng, entities, &config.world, &config.events);
+        self.events.extend(event);
+        self.weather = self
+            .weather
+            .next(&mut self.rng, &config.world, &config.weather);
+        for hive in &mut entities.hives {
+            if let Some(vacated) = hive.travel() {
+                self.spawn_points.push(vacated);
+            }
+        }
+        let totals = entities.totals();
+        let stats: Vec<_> = totals.into_iter().map(|(h, _, s)| (h.player, s)).collect();
+        self.achievements.update(&stats);
+        self.ticks += 1;
+    }
+
+    /// Take any achievements unlocked since this was last called.
+    pub fn drain_achievements(&mut self) -> impl Iterator<Item = (Player, Achievement)> + '_ {
+        self.achievements.drain()
+    }
+
+    /// Take any random events that happened since this was last called.
+    pub fn drain_events(&mut self) -> impl Iterator<Item = Event> + '_ {
+        self.events.drain(..)
+    }
+
+    /// Take the bees that couldn't move onto a [full tile][Config::max_bees_per_tile]
+    /// since this was last called.
+    pub fn drain_blocked(&mut self) -> impl Iterator<Item = (Player, BeeID)> + '_ {
+        self.blocked.drain(..)
+    }
+}
+
+/// The positions each player can see, when playing with [`Config::fog_of_war`].
+///
+/// Players can see everything within sight range of their bees and hives,
+/// and share what they see with their teammates.
+#[derive(Debug, Clone, Default)]
+struct Visibility {
+    /// The index into `seen` of the positions each player can see.
+    groups: HashMap<Player, usize>,
+    /// The positions seen by each team, or by each player not on a team.
+    seen: Vec<HashSet<Position>>,
+}
+
+impl Visibility {
+    /// Work out what each player can see among the `entities`,
+    /// reusing the memory from the last time.
+    fn update(&mut self, entities: &Entities, world: &World) {
+        self.groups.clear();
+        let mut teams: HashMap<&str, usize> = HashMap::new();
+        let mut groups = 0;
+        let hives = (entities.hives.iter()).map(|h| (h.player, h.team.as_deref()));
+        let bees = (entities.bees.iter()).map(|b| (b.player, None));
+        for (player, team) in hives.chain(bees) {
+            if self.groups.contains_key(&player) {
+                continue;
+            }
+            let group = match team {
+                Some(team) => *teams.entry(team).or_insert(groups),
+                None => groups,
+            };
+            if group == groups {
+                groups += 1;
+            }
+            self.groups.insert(player, group);
+        }
+        self.seen.truncate(groups);
+        self.seen.iter_mut().for_each(HashSet::clear);
+        self.seen.resize_with(groups, HashSet::new);
+
+        let bees = (entities.bees.iter()).map(|b| (b.player, b.position, b.role.sight_range()));
+        let hives = (entities.hives.iter()).map(|h| (h.player, h.position, HIVE_SIGHT_RANGE));
+        for (player, position, range) in bees.chain(hives) {
+            if let Some(&group) = self.groups.get(&player) {
+                self.seen[group].extend(world.within_range(position, range));
+            }
+        }
+    }
+
+    /// Whether `player` can see the `position`.
+    fn can_see(&self, player: Player, position: Position) -> bool {
+        (self.groups.get(&player)).is_some_and(|&group| self.seen[group].contains(&position))
     }
 }
 
@@ -305,13 +2110,161 @@ impl State {
 ///
 /// Refer to [`State::make_serializer`] for more details.
 #[derive(Debug, Clone)]
-pub struct Serializer(Arc<Entities>);
+pub struct Serializer {
+    /// The entities to serialise.
+    entities: Arc<Entities>,
+    /// The current weather.
+    weather: Weather,
+    /// The player the state is being shown to, or `None` for observers.
+    viewer: Option<Player>,
+    /// The area of the world the viewer is interested in, or `None` for the whole world.
+    viewport: Option<Rect>,
+    /// The positions each player can see, or `None` if there is no fog of war.
+    visible: Option<Arc<Visibility>>,
+}
+
+impl Serializer {
+    /// Restrict the serialised state to what `player` is allowed to see.
+    ///
+    /// Observers can see everything.
+    #[must_use]
+    pub fn view(&self, player: Player) -> Serializer {
+        Serializer {
+            entities: Arc::clone(&self.entities),
+            weather: self.weather,
+            viewer: (!player.is_observer()).then_some(player),
+            viewport: None,
+            visible: self.visible.clone(),
+        }
+    }
+
+    /// Restrict the serialised state to entities within the `viewport`, if any.
+    ///
+    /// The viewer's own bees and hives are always included.
+    #[must_use]
+    pub fn within(mut self, viewport: Option<Rect>) -> Serializer {
+        self.viewport = viewport;
+        self
+    }
+
+    /// Whether an entity at the position should be shown to the viewer.
+    ///
+    /// With fog of war, players only see positions near their own bees and hives.
+    #[must_use]
+    fn shows(&self, position: Position) -> bool {
+        let in_sight = match (self.viewer, &self.visible) {
+            (Some(viewer), Some(visible)) => visible.can_see(viewer, position),
+            _ => true,
+        };
+        in_sight && self.viewport.is_none_or(|rect| rect.contains(position))
+    }
+
+    /// Whether an entity at the position owned by `player` should be shown to the viewer.
+    #[must_use]
+    fn shows_owned(&self, player: Player, position: Position) -> bool {
+        self.viewer == Some(player) || self.shows(position)
+    }
+}
+
+/// The entities that the viewer of a [`Serializer`] can see.
+#[derive(Serialize, JsonSchema)]
+#[schemars(rename = "GameState")]
+struct View<'a> {
+    /// Every visible bee.
+    bees: Vec<&'a Bee>,
+    /// Every visible hive.
+    hives: Vec<&'a Hive>,
+    /// Every visible flower.
+    flowers: Vec<&'a Flower>,
+    /// Every visible bird.
+    birds: Vec<&'a Bird>,
+    /// Every visible car.
+    cars: Vec<&'a Car>,
+    /// Every visible item.
+    items: Vec<&'a Item>,
+    /// Every visible wasp nest.
+    nests: Vec<&'a Nest>,
+    /// Every visible wasp.
+    wasps: Vec<&'a Wasp>,
+    /// Dances by the viewer's own and their teammates' bees, or every dance for observers.
+    dances: Vec<&'a Dance>,
+    /// The current weather.
+    weather: Weather,
+}
+
+/// The entities in an update, as a client receives them.
+///
+/// Used to [rebuild the game][State::from_snapshot] on the client.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Snapshot {
+    /// Every visible bee.
+    pub bees: Vec<Bee>,
+    /// Every visible hive.
+    pub hives: Vec<Hive>,
+    /// Every visible flower.
+    pub flowers: Vec<Flower>,
+    /// Every visible bird.
+    pub birds: Vec<Bird>,
+    /// Every visible car.
+    pub cars: Vec<Car>,
+    /// Every visible item.
+    pub items: Vec<Item>,
+    /// Every visible wasp nest.
+    pub nests: Vec<Nest>,
+    /// Every visible wasp.
+    pub wasps: Vec<Wasp>,
+    /// The dances the client was shown.
+    pub dances: Vec<Dance>,
+    /// The current weather.
+    pub weather: Weather,
+}
+
+impl JsonSchema for Serializer {
+    fn schema_name() -> String {
+        View::schema_name()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        View::json_schema(gen)
+    }
+}
 
 impl Serialize for Serializer {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        self.0.serialize(serializer)
+        let entities = &*self.entities;
+        let view = View {
+            bees: (entities.bees.iter())
+                .filter(|b| self.shows_owned(b.player, b.position))
+                .collect(),
+            hives: (entities.hives.iter())
+                .filter(|h| self.shows_owned(h.player, h.position))
+                .collect(),
+            flowers: (entities.flowers.values())
+                .filter(|f| self.shows(f.position))
+                .collect(),
+            birds: (entities.birds.iter())
+                .filter(|b| self.shows(b.position))
+                .collect(),
+            cars: (entities.cars.iter())
+                .filter(|c| self.shows(c.position))
+                .collect(),
+            items: (entities.items.iter())
+                .filter(|i| self.shows(i.position))
+                .collect(),
+            nests: (entities.nests.iter())
+                .filter(|n| self.shows(n.position))
+                .collect(),
+            wasps: (entities.wasps.iter())
+                .filter(|w| self.shows(w.position))
+                .collect(),
+            dances: (entities.dances.iter())
+                .filter(|d| entities.sees_dances_of(self.viewer, d.player))
+                .collect(),
+            weather: self.weather,
+        };
+        view.serialize(serializer)
     }
 }
@@ -0,0 +1,207 @@
+//! Wasp nests, a shared enemy for every player.
+//!
+//! Nests [occasionally][`WaspConfig::nest_spawn_chance`] appear on the map,
+//! and every so often send out a wasp to raid the nearest hive with pollen in it.
+//! Wasps steal pollen and carry it back to their nest.
+//! Nests keep sending out wasps until enough bees gather on them to destroy them,
+//! which usually takes several players working together.
+
+use rand::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    deserialize_chance,
+    entity::Hive,
+    world::{Position, World},
+    Entities,
+};
+
+/// A nest that sends out wasps to raid hives.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Nest {
+    /// Where the nest is.
+    pub position: Position,
+    /// How much more damage the nest can take before it's destroyed.
+    pub health: u32,
+    /// The number of ticks since the nest last sent out a wasp.
+    #[serde(skip)]
+    timer: u32,
+}
+
+impl Nest {
+    /// Create a nest with the given health.
+    #[must_use]
+    pub fn new(position: Position, health: u32) -> Self {
+        Self {
+            position,
+            health,
+            timer: 0,
+        }
+    }
+
+    /// Wait another tick, returning whether it's time to send out another wasp.
+    fn hatch(&mut self, interval: u32) -> bool {
+        self.timer += 1;
+        if self.timer >= interval {
+            self.timer = 0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// A wasp raiding hives for pollen.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Wasp {
+    /// Where the wasp is.
+    pub position: Position,
+    /// The position of the nest the wasp came from, and returns to.
+    pub nest: Position,
+    /// How much pollen the wasp has stolen.
+    pub pollen: i32,
+}
+
+impl Wasp {
+    /// Fly one tile towards the nearest hive with pollen to steal,
+    /// or back to the nest once the wasp has stolen some.
+    pub fn step(&mut self, world: &World, hives: &[Hive]) {
+        let target = if self.pollen > 0 {
+            Some(self.nest)
+        } else {
+            (hives.iter())
+                .filter(|h| h.score() > 0 && !h.is_in_transit())
+                .map(|h| h.position)
+                .min_by_key(|&pos| world.distance(self.position, pos))
+        };
+        let dir = target.and_then(|target| world.direction_towards(self.position, target));
+        if let Some(next) = dir.and_then(|dir| world.step(self.position, dir)) {
+            self.position = next;
+        }
+    }
+
+    /// Steal pollen from the `hive`, if the wasp is on it and hasn't stolen any yet.
+    pub fn raid(&mut self, hive: &mut Hive, amount: i32) {
+        if self.pollen == 0 && self.position == hive.position && !hive.is_in_transit() {
+            self.pollen = hive.withdraw(amount);
+            hive.stats.pollen_stolen += self.pollen;
+        }
+    }
+
+    /// Whether the wasp has made it back to its nest with stolen pollen.
+    #[must_use]
+    pub fn is_home(&self) -> bool {
+        self.pollen > 0 && self.position == self.nest
+    }
+}
+
+/// Configure how often wasp nests appear, and how dangerous they are.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WaspConfig {
+    /// Chance that a new nest appears each tick.
+    ///
+    /// If zero, there are no wasps.
+    #[serde(deserialize_with = "deserialize_chance")]
+    pub nest_spawn_chance: f64,
+    /// The most nests that can be on the map at once.
+    pub max_nests: usize,
+    /// The damage a nest takes before being destroyed.
+    ///
+    /// Each tick, a nest takes damage equal to the combined [strength][super::Role::strength]
+    /// of the bees on it.
+    pub nest_health: u32,
+    /// The number of ticks between each wasp a nest sends out.
+    pub spawn_interval: u32,
+    /// The most wasps that can be out at once, across every nest.
+    pub max_wasps: usize,
+    /// The most pollen a wasp steals from a hive.
+    pub steal_amount: i32,
+}
+
+impl Default for WaspConfig {
+    fn default() -> Self {
+        Self {
+            nest_spawn_chance: 0.0,
+            max_nests: 1,
+            nest_health: 30,
+            spawn_interval: 20,
+            max_wasps: 4,
+            steal_amount: 3,
+        }
+    }
+}
+
+/// Run one tick for every nest and wasp in the `entities`.
+///
+/// New nests may appear, bees damage the nests they're on,
+/// nests send out wasps, and wasps raid hives and return home.
+/// Wasps leave once their nest is destroyed.
+pub(super) fn tick<R: Rng + ?Sized>(
+    rng: &mut R,
+    entities: &mut Entities,
+    world: &World,
+    config: &WaspConfig,
+) {
+    if entities.nests.len() < config.max_nests && rng.gen_bool(config.nest_spawn_chance) {
+        let position = Position::new(
+            rng.gen_range(0..world.width),
+            rng.gen_range(0..world.height),
+        );
+        let free = world
+            .get(position)
+            .is_some_and(|t| t.is_passable() && !t.is_spawn_point());
+        if free && entities.nests.iter().all(|n| n.position != position) {
+            entities.nests.push(Nest::new(position, config.nest_health));
+        }
+    }
+
+    // bees on a nest damage it, and everyone who helped destroy it gets the credit
+    let bees = &entities.bees;
+    let hives = &mut entities.hives;
+    entities.nests.retain_mut(|nest| {
+        let position = nest.position;
+        let here = || bees.iter().filter(|b| b.position == position);
+        let damage: u32 = here().map(|b| b.role.strength()).sum();
+        nest.health = nest.health.saturating_sub(damage);
+        if nest.health > 0 {
+            return true;
+        }
+        let mut players = Vec::new();
+        for bee in here() {
+            if !players.contains(&bee.player) {
+                players.push(bee.player);
+            }
+        }
+        for player in players {
+            if let Some(hive) = hives.iter_mut().find(|h| h.player == player) {
+                hive.stats.nests_destroyed += 1;
+            }
+        }
+        false
+    });
+    // wasps from destroyed nests give up and fly away
+    let nests = &entities.nests;
+    entities
+        .wasps
+        .retain(|w| nests.iter().any(|n| n.position == w.nest));
+
+    for nest in &mut entities.nests {
+        if nest.hatch(config.spawn_interval) && entities.wasps.len() < config.max_wasps {
+            entities.wasps.push(Wasp {
+                position: nest.position,
+                nest: nest.position,
+                pollen: 0,
+            });
+        }
+    }
+
+    for wasp in &mut entities.wasps {
+        wasp.step(world, &entities.hives);
+        for hive in &mut entities.hives {
+            wasp.raid(hive, config.steal_amount);
+        }
+    }
+    entities.wasps.retain(|w| !w.is_home());
+}
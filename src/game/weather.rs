@@ -0,0 +1,99 @@
+//! Weather that changes over the course of a game.
+//!
+//! The weather starts out [sunny][`Weather::Sunny`],
+//! which has no effect on the game.
+//! If the [configuration][`WeatherConfig::change_chance`] allows it,
+//! the weather changes at random as the game goes on,
+//! affecting how often flowers spawn and how bees move and tire.
+
+use rand::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    deserialize_chance,
+    world::{Direction, World},
+};
+
+/// The current weather in a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Weather {
+    /// Clear skies, with no effect on the game.
+    #[default]
+    Sunny,
+    /// Flowers spawn more often, but bees tire faster.
+    Rain,
+    /// Fewer flowers spawn, and bees are sometimes blown one tile downwind.
+    Wind {
+        /// The direction the wind blows bees in.
+        direction: Direction,
+    },
+}
+
+impl Weather {
+    /// How much more likely flowers are to spawn than usual.
+    #[must_use]
+    pub fn flower_spawn_factor(self) -> f64 {
+        match self {
+            Weather::Sunny => 1.0,
+            Weather::Rain => 2.0,
+            Weather::Wind { .. } => 0.5,
+        }
+    }
+
+    /// The extra energy each bee spends every tick.
+    #[must_use]
+    pub fn energy_drain(self) -> i32 {
+        match self {
+            Weather::Rain => 1,
+            Weather::Sunny | Weather::Wind { .. } => 0,
+        }
+    }
+
+    /// The weather for the next tick.
+    ///
+    /// Changes to a different kind of weather with [`WeatherConfig::change_chance`].
+    /// The wind only blows in directions that bees can move in on the `world`.
+    #[must_use]
+    pub fn next<R: Rng + ?Sized>(self, rng: &mut R, world: &World, config: &WeatherConfig) -> Self {
+        if !rng.gen_bool(config.change_chance) {
+            return self;
+        }
+        let wind = world
+            .grid
+            .directions()
+            .choose(rng)
+            .map(|&direction| Weather::Wind { direction });
+        let choices: Vec<_> = [Some(Weather::Sunny), Some(Weather::Rain), wind]
+            .iter()
+            .flatten()
+            .copied()
+            .filter(|w| std::mem::discriminant(w) != std::mem::discriminant(&self))
+            .collect();
+        choices.choose(rng).copied().unwrap_or(self)
+    }
+}
+
+/// Configure how the weather changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WeatherConfig {
+    /// Chance that the weather changes each tick.
+    ///
+    /// If zero, the weather stays sunny for the whole game.
+    #[serde(deserialize_with = "deserialize_chance")]
+    pub change_chance: f64,
+    /// Chance that the wind blows each bee one tile each tick, while it's windy.
+    #[serde(deserialize_with = "deserialize_chance")]
+    pub wind_push_chance: f64,
+}
+
+impl Default for WeatherConfig {
+    fn default() -> Self {
+        Self {
+            change_chance: 0.0,
+            wind_push_chance: 0.25,
+        }
+    }
+}
@@ -1,26 +1,173 @@
 //! Types used to describe the game world.
 
-use std::{convert::TryFrom, iter::from_fn, ops::Index};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    convert::TryFrom,
+    iter::from_fn,
+    ops::Index,
+    sync::OnceLock,
+};
 
 use anyhow::{bail, Context, Error};
 use rand::{distributions::WeightedIndex, prelude::*};
-use serde::{Deserialize, Serialize};
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{Deserialize, Serialize, Serializer};
 
-use super::{entity::Flower, Config};
+use super::{
+    entity::{Flower, Species},
+    Config,
+};
 
-/// Represents the cardinal directions on the plane.
+/// Represents the cardinal and diagonal directions on the plane.
+///
+/// Each direction can also be written using its initials, such as `"N"` or `"SW"`.
 ///
 /// See also [`World`].
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 pub enum Direction {
+    #[serde(alias = "N")]
     North,
+    #[serde(alias = "NE", alias = "Northeast")]
+    NorthEast,
+    #[serde(alias = "E")]
     East,
+    #[serde(alias = "SE", alias = "Southeast")]
+    SouthEast,
+    #[serde(alias = "S")]
     South,
+    #[serde(alias = "SW", alias = "Southwest")]
+    SouthWest,
+    #[serde(alias = "W")]
     West,
+    #[serde(alias = "NW", alias = "Northwest")]
+    NorthWest,
+}
+
+impl Direction {
+    /// Every direction.
+    pub const ALL: [Direction; 8] = [
+        Direction::North,
+        Direction::NorthEast,
+        Direction::East,
+        Direction::SouthEast,
+        Direction::South,
+        Direction::SouthWest,
+        Direction::West,
+        Direction::NorthWest,
+    ];
+
+    /// The change in `x` and `y` from moving one step in this direction.
+    #[must_use]
+    pub fn offset(self) -> (i32, i32) {
+        match self {
+            Direction::North => (0, 1),
+            Direction::NorthEast => (1, 1),
+            Direction::East => (1, 0),
+            Direction::SouthEast => (1, -1),
+            Direction::South => (0, -1),
+            Direction::SouthWest => (-1, -1),
+            Direction::West => (-1, 0),
+            Direction::NorthWest => (-1, 1),
+        }
+    }
+
+    /// The direction pointing the opposite way.
+    #[must_use]
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::NorthEast => Direction::SouthWest,
+            Direction::East => Direction::West,
+            Direction::SouthEast => Direction::NorthWest,
+            Direction::South => Direction::North,
+            Direction::SouthWest => Direction::NorthEast,
+            Direction::West => Direction::East,
+            Direction::NorthWest => Direction::SouthEast,
+        }
+    }
+}
+
+/// The shape of the tiles making up a [`World`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Grid {
+    /// Square tiles, which can be moved between in all eight directions.
+    #[default]
+    Square,
+    /// Hexagonal tiles using axial coordinates.
+    ///
+    /// Each row is shifted half a tile to the right of the row below it,
+    /// so that the six neighbours of a tile are to the east and west,
+    /// and to the north-east, north-west, south-east, and south-west.
+    Hex,
+}
+
+impl Grid {
+    /// The directions that can be moved in on this grid.
+    #[must_use]
+    pub fn directions(self) -> &'static [Direction] {
+        match self {
+            Grid::Square => &Direction::ALL,
+            Grid::Hex => &[
+                Direction::NorthEast,
+                Direction::East,
+                Direction::SouthEast,
+                Direction::SouthWest,
+                Direction::West,
+                Direction::NorthWest,
+            ],
+        }
+    }
+
+    /// The directions cars can drive in on this grid.
+    ///
+    /// Cars don't drive diagonally on a square grid.
+    #[must_use]
+    pub fn road_directions(self) -> &'static [Direction] {
+        match self {
+            Grid::Square => &[
+                Direction::North,
+                Direction::East,
+                Direction::South,
+                Direction::West,
+            ],
+            Grid::Hex => self.directions(),
+        }
+    }
+
+    /// The change in `x` and `y` from moving one step in the given direction,
+    /// or `None` if the direction can't be moved in on this grid.
+    #[must_use]
+    pub fn offset(self, dir: Direction) -> Option<(i32, i32)> {
+        match self {
+            Grid::Square => Some(dir.offset()),
+            Grid::Hex => match dir {
+                Direction::North | Direction::South => None,
+                Direction::NorthEast => Some((0, 1)),
+                Direction::NorthWest => Some((-1, 1)),
+                Direction::SouthEast => Some((1, -1)),
+                Direction::SouthWest => Some((0, -1)),
+                _ => Some(dir.offset()),
+            },
+        }
+    }
+
+    /// The number of steps needed to get from `from` to `to`, ignoring any obstacles.
+    #[must_use]
+    pub fn distance(self, from: Position, to: Position) -> i32 {
+        match self {
+            Grid::Square => from.distance(to),
+            Grid::Hex => {
+                let (dx, dy) = (to.x - from.x, to.y - from.y);
+                (dx.abs() + dy.abs() + (dx + dy).abs()) / 2
+            }
+        }
+    }
 }
 
 /// A position on the [`World`] grid.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 pub struct Position {
     /// The horizontal position; 0 is closest to the left.
     pub x: i32,
@@ -36,27 +183,59 @@ impl Position {
     }
 
     /// Get the next tile immediately in the given direction.
+    ///
+    /// This assumes a [square grid][`Grid::Square`]; see [`World::step`] for other grids.
     #[must_use]
     pub fn step(self, dir: Direction) -> Position {
-        match dir {
-            Direction::North => Position::new(self.x, self.y + 1),
-            Direction::East => Position::new(self.x + 1, self.y),
-            Direction::South => Position::new(self.x, self.y - 1),
-            Direction::West => Position::new(self.x - 1, self.y),
-        }
+        let (dx, dy) = dir.offset();
+        Position::new(self.x + dx, self.y + dy)
+    }
+
+    /// The number of steps needed to get to `other` on a square grid, ignoring any obstacles.
+    ///
+    /// Since bees can move diagonally, this is the larger of the horizontal
+    /// and vertical distances. See [`Grid::distance`] for other grids.
+    #[must_use]
+    pub fn distance(self, other: Position) -> i32 {
+        (self.x - other.x).abs().max((self.y - other.y).abs())
+    }
+}
+
+/// A rectangular area of the [`World`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Rect {
+    /// The horizontal position of the left-most column.
+    pub x: i32,
+    /// The vertical position of the bottom-most row.
+    pub y: i32,
+    /// The number of columns covered.
+    pub width: i32,
+    /// The number of rows covered.
+    pub height: i32,
+}
+
+impl Rect {
+    /// Whether the position lies within the rectangle.
+    ///
+    /// Rectangles don't wrap around the edges of the world,
+    /// even if the [world does][`World::wrap_edges`].
+    #[must_use]
+    pub fn contains(self, pos: Position) -> bool {
+        (self.x..self.x + self.width).contains(&pos.x)
+            && (self.y..self.y + self.height).contains(&pos.y)
     }
 }
 
 /// Different kinds of tiles on the map.
 ///
 /// These are unchanging and constant throughout the duration of a game.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
 pub enum Tile {
     /// Normal terroritory. Can spawn flowers.
     Grass,
     /// Very "flowerful" terrain.
     Garden,
-    /// Passable terrain, but cannot spawn flowers: water, footpaths, etc.
+    /// Passable terrain, but cannot spawn flowers: footpaths, car parks, etc.
     Neutral,
     /// Passable terrain, cannot spawn flowers; cars can drive through.
     Road,
@@ -64,15 +243,36 @@ pub enum Tile {
     Block,
     /// Can spawn hives on it, but will not spawn flowers etc.
     SpawnPoint,
+    /// Rich terrain: flowers spawn here, and yield double pollen to bees.
+    Meadow,
+    /// Ponds and streams: tiring for bees to cross, and cars can't drive through.
+    Water,
+    /// A road tile where cars must yield to anything crossing.
+    Crossing,
 }
 
 impl Tile {
     /// Whether this tile can be passed through by bees.
+    ///
+    /// Birds can fly over any tile, while cars only drive on [roads][`Tile::is_road`].
     #[must_use]
     pub fn is_passable(self) -> bool {
         !matches!(self, Self::Block)
     }
 
+    /// The energy a bee spends moving onto this tile.
+    ///
+    /// Bees that stay where they are always spend one energy instead.
+    #[must_use]
+    pub fn movement_cost(self) -> i32 {
+        match self {
+            Self::Garden => 0,
+            Self::Neutral => 2,
+            Self::Water => 3,
+            _ => 1,
+        }
+    }
+
     /// The weighting for how likely flowers are to spawn on this tile.
     ///
     /// Higher values mean more likely to spawn here, if a flower should be spawned.
@@ -82,10 +282,33 @@ impl Tile {
         match self {
             Self::Grass => 0.3,
             Self::Garden => 1.0,
+            Self::Meadow => 0.5,
             _ => 0.0,
         }
     }
 
+    /// How much pollen a bee gains for each unit collected from a flower on this tile.
+    #[must_use]
+    pub fn pollen_yield(self) -> i32 {
+        match self {
+            Self::Meadow => 2,
+            _ => 1,
+        }
+    }
+
+    /// Returns `true` if cars can drive on the tile:
+    /// either a [`Road`][`Tile::Road`] or a [`Crossing`][`Tile::Crossing`].
+    #[must_use]
+    pub fn is_road(self) -> bool {
+        matches!(self, Self::Road | Self::Crossing)
+    }
+
+    /// Returns `true` if the tile is a [`Crossing`][`Tile::Crossing`].
+    #[must_use]
+    pub fn is_crossing(self) -> bool {
+        matches!(self, Self::Crossing)
+    }
+
     /// Returns `true` if the tile is a [`SpawnPoint`][`Tile::SpawnPoint`].
     #[must_use]
     pub fn is_spawn_point(self) -> bool {
@@ -93,26 +316,279 @@ impl Tile {
     }
 }
 
+/// The width and height of each [`Chunk`] of a [`World`], in tiles.
+pub const CHUNK_SIZE: i32 = 32;
+
+/// Worlds with more tiles than this are sent to clients one [`Chunk`] at a time.
+pub const STREAM_THRESHOLD: usize = 256 * 256;
+
+/// The most tiles [`World::find_path`] explores before giving up on reaching its target.
+///
+/// Paths are searched for in the game loop, so this bounds the work done for each move.
+pub const PATH_SEARCH_LIMIT: usize = 4096;
+
+/// A rectangular section of the world map.
+///
+/// Chunks are [`CHUNK_SIZE`] tiles across,
+/// except for those along the top and right edges of the world,
+/// which may be smaller.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(try_from = "ChunkDeserializer")]
+pub struct Chunk {
+    /// The position of the bottom-left tile of the chunk.
+    #[serde(flatten)]
+    origin: Position,
+    /// The width of the chunk, in number of tiles.
+    width: i32,
+    /// The height of the chunk, in number of tiles.
+    height: i32,
+    /// The contents of the chunk. Row-major, with the first cell at the bottom-left.
+    map: Vec<Tile>,
+    /// The sum of the spawn weights of every tile in the chunk.
+    #[serde(skip)]
+    total_weight: f64,
+    /// Cache the spawn weights of each tile, computed the first time they're needed.
+    #[serde(skip)]
+    weights: OnceLock<WeightedIndex<f64>>,
+}
+
+impl Chunk {
+    /// Create a chunk from the tiles of `map` (of the given `width`)
+    /// covering `width` by `height` tiles from `origin`.
+    fn new(map: &[Tile], map_width: i32, origin: Position, width: i32, height: i32) -> Self {
+        let map = (origin.y..origin.y + height)
+            .flat_map(|y| {
+                let start = (origin.x + y * map_width) as usize;
+                map[start..start + width as usize].iter().copied()
+            })
+            .collect();
+        Self::from_tiles(origin, width, height, map)
+    }
+
+    /// Create a chunk covering `width` by `height` tiles from `origin`, made up of the tiles of `map`.
+    fn from_tiles(origin: Position, width: i32, height: i32, map: Vec<Tile>) -> Self {
+        let total_weight = map.iter().copied().map(Tile::spawn_weight).sum();
+        Self {
+            origin,
+            width,
+            height,
+            map,
+            total_weight,
+            weights: OnceLock::new(),
+        }
+    }
+
+    /// Every tile in the chunk, along with its position.
+    pub fn tiles(&self) -> impl Iterator<Item = (Position, Tile)> + '_ {
+        (self.map.iter().enumerate()).map(move |(i, &tile)| (self.position(i), tile))
+    }
+
+    /// The index into [`Chunk::map`] of a position within the chunk.
+    #[must_use]
+    fn index(&self, pos: Position) -> usize {
+        ((pos.x - self.origin.x) + (pos.y - self.origin.y) * self.width) as usize
+    }
+
+    /// The position of the tile at an index into [`Chunk::map`].
+    #[must_use]
+    fn position(&self, index: usize) -> Position {
+        Position::new(
+            self.origin.x + index as i32 % self.width,
+            self.origin.y + index as i32 / self.width,
+        )
+    }
+
+    /// The spawn weights of each tile in the chunk.
+    ///
+    /// Returns `None` if no flowers can spawn anywhere in the chunk.
+    fn weights(&self) -> Option<&WeightedIndex<f64>> {
+        if self.total_weight <= 0.0 {
+            return None;
+        }
+        let weights = self.weights.get_or_init(|| {
+            let weights = self.map.iter().copied().map(Tile::spawn_weight);
+            WeightedIndex::new(weights).expect("chunk has positive total weight")
+        });
+        Some(weights)
+    }
+}
+
+/// The flower spawn weights of every tile in a [`World`],
+/// leaving out the tiles that already have a flower on them.
+///
+/// The weights are kept up to date as flowers come and go,
+/// so that spawning flowers only has to deal with the tiles that changed
+/// rather than rebuilding the weights of the whole map.
+#[derive(Debug, Clone)]
+pub struct SpawnWeights {
+    /// The spawn weights of the tiles in each chunk,
+    /// or `None` if no flowers can spawn anywhere in the chunk.
+    chunks: Vec<Option<WeightedIndex<f64>>>,
+    /// The total spawn weight of the free tiles in each chunk.
+    free: Vec<f64>,
+    /// The tiles that had a flower on them when the weights were last updated.
+    occupied: HashSet<Position>,
+}
+
+impl SpawnWeights {
+    /// The spawn weights of every tile in the `world`, with no flowers on any of them.
+    #[must_use]
+    pub fn new(world: &World) -> Self {
+        Self {
+            chunks: world.chunks.iter().map(|c| c.weights().cloned()).collect(),
+            free: world.chunks.iter().map(|c| c.total_weight).collect(),
+            occupied: HashSet::new(),
+        }
+    }
+
+    /// Bring the weights up to date with the tiles that gained (`true`) or lost (`false`) a flower
+    /// since they were last updated, in the order the `changes` happened.
+    ///
+    /// Flowers spawned by [`World::spawn_flowers`] are already accounted for.
+    pub(super) fn update(
+        &mut self,
+        world: &World,
+        changes: impl IntoIterator<Item = (Position, bool)>,
+    ) {
+        let changes: Vec<_> = changes.into_iter().collect();
+        self.apply(world, &changes);
+    }
+
+    /// Mark the tile at `position` as having a flower on it.
+    fn occupy(&mut self, world: &World, position: Position) {
+        self.apply(world, &[(position, true)]);
+    }
+
+    /// Change whether each of the given tiles has a flower on it.
+    fn apply(&mut self, world: &World, changes: &[(Position, bool)]) {
+        let mut by_chunk: HashMap<usize, Vec<(usize, bool)>> = HashMap::new();
+        for &(position, occupied) in changes {
+            let chunk = match world.chunk_index(position) {
+                Some(chunk) => chunk,
+                None => continue,
+            };
+            let changed = if occupied {
+                self.occupied.insert(position)
+            } else {
+                self.occupied.remove(&position)
+            };
+            if changed {
+                let index = world.chunks[chunk].index(position);
+                by_chunk.entry(chunk).or_default().push((index, occupied));
+            }
+        }
+
+        for (chunk_index, mut changes) in by_chunk {
+            changes.sort_unstable_by_key(|&(index, _)| index);
+            let chunk = &world.chunks[chunk_index];
+            let weight = |index: usize, occupied| {
+                if occupied {
+                    0.0
+                } else {
+                    chunk.map[index].spawn_weight()
+                }
+            };
+            let weights: Vec<_> = (changes.iter())
+                .map(|&(index, occupied)| (index, weight(index, occupied)))
+                .collect();
+            let delta: f64 = (changes.iter())
+                .map(|&(index, occupied)| weight(index, occupied) - weight(index, !occupied))
+                .sum();
+
+            let new_weights: Vec<_> = weights.iter().map(|(index, w)| (*index, w)).collect();
+            let updated = (self.chunks[chunk_index].as_mut())
+                .is_some_and(|dist| dist.update_weights(&new_weights).is_ok());
+            if updated {
+                self.free[chunk_index] += delta;
+            } else {
+                // either the chunk has filled up, or it had been full and now has space again
+                self.rebuild(world, chunk_index);
+            }
+        }
+    }
+
+    /// Recalculate the weights of a chunk from scratch.
+    fn rebuild(&mut self, world: &World, chunk_index: usize) {
+        let chunk = &world.chunks[chunk_index];
+        let weights: Vec<_> = (0..chunk.map.len())
+            .map(|index| {
+                if self.occupied.contains(&chunk.position(index)) {
+                    0.0
+                } else {
+                    chunk.map[index].spawn_weight()
+                }
+            })
+            .collect();
+        self.free[chunk_index] = weights.iter().sum();
+        self.chunks[chunk_index] = WeightedIndex::new(weights).ok();
+    }
+}
+
 /// Stores the world map for the game.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// The map is split into [chunks][`Chunk`], so that large maps
+/// can be sent to clients piece by piece,
+/// and so that spawning flowers doesn't need to consider the whole map at once.
+///
+/// Road tiles may also be given a preferred direction of traffic, or [lane][`Lane`].
+/// These are used to build a road graph that cars follow around the map.
+#[derive(Debug, Clone, Deserialize)]
 #[serde(try_from = "WorldDeserializer")]
 pub struct World {
     /// The width of the map, in number of tiles.
     pub width: i32,
     /// The height of the map, in number of tiles.
     pub height: i32,
-    /// The contents of the map. Row-major, with the first cell at the bottom-left.
-    map: Vec<Tile>,
-    /// Cache the spawn weights of each tile.
-    #[serde(skip_serializing)]
-    weights: WeightedIndex<f64>,
+    /// The shape of the tiles.
+    pub grid: Grid,
+    /// Whether moving off one edge of the map wraps around to the opposite edge.
+    pub wrap_edges: bool,
+    /// The chunks making up the map. Row-major, with the first chunk at the bottom-left.
+    chunks: Vec<Chunk>,
+    /// The direction traffic drives in on road tiles that have a lane.
+    lanes: HashMap<Position, Direction>,
+    /// The directions cars can drive in from each road tile.
+    ///
+    /// Includes directions leading off the edge of the map, where cars leave.
+    roads: HashMap<Position, Vec<Direction>>,
+    /// The road tiles along the edge of the map where cars can drive in,
+    /// along with the direction they drive in.
+    entrances: Vec<(Position, Direction)>,
+}
+
+/// The preferred direction of traffic on a road tile.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+pub struct Lane {
+    /// The road tile.
+    #[serde(flatten)]
+    pub position: Position,
+    /// The only direction cars drive in from this tile.
+    pub direction: Direction,
 }
 
 impl Index<Position> for World {
     type Output = Tile;
-    #[must_use]
     fn index(&self, pos: Position) -> &Self::Output {
-        &self.map[self.pos_to_index(pos)]
+        match self.get(pos) {
+            Some(tile) => tile,
+            None => panic!("{:?} is outside the map", pos),
+        }
+    }
+}
+
+impl Serialize for World {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        WorldSerializer::new(self, true).serialize(serializer)
+    }
+}
+
+impl JsonSchema for World {
+    fn schema_name() -> String {
+        WorldSerializer::schema_name()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        WorldSerializer::json_schema(gen)
     }
 }
 
@@ -132,6 +608,15 @@ impl World {
     ///
     /// More error checking for bad game maps (e.g. no spawn points)
     pub fn new(width: i32, height: i32, map: Vec<Tile>) -> Result<Self, Error> {
+        Self::with_grid(width, height, map, Grid::Square)
+    }
+
+    /// Create a new world made up of the given shape of tiles.
+    ///
+    /// # Errors
+    ///
+    /// As for [`World::new`].
+    pub fn with_grid(width: i32, height: i32, map: Vec<Tile>, grid: Grid) -> Result<Self, Error> {
         if width <= 0 || height <= 0 {
             bail!("dims ({}, {}) are not both >= 0", width, height);
         }
@@ -143,15 +628,171 @@ impl World {
             bail!("dims ({}, {}) != map length ({})", width, height, map.len());
         }
 
-        let weights = map.iter().copied().map(Tile::spawn_weight);
-        let weights = WeightedIndex::new(weights).context("couldn't create map weightings")?;
+        if !map.iter().any(|tile| tile.spawn_weight() > 0.0) {
+            bail!("couldn't create map weightings: no tiles can spawn flowers");
+        }
 
-        Ok(Self {
+        let mut chunks = Vec::new();
+        for y in (0..height).step_by(CHUNK_SIZE as usize) {
+            for x in (0..width).step_by(CHUNK_SIZE as usize) {
+                let chunk_width = CHUNK_SIZE.min(width - x);
+                let chunk_height = CHUNK_SIZE.min(height - y);
+                let origin = Position::new(x, y);
+                chunks.push(Chunk::new(&map, width, origin, chunk_width, chunk_height));
+            }
+        }
+
+        let mut world = Self {
             width,
             height,
-            map,
-            weights,
-        })
+            grid,
+            wrap_edges: false,
+            chunks,
+            lanes: HashMap::new(),
+            roads: HashMap::new(),
+            entrances: Vec::new(),
+        };
+        world.connect_roads();
+        Ok(world)
+    }
+
+    /// Set the preferred direction of traffic on road tiles, and rebuild the road graph.
+    ///
+    /// # Errors
+    ///
+    /// Each lane must be on a road tile, and cars must be able to drive in its direction.
+    pub fn set_lanes(&mut self, lanes: Vec<Lane>) -> Result<(), Error> {
+        let mut map = HashMap::new();
+        for Lane {
+            position,
+            direction,
+        } in lanes
+        {
+            if !self.get(position).is_some_and(|tile| tile.is_road()) {
+                bail!("lane at ({}, {}) is not on a road", position.x, position.y);
+            }
+            if !self.grid.road_directions().contains(&direction) {
+                bail!("cars can't drive {:?} on a {:?} grid", direction, self.grid);
+            }
+            map.insert(self.wrap(position), direction);
+        }
+        self.lanes = map;
+        self.connect_roads();
+        Ok(())
+    }
+
+    /// Build the road graph from the road tiles and their lanes.
+    ///
+    /// Cars can drive from a road tile onto any neighbouring road tile, or off the edge of the map,
+    /// but only in the direction of the tile's lane if it has one,
+    /// and never against the lane of the tile they're driving onto.
+    fn connect_roads(&mut self) {
+        let mut roads = HashMap::new();
+        let mut entrances = Vec::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let pos = Position::new(x, y);
+                if !self[pos].is_road() {
+                    continue;
+                }
+                let lane = self.lanes.get(&pos).copied();
+                let allowed = (self.grid.road_directions().iter().copied())
+                    .filter(|&dir| lane.is_none_or(|lane| lane == dir));
+                let off_map = |dir| self.step(pos, dir).is_some_and(|p| self.get(p).is_none());
+
+                let mut exits = Vec::new();
+                for dir in allowed {
+                    if off_map(dir.opposite()) {
+                        entrances.push((pos, dir));
+                    }
+                    let next = match self.step(pos, dir) {
+                        Some(next) => next,
+                        None => continue,
+                    };
+                    let drivable = match self.get(next) {
+                        Some(tile) => {
+                            tile.is_road() && self.lanes.get(&next) != Some(&dir.opposite())
+                        }
+                        None => true,
+                    };
+                    if drivable {
+                        exits.push(dir);
+                    }
+                }
+                roads.insert(pos, exits);
+            }
+        }
+        self.roads = roads;
+        self.entrances = entrances;
+    }
+
+    /// The directions a car can drive in from the given position,
+    /// which is empty if the position isn't a road.
+    #[must_use]
+    pub fn road_exits(&self, pos: Position) -> &[Direction] {
+        self.roads.get(&self.wrap(pos)).map_or(&[], Vec::as_slice)
+    }
+
+    /// The road tiles along the edge of the map where cars can drive in,
+    /// along with the direction they start driving in.
+    ///
+    /// Maps that [wrap][`World::wrap_edges`] have no edges, and so no entrances.
+    #[must_use]
+    pub fn road_entrances(&self) -> &[(Position, Direction)] {
+        &self.entrances
+    }
+
+    /// The number of tiles in the map.
+    #[must_use]
+    fn area(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// The lanes on road tiles, in row-major order from the bottom-left.
+    #[must_use]
+    pub fn lanes(&self) -> Vec<Lane> {
+        let mut lanes: Vec<_> = (self.lanes.iter())
+            .map(|(&position, &direction)| Lane {
+                position,
+                direction,
+            })
+            .collect();
+        lanes.sort_by_key(|lane| (lane.position.y, lane.position.x));
+        lanes
+    }
+
+    /// The chunks making up the map, starting from the bottom-left.
+    #[must_use]
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    /// Whether the map is large enough that it should be sent to clients one chunk at a time.
+    #[must_use]
+    pub fn is_streamed(&self) -> bool {
+        self.area() > STREAM_THRESHOLD
+    }
+
+    /// A serializable view of the world without the tile map,
+    /// for worlds that are [streamed][`World::is_streamed`] to clients.
+    pub fn without_map(&self) -> impl Serialize + '_ {
+        WorldSerializer::new(self, false)
+    }
+
+    /// The index into [`World::chunks`] of the chunk containing a position,
+    /// or `None` if the position is out of bounds.
+    #[must_use]
+    fn chunk_index(&self, pos: Position) -> Option<usize> {
+        if pos.x < 0 || pos.x >= self.width || pos.y < 0 || pos.y >= self.height {
+            return None;
+        }
+        let across = (self.width + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        Some((pos.x / CHUNK_SIZE + across * (pos.y / CHUNK_SIZE)) as usize)
+    }
+
+    /// Every tile in the map, row-major from the bottom-left.
+    fn tiles(&self) -> impl Iterator<Item = Tile> + '_ {
+        (0..self.height).flat_map(move |y| (0..self.width).map(move |x| self[Position::new(x, y)]))
     }
 
     /// Convert a position into an index
@@ -171,44 +812,221 @@ impl World {
         }
     }
 
+    /// Wrap a position around the edges of the map, if the map [wraps][`World::wrap_edges`].
+    #[must_use]
+    fn wrap(&self, pos: Position) -> Position {
+        if self.wrap_edges {
+            Position::new(pos.x.rem_euclid(self.width), pos.y.rem_euclid(self.height))
+        } else {
+            pos
+        }
+    }
+
     /// Get the tile at the specified position, or `None` if out of bounds.
+    ///
+    /// Positions are never out of bounds if the map [wraps][`World::wrap_edges`].
     #[must_use]
     pub fn get(&self, pos: Position) -> Option<&Tile> {
-        if pos.x >= 0 && pos.x < self.width && pos.y >= 0 && pos.y < self.height {
-            self.map.get(self.pos_to_index(pos))
-        } else {
-            None
+        let pos = self.wrap(pos);
+        let chunk = &self.chunks[self.chunk_index(pos)?];
+        Some(&chunk.map[chunk.index(pos)])
+    }
+
+    /// Get the next position immediately in the given direction.
+    ///
+    /// Returns `None` if the direction can't be moved in on this world's [grid][`Grid`].
+    /// The position returned may be out of bounds, unless the map [wraps][`World::wrap_edges`].
+    #[must_use]
+    pub fn step(&self, pos: Position, dir: Direction) -> Option<Position> {
+        let (dx, dy) = self.grid.offset(dir)?;
+        Some(self.wrap(Position::new(pos.x + dx, pos.y + dy)))
+    }
+
+    /// The number of steps needed to get from `from` to `to`, ignoring any obstacles.
+    ///
+    /// Takes the shortest way around the edges if the map [wraps][`World::wrap_edges`].
+    #[must_use]
+    pub fn distance(&self, from: Position, to: Position) -> i32 {
+        if !self.wrap_edges {
+            return self.grid.distance(from, to);
+        }
+        let (from, to) = (self.wrap(from), self.wrap(to));
+        let shifts = [-1, 0, 1];
+        shifts
+            .iter()
+            .flat_map(|&dx| shifts.iter().map(move |&dy| (dx, dy)))
+            .map(|(dx, dy)| {
+                let to = Position::new(to.x + dx * self.width, to.y + dy * self.height);
+                self.grid.distance(from, to)
+            })
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// The direction whose first step gets closest to `to`, ignoring any obstacles
+    /// but staying on the map.
+    ///
+    /// Returns `None` if already at `to`.
+    #[must_use]
+    pub fn direction_towards(&self, from: Position, to: Position) -> Option<Direction> {
+        let here = self.distance(from, to);
+        (self.grid.directions().iter().copied())
+            .filter_map(|dir| Some((self.step(from, dir)?, dir)))
+            .filter(|&(next, _)| self.get(next).is_some())
+            .map(|(next, dir)| (self.distance(next, to), dir))
+            .filter(|&(distance, _)| distance < here)
+            .min_by_key(|&(distance, _)| distance)
+            .map(|(_, dir)| dir)
+    }
+
+    /// The tiles along the edge of the map that something moving in `dir` would enter from.
+    ///
+    /// This is the same whether or not the map [wraps][`World::wrap_edges`].
+    #[must_use]
+    pub fn edge(&self, dir: Direction) -> Vec<Position> {
+        let (dx, dy) = match self.grid.offset(dir) {
+            Some(offset) => offset,
+            None => return Vec::new(),
+        };
+        let on_map = |x, y| (0..self.width).contains(&x) && (0..self.height).contains(&y);
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| Position::new(x, y)))
+            .filter(|pos| !on_map(pos.x - dx, pos.y - dy))
+            .collect()
+    }
+
+    /// Every position on the map at most `range` steps from `center`, ignoring any obstacles.
+    pub fn within_range(
+        &self,
+        center: Position,
+        range: i32,
+    ) -> impl Iterator<Item = Position> + '_ {
+        (-range..=range)
+            .flat_map(move |dy| (-range..=range).map(move |dx| (dx, dy)))
+            .map(move |(dx, dy)| self.wrap(Position::new(center.x + dx, center.y + dy)))
+            .filter(move |&pos| self.get(pos).is_some() && self.distance(center, pos) <= range)
+    }
+
+    /// Find a cheapest path over passable tiles from `from` to `to`, using A*.
+    ///
+    /// Paths cost the [movement cost][Tile::movement_cost] of each tile moved onto,
+    /// and the path with the fewest steps is chosen out of those costing the least energy.
+    /// Returns the directions to move in, in order, or `None` if there is no such path
+    /// within [`PATH_SEARCH_LIMIT`] explored tiles.
+    /// If `avoid_roads` is set then the path won't go onto any roads.
+    #[must_use]
+    pub fn find_path(
+        &self,
+        from: Position,
+        to: Position,
+        avoid_roads: bool,
+    ) -> Option<VecDeque<Direction>> {
+        let allowed = |pos| match self.get(pos) {
+            Some(tile) => tile.is_passable() && !(avoid_roads && tile.is_road()),
+            None => false,
+        };
+        if self.get(from).is_none() || !allowed(to) {
+            return None;
+        }
+
+        // costs are (energy, steps), compared in that order;
+        // some tiles cost no energy, so only the steps can be estimated
+        let distance = |pos| self.distance(pos, to);
+        let start = self.pos_to_index(self.wrap(from));
+        let goal = self.pos_to_index(self.wrap(to));
+        let mut cost = HashMap::new();
+        let mut came_from: HashMap<usize, (usize, Direction)> = HashMap::new();
+        let mut open = BinaryHeap::new();
+        let mut explored = 0;
+        cost.insert(start, (0, 0));
+        open.push(Reverse(((0, distance(from)), start)));
+
+        while let Some(Reverse(((energy, estimate), index))) = open.pop() {
+            if index == goal {
+                break;
+            }
+            let pos = self.index_to_pos(index);
+            let current = (energy, estimate - distance(pos));
+            if current > cost[&index] {
+                // already explored along a cheaper path
+                continue;
+            }
+            explored += 1;
+            if explored > PATH_SEARCH_LIMIT {
+                return None;
+            }
+            for &dir in self.grid.directions() {
+                let next = match self.step(pos, dir) {
+                    Some(next) if allowed(next) => next,
+                    _ => continue,
+                };
+                let next_index = self.pos_to_index(next);
+                let next_cost = (current.0 + self[next].movement_cost(), current.1 + 1);
+                if cost.get(&next_index).is_none_or(|&c| next_cost < c) {
+                    cost.insert(next_index, next_cost);
+                    came_from.insert(next_index, (index, dir));
+                    let estimate = (next_cost.0, next_cost.1 + distance(next));
+                    open.push(Reverse((estimate, next_index)));
+                }
+            }
+        }
+
+        if !cost.contains_key(&goal) {
+            return None;
         }
+        let mut path = VecDeque::new();
+        let mut index = goal;
+        while let Some(&(prev, dir)) = came_from.get(&index) {
+            path.push_front(dir);
+            index = prev;
+        }
+        Some(path)
     }
 
     /// Get a random position to spawn a new flower in.
     ///
-    /// Will not spawn a flower in any of the positions of existing `flowers`.
+    /// Each flower spawns with the given `chance`, until one fails to spawn.
+    /// Will only spawn flowers on free tiles according to the spawn `weights`,
+    /// which are updated to include each new flower.
     pub(super) fn spawn_flowers<'a, R: Rng + ?Sized>(
         &'a self,
         rng: &'a mut R,
         config: &'a Config,
-        flowers: &[Flower],
+        chance: f64,
+        weights: &'a mut SpawnWeights,
     ) -> impl Iterator<Item = Flower> + 'a {
-        let mut updates: Vec<_> = flowers
-            .iter()
-            .map(|f| (self.pos_to_index(f.position), &0_f64))
-            .collect();
-        updates.sort_unstable_by_key(|x| x.0);
-        let mut dist = self.weights.clone();
-
+        // how much space each chunk has, which shrinks as flowers spawn
+        let free = weights.free.iter().map(|&w| w.max(0.0));
+        let mut free = WeightedIndex::new(free).ok();
         from_fn(move || {
-            if rng.gen_bool(config.flower_spawn_chance) {
-                // update the weight distribution
-                dist.update_weights(updates.as_slice()).ok()?;
+            if rng.gen_bool(chance) {
+                // choose the chunk to spawn in, then the tile within that chunk
+                let chunk_index = free.as_ref()?.sample(rng);
+                let chunk = &self.chunks[chunk_index];
+                let index = weights.chunks[chunk_index].as_ref()?.sample(rng);
 
-                // get the next index (and prepare to fix up the weights for next time)
-                let index = dist.sample(rng);
-                updates = vec![(index, &0_f64)];
+                // don't spawn another flower here
+                let position = chunk.position(index);
+                weights.occupy(self, position);
+                let space = weights.free[chunk_index].max(0.0);
+                let updated = (free.as_mut())
+                    .is_some_and(|free| free.update_weights(&[(chunk_index, &space)]).is_ok());
+                if !updated {
+                    // there's nowhere left to spawn flowers
+                    free = None;
+                }
 
-                let position = self.index_to_pos(index);
+                let species = if config.flower_species {
+                    Species::choose(rng, chunk.map[index])
+                } else {
+                    Species::Wildflower
+                };
                 let pollen = rng.gen_range(config.flower_initial_pollen.clone());
-                Some(Flower::new(position, pollen))
+                Some(Flower::new(
+                    position,
+                    species,
+                    pollen * species.pollen_factor(),
+                ))
             } else {
                 None
             }
@@ -218,10 +1036,10 @@ impl World {
     /// List all tile that can be used as spawn points for player hives.
     #[must_use]
     pub fn get_spawn_points(&self) -> Vec<Position> {
-        self.map
-            .iter()
+        self.tiles()
             .enumerate()
-            .filter_map(|(index, tile)| tile.is_spawn_point().then(|| self.index_to_pos(index)))
+            .filter(|(_, tile)| tile.is_spawn_point())
+            .map(|(index, _)| self.index_to_pos(index))
             .collect()
     }
 }
@@ -242,6 +1060,92 @@ impl Default for World {
     }
 }
 
+/// Intermediary type used to serialise a [`World`],
+/// converting its chunks back into a single tile map.
+///
+/// Also describes the serialised world's [schema][`JsonSchema`],
+/// so its fields are documented for clients rather than pointing at [`World`].
+#[derive(Serialize, JsonSchema)]
+#[schemars(rename = "World")]
+struct WorldSerializer<'a> {
+    /// The width of the map, in number of tiles.
+    width: i32,
+    /// The height of the map, in number of tiles.
+    height: i32,
+    /// The shape of the tiles.
+    grid: Grid,
+    /// Whether moving off one edge of the map wraps around to the opposite edge.
+    wrap_edges: bool,
+    /// The width and height of each chunk; see [`CHUNK_SIZE`].
+    chunk_size: i32,
+    /// The contents of the map. Row-major, with the first cell at the bottom-left.
+    ///
+    /// `None` if the tiles are sent separately as chunks.
+    #[serde(serialize_with = "serialize_tiles")]
+    #[schemars(with = "Option<Vec<Tile>>")]
+    map: Option<&'a World>,
+    /// The lanes on road tiles, from the bottom-left.
+    lanes: Vec<Lane>,
+}
+
+impl<'a> WorldSerializer<'a> {
+    /// Prepare to serialise the `world`, with or without its tile map.
+    fn new(world: &'a World, with_map: bool) -> Self {
+        Self {
+            width: world.width,
+            height: world.height,
+            grid: world.grid,
+            wrap_edges: world.wrap_edges,
+            chunk_size: CHUNK_SIZE,
+            map: Some(world).filter(|_| with_map),
+            lanes: world.lanes(),
+        }
+    }
+}
+
+/// Serialise every tile of a world as a single sequence, or `null` if there is no world.
+fn serialize_tiles<S: Serializer>(
+    world: &Option<&World>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match world {
+        Some(world) => serializer.collect_seq(world.tiles()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Intermediary type used to deserialise a [`Chunk`], handling any errors.
+#[derive(Deserialize)]
+struct ChunkDeserializer {
+    /// See [`Chunk::origin`].
+    #[serde(flatten)]
+    origin: Position,
+    /// See [`Chunk::width`].
+    width: i32,
+    /// See [`Chunk::height`].
+    height: i32,
+    /// See [`Chunk::map`].
+    map: Vec<Tile>,
+}
+
+impl TryFrom<ChunkDeserializer> for Chunk {
+    type Error = Error;
+    fn try_from(
+        ChunkDeserializer {
+            origin,
+            width,
+            height,
+            map,
+        }: ChunkDeserializer,
+    ) -> Result<Self, Self::Error> {
+        let expected_dim = (width.max(0) as usize).checked_mul(height.max(0) as usize);
+        if expected_dim != Some(map.len()) {
+            bail!("dims ({}, {}) != map length ({})", width, height, map.len());
+        }
+        Ok(Chunk::from_tiles(origin, width, height, map))
+    }
+}
+
 /// Intermediary type used to deserialise a [`World`], handling any errors.
 #[derive(Deserialize)]
 struct WorldDeserializer {
@@ -249,15 +1153,193 @@ struct WorldDeserializer {
     width: i32,
     /// See [`World::height`].
     height: i32,
+    /// See [`World::grid`].
+    #[serde(default)]
+    grid: Grid,
+    /// See [`World::wrap_edges`].
+    #[serde(default)]
+    wrap_edges: bool,
     /// See [`World::map`].
     map: Vec<Tile>,
+    /// See [`World::set_lanes`].
+    #[serde(default)]
+    lanes: Vec<Lane>,
 }
 
 impl TryFrom<WorldDeserializer> for World {
     type Error = Error;
     fn try_from(
-        WorldDeserializer { width, height, map }: WorldDeserializer,
+        WorldDeserializer {
+            width,
+            height,
+            grid,
+            wrap_edges,
+            map,
+            lanes,
+        }: WorldDeserializer,
     ) -> Result<Self, Self::Error> {
-        World::new(width, height, map)
+        let mut world = World::with_grid(width, height, map, grid)?;
+        world.wrap_edges = wrap_edges;
+        world.set_lanes(lanes)?;
+        Ok(world)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a world from rows of tiles, listed from the top of the map down.
+    ///
+    /// `.` is grass, `g` garden, `n` neutral, `r` road, `#` a block, and `S` a spawn point.
+    fn world(rows: &[&str]) -> World {
+        let map = (rows.iter().rev())
+            .flat_map(|row| row.chars())
+            .map(|c| match c {
+                '.' => Tile::Grass,
+                'g' => Tile::Garden,
+                'n' => Tile::Neutral,
+                'r' => Tile::Road,
+                '#' => Tile::Block,
+                'S' => Tile::SpawnPoint,
+                _ => panic!("unknown tile {:?}", c),
+            })
+            .collect();
+        World::new(rows[0].len() as i32, rows.len() as i32, map).unwrap()
+    }
+
+    /// The tiles visited by following `path` from `from`, not including `from` itself.
+    fn follow(world: &World, from: Position, path: &VecDeque<Direction>) -> Vec<Position> {
+        let mut pos = from;
+        (path.iter())
+            .map(|&dir| {
+                pos = world
+                    .step(pos, dir)
+                    .expect("paths should only use valid directions");
+                pos
+            })
+            .collect()
+    }
+
+    #[test]
+    fn path_goes_straight_across_open_ground() {
+        let world = world(&["....."]);
+        let path = world.find_path(Position::new(0, 0), Position::new(4, 0), false);
+        assert_eq!(path, Some(VecDeque::from(vec![Direction::East; 4])));
+    }
+
+    #[test]
+    fn path_to_the_start_is_empty() {
+        let world = world(&["..."]);
+        let path = world.find_path(Position::new(1, 0), Position::new(1, 0), false);
+        assert_eq!(path, Some(VecDeque::new()));
+    }
+
+    #[test]
+    fn path_goes_around_blocks() {
+        let world = world(&[
+            "....", //
+            ".##.", //
+            ".#..", //
+            ".#..", //
+        ]);
+        let (from, to) = (Position::new(0, 0), Position::new(2, 0));
+        let path = world.find_path(from, to, false).unwrap();
+        let visited = follow(&world, from, &path);
+        assert_eq!(visited.last(), Some(&to));
+        assert!(visited.iter().all(|&pos| world[pos].is_passable()));
+        // up the left, over the top, and back down, cutting the corners diagonally
+        assert_eq!(path.len(), 7);
+    }
+
+    #[test]
+    fn path_prefers_cheaper_tiles() {
+        let world = world(&[
+            "ggg", //
+            "nnn", //
+        ]);
+        let (from, to) = (Position::new(0, 0), Position::new(2, 0));
+        let path = world.find_path(from, to, false).unwrap();
+        let visited = follow(&world, from, &path);
+        // flying over the garden costs nothing, so it's cheaper than staying on neutral ground
+        assert_eq!(visited, [Position::new(1, 1), to]);
+        let energy: i32 = visited.iter().map(|&pos| world[pos].movement_cost()).sum();
+        assert_eq!(energy, Tile::Neutral.movement_cost());
+    }
+
+    #[test]
+    fn path_can_avoid_roads() {
+        let world = world(&[
+            ".r.", //
+            ".r.", //
+        ]);
+        let (from, to) = (Position::new(0, 0), Position::new(2, 0));
+        assert!(world.find_path(from, to, false).is_some());
+        assert_eq!(world.find_path(from, to, true), None);
+    }
+
+    #[test]
+    fn no_path_onto_or_behind_blocks() {
+        let world = world(&[".#."]);
+        let from = Position::new(0, 0);
+        assert_eq!(world.find_path(from, Position::new(1, 0), false), None);
+        assert_eq!(world.find_path(from, Position::new(2, 0), false), None);
+        assert_eq!(world.find_path(from, Position::new(5, 0), false), None);
+    }
+
+    #[test]
+    fn path_wraps_around_the_edges() {
+        let mut world = world(&["....."]);
+        world.wrap_edges = true;
+        let (from, to) = (Position::new(0, 0), Position::new(4, 0));
+        let path = world.find_path(from, to, false).unwrap();
+        assert_eq!(follow(&world, from, &path), [to]);
+    }
+
+    #[test]
+    fn spawn_weights_follow_flowers_coming_and_going() {
+        let world = world(&["g.S"]);
+        let mut weights = SpawnWeights::new(&world);
+        let total = Tile::Garden.spawn_weight() + Tile::Grass.spawn_weight();
+        assert!((weights.free[0] - total).abs() < 1e-9);
+
+        let garden = Position::new(0, 0);
+        weights.update(&world, [(garden, true)]);
+        assert!((weights.free[0] - Tile::Grass.spawn_weight()).abs() < 1e-9);
+        // adding a flower where there already is one changes nothing
+        weights.update(&world, [(garden, true)]);
+        assert!((weights.free[0] - Tile::Grass.spawn_weight()).abs() < 1e-9);
+
+        weights.update(&world, [(garden, false)]);
+        assert!((weights.free[0] - total).abs() < 1e-9);
+    }
+
+    #[test]
+    fn flowers_spawn_once_on_each_free_tile() {
+        let world = world(&[
+            "g.S", //
+            "#n.", //
+        ]);
+        let config = Config::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut weights = SpawnWeights::new(&world);
+        weights.update(&world, [(Position::new(2, 0), true)]);
+
+        let flowers: Vec<_> = world
+            .spawn_flowers(&mut rng, &config, 1.0, &mut weights)
+            .map(|f| f.position)
+            .collect();
+        let spawned: HashSet<_> = flowers.iter().copied().collect();
+        assert_eq!(flowers.len(), spawned.len());
+        let expected = [Position::new(0, 1), Position::new(1, 1)];
+        assert_eq!(spawned, expected.iter().copied().collect());
+
+        // once a flower goes, its tile is the only place left for the next one
+        weights.update(&world, [(Position::new(1, 1), false)]);
+        let next: Vec<_> = world
+            .spawn_flowers(&mut rng, &config, 1.0, &mut weights)
+            .map(|f| f.position)
+            .collect();
+        assert_eq!(next, [Position::new(1, 1)]);
     }
 }
@@ -0,0 +1,18 @@
+//! Libraries for working with the bees game.
+//!
+//! The server itself is the `beeeees` binary;
+//! this crate provides the pieces that other programs can build on.
+//!
+//! - [`client`]: a typed async client, for writing bots in Rust.
+//! - [`game`]: the game itself, which the server runs and clients can simulate for themselves.
+//!
+//! The game has none of the server's dependencies,
+//! so it can be built for WebAssembly with `cargo build --lib --target wasm32-unknown-unknown`.
+//! The client isn't available there, since it needs TCP sockets,
+//! but there the `wasm` module lets pages run the game from JavaScript instead.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod client;
+pub mod game;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
@@ -13,6 +13,8 @@
 //! and its documentation can be viewed by running `./beeeees --help`.
 //!
 //! Game configuration is serialised to and from a [`game::Config`] instance.
+//! The config file may instead describe several arenas that are played at once;
+//! see [`server::arena`].
 //!
 //! ## Code layout
 //!
@@ -23,6 +25,8 @@
 //!
 //! Structures used for processing and running the game itself
 //! can be found in the [`game`] module.
+//! It lives in the crate's library rather than the server,
+//! so that clients can also run the game, even from WebAssembly.
 //! All game logic is defined and controlled here.
 //!
 //! The communication protocols, as well as the game server,
@@ -36,18 +40,37 @@
 #![allow(dead_code)]
 #![allow(rustdoc::private_intra_doc_links)]
 
-mod game;
 mod server;
 
-use std::{fs::File, io::BufReader, net::SocketAddr, path::PathBuf, time::Duration};
+use std::{
+    fs::File,
+    io::BufReader,
+    net::{IpAddr, SocketAddr},
+    num::{NonZeroU64, NonZeroUsize},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use anyhow::{Context, Result};
-use futures::{future, Sink, SinkExt, Stream, TryStreamExt};
-use log::{debug, error, info};
+use anyhow::{bail, Context, Result};
+use beeeees::game;
+use futures::{future, future::BoxFuture, Future, FutureExt, Sink, SinkExt, Stream, TryStreamExt};
+use ipnet::IpNet;
+use log::{debug, error, info, warn};
+use rust_embed::RustEmbed;
+use serde::Serialize;
 use structopt::{clap::AppSettings, StructOpt};
-use tokio::{net::TcpListener, signal};
+use tokio::{net::TcpListener, signal, task::JoinHandle};
 use tokio_util::codec::{Decoder, LinesCodec, LinesCodecError};
-use warp::{ws::Message, Filter};
+use warp::{filters::BoxedFilter, host::Authority, ws::Message, Filter, Reply};
+
+use server::spec::Method;
+
+/// The largest request body accepted when an admin kicks a player, in bytes.
+const MAX_KICK_REQUEST_SIZE: u64 = 4096;
+
+/// The fewest ticks allowed between each post of the live scores,
+/// so that Discord and webhooks aren't flooded on servers with fast ticks.
+const MIN_SCORE_INTERVAL: u64 = 10;
 
 /// Simple bees game.
 ///
@@ -69,6 +92,24 @@ struct Opts {
     #[structopt(short, long, requires("config-file"))]
     dump_config: bool,
 
+    /// Path to a tournament file, describing a sequence of rounds to play.
+    ///
+    /// Each round is a game config with an additional "participants" field,
+    /// giving the number of players who may take part in the round.
+    #[structopt(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        conflicts_with("config-file")
+    )]
+    tournament: Option<PathBuf>,
+
+    /// Start a new game whenever the current game finishes.
+    ///
+    /// Connected players are automatically added to each new game.
+    #[structopt(long, conflicts_with("tournament"))]
+    restart: bool,
+
     /// The number of seconds to spend on each game tick.
     #[structopt(
         short = "r",
@@ -79,6 +120,171 @@ struct Opts {
     )]
     tick_rate: Duration,
 
+    /// Slow down the tick rate if ticks keep taking longer than it to process,
+    /// and speed it back up towards the normal rate once they keep up again.
+    ///
+    /// Clients are told the new tick rate when it changes.
+    #[structopt(long)]
+    adaptive_tick_rate: bool,
+
+    /// What to do when the server falls behind on its game ticks.
+    ///
+    /// Missed ticks can push back every later tick ("delay"),
+    /// be processed straight away to catch up ("burst"),
+    /// or be skipped to keep to the original schedule ("skip").
+    #[structopt(
+        long,
+        default_value = "delay",
+        value_name = "POLICY",
+        possible_values = &["delay", "burst", "skip"],
+    )]
+    missed_tick_policy: server::MissedTickPolicy,
+
+    /// The most missed ticks to process in a row when catching up,
+    /// if the missed tick policy is "burst".
+    ///
+    /// Any further missed ticks are skipped.
+    /// By default every missed tick is caught up.
+    #[structopt(long, value_name = "COUNT")]
+    max_catch_up: Option<u32>,
+
+    /// Show chat messages sent by observers to players as well.
+    ///
+    /// By default observers can only chat amongst themselves.
+    #[structopt(long)]
+    public_spectator_chat: bool,
+
+    /// Remove players from the game if they stay disconnected for this many seconds.
+    ///
+    /// By default disconnected players keep their hives and bees until the game finishes.
+    #[structopt(
+        long,
+        value_name = "SECONDS",
+        parse(try_from_str = str_to_duration),
+    )]
+    disconnect_grace: Option<Duration>,
+
+    /// The number of messages that can be queued for each client before it starts missing updates.
+    ///
+    /// Larger values let clients survive longer stalls,
+    /// at the cost of keeping more old updates in memory
+    /// (each is stored once, however many clients are connected).
+    #[structopt(long, default_value = "16", value_name = "COUNT")]
+    broadcast_capacity: NonZeroUsize,
+
+    /// The most observers that can watch the game at once.
+    ///
+    /// By default any number of observers can connect.
+    #[structopt(long, value_name = "COUNT")]
+    max_observers: Option<usize>,
+
+    /// The most connections that can be open from a single address at once.
+    ///
+    /// By default any number of connections can come from the same address.
+    #[structopt(long, value_name = "COUNT")]
+    max_connections_per_ip: Option<usize>,
+
+    /// The most messages each player can send per tick.
+    ///
+    /// Every kind of message counts, such as moves, chat and queries, except for replies to pings.
+    /// Extra messages are ignored with a warning,
+    /// and players who keep going over the limit are disconnected.
+    /// By default players can send any number of messages.
+    #[structopt(long, value_name = "COUNT")]
+    max_moves_per_tick: Option<u32>,
+
+    /// The longest message that can be received from a client, in bytes.
+    ///
+    /// Applies to TCP lines, WebSocket messages, and gRPC messages.
+    #[structopt(long, default_value = "8192", value_name = "BYTES")]
+    max_message_size: usize,
+
+    /// The most bee moves that can be sent in a single moves message.
+    ///
+    /// Each bee in a group counts as a separate move.
+    /// By default there is no limit beyond the maximum message size.
+    #[structopt(long, value_name = "COUNT")]
+    max_moves_per_message: Option<usize>,
+
+    /// The most paths to a `target` each player can have found per tick.
+    ///
+    /// Moves to a target beyond the limit aren't accepted,
+    /// and the bees carry on with whatever they were doing.
+    #[structopt(long, default_value = "32", value_name = "COUNT")]
+    max_paths_per_tick: usize,
+
+    /// What to do when a player falls behind and misses some updates.
+    ///
+    /// Players can be sent a warning, skipped silently,
+    /// or warned and then disconnected once they've fallen behind `--max-lags` times.
+    #[structopt(
+        long,
+        default_value = "warn",
+        value_name = "POLICY",
+        possible_values = &["warn", "skip", "disconnect"],
+    )]
+    player_lag_policy: server::LagPolicy,
+
+    /// What to do when an observer falls behind and misses some updates.
+    ///
+    /// Takes the same values as `--player-lag-policy`.
+    #[structopt(
+        long,
+        default_value = "skip",
+        value_name = "POLICY",
+        possible_values = &["warn", "skip", "disconnect"],
+    )]
+    observer_lag_policy: server::LagPolicy,
+
+    /// The number of times a client can fall behind before being disconnected,
+    /// if its lag policy is "disconnect".
+    #[structopt(long, default_value = "3", value_name = "COUNT")]
+    max_lags: u32,
+
+    /// Path to a file of API tokens that players must register with.
+    ///
+    /// The file is a JSON object mapping each token to the name of the player it belongs to;
+    /// players always play under the name their token belongs to.
+    #[structopt(long, value_name = "PATH", parse(from_os_str))]
+    tokens_file: Option<PathBuf>,
+
+    /// A secret token that lets admins manage the server over the website's API,
+    /// such as to kick players.
+    ///
+    /// Admin requests give the token as `Authorization: Bearer <TOKEN>`.
+    /// By default admin requests are disabled.
+    #[structopt(
+        long,
+        value_name = "TOKEN",
+        env = "BEEEEES_ADMIN_TOKEN",
+        hide_env_values = true
+    )]
+    admin_token: Option<String>,
+
+    /// Only accept connections from this network, given in CIDR notation or as a single address.
+    ///
+    /// May be given several times to allow several networks.
+    /// By default connections are accepted from anywhere.
+    #[structopt(
+        long,
+        value_name = "CIDR",
+        number_of_values = 1,
+        parse(try_from_str = str_to_net),
+    )]
+    allow: Vec<IpNet>,
+
+    /// Refuse connections from this network, given in CIDR notation or as a single address.
+    ///
+    /// May be given several times to deny several networks.
+    /// Takes priority over `--allow`.
+    #[structopt(
+        long,
+        value_name = "CIDR",
+        number_of_values = 1,
+        parse(try_from_str = str_to_net),
+    )]
+    deny: Vec<IpNet>,
+
     /// Address to bind the TCP listener.
     #[structopt(short, long, default_value = "127.0.0.1:49998", value_name = "ADDRESS")]
     tcp_addr: SocketAddr,
@@ -86,6 +292,127 @@ struct Opts {
     /// Address to host the website.
     #[structopt(short, long, default_value = "127.0.0.1:8080", value_name = "ADDRESS")]
     web_addr: SocketAddr,
+
+    /// Address to listen for gRPC connections on, described by `proto/beeeees.proto`.
+    ///
+    /// Requires the server to be built with the `grpc` feature.
+    /// By default gRPC is disabled.
+    #[structopt(short, long, value_name = "ADDRESS")]
+    grpc_addr: Option<SocketAddr>,
+
+    /// Don't listen for TCP connections, so clients can only connect using WebSockets.
+    #[structopt(long, conflicts_with("no-web"))]
+    no_tcp: bool,
+
+    /// Don't host the website, so clients can only connect over TCP.
+    #[structopt(long)]
+    no_web: bool,
+
+    /// Serve the website from this folder, instead of the copy built into the server.
+    ///
+    /// Useful when working on the website, as changes show up without rebuilding the server.
+    #[structopt(long, value_name = "PATH", parse(from_os_str))]
+    website_dir: Option<PathBuf>,
+
+    /// Let web pages from this origin (such as `https://dashboard.example`) use the REST API
+    /// and embed the website, or `*` to allow any origin.
+    ///
+    /// May be given several times to allow several origins.
+    /// By default only pages served by the server itself can do so.
+    #[structopt(
+        long,
+        value_name = "ORIGIN",
+        number_of_values = 1,
+        parse(try_from_str = str_to_origin),
+    )]
+    cors_origin: Vec<String>,
+
+    /// URL of a server directory to announce the server to, for listing in a server browser.
+    ///
+    /// The server's public information is posted to the directory as JSON every so often.
+    /// Both `http://` and `https://` URLs are supported.
+    #[structopt(long, value_name = "URL")]
+    announce_url: Option<hyper::Uri>,
+
+    /// The number of seconds between each announcement to the server directory.
+    #[structopt(
+        long,
+        default_value = "60",
+        value_name = "SECONDS",
+        parse(try_from_str = str_to_duration),
+    )]
+    announce_interval: Duration,
+
+    /// URL of a webhook to post game milestones to as JSON,
+    /// such as games starting and finishing, players joining, and achievements.
+    ///
+    /// May be given several times to post to several webhooks.
+    /// Both `http://` and `https://` URLs are supported.
+    #[structopt(long, value_name = "URL", number_of_values = 1)]
+    webhook_url: Vec<hyper::Uri>,
+
+    /// Send the live scores to webhooks and Discord every this many ticks, at least 10.
+    ///
+    /// By default only the final scores are sent.
+    #[structopt(long, value_name = "TICKS")]
+    score_interval: Option<NonZeroU64>,
+
+    /// ID of a Discord channel to announce the scores of each game to.
+    ///
+    /// Requires the server to be built with the `discord` feature,
+    /// and a bot token for an account that can post to the channel.
+    #[structopt(long, value_name = "ID", requires("discord-token"))]
+    discord_channel: Option<u64>,
+
+    /// The token of the Discord bot used to announce scores.
+    #[structopt(
+        long,
+        value_name = "TOKEN",
+        env = "BEEEEES_DISCORD_TOKEN",
+        hide_env_values = true
+    )]
+    discord_token: Option<String>,
+
+    /// Address of an MQTT broker (`HOST` or `HOST:PORT`) to publish the live scores to,
+    /// for scoreboards and other displays.
+    ///
+    /// Requires the server to be built with the `mqtt` feature.
+    #[structopt(long, value_name = "ADDRESS", parse(try_from_str = str_to_broker))]
+    mqtt_broker: Option<(String, u16)>,
+
+    /// The prefix of every topic published to the MQTT broker.
+    #[structopt(long, default_value = "beeeees", value_name = "TOPIC")]
+    mqtt_topic: String,
+
+    /// Advertise the server on the local network using mDNS, so clients can find it.
+    ///
+    /// Requires the server to be built with the `mdns` feature.
+    #[structopt(long)]
+    mdns: bool,
+
+    /// Path to a file to record the results of finished games.
+    ///
+    /// Existing results in the file are used to compute the leaderboard.
+    #[structopt(long, value_name = "PATH", parse(from_os_str))]
+    results_file: Option<PathBuf>,
+
+    /// Path to a file to write the server's process ID to once it's listening.
+    ///
+    /// The file is removed again when the server shuts down.
+    #[structopt(long, value_name = "PATH", parse(from_os_str))]
+    pid_file: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+/// Things to do instead of running the server.
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Print an AsyncAPI document describing the protocol for playing and watching the game.
+    Asyncapi,
+    /// Print an OpenAPI document describing the REST API served by the website.
+    Openapi,
 }
 
 #[tokio::main]
@@ -98,22 +425,108 @@ async fn main() -> Result<()> {
     let Opts {
         config_file,
         dump_config,
+        tournament,
+        restart,
         tick_rate,
+        adaptive_tick_rate,
+        missed_tick_policy,
+        max_catch_up,
+        public_spectator_chat,
+        disconnect_grace,
+        broadcast_capacity,
+        max_observers,
+        max_connections_per_ip,
+        max_moves_per_tick,
+        max_message_size,
+        max_moves_per_message,
+        max_paths_per_tick,
+        player_lag_policy,
+        observer_lag_policy,
+        max_lags,
+        tokens_file,
+        admin_token,
+        allow,
+        deny,
         tcp_addr,
         web_addr,
+        grpc_addr,
+        no_tcp,
+        no_web,
+        website_dir,
+        cors_origin,
+        announce_url,
+        announce_interval,
+        webhook_url,
+        score_interval,
+        discord_channel,
+        discord_token,
+        mqtt_broker,
+        mqtt_topic,
+        mdns,
+        results_file,
+        pid_file,
+        command,
     } = Opts::from_args();
 
-    let config = config_file.as_ref().map_or_else(
-        || Ok(game::Config::default()),
-        |path| {
-            // using std (blocking) types is OK here, as we have not started any async work
-            let buf = BufReader::new(File::open(path).context("Could not open config file")?);
-            serde_json::from_reader(buf).context("Could not parse config file")
-        },
-    );
-    let config = config?;
+    if let Some(command) = command {
+        let spec = match command {
+            Command::Asyncapi => server::spec::asyncapi(),
+            Command::Openapi => server::spec::openapi(),
+        };
+        println!("{}", serde_json::to_string_pretty(&spec)?);
+        return Ok(());
+    }
+
+    if announce_url
+        .as_ref()
+        .is_some_and(|url| !matches!(url.scheme_str(), Some("http" | "https")))
+    {
+        bail!("Only http:// and https:// URLs are supported for announcing the server");
+    }
+    if webhook_url
+        .iter()
+        .any(|url| !matches!(url.scheme_str(), Some("http" | "https")))
+    {
+        bail!("Only http:// and https:// URLs are supported for webhooks");
+    }
+    if announce_interval.is_zero() {
+        bail!("The announce interval must be positive");
+    }
+    if score_interval.is_some_and(|n| n.get() < MIN_SCORE_INTERVAL) {
+        bail!(
+            "The score interval must be at least {} ticks",
+            MIN_SCORE_INTERVAL
+        );
+    }
+    if discord_channel.is_some() && !cfg!(feature = "discord") {
+        bail!("Announcing to Discord requires building the server with the discord feature");
+    }
+    if discord_channel.is_some() != discord_token.is_some() {
+        bail!("Announcing to Discord needs both --discord-channel and --discord-token");
+    }
+    if mqtt_broker.is_some() && !cfg!(feature = "mqtt") {
+        bail!("Publishing to MQTT requires building the server with the mqtt feature");
+    }
+    if mdns && !cfg!(feature = "mdns") {
+        bail!("Advertising with mDNS requires building the server with the mdns feature");
+    }
+
+    let config = match &config_file {
+        // using std (blocking) types is OK here, as we have not started any async work
+        Some(path) => {
+            let buf = std::fs::read(path).context("Could not open config file")?;
+            server::arena::ConfigFile::from_slice(&buf).context("Could not parse config file")?
+        }
+        None => server::arena::ConfigFile::Single(Box::default()),
+    };
 
     if dump_config {
+        let config = match config {
+            server::arena::ConfigFile::Single(config) => config,
+            server::arena::ConfigFile::Arenas(_) => {
+                bail!("Cannot dump a config file with several arenas")
+            }
+        };
         let path = config_file.expect("config-file is required by -d");
         let output = File::create(&path).context("Could not create specified config file")?;
         serde_json::to_writer_pretty(output, &config).context("Could not write to config file")?;
@@ -122,54 +535,598 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
-    let state = game::State::new(config);
-    let game_server = server::make_game_server(state, tick_rate);
-    tokio::spawn(game_server.server);
+    let mut tournament = match tournament {
+        Some(path) => {
+            let buf = BufReader::new(File::open(path).context("Could not open tournament file")?);
+            let config: server::tournament::TournamentConfig =
+                serde_json::from_reader(buf).context("Could not parse tournament file")?;
+            for (i, round) in config.rounds.iter().enumerate() {
+                (round.config.validate())
+                    .with_context(|| format!("Could not use round {} of the tournament", i + 1))?;
+            }
+            Some(server::tournament::Tournament::new(config))
+        }
+        None => None,
+    };
 
-    let client_info = game_server.client_info;
-    let tcpserver = tokio::spawn(make_tcp_server(tcp_addr, client_info.clone()));
-    let webserver = tokio::spawn(make_web_server(web_addr, client_info.clone()));
-    info!("Listening on tcp://{} and http://{}", tcp_addr, web_addr);
+    let arenas = match config {
+        server::arena::ConfigFile::Single(config) => {
+            let addrs = (Some(tcp_addr), Some(web_addr), grpc_addr);
+            vec![(None, *config, tick_rate, addrs)]
+        }
+        server::arena::ConfigFile::Arenas(_) if tournament.is_some() => {
+            bail!("Cannot play a tournament with several arenas")
+        }
+        server::arena::ConfigFile::Arenas(arenas) => (arenas.into_iter())
+            .map(|arena| {
+                let tick_rate = arena.tick_rate.unwrap_or(tick_rate);
+                let addrs = (arena.tcp_addr, arena.web_addr, arena.grpc_addr);
+                (Some(arena.name), arena.config, tick_rate, addrs)
+            })
+            .collect(),
+    };
+
+    let tokens = match tokens_file {
+        Some(path) => {
+            let buf = BufReader::new(File::open(path).context("Could not open tokens file")?);
+            Some(serde_json::from_reader(buf).context("Could not parse tokens file")?)
+        }
+        None => None,
+    };
 
-    // we're done with the channels, drop now to assist in cleanup later
-    drop(client_info);
+    let results = server::results::Results::open(results_file)?;
+    let options = server::Options {
+        name: None,
+        tick_rate,
+        adaptive_tick_rate,
+        missed_tick_policy,
+        max_catch_up,
+        public_spectator_chat,
+        disconnect_grace,
+        broadcast_capacity: broadcast_capacity.get(),
+        score_interval,
+        max_observers,
+        max_connections_per_ip,
+        max_moves_per_tick,
+        max_moves_per_message,
+        max_paths_per_tick,
+        player_lag_policy,
+        observer_lag_policy,
+        max_lags,
+        tokens,
+        admin_token,
+        access: server::access::AccessList { allow, deny },
+    };
+
+    if let Some(dir) = &website_dir {
+        if !dir.is_dir() {
+            warn!(
+                "Website folder {} does not exist, so the website will not be available",
+                dir.display()
+            );
+        } else if !dir.join("index.html").is_file() {
+            warn!("Website folder {} has no index.html", dir.display());
+        }
+    }
+
+    let listen = ListenOptions {
+        max_message_size,
+        website_dir,
+        cors_origins: cors_origin,
+        webhook_urls: webhook_url,
+        discord: discord_channel.zip(discord_token),
+        mqtt_broker,
+        mqtt_topic,
+    };
+
+    let mut running = Vec::with_capacity(arenas.len());
+    for (name, config, tick_rate, (tcp_addr, web_addr, grpc_addr)) in arenas {
+        let tcp_addr = tcp_addr.filter(|_| !no_tcp);
+        let web_addr = web_addr.filter(|_| !no_web);
+        #[cfg(feature = "mdns")]
+        let (map, slots) = {
+            let slots = config.world.get_spawn_points().len();
+            let slots = config.max_players.map_or(slots, |max| max.min(slots));
+            ((config.world.width, config.world.height), slots)
+        };
+        let schedule = match tournament.take() {
+            Some(tournament) => server::schedule::Schedule::Tournament(tournament),
+            None if restart => server::schedule::Schedule::repeat(config),
+            None => server::schedule::Schedule::Single(Some(config)),
+        };
+        let options = server::Options {
+            name: name.clone(),
+            tick_rate,
+            ..options.clone()
+        };
+        let results = results.clone();
+        let addrs = (tcp_addr, web_addr, grpc_addr);
+        let arena = Arena::start(name, schedule, results, options, addrs, &listen);
+        let arena = arena.await?;
+        #[cfg(feature = "mdns")]
+        let arena = if mdns {
+            (arena.advertise(map, slots)).context("Could not advertise on the local network")?
+        } else {
+            arena
+        };
+        if let Some(url) = &announce_url {
+            let announcing = server::announce::announce(
+                url.clone(),
+                announce_interval,
+                arena.client_info.clone(),
+            );
+            tokio::spawn(announcing);
+        }
+        running.push(arena);
+    }
 
-    let _ = signal::ctrl_c().await;
+    let _pid_file = pid_file
+        .map(PidFile::create)
+        .transpose()
+        .context("Could not write PID file")?;
 
-    info!("Interrupt requested, cleaning up...");
-    game_server.shutdown.await;
+    let mut signals = Signals::new().context("Could not listen for signals")?;
+    loop {
+        match (signals.recv().await, &config_file) {
+            (Signal::Shutdown, _) => break,
+            (Signal::Reload, Some(path)) => match reload_config(path).await {
+                Ok(config) => reload(&running, config).await,
+                Err(e) => error!("Keeping the current configuration: {:?}", e),
+            },
+            (Signal::Reload, None) => {
+                info!("Received SIGHUP, but there is no config file to reload")
+            }
+        }
+    }
 
-    debug!("Ensuring external servers have cleaned up");
-    webserver.await?;
-    tcpserver.await?;
+    info!("Shutdown requested, cleaning up...");
+    future::try_join_all(running.into_iter().map(Arena::shutdown)).await?;
 
     Ok(())
 }
 
+/// A game server playing its own games, along with the servers listening for its clients.
+///
+/// A config file for a single game describes a single arena, which has no name.
+struct Arena {
+    /// The arena's name, if the config file describes several arenas.
+    name: Option<String>,
+    /// Channel information used to communicate with the game server.
+    client_info: server::ClientState,
+    /// A future that can be awaited to clean up the game server.
+    shutdown: BoxFuture<'static, ()>,
+    /// The TCP server, unless disabled.
+    tcpserver: Option<JoinHandle<()>>,
+    /// The web server, unless disabled.
+    webserver: Option<JoinHandle<()>>,
+    /// The gRPC server, if enabled.
+    grpcserver: Option<JoinHandle<()>>,
+    /// The addresses the arena is listening on.
+    listening: server::Listening,
+    /// The arena's advertisement on the local network, if any.
+    #[cfg(feature = "mdns")]
+    advertisement: Option<server::mdns::Advertisement>,
+}
+
+impl Arena {
+    /// Start playing the games in the `schedule`,
+    /// listening for clients on the TCP, web and gRPC addresses in `addrs`, if provided.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is nothing to listen on,
+    /// if gRPC is requested without the `grpc` feature,
+    /// or if any server couldn't bind to its address.
+    async fn start(
+        name: Option<String>,
+        schedule: server::schedule::Schedule,
+        results: server::results::Results,
+        options: server::Options,
+        (tcp_addr, web_addr, grpc_addr): (
+            Option<SocketAddr>,
+            Option<SocketAddr>,
+            Option<SocketAddr>,
+        ),
+        listen: &ListenOptions,
+    ) -> Result<Self> {
+        if tcp_addr.is_none() && web_addr.is_none() && grpc_addr.is_none() {
+            match &name {
+                Some(name) => bail!("Arena {:?} has nothing to listen on", name),
+                None => bail!("Nothing to listen on"),
+            }
+        }
+        if grpc_addr.is_some() && !cfg!(feature = "grpc") {
+            bail!("Serving gRPC requires building the server with the grpc feature");
+        }
+
+        let game_server = server::make_game_server(schedule, results, options);
+        let client_info = game_server.client_info;
+
+        let tcp_listener = match tcp_addr {
+            Some(addr) => Some(
+                TcpListener::bind(addr)
+                    .await
+                    .with_context(|| format!("Could not listen on tcp://{}", addr))?,
+            ),
+            None => None,
+        };
+        let webserver = web_addr
+            .map(|addr| {
+                make_web_server(addr, listen, client_info.clone())
+                    .with_context(|| format!("Could not listen on http://{}", addr))
+            })
+            .transpose()?;
+        let grpc_listener = match grpc_addr {
+            Some(addr) => Some(
+                TcpListener::bind(addr)
+                    .await
+                    .with_context(|| format!("Could not listen on grpc://{}", addr))?,
+            ),
+            None => None,
+        };
+
+        // report the addresses actually bound, in case any asked for port zero
+        let listening = server::Listening {
+            tcp: (tcp_listener.as_ref())
+                .map(TcpListener::local_addr)
+                .transpose()?,
+            web: webserver.as_ref().map(|&(addr, _)| addr),
+            grpc: (grpc_listener.as_ref())
+                .map(TcpListener::local_addr)
+                .transpose()?,
+        };
+        client_info.set_listening(listening);
+
+        // subscribe webhooks before the game starts, so they hear about it
+        for url in &listen.webhook_urls {
+            tokio::spawn(server::webhook::notify(url.clone(), client_info.clone()));
+        }
+        #[cfg(feature = "discord")]
+        if let Some((channel, token)) = &listen.discord {
+            let announcing =
+                server::discord::announce(*channel, token.clone(), client_info.clone());
+            tokio::spawn(announcing);
+        }
+        #[cfg(feature = "mqtt")]
+        if let Some((host, port)) = &listen.mqtt_broker {
+            let publishing = server::mqtt::publish(
+                host.clone(),
+                *port,
+                listen.mqtt_topic.clone(),
+                client_info.clone(),
+            );
+            tokio::spawn(publishing);
+        }
+        tokio::spawn(game_server.server);
+        let tcpserver = tcp_listener.map(|listener| {
+            tokio::spawn(make_tcp_server(
+                listener,
+                listen.max_message_size,
+                client_info.clone(),
+            ))
+        });
+        let webserver = webserver.map(|(_, server)| tokio::spawn(server));
+        #[cfg(feature = "grpc")]
+        let grpcserver = grpc_listener.map(|listener| {
+            tokio::spawn(server::grpc::serve(
+                listener,
+                listen.max_message_size,
+                client_info.clone(),
+            ))
+        });
+        // without the feature there's no listener, as asking for one was refused above
+        #[cfg(not(feature = "grpc"))]
+        let grpcserver = grpc_listener.and(None);
+
+        let addrs: Vec<_> = (listening
+            .tcp
+            .map(|addr| format!("tcp://{}", addr))
+            .into_iter())
+        .chain(listening.web.map(|addr| format!("http://{}", addr)))
+        .chain(listening.grpc.map(|addr| format!("grpc://{}", addr)))
+        .collect();
+        let addrs = addrs.join(" and ");
+        match &name {
+            Some(name) => info!("Arena {:?} listening on {}", name, addrs),
+            None => info!("Listening on {}", addrs),
+        }
+        let ready = Ready {
+            arena: name.as_deref(),
+            listening,
+        };
+        println!("{}", serde_json::to_string(&ready)?);
+
+        Ok(Self {
+            name,
+            client_info,
+            shutdown: game_server.shutdown.boxed(),
+            tcpserver,
+            webserver,
+            grpcserver,
+            listening,
+            #[cfg(feature = "mdns")]
+            advertisement: None,
+        })
+    }
+
+    /// Advertise the arena on the local network,
+    /// describing it as playing on a map of the given size with `slots` players.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the arena couldn't be advertised.
+    #[cfg(feature = "mdns")]
+    fn advertise(mut self, map: (i32, i32), slots: usize) -> Result<Self> {
+        let details = server::mdns::Details {
+            name: (self.name.clone()).unwrap_or_else(|| String::from("beeeees")),
+            listening: self.listening,
+            map,
+            slots,
+        };
+        self.advertisement = Some(server::mdns::Advertisement::start(&details)?);
+        info!("Advertising {:?} on the local network", details.name);
+        Ok(self)
+    }
+
+    /// Shut down the arena's game server, and wait for its servers to clean up.
+    ///
+    /// # Errors
+    ///
+    /// Fails if any server panicked.
+    async fn shutdown(self) -> Result<()> {
+        // we're done with the channels, drop now to assist in cleanup
+        drop(self.client_info);
+        self.shutdown.await;
+
+        debug!("Ensuring external servers have cleaned up");
+        if let Some(webserver) = self.webserver {
+            webserver.await?;
+        }
+        if let Some(tcpserver) = self.tcpserver {
+            tcpserver.await?;
+        }
+        if let Some(grpcserver) = self.grpcserver {
+            grpcserver.await?;
+        }
+        Ok(())
+    }
+}
+
+/// Options for the servers listening for clients, and the services notified of each game,
+/// shared by every arena.
+#[derive(Debug, Clone)]
+struct ListenOptions {
+    /// The longest message that can be received from a client, in bytes.
+    max_message_size: usize,
+    /// The folder to serve the website from, instead of the copy built into the server.
+    website_dir: Option<PathBuf>,
+    /// The origins of other websites allowed to use the REST API and embed the website,
+    /// where `*` allows any origin.
+    cors_origins: Vec<String>,
+    /// The webhooks to post each game's milestones to.
+    webhook_urls: Vec<hyper::Uri>,
+    /// The Discord channel to announce scores to, and the token of the bot that announces them.
+    discord: Option<(u64, String)>,
+    /// The host and port of the MQTT broker to publish scores to.
+    mqtt_broker: Option<(String, u16)>,
+    /// The prefix of every topic published to the MQTT broker.
+    mqtt_topic: String,
+}
+
+/// Printed to standard output as a line of JSON once an arena is listening,
+/// so that whatever started the server can find out where to connect.
+#[derive(Debug, Serialize)]
+struct Ready<'a> {
+    /// The arena's name, if the config file describes several arenas.
+    arena: Option<&'a str>,
+    /// The addresses the arena is listening on.
+    #[serde(flatten)]
+    listening: server::Listening,
+}
+
+/// Pass on a reloaded `config` file to each of the running `arenas` it describes.
+///
+/// Arenas no longer described by the file keep their current configuration.
+async fn reload(arenas: &[Arena], config: server::arena::ConfigFile) {
+    match config {
+        server::arena::ConfigFile::Single(config) => match arenas {
+            [arena] if arena.name.is_none() => arena.client_info.reload(*config).await,
+            _ => error!("Keeping the current configuration, as the config file has no arenas"),
+        },
+        server::arena::ConfigFile::Arenas(configs) => {
+            for arena in arenas {
+                let config = configs
+                    .iter()
+                    .find(|c| arena.name.as_ref() == Some(&c.name));
+                match (config, &arena.name) {
+                    (Some(config), _) => arena.client_info.reload(config.config.clone()).await,
+                    (None, Some(name)) => warn!(
+                        "Keeping the current configuration for arena {:?}, as it's no longer in the config file",
+                        name
+                    ),
+                    (None, None) => {
+                        error!("Keeping the current configuration, as the config file now has arenas")
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A request from the operating system for the server to do something.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Signal {
+    /// Shut down cleanly, after Ctrl-C or `SIGTERM`.
+    Shutdown,
+    /// Reload the configuration, after `SIGHUP`.
+    Reload,
+}
+
+/// Listens for the signals the server responds to.
+///
+/// On Unix this includes `SIGTERM`, so that service managers like systemd or Kubernetes
+/// can stop the server cleanly, and `SIGHUP`, which would otherwise kill the server.
+/// Elsewhere only Ctrl-C is handled.
+struct Signals {
+    /// Receives `SIGTERM`.
+    #[cfg(unix)]
+    terminate: signal::unix::Signal,
+    /// Receives `SIGHUP`.
+    #[cfg(unix)]
+    hangup: signal::unix::Signal,
+}
+
+impl Signals {
+    /// Start listening for signals.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the signal handlers couldn't be installed.
+    #[cfg(unix)]
+    fn new() -> Result<Self> {
+        use signal::unix::{signal, SignalKind};
+        Ok(Self {
+            terminate: signal(SignalKind::terminate())?,
+            hangup: signal(SignalKind::hangup())?,
+        })
+    }
+
+    /// Start listening for signals.
+    #[cfg(not(unix))]
+    fn new() -> Result<Self> {
+        Ok(Self {})
+    }
+
+    /// Wait for the next signal.
+    #[cfg(unix)]
+    async fn recv(&mut self) -> Signal {
+        tokio::select! {
+            _ = signal::ctrl_c() => Signal::Shutdown,
+            _ = self.terminate.recv() => Signal::Shutdown,
+            _ = self.hangup.recv() => Signal::Reload,
+        }
+    }
+
+    /// Wait for the next signal.
+    #[cfg(not(unix))]
+    async fn recv(&mut self) -> Signal {
+        let _ = signal::ctrl_c().await;
+        Signal::Shutdown
+    }
+}
+
+/// A file holding the server's process ID, removed again once the server shuts down.
+#[derive(Debug)]
+struct PidFile(PathBuf);
+
+impl PidFile {
+    /// Write the current process ID to the file at `path`, replacing anything already there.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file couldn't be written.
+    fn create(path: PathBuf) -> Result<Self> {
+        std::fs::write(&path, format!("{}\n", std::process::id()))?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.0) {
+            error!("Could not remove PID file {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+/// Read the config file at `path` again while the server is running.
+///
+/// # Errors
+///
+/// Fails if the file can no longer be read or parsed.
+async fn reload_config(path: &Path) -> Result<server::arena::ConfigFile> {
+    let buf = tokio::fs::read(path)
+        .await
+        .context("Could not open config file")?;
+    server::arena::ConfigFile::from_slice(&buf).context("Could not parse config file")
+}
+
 /// Convert a string into a duration.
 fn str_to_duration(s: &str) -> Result<Duration, std::num::ParseFloatError> {
     let secs = s.parse()?;
     Ok(Duration::from_secs_f64(secs))
 }
 
-/// Create a TCP server hosted at the given address.
+/// Convert a string into a network, treating a single address as a network of its own.
+fn str_to_net(s: &str) -> Result<IpNet, ipnet::AddrParseError> {
+    s.parse()
+        .or_else(|e| s.parse::<IpAddr>().map(IpNet::from).map_err(|_| e))
+}
+
+/// Convert a string into a web origin, such as `https://example.com:8443`,
+/// leaving `*` (for any origin) as it is.
+fn str_to_origin(s: &str) -> Result<String> {
+    if s == "*" {
+        return Ok(String::from(s));
+    }
+    let uri: hyper::Uri = s.parse()?;
+    match (uri.scheme_str(), uri.authority(), uri.path_and_query()) {
+        (Some(scheme @ ("http" | "https")), Some(authority), path)
+            if !authority.as_str().contains('@')
+                && path.is_none_or(|path| path.as_str() == "/") =>
+        {
+            Ok(format!("{}://{}", scheme, authority))
+        }
+        _ => bail!("Expected an origin like https://example.com"),
+    }
+}
+
+/// Convert a string into the host and port of an MQTT broker,
+/// using the standard port 1883 if none is given.
+fn str_to_broker(s: &str) -> Result<(String, u16)> {
+    let authority: hyper::http::uri::Authority = s.parse()?;
+    if authority.as_str().contains('@') {
+        bail!("Expected an address like mqtt.example or mqtt.example:1883");
+    }
+    Ok((
+        authority.host().to_owned(),
+        authority.port_u16().unwrap_or(1883),
+    ))
+}
+
+/// Create a TCP server accepting connections from the given listener.
 ///
-/// Clients are initialized using the provided `client_info`.
+/// Clients are initialized using the provided `client_info`,
+/// and lines longer than `max_message_size` bytes are rejected.
 /// Runs until it receives a shutdown signal over `client_info`.
-async fn make_tcp_server(addr: SocketAddr, client_info: server::ClientState) {
-    let tcp_listener = TcpListener::bind(addr)
-        .await
-        .expect("Couldn't bind to address");
+async fn make_tcp_server(
+    tcp_listener: TcpListener,
+    max_message_size: usize,
+    client_info: server::ClientState,
+) {
     let mut shutdown = client_info.get_shutdown_notifier();
 
     loop {
-        let (socket, addr) = tokio::select! {
-            result = tcp_listener.accept() => result.expect("Couldn't accept new client"),
+        let result = tokio::select! {
+            result = tcp_listener.accept() => result,
             _ = shutdown.recv() => break,
         };
+        // failing to accept one client (e.g. running out of file descriptors) isn't fatal
+        let (socket, addr) = match result {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                error!("Couldn't accept new client: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        if !client_info.permits(addr.ip()) {
+            info!("Refusing connection from {}", addr);
+            continue;
+        }
 
-        let socket = LinesCodec::new_with_max_length(8192).framed(socket);
-        let socket = use_json_protocol(socket);
+        let socket = LinesCodec::new_with_max_length(max_message_size).framed(socket);
+        let socket = use_json_protocol(socket, client_info.max_moves_per_message());
 
         let channels = client_info.clone();
         tokio::spawn(async move {
@@ -186,27 +1143,50 @@ async fn make_tcp_server(addr: SocketAddr, client_info: server::ClientState) {
 /// Create a web server hosted at the given address.
 ///
 /// This serves the website used to observer the game,
-/// and provides the websocket interface.
-/// Clients are initialized using the provided `client_info`.
-/// Server runs until it receives a shutdown signal over `client_info`.
+/// provides the websocket interface,
+/// and hosts a simple REST API under `/api`.
+/// Clients are initialized using the provided `client_info`,
+/// and are disconnected if they send a WebSocket message longer than
+/// the maximum message size in `listen`.
+/// Returns the address the server actually bound to,
+/// along with the server itself,
+/// which runs until it receives a shutdown signal over `client_info`.
+///
+/// The website is served from the copy built into the server,
+/// unless `listen` gives a folder to serve it from instead.
 ///
-/// The served files should be accessible from a folder `./website`,
-/// relative to the program's current directory.
-async fn make_web_server(addr: SocketAddr, client_info: server::ClientState) {
+/// # Errors
+///
+/// Fails if the server couldn't bind to the address.
+fn make_web_server(
+    addr: SocketAddr,
+    listen: &ListenOptions,
+    client_info: server::ClientState,
+) -> Result<(SocketAddr, impl Future<Output = ()>)> {
     let mut signal = client_info.get_shutdown_notifier();
+    let results = client_info.results().clone();
+    let tournament = client_info.tournament().cloned();
+    let status_info = client_info.clone();
+    let info_info = client_info.clone();
+    let access_info = client_info.clone();
+    let kick_info = client_info.clone();
+    #[cfg(feature = "graphql")]
+    let graphql_info = client_info.clone();
+    let max_message_size = listen.max_message_size;
+    let max_moves = client_info.max_moves_per_message();
 
     // transform a WebSocket into a stream matching the protocol
-    let prepare = |socket: warp::ws::WebSocket| {
+    let prepare = move |socket: warp::ws::WebSocket| {
         let socket = socket
             .try_take_while(|msg| future::ok(!msg.is_close()))
             .try_filter_map(|msg| future::ok(msg.to_str().map(String::from).ok()))
             .with(|s| future::ok(Message::text(s)));
-        use_json_protocol(socket)
+        use_json_protocol(socket, max_moves)
     };
 
     let to_websocket = warp::addr::remote()
         .map(|addr: Option<SocketAddr>| addr.expect("no socket address available"))
-        .and(warp::ws())
+        .and(warp::ws().map(move |ws: warp::ws::Ws| ws.max_message_size(max_message_size)))
         .and(warp::any().map(move || client_info.clone()));
 
     let play = warp::path("play").and(to_websocket.clone()).map(
@@ -235,14 +1215,287 @@ async fn make_web_server(addr: SocketAddr, client_info: server::ClientState) {
         },
     );
 
-    let server = warp::serve(play.or(observe).or(warp::fs::dir("./website")));
+    let leaderboard = api_route(Method::Get, "leaderboard")
+        .map(move || warp::reply::json(&results.leaderboard()).into_response());
 
-    let (_, server) = server.bind_with_graceful_shutdown(addr, async move {
+    let tournament = api_route(Method::Get, "tournament").map(move || {
+        let bracket = tournament
+            .as_ref()
+            .map(server::tournament::Tournament::bracket);
+        match bracket {
+            Some(bracket) => warp::reply::json(&bracket).into_response(),
+            None => warp::reply::with_status(
+                "No tournament is running",
+                warp::http::StatusCode::NOT_FOUND,
+            )
+            .into_response(),
+        }
+    });
+
+    let status = api_route(Method::Get, "status").and_then(move || {
+        let status_info = status_info.clone();
+        async move {
+            let status = status_info.status().await;
+            Ok::<_, warp::Rejection>(warp::reply::json(&status))
+        }
+    });
+
+    let info = api_route(Method::Get, "info").and_then(move || {
+        let info_info = info_info.clone();
+        async move {
+            let info = info_info.info().await;
+            Ok::<_, warp::Rejection>(warp::reply::json(&info))
+        }
+    });
+
+    // admins can kick players out of the game for good
+    let kick = api_route(Method::Post, "kick")
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::body::content_length_limit(MAX_KICK_REQUEST_SIZE))
+        .and(warp::body::json())
+        .and_then(move |auth: Option<String>, request: server::KickRequest| {
+            let kick_info = kick_info.clone();
+            async move {
+                let token = auth.as_deref().and_then(|a| a.strip_prefix("Bearer "));
+                let (status, msg) = if !token.is_some_and(|t| kick_info.is_admin(t)) {
+                    let msg = String::from("A valid admin token is required");
+                    (warp::http::StatusCode::UNAUTHORIZED, msg)
+                } else if let Err(e) = kick_info.kick(&request.name).await {
+                    (warp::http::StatusCode::NOT_FOUND, e.to_string())
+                } else {
+                    (
+                        warp::http::StatusCode::OK,
+                        format!("Kicked {}", request.name),
+                    )
+                };
+                Ok::<_, warp::Rejection>(warp::reply::with_status(msg, status))
+            }
+        });
+
+    let (website, index) = match &listen.website_dir {
+        Some(dir) => (
+            warp::fs::dir(dir.clone())
+                .map(warp::Reply::into_response)
+                .boxed(),
+            warp::fs::file(dir.join("index.html"))
+                .map(warp::Reply::into_response)
+                .boxed(),
+        ),
+        None => (
+            warp::get()
+                .and(warp::path::tail())
+                .and_then(|tail: warp::path::Tail| future::ready(embedded_file(tail.as_str())))
+                .boxed(),
+            warp::any()
+                .and_then(|| future::ready(embedded_file("")))
+                .boxed(),
+        ),
+    };
+
+    // let the website handle its own routes, so pages can be linked to and reloaded
+    let app_route = warp::get()
+        .and(warp::path::tail())
+        .and_then(|tail: warp::path::Tail| {
+            future::ready(if is_app_route(tail.as_str()) {
+                Ok(())
+            } else {
+                Err(warp::reject::not_found())
+            })
+        })
+        .untuple_one()
+        .and(index);
+
+    let api = leaderboard
+        .or(tournament)
+        .unify()
+        .or(status.map(warp::Reply::into_response))
+        .unify()
+        .or(info.map(warp::Reply::into_response))
+        .unify()
+        .or(kick.map(warp::Reply::into_response))
+        .unify()
+        .boxed();
+    let api = with_cors(api, &listen.cors_origins, &["GET"], &[]);
+    // GraphiQL and dashboards send queries as JSON
+    #[cfg(feature = "graphql")]
+    let api = api
+        .or(with_cors(
+            (api_route(Method::Post, "graphql").and(server::graphql::query(graphql_info)))
+                .or(api_route(Method::Get, "graphql").and(server::graphql::graphiql()))
+                .unify()
+                .boxed(),
+            &listen.cors_origins,
+            &["GET", "POST"],
+            &["content-type"],
+        ))
+        .unify()
+        .boxed();
+    let any_origin = listen.cors_origins.iter().any(|origin| origin == "*");
+    let api = warp::path("api").and(api);
+
+    let frame_ancestors = if any_origin {
+        String::from("*")
+    } else {
+        std::iter::once("'self'")
+            .chain(listen.cors_origins.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let mut security_headers = warp::http::HeaderMap::new();
+    security_headers.insert(
+        "X-Content-Type-Options",
+        warp::http::HeaderValue::from_static("nosniff"),
+    );
+    security_headers.insert(
+        "Referrer-Policy",
+        warp::http::HeaderValue::from_static("no-referrer"),
+    );
+    security_headers.insert(
+        "Content-Security-Policy",
+        warp::http::HeaderValue::from_str(&format!("frame-ancestors {}", frame_ancestors))?,
+    );
+
+    // refuse anyone not allowed access before doing anything else
+    let permitted = warp::addr::remote()
+        .and_then(move |addr: Option<SocketAddr>| {
+            let allowed = addr.is_some_and(|addr| access_info.permits(addr.ip()));
+            future::ready(if allowed {
+                Ok(())
+            } else {
+                debug!("Refusing web request from {:?}", addr);
+                Err(warp::reject::custom(Forbidden))
+            })
+        })
+        .untuple_one();
+
+    let server = warp::serve(
+        permitted
+            .and(play.or(observe).or(api).or(website).or(app_route))
+            .recover(forbidden)
+            .with(warp::reply::with::headers(security_headers)),
+    );
+
+    let bound = server.try_bind_with_graceful_shutdown(addr, async move {
         signal.recv().await;
         debug!("Web server shutting down");
-    });
+    })?;
+
+    Ok(bound)
+}
+
+/// The website's files, built into the server so it can be run from anywhere.
+///
+/// Debug builds read the files from the `website` folder on each request instead.
+#[derive(RustEmbed)]
+#[folder = "website/"]
+struct Website;
+
+/// Respond with the website's file at `path`,
+/// or the `index.html` within it if `path` is a folder.
+fn embedded_file(path: &str) -> Result<warp::reply::Response, warp::Rejection> {
+    let path = if path.is_empty() || path.ends_with('/') {
+        format!("{}index.html", path)
+    } else {
+        String::from(path)
+    };
+    let file = Website::get(&path).ok_or_else(warp::reject::not_found)?;
+    let content_type = warp::http::HeaderValue::from_str(file.metadata.mimetype())
+        .unwrap_or_else(|_| warp::http::HeaderValue::from_static("application/octet-stream"));
+
+    let mut response = warp::reply::Response::new(file.data.into());
+    (response.headers_mut()).insert(warp::http::header::CONTENT_TYPE, content_type);
+    Ok(response)
+}
+
+/// Match requests to the REST endpoint at `path` under `/api` using `method`.
+///
+/// # Panics
+///
+/// Panics if the endpoint isn't listed in [`server::spec::API_ROUTES`],
+/// so that every endpoint the website serves is documented.
+fn api_route(method: Method, path: &'static str) -> BoxedFilter<()> {
+    assert!(
+        server::spec::API_ROUTES.contains(&(method, path)),
+        "/api/{} should be listed in the API routes",
+        path
+    );
+    let method = match method {
+        Method::Get => warp::get().boxed(),
+        Method::Post => warp::post().boxed(),
+    };
+    warp::path(path).and(warp::path::end()).and(method).boxed()
+}
 
-    server.await;
+/// Only let pages from the given `origins` (or any origin, if they include `*`)
+/// make requests to the `api` with the given `methods` and `headers` from the browser.
+///
+/// Requests from the server's own website are always allowed, as browsers allow them anyway,
+/// as are requests from bots and other programs that aren't browsers.
+fn with_cors(
+    api: BoxedFilter<(warp::reply::Response,)>,
+    origins: &[String],
+    methods: &[&str],
+    headers: &[&str],
+) -> BoxedFilter<(warp::reply::Response,)> {
+    if origins.is_empty() {
+        return api;
+    }
+    let cors = warp::cors()
+        .allow_methods(methods.iter().copied())
+        .allow_headers(headers.iter().copied());
+    let cors = if origins.iter().any(|origin| origin == "*") {
+        cors.allow_any_origin()
+    } else {
+        cors.allow_origins(origins.iter().map(String::as_str))
+    };
+    let same_origin = |expected: bool| {
+        (warp::header::optional::<String>("origin"))
+            .and(warp::host::optional())
+            .and_then(move |origin: Option<String>, host: Option<Authority>| {
+                let same = origin
+                    .as_deref()
+                    .and_then(|origin| origin.split_once("://"))
+                    .zip(host)
+                    .is_some_and(|((_, origin), host)| origin == host.as_str());
+                future::ready(if same == expected {
+                    Ok(())
+                } else {
+                    Err(warp::reject::not_found())
+                })
+            })
+            .untuple_one()
+    };
+    (same_origin(true).and(api.clone()))
+        .or(same_origin(false).and(api.with(cors).map(warp::Reply::into_response)))
+        .unify()
+        .boxed()
+}
+
+/// Whether `path` looks like one of the website's own pages,
+/// rather than a file or one of the server's endpoints.
+fn is_app_route(path: &str) -> bool {
+    let first = path.split('/').next().unwrap_or_default();
+    let last = path.rsplit('/').next().unwrap_or_default();
+    !matches!(first, "api" | "play" | "observe") && !last.contains('.')
+}
+
+/// Rejection for web requests from addresses that aren't allowed access.
+#[derive(Debug)]
+struct Forbidden;
+
+impl warp::reject::Reject for Forbidden {}
+
+/// Respond to [`Forbidden`] requests with a `403 Forbidden` status,
+/// passing on any other rejection.
+async fn forbidden(err: warp::Rejection) -> Result<impl warp::Reply, warp::Rejection> {
+    if err.find::<Forbidden>().is_some() {
+        Ok(warp::reply::with_status(
+            "Forbidden",
+            warp::http::StatusCode::FORBIDDEN,
+        ))
+    } else {
+        Err(err)
+    }
 }
 
 /// Error type used to combine many kinds of protocol errors.
@@ -294,9 +1547,12 @@ impl From<serde_json::Error> for ProtocolError {
 /// This allows the stream to be used as the parameter
 /// to functions like [`server::handle_player`].
 ///
+/// `moves` messages with more than `max_moves` moves are refused as they're parsed;
+/// see [`server::protocol::Receive::from_json`].
 /// Errors are coerced to [`ProtocolError`] for consistency.
 fn use_json_protocol<S, E>(
     socket: S,
+    max_moves: Option<usize>,
 ) -> impl Stream<Item = Result<server::protocol::Receive, ProtocolError>>
        + Sink<server::protocol::Send, Error = ProtocolError>
        + Unpin
@@ -307,11 +1563,13 @@ where
     socket
         .err_into()
         .sink_err_into()
-        .and_then(|line| {
-            future::ready(serde_json::from_str(&line).map_err(|e| {
-                debug!("Couldn't parse {}: {}", line, e);
-                ProtocolError::from(e)
-            }))
+        .and_then(move |line| {
+            future::ready(
+                server::protocol::Receive::from_json(&line, max_moves).map_err(|e| {
+                    debug!("Couldn't parse {}: {}", line, e);
+                    ProtocolError::from(e)
+                }),
+            )
         })
         .with(|s| future::ready(serde_json::to_string(&s).map_err(ProtocolError::from)))
 }
@@ -0,0 +1,32 @@
+//! Control which network addresses may connect to the server.
+//!
+//! Connections are checked against an [`AccessList`] as soon as they arrive,
+//! before any of the protocol is handled,
+//! so that a server can be locked down to (for example) a venue's network.
+
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+
+/// Lists of networks allowed or denied access to the server.
+#[derive(Debug, Clone, Default)]
+pub struct AccessList {
+    /// Networks allowed to connect.
+    ///
+    /// If empty, any address that isn't denied is allowed.
+    pub allow: Vec<IpNet>,
+    /// Networks never allowed to connect, even if they are also allowed.
+    pub deny: Vec<IpNet>,
+}
+
+impl AccessList {
+    /// Whether a client connecting from `addr` is allowed access.
+    ///
+    /// IPv4 addresses mapped into IPv6 are treated as plain IPv4 addresses.
+    #[must_use]
+    pub fn permits(&self, addr: IpAddr) -> bool {
+        let addr = addr.to_canonical();
+        let allowed = self.allow.is_empty() || self.allow.iter().any(|net| net.contains(&addr));
+        allowed && !self.deny.iter().any(|net| net.contains(&addr))
+    }
+}
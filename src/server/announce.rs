@@ -0,0 +1,57 @@
+//! Registering the server with a directory, so it can be listed in a server browser.
+//!
+//! Every so often the server's [public information][Info] is posted as JSON to the directory.
+//! Directories can also poll `/api/info` on the server's website for the same information.
+//! If the server is listening on an unspecified address (such as `0.0.0.0`),
+//! the directory should use the address the announcement came from instead.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use hyper::{header, Body, Request, Uri};
+use tokio::time;
+
+use super::{
+    http_client,
+    relay::{self, Outlet},
+    ClientState, HttpClient, Info,
+};
+
+/// How long to wait for the directory to respond to an announcement.
+const ANNOUNCE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Announce the server to the directory at `url` every `interval`,
+/// until it receives a shutdown signal over `client_info`.
+///
+/// Announcements that fail are dropped, and the next is made at the following interval.
+pub async fn announce(url: Uri, interval: Duration, client_info: ClientState) {
+    let client = http_client();
+    let mut announcements = time::interval(interval);
+    let outlet = Outlet::new(format!("directory {}", url));
+    let info = client_info.clone();
+    let next = async move || {
+        announcements.tick().await;
+        Some(info.info().await)
+    };
+    let send = async move |info| post(&client, &url, &info).await;
+    relay::relay(outlet, client_info, next, send).await;
+}
+
+/// Post the server's `info` to the directory at `url`.
+///
+/// # Errors
+///
+/// Fails if the request couldn't be sent or timed out, or if the directory didn't accept it.
+async fn post(client: &HttpClient, url: &Uri, info: &Info) -> Result<()> {
+    let body = serde_json::to_vec(info)?;
+    let request = Request::post(url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))?;
+    let response = time::timeout(ANNOUNCE_TIMEOUT, client.request(request))
+        .await
+        .map_err(|_| anyhow!("Timed out waiting for the directory to respond"))??;
+    if !response.status().is_success() {
+        bail!("The directory responded with {}", response.status());
+    }
+    Ok(())
+}
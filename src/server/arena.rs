@@ -0,0 +1,106 @@
+//! Hosting several games from one server, each in its own arena.
+//!
+//! A config file usually describes a single game,
+//! but it may instead list several [arenas][ArenaConfig],
+//! such as one for beginners and another for more advanced players.
+//! Each arena plays its own games with its own rules and tick rate,
+//! and listens for players and observers on its own addresses.
+
+use std::{net::SocketAddr, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Deserializer};
+
+use crate::game;
+
+/// The contents of a config file.
+#[derive(Debug)]
+pub enum ConfigFile {
+    /// The rules and environment for a single game.
+    Single(Box<game::Config>),
+    /// The arenas to host, each with their own games.
+    Arenas(Vec<ArenaConfig>),
+}
+
+impl ConfigFile {
+    /// Parse a config file from its contents.
+    ///
+    /// The file describes several arenas if it has an `"arenas"` field,
+    /// and a single game otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the contents aren't a valid config file,
+    /// if any of the games can't be played as [validated][game::Config::validate],
+    /// or if several arenas share a name.
+    pub fn from_slice(buf: &[u8]) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_slice(buf)?;
+        if value.get("arenas").is_none() {
+            let config = game::Config::deserialize(value)?;
+            config.validate()?;
+            return Ok(ConfigFile::Single(Box::new(config)));
+        }
+        let ArenasConfig { arenas } = ArenasConfig::deserialize(value)?;
+        for (i, arena) in arenas.iter().enumerate() {
+            if arenas[..i].iter().any(|a| a.name == arena.name) {
+                bail!("There is more than one arena named {:?}", arena.name);
+            }
+            (arena.config.validate())
+                .with_context(|| format!("Could not use arena {:?}", arena.name))?;
+        }
+        Ok(ConfigFile::Arenas(arenas))
+    }
+}
+
+/// Configuration for several arenas.
+#[derive(Debug, Deserialize)]
+struct ArenasConfig {
+    /// The arenas to host.
+    arenas: Vec<ArenaConfig>,
+}
+
+/// Configuration for a single arena.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ArenaConfig {
+    /// The arena's name, used to tell arenas apart in logs and when reloading the config file.
+    pub name: String,
+    /// The number of seconds to spend on each game tick.
+    ///
+    /// If not provided, the `--tick-rate` given to the server is used.
+    #[serde(default, deserialize_with = "deserialize_tick_rate")]
+    pub tick_rate: Option<Duration>,
+    /// The address to listen for TCP connections on.
+    ///
+    /// If not provided, the arena can only be played using WebSockets.
+    #[serde(default)]
+    pub tcp_addr: Option<SocketAddr>,
+    /// The address to host the website on.
+    ///
+    /// If not provided, the arena can only be played over TCP.
+    #[serde(default)]
+    pub web_addr: Option<SocketAddr>,
+    /// The address to listen for gRPC connections on.
+    ///
+    /// If not provided, the arena can't be played over gRPC.
+    #[serde(default)]
+    pub grpc_addr: Option<SocketAddr>,
+    /// The map and rules for the arena's games.
+    #[serde(flatten)]
+    pub config: game::Config,
+}
+
+/// Deserialize a tick rate in seconds, ensuring that it's positive.
+fn deserialize_tick_rate<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::{Error, Unexpected};
+
+    match Option::<f64>::deserialize(deserializer)? {
+        Some(secs) if !(secs.is_finite() && secs > 0.0) => Err(Error::invalid_value(
+            Unexpected::Float(secs),
+            &"a positive number of seconds",
+        )),
+        secs => Ok(secs.map(Duration::from_secs_f64)),
+    }
+}
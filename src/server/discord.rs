@@ -0,0 +1,191 @@
+//! Announcing the scores of each game to a Discord channel.
+//!
+//! A bot posts to the channel when each game starts, the live scores every `--score-interval` ticks,
+//! and the final standings when the game is over.
+//! Player names are escaped so they can't format the message or mention anyone.
+//! If Discord says the bot is posting too quickly, it waits as long as Discord asks before retrying.
+
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use futures::Future;
+use hyper::{body, header, Body, Request, StatusCode, Uri};
+use log::debug;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::time;
+
+use super::{
+    http_client,
+    relay::{self, Outlet},
+    ClientState, HttpClient, Milestone, Standing,
+};
+
+/// Where Discord's API is hosted.
+const API_URL: &str = "https://discord.com/api/v10";
+
+/// How long to wait for Discord to respond.
+const DISCORD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait before retrying if Discord doesn't say how long the bot is rate limited for.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// The longest to wait before retrying a rate limited announcement,
+/// after which the announcement is dropped instead.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// The most players to list in each announcement, to keep within Discord's message limit.
+const MAX_STANDINGS: usize = 10;
+
+/// Announce the milestones sent over `client_info` to the Discord `channel`,
+/// posting as the bot with the given `token`, until it receives a shutdown signal.
+///
+/// Only milestones sent after this is called are announced, as described in [`relay::milestones`].
+pub fn announce(channel: u64, token: String, client_info: ClientState) -> impl Future<Output = ()> {
+    let url: Uri = format!("{}/channels/{}/messages", API_URL, channel)
+        .parse()
+        .expect("Discord API URL should be valid");
+    let client = http_client();
+    let arena = client_info.name.clone();
+    let outlet = Outlet::new(format!("Discord channel {}", channel));
+    let news = move |milestone| {
+        let message = message(&milestone)?;
+        Some(match &arena {
+            Some(arena) => format!("**{}**: {}", escape(arena), message),
+            None => message,
+        })
+    };
+    relay::milestones(outlet, client_info, news, async move |message| {
+        send(&client, &url, &token, &message).await
+    })
+}
+
+/// Describe a `milestone` as a Discord message, if it should be announced.
+fn message(milestone: &Milestone) -> Option<String> {
+    match milestone {
+        Milestone::GameStarted { width, height, .. } => Some(format!(
+            "A new game has started on a {}×{} map",
+            width, height
+        )),
+        Milestone::Scores { tick, players } if !players.is_empty() => Some(format!(
+            "Scores after tick {}:\n{}",
+            tick,
+            standings(players)
+        )),
+        Milestone::GameOver { ticks, players } if !players.is_empty() => Some(format!(
+            "Game over after {} ticks!\n{}",
+            ticks,
+            standings(players)
+        )),
+        Milestone::GameOver { ticks, .. } => Some(format!(
+            "Game over after {} ticks, with nobody taking part",
+            ticks
+        )),
+        _ => None,
+    }
+}
+
+/// List the best `players` and their scores, one per line.
+fn standings(players: &[Standing]) -> String {
+    let mut lines: Vec<_> = (players.iter().take(MAX_STANDINGS))
+        .map(|standing| {
+            let team = match &standing.team {
+                Some(team) => format!(" ({})", escape(team)),
+                None => String::new(),
+            };
+            format!(
+                "{}. {}{} — {}",
+                standing.rank,
+                escape(&standing.name),
+                team,
+                standing.score
+            )
+        })
+        .collect();
+    if players.len() > MAX_STANDINGS {
+        lines.push(format!("…and {} more", players.len() - MAX_STANDINGS));
+    }
+    lines.join("\n")
+}
+
+/// Escape any characters in `text` that Discord would treat as formatting.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '#' | '-' | '[' | ']'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// The body of Discord's response when the bot is posting too quickly.
+#[derive(Debug, Deserialize)]
+struct RateLimited {
+    /// How many seconds to wait before trying again.
+    retry_after: f64,
+}
+
+/// Post a message with the given `content` to the Discord channel at `url`,
+/// waiting and trying again for as long as Discord says the bot is posting too quickly.
+///
+/// # Errors
+///
+/// Fails if a request failed or timed out,
+/// or if Discord asked the bot to wait longer than [`MAX_RETRY_AFTER`].
+async fn send(client: &HttpClient, url: &Uri, token: &str, content: &str) -> Result<()> {
+    loop {
+        let wait = match time::timeout(DISCORD_TIMEOUT, post(client, url, token, content)).await {
+            Ok(Ok(None)) => return Ok(()),
+            Ok(Ok(Some(wait))) => wait,
+            Ok(Err(e)) => return Err(e),
+            Err(_) => bail!("Timed out waiting for Discord to respond"),
+        };
+        if wait > MAX_RETRY_AFTER {
+            bail!("Discord is rate limiting the bot for {:?}", wait);
+        }
+        debug!("Discord is rate limiting the bot, retrying in {:?}", wait);
+        time::sleep(wait).await;
+    }
+}
+
+/// Post a message with the given `content` to the Discord channel at `url`.
+///
+/// Mentions in the message are never resolved, so players can't ping the channel.
+/// Returns how long to wait before trying again if Discord is rate limiting the bot,
+/// in which case the message wasn't posted.
+///
+/// # Errors
+///
+/// Fails if the request couldn't be sent, or if Discord didn't accept it.
+async fn post(
+    client: &HttpClient,
+    url: &Uri,
+    token: &str,
+    content: &str,
+) -> Result<Option<Duration>> {
+    let body = json!({
+        "content": content,
+        "allowed_mentions": { "parse": [] },
+    });
+    let request = Request::post(url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, format!("Bot {}", token))
+        .body(Body::from(serde_json::to_vec(&body)?))?;
+    let response = client.request(request).await?;
+    if response.status() == StatusCode::TOO_MANY_REQUESTS {
+        let body = body::to_bytes(response.into_body()).await?;
+        let wait = (serde_json::from_slice(&body).ok())
+            .and_then(|limit: RateLimited| Duration::try_from_secs_f64(limit.retry_after).ok())
+            .unwrap_or(DEFAULT_RETRY_AFTER);
+        return Ok(Some(wait));
+    }
+    if !response.status().is_success() {
+        bail!("Discord responded with {}", response.status());
+    }
+    Ok(None)
+}
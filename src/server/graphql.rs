@@ -0,0 +1,464 @@
+//! A read-only GraphQL API for the game currently being played.
+//!
+//! The API is served from `/api/graphql` on the website,
+//! which also hosts GraphiQL for trying out queries in the browser.
+//! Entities are described as observers see them in each update,
+//! so the fields match those documented in `protocol.md`,
+//! and each query is answered from a fresh [`Snapshot`] of the game.
+
+use std::sync::Arc;
+
+use async_graphql::{
+    http::GraphiQLSource, Context, EmptyMutation, EmptySubscription, Object, SimpleObject,
+};
+use serde::Deserialize;
+use warp::{filters::BoxedFilter, Filter, Reply};
+
+use crate::game::world::World;
+
+use super::{protocol::RosterEntry, ClientState, Snapshot};
+
+/// The longest query that can be sent, in bytes.
+const MAX_QUERY_SIZE: u64 = 64 * 1024;
+
+/// The most deeply nested a query can be.
+const MAX_QUERY_DEPTH: usize = 16;
+
+/// The GraphQL schema served by the API.
+pub type Schema = async_graphql::Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// Create the schema for the game server behind `client_info`.
+pub fn schema(client_info: ClientState) -> Schema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .data(client_info)
+        .limit_depth(MAX_QUERY_DEPTH)
+        .finish()
+}
+
+/// Answer queries about the game server behind `client_info`, sent as JSON with `POST`.
+pub fn query(client_info: ClientState) -> BoxedFilter<(warp::reply::Response,)> {
+    let schema = schema(client_info);
+    warp::body::content_length_limit(MAX_QUERY_SIZE)
+        .and(warp::body::json())
+        .and_then(move |request: async_graphql::Request| {
+            let schema = schema.clone();
+            async move {
+                let response = schema.execute(request).await;
+                Ok::<_, warp::Rejection>(warp::reply::json(&response).into_response())
+            }
+        })
+        .boxed()
+}
+
+/// Show GraphiQL, for trying out queries in the browser.
+pub fn graphiql() -> BoxedFilter<(warp::reply::Response,)> {
+    warp::any()
+        .map(|| {
+            let page = GraphiQLSource::build().endpoint("/api/graphql").finish();
+            warp::reply::html(page).into_response()
+        })
+        .boxed()
+}
+
+/// The root of every query.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// The game currently being played, or `null` if the server has finished playing.
+    async fn game(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Game>> {
+        let client_info = ctx.data::<ClientState>()?;
+        match client_info.snapshot().await {
+            Some(snapshot) => Ok(Some(Game::new(snapshot)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A game being played.
+#[derive(Debug, Clone)]
+pub struct Game(Arc<GameData>);
+
+/// The parts of a [`Snapshot`] that queries can look at.
+#[derive(Debug)]
+struct GameData {
+    /// The world map.
+    world: Arc<World>,
+    /// The number of ticks played so far.
+    tick: u64,
+    /// Each player taking part in the game, along with their ID.
+    players: Vec<(u64, RosterEntry)>,
+    /// The game's entities.
+    entities: Entities,
+}
+
+impl Game {
+    /// Collect the game's state from a `snapshot`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the players or entities couldn't be converted.
+    fn new(snapshot: Snapshot) -> serde_json::Result<Self> {
+        let players = (snapshot.players.into_iter())
+            .map(|entry| {
+                Ok((
+                    serde_json::from_value(serde_json::to_value(entry.player)?)?,
+                    entry,
+                ))
+            })
+            .collect::<serde_json::Result<_>>()?;
+        let entities = serde_json::to_value(&snapshot.entities)?;
+        Ok(Game(Arc::new(GameData {
+            world: snapshot.world,
+            tick: snapshot.tick,
+            players,
+            entities: serde_json::from_value(entities)?,
+        })))
+    }
+}
+
+#[Object]
+impl Game {
+    /// The number of ticks played so far.
+    async fn tick(&self) -> u64 {
+        self.0.tick
+    }
+
+    /// The world map.
+    async fn world(&self) -> WorldMap {
+        WorldMap(Arc::clone(&self.0.world))
+    }
+
+    /// The current weather.
+    async fn weather(&self) -> &Weather {
+        &self.0.entities.weather
+    }
+
+    /// Each player taking part in the game.
+    async fn players(&self) -> Vec<PlayerNode> {
+        (0..self.0.players.len())
+            .map(|index| PlayerNode {
+                game: Arc::clone(&self.0),
+                index,
+            })
+            .collect()
+    }
+
+    /// The player with the given ID, if they're taking part in the game.
+    async fn player(&self, id: u64) -> Option<PlayerNode> {
+        let index = (self.0.players.iter()).position(|&(player, _)| player == id)?;
+        Some(PlayerNode {
+            game: Arc::clone(&self.0),
+            index,
+        })
+    }
+
+    /// The living bees, optionally only those belonging to one player.
+    async fn bees(&self, player: Option<u64>) -> Vec<&Bee> {
+        let bees = self.0.entities.bees.iter();
+        bees.filter(|bee| player.is_none_or(|player| bee.player == player))
+            .collect()
+    }
+
+    /// The hives, optionally only those belonging to one player.
+    async fn hives(&self, player: Option<u64>) -> Vec<&Hive> {
+        let hives = self.0.entities.hives.iter();
+        hives
+            .filter(|hive| player.is_none_or(|player| hive.player == player))
+            .collect()
+    }
+
+    /// The living flowers.
+    async fn flowers(&self) -> &[Flower] {
+        &self.0.entities.flowers
+    }
+
+    /// The birds flying around the world.
+    async fn birds(&self) -> &[Bird] {
+        &self.0.entities.birds
+    }
+
+    /// The cars driving along the roads.
+    async fn cars(&self) -> &[Car] {
+        &self.0.entities.cars
+    }
+
+    /// The power-ups waiting to be collected.
+    async fn items(&self) -> &[Item] {
+        &self.0.entities.items
+    }
+
+    /// The wasp nests on the map.
+    async fn nests(&self) -> &[Nest] {
+        &self.0.entities.nests
+    }
+
+    /// The wasps out raiding hives.
+    async fn wasps(&self) -> &[Wasp] {
+        &self.0.entities.wasps
+    }
+}
+
+/// The world map.
+#[derive(Debug, Clone)]
+pub struct WorldMap(Arc<World>);
+
+#[Object(name = "World")]
+impl WorldMap {
+    /// The number of columns in the map.
+    async fn width(&self) -> i32 {
+        self.0.width
+    }
+
+    /// The number of rows in the map.
+    async fn height(&self) -> i32 {
+        self.0.height
+    }
+
+    /// Whether moving off one edge of the map comes back on the opposite edge.
+    async fn wrap_edges(&self) -> bool {
+        self.0.wrap_edges
+    }
+
+    /// The positions where hives can be placed.
+    async fn spawn_points(&self) -> Vec<Position> {
+        (self.0.get_spawn_points().into_iter())
+            .map(|p| Position { x: p.x, y: p.y })
+            .collect()
+    }
+
+    /// The kind of tile at the given position, such as `"Grass"`,
+    /// or `null` if the position is off the map.
+    async fn tile(&self, x: i32, y: i32) -> Option<String> {
+        let tile = self.0.get(crate::game::world::Position::new(x, y))?;
+        match serde_json::to_value(tile).ok()? {
+            serde_json::Value::String(tile) => Some(tile),
+            other => Some(other.to_string()),
+        }
+    }
+}
+
+/// A player taking part in the game.
+#[derive(Debug, Clone)]
+pub struct PlayerNode {
+    /// The game the player is taking part in.
+    game: Arc<GameData>,
+    /// The player's position in the game's list of players.
+    index: usize,
+}
+
+impl PlayerNode {
+    /// The player's ID.
+    fn player_id(&self) -> u64 {
+        self.game.players[self.index].0
+    }
+
+    /// The player's entry in the game's list of players.
+    fn entry(&self) -> &RosterEntry {
+        &self.game.players[self.index].1
+    }
+}
+
+#[Object(name = "Player")]
+impl PlayerNode {
+    /// The player's ID.
+    async fn id(&self) -> u64 {
+        self.player_id()
+    }
+
+    /// The name the player registered with.
+    async fn name(&self) -> &str {
+        &self.entry().name
+    }
+
+    /// The player's current score.
+    async fn score(&self) -> i32 {
+        self.entry().score
+    }
+
+    /// The team the player is on, if any.
+    async fn team(&self) -> Option<&str> {
+        self.entry().team.as_deref()
+    }
+
+    /// Whether the player is currently connected.
+    async fn connected(&self) -> bool {
+        self.entry().connected
+    }
+
+    /// The player's living bees.
+    async fn bees(&self) -> Vec<&Bee> {
+        let id = self.player_id();
+        let bees = self.game.entities.bees.iter();
+        bees.filter(|bee| bee.player == id).collect()
+    }
+
+    /// The player's hives.
+    async fn hives(&self) -> Vec<&Hive> {
+        let id = self.player_id();
+        let hives = self.game.entities.hives.iter();
+        hives.filter(|hive| hive.player == id).collect()
+    }
+}
+
+/// The entities in an update, as sent to observers.
+#[derive(Debug, Deserialize)]
+struct Entities {
+    #[serde(default)]
+    bees: Vec<Bee>,
+    #[serde(default)]
+    hives: Vec<Hive>,
+    #[serde(default)]
+    flowers: Vec<Flower>,
+    #[serde(default)]
+    birds: Vec<Bird>,
+    #[serde(default)]
+    cars: Vec<Car>,
+    #[serde(default)]
+    items: Vec<Item>,
+    #[serde(default)]
+    nests: Vec<Nest>,
+    #[serde(default)]
+    wasps: Vec<Wasp>,
+    weather: Weather,
+}
+
+/// A position on the world grid.
+#[derive(Debug, Clone, Copy, Deserialize, SimpleObject)]
+pub struct Position {
+    /// The horizontal position; 0 is closest to the left.
+    x: i32,
+    /// The vertical position; 0 is closest to the bottom.
+    y: i32,
+}
+
+/// A bee.
+#[derive(Debug, Clone, Deserialize, SimpleObject)]
+pub struct Bee {
+    /// The bee's ID.
+    id: u64,
+    /// The ID of the player controlling the bee.
+    player: u64,
+    /// Where the bee is.
+    position: Position,
+    /// The pollen the bee is carrying.
+    pollen: i32,
+    /// The nectar the bee is carrying.
+    nectar: i32,
+    /// The bee's remaining energy.
+    energy: i32,
+    /// The most energy the bee can have.
+    max_energy: i32,
+    /// The most pollen the bee can carry, if limited.
+    max_pollen: Option<i32>,
+    /// The number of tiles the bee flies each move.
+    speed: u32,
+    /// Whether the bee is a queen.
+    queen: bool,
+    /// The bee's role, such as `"worker"`.
+    role: String,
+    /// The number of ticks the bee has been alive.
+    age: u32,
+    /// The age at which the bee dies, if it ages.
+    max_age: Option<u32>,
+    /// The bee's stage of life, such as `"forager"`.
+    stage: String,
+}
+
+/// A player's hive.
+#[derive(Debug, Clone, Deserialize, SimpleObject)]
+pub struct Hive {
+    /// The ID of the player owning the hive.
+    player: u64,
+    /// Where the hive is.
+    position: Position,
+    /// The colour of the hive, if chosen.
+    color: Option<String>,
+    /// The emoji shown on the hive, if chosen.
+    emoji: Option<String>,
+    /// The team the hive's player is on, if any.
+    team: Option<String>,
+    /// Whether the hive has a queen.
+    queen: bool,
+    /// The nectar stored in the hive.
+    nectar: i32,
+}
+
+/// A flower.
+#[derive(Debug, Clone, Deserialize, SimpleObject)]
+pub struct Flower {
+    /// The flower's ID.
+    id: u64,
+    /// The kind of flower, such as `"sunflower"`.
+    species: String,
+    /// Where the flower is.
+    position: Position,
+    /// The pollen left on the flower.
+    pollen: i32,
+    /// The most pollen the flower can hold.
+    max_pollen: i32,
+    /// The nectar left on the flower.
+    nectar: i32,
+    /// Whether the flower has been pollinated.
+    is_pollinated: bool,
+    /// The number of ticks the flower has been alive.
+    age: u32,
+}
+
+/// A bird.
+#[derive(Debug, Clone, Deserialize, SimpleObject)]
+pub struct Bird {
+    /// Where the bird is.
+    position: Position,
+    /// The direction the bird is flying, such as `"North"`.
+    heading: String,
+}
+
+/// A car.
+#[derive(Debug, Clone, Deserialize, SimpleObject)]
+pub struct Car {
+    /// Where the car is.
+    position: Position,
+    /// The direction the car is driving, such as `"East"`.
+    facing: String,
+}
+
+/// A power-up waiting to be collected.
+#[derive(Debug, Clone, Deserialize, SimpleObject)]
+pub struct Item {
+    /// The kind of power-up.
+    kind: String,
+    /// Where the power-up is.
+    position: Position,
+}
+
+/// A wasp nest.
+#[derive(Debug, Clone, Deserialize, SimpleObject)]
+pub struct Nest {
+    /// Where the nest is.
+    position: Position,
+    /// The nest's remaining health.
+    health: u32,
+}
+
+/// A wasp.
+#[derive(Debug, Clone, Deserialize, SimpleObject)]
+pub struct Wasp {
+    /// Where the wasp is.
+    position: Position,
+    /// Where the wasp's nest is.
+    nest: Position,
+    /// The pollen the wasp has stolen.
+    pollen: i32,
+}
+
+/// The weather.
+#[derive(Debug, Clone, Deserialize, SimpleObject)]
+pub struct Weather {
+    /// The kind of weather, such as `"sunny"`.
+    kind: String,
+    /// The direction the wind blows bees in, if it's windy.
+    #[serde(default)]
+    direction: Option<String>,
+}
@@ -0,0 +1,479 @@
+//! Serving the game over gRPC, as described by `proto/beeeees.proto`.
+//!
+//! Each gRPC stream is handled just like a TCP connection or WebSocket:
+//! messages are converted to and from the [JSON protocol's types][protocol]
+//! and passed on to [`handle_player`] or [`handle_observer`].
+//! The conversion goes by way of each message's JSON representation,
+//! so that gRPC clients are checked and answered in exactly the same way as everyone else.
+
+use std::{
+    convert::TryFrom,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{channel::mpsc, stream::BoxStream, Sink, SinkExt, Stream, StreamExt};
+use log::{debug, error, info};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use tonic::{Request, Response, Status, Streaming};
+
+use super::{handle_observer, handle_player, protocol, ClientState};
+
+/// The types and service generated from `proto/beeeees.proto`.
+pub mod proto {
+    tonic::include_proto!("beeeees");
+}
+
+pub use proto::beeeees_server::BeeeeesServer;
+
+/// The number of messages that can be waiting to be sent to each gRPC client.
+///
+/// Updates are already queued by the game server, so this only needs to smooth out sending.
+const SEND_BUFFER: usize = 16;
+
+/// The gRPC service, handing each stream over to the game server.
+#[derive(Debug, Clone)]
+pub struct Service {
+    /// Channel information used to communicate with the game server.
+    client_info: ClientState,
+}
+
+impl Service {
+    /// Create a service for clients of the game server behind `client_info`,
+    /// refusing any message longer than `max_message_size` bytes.
+    pub fn new(client_info: ClientState, max_message_size: usize) -> BeeeeesServer<Self> {
+        BeeeeesServer::new(Self { client_info }).max_decoding_message_size(max_message_size)
+    }
+
+    /// Hand the client's stream over to the game server as a player or an observer,
+    /// returning the stream of messages to send back.
+    // the error type is the one tonic's service methods return
+    #[allow(clippy::result_large_err)]
+    fn connect(
+        &self,
+        request: Request<Streaming<proto::ClientMessage>>,
+        observer: bool,
+    ) -> Result<Response<BoxStream<'static, Result<proto::ServerMessage, Status>>>, Status> {
+        let addr = request
+            .remote_addr()
+            .ok_or_else(|| Status::internal("no socket address available"))?;
+        if !self.client_info.permits(addr.ip()) {
+            info!("Refusing gRPC connection from {}", addr);
+            return Err(Status::permission_denied("Forbidden"));
+        }
+
+        let (tx, rx) = mpsc::channel(SEND_BUFFER);
+        let socket = Socket {
+            incoming: request.into_inner(),
+            outgoing: tx,
+        };
+        let channels = self.client_info.clone();
+        tokio::spawn(async move {
+            info!("Handling new gRPC connection with address {}", addr);
+            let result = if observer {
+                handle_observer(socket, addr, channels).await
+            } else {
+                handle_player(socket, addr, channels).await
+            };
+            if let Err(x) = result {
+                error!("When handling gRPC for {}: {:?}", addr, x);
+            }
+        });
+
+        Ok(Response::new(rx.map(Ok).boxed()))
+    }
+}
+
+#[tonic::async_trait]
+impl proto::beeeees_server::Beeeees for Service {
+    type PlayStream = BoxStream<'static, Result<proto::ServerMessage, Status>>;
+    type ObserveStream = BoxStream<'static, Result<proto::ServerMessage, Status>>;
+
+    async fn play(
+        &self,
+        request: Request<Streaming<proto::ClientMessage>>,
+    ) -> Result<Response<Self::PlayStream>, Status> {
+        self.connect(request, false)
+    }
+
+    async fn observe(
+        &self,
+        request: Request<Streaming<proto::ClientMessage>>,
+    ) -> Result<Response<Self::ObserveStream>, Status> {
+        self.connect(request, true)
+    }
+}
+
+/// Errors from a gRPC stream.
+#[derive(Debug)]
+pub enum Error {
+    /// The stream failed.
+    Status(Box<Status>),
+    /// A message couldn't be converted.
+    Json(serde_json::Error),
+    /// A message was empty, or used a value the server doesn't know about.
+    Invalid(&'static str),
+    /// The client has gone away.
+    Closed,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Status(status) => write!(f, "{}", status.message()),
+            Error::Json(err) => err.fmt(f),
+            Error::Invalid(msg) => write!(f, "{}", msg),
+            Error::Closed => write!(f, "The client has gone away"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+/// A gRPC stream, as a stream of received messages and a sink for messages to send.
+struct Socket {
+    /// Messages from the client.
+    incoming: Streaming<proto::ClientMessage>,
+    /// Messages to the client, passed on as the response stream.
+    outgoing: mpsc::Sender<proto::ServerMessage>,
+}
+
+impl Stream for Socket {
+    type Item = Result<protocol::Receive, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.incoming.poll_next_unpin(cx).map(|msg| {
+            msg.map(|msg| match msg {
+                Ok(msg) => from_client(msg),
+                Err(status) => Err(Error::Status(Box::new(status))),
+            })
+        })
+    }
+}
+
+impl Sink<protocol::Send> for Socket {
+    type Error = Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        (self.outgoing.poll_ready_unpin(cx)).map_err(|_| Error::Closed)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: protocol::Send) -> Result<(), Error> {
+        let msg = to_server(&item)?;
+        (self.outgoing.start_send_unpin(msg)).map_err(|_| Error::Closed)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        (self.outgoing.poll_flush_unpin(cx)).map_err(|_| Error::Closed)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        (self.outgoing.poll_close_unpin(cx)).map_err(|_| Error::Closed)
+    }
+}
+
+/// Convert a message from a gRPC client into the message it stands for in the JSON protocol.
+///
+/// # Errors
+///
+/// Fails if the message is empty or uses an unknown value,
+/// or if it isn't a valid message in the JSON protocol.
+fn from_client(msg: proto::ClientMessage) -> Result<protocol::Receive, Error> {
+    use proto::client_message::Message;
+
+    let value = match msg.message.ok_or(Error::Invalid("Empty message"))? {
+        Message::Register(proto::Register {
+            name,
+            team,
+            color,
+            emoji,
+            password,
+            invite,
+            token,
+        }) => json!({
+            "type": "register",
+            "name": name,
+            "team": team,
+            "color": color,
+            "emoji": emoji,
+            "password": password,
+            "invite": invite,
+            "token": token,
+        }),
+        Message::Moves(proto::Moves {
+            moves,
+            groups,
+            tick,
+        }) => {
+            let moves = (moves.into_iter())
+                .map(|m| {
+                    let plan = (m.plan.into_iter())
+                        .map(|d| direction(d).and_then(|d| d.ok_or(Error::Invalid("Empty plan"))))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(json!({
+                        "bee": m.bee,
+                        "direction": direction(m.direction)?,
+                        "handoff": m.handoff,
+                        "dance": m.dance,
+                        "target": m.target.map(position),
+                        "avoid_roads": m.avoid_roads,
+                        "plan": plan,
+                    }))
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            let groups = (groups.into_iter())
+                .map(|g| Ok(json!({ "bees": g.bees, "direction": direction(g.direction)? })))
+                .collect::<Result<Vec<_>, Error>>()?;
+            json!({ "type": "moves", "moves": moves, "groups": groups, "tick": tick })
+        }
+        Message::Chat(proto::Chat { msg }) => json!({ "type": "chat", "msg": msg }),
+        Message::Transfer(proto::Transfer { to, amount }) => {
+            json!({ "type": "transfer", "to": to, "amount": amount })
+        }
+        Message::BuildHive(proto::BuildHive { bee }) => json!({ "type": "build_hive", "bee": bee }),
+        Message::RelocateHive(proto::RelocateHive { hive, to }) => json!({
+            "type": "relocate_hive",
+            "hive": hive.map(position),
+            "to": to.map(position),
+        }),
+        Message::UpgradeHive(proto::UpgradeHive { hive, upgrade }) => {
+            let upgrade = match proto::Upgrade::try_from(upgrade) {
+                Ok(proto::Upgrade::SpawnRate) => "spawn_rate",
+                Ok(proto::Upgrade::Energy) => "energy",
+                Ok(proto::Upgrade::Capacity) => "capacity",
+                Ok(proto::Upgrade::Speed) => "speed",
+                Ok(proto::Upgrade::Unspecified) | Err(_) => {
+                    return Err(Error::Invalid("Unknown upgrade"))
+                }
+            };
+            json!({ "type": "upgrade_hive", "hive": hive.map(position), "upgrade": upgrade })
+        }
+        Message::AssignRole(proto::AssignRole { bee, role }) => {
+            let role = match proto::Role::try_from(role) {
+                Ok(proto::Role::Worker) => "worker",
+                Ok(proto::Role::Scout) => "scout",
+                Ok(proto::Role::Guard) => "guard",
+                Ok(proto::Role::Unspecified) | Err(_) => {
+                    return Err(Error::Invalid("Unknown role"))
+                }
+            };
+            json!({ "type": "assign_role", "bee": bee, "role": role })
+        }
+        Message::SetViewport(proto::SetViewport { rect }) => {
+            let rect =
+                rect.map(|r| json!({ "x": r.x, "y": r.y, "width": r.width, "height": r.height }));
+            json!({ "type": "set_viewport", "rect": rect })
+        }
+        Message::Rename(proto::Rename { name }) => json!({ "type": "rename", "name": name }),
+        Message::Query(proto::Query { what }) => {
+            let what = match proto::QueryKind::try_from(what) {
+                Ok(proto::QueryKind::Players) => "players",
+                Ok(proto::QueryKind::Unspecified) | Err(_) => {
+                    return Err(Error::Invalid("Unknown query"))
+                }
+            };
+            json!({ "type": "query", "what": what })
+        }
+        Message::Pong(proto::Pong { id }) => json!({ "type": "pong", "id": id }),
+    };
+
+    Ok(serde_json::from_value(value)?)
+}
+
+/// The name of a direction in the JSON protocol, or `None` if it's unspecified.
+///
+/// # Errors
+///
+/// Fails if the direction is unknown.
+fn direction(direction: i32) -> Result<Option<&'static str>, Error> {
+    use proto::Direction;
+
+    Ok(Some(match Direction::try_from(direction) {
+        Ok(Direction::Unspecified) => return Ok(None),
+        Ok(Direction::North) => "North",
+        Ok(Direction::NorthEast) => "NorthEast",
+        Ok(Direction::East) => "East",
+        Ok(Direction::SouthEast) => "SouthEast",
+        Ok(Direction::South) => "South",
+        Ok(Direction::SouthWest) => "SouthWest",
+        Ok(Direction::West) => "West",
+        Ok(Direction::NorthWest) => "NorthWest",
+        Err(_) => return Err(Error::Invalid("Unknown direction")),
+    }))
+}
+
+/// A position in the JSON protocol.
+fn position(position: proto::Position) -> Value {
+    json!({ "x": position.x, "y": position.y })
+}
+
+/// Convert a message in the JSON protocol into the message to send to a gRPC client.
+///
+/// # Errors
+///
+/// Fails if the message couldn't be serialized.
+fn to_server(msg: &protocol::Send) -> Result<proto::ServerMessage, Error> {
+    use proto::server_message::Message;
+
+    let mut value = serde_json::to_value(msg)?;
+    let fields = value
+        .as_object_mut()
+        .ok_or(Error::Invalid("Message isn't an object"))?;
+    let kind = fields.remove("type").unwrap_or_default();
+    let value = Value::Object(fields.clone());
+
+    let message = match kind.as_str().unwrap_or_default() {
+        "registration" => Message::Registration(proto::Registration {
+            world_json: value["world"].to_string(),
+            player: field(&value, "player")?,
+            tick_rate: field(&value, "tick_rate")?,
+            profile_json: Some(&value["profile"])
+                .filter(|profile| !profile.is_null())
+                .map(Value::to_string),
+        }),
+        "chunk" => Message::Chunk(proto::Chunk {
+            json: value.to_string(),
+        }),
+        "update" => Message::Update(proto::Update {
+            data_json: value["data"].to_string(),
+        }),
+        "tick_rate" => Message::TickRate(proto::TickRate {
+            tick_rate: field(&value, "tick_rate")?,
+        }),
+        "tick_schedule" => Message::TickSchedule(proto::TickSchedule {
+            tick: field(&value, "tick")?,
+            next_tick_in: field(&value, "next_tick_in")?,
+        }),
+        "chat" => {
+            let channel = match value["channel"].as_str() {
+                Some("players") => proto::ChatChannel::Players,
+                _ => proto::ChatChannel::Spectators,
+            };
+            Message::Chat(proto::ChatReceived {
+                player: field(&value, "player")?,
+                name: field(&value, "name")?,
+                msg: field(&value, "msg")?,
+                channel: channel.into(),
+            })
+        }
+        "roster" => {
+            let players: Vec<Value> = field(&value, "players")?;
+            let players = (players.iter())
+                .map(|entry| {
+                    Ok(proto::RosterEntry {
+                        player: field(entry, "player")?,
+                        name: field(entry, "name")?,
+                        score: field(entry, "score")?,
+                        team: field(entry, "team")?,
+                        connected: field(entry, "connected")?,
+                        latency_ms: field(entry, "latency_ms")?,
+                    })
+                })
+                .collect::<Result<_, Error>>()?;
+            Message::Roster(proto::Roster { players })
+        }
+        "transfer" => Message::Transfer(proto::Transferred {
+            from: field(&value, "from")?,
+            to: field(&value, "to")?,
+            amount: field(&value, "amount")?,
+        }),
+        "rename" => Message::Rename(proto::Renamed {
+            player: field(&value, "player")?,
+            name: field(&value, "name")?,
+        }),
+        "removed" => Message::Removed(proto::Removed {
+            player: field(&value, "player")?,
+            score: field(&value, "score")?,
+        }),
+        "achievement" => Message::Achievement(proto::AchievementUnlocked {
+            player: field(&value, "player")?,
+            achievement: field(&value, "achievement")?,
+        }),
+        "event" => Message::Event(proto::Event {
+            json: value.to_string(),
+        }),
+        "ack" => Message::Ack(proto::Ack {
+            tick: field(&value, "tick")?,
+            accepted: field(&value, "accepted")?,
+            received: field(&value, "received")?,
+        }),
+        "blocked" => Message::Blocked(proto::Blocked {
+            bees: field(&value, "bees")?,
+        }),
+        "score" => Message::Score(proto::Score {
+            score: field(&value, "score")?,
+        }),
+        "summary" => Message::Summary(proto::Summary {
+            json: value.to_string(),
+        }),
+        "warning" => Message::Warning(proto::Warning {
+            msg: field(&value, "msg")?,
+        }),
+        "error" => Message::Error(proto::Error {
+            msg: field(&value, "msg")?,
+            code: field(&value, "code")?,
+        }),
+        "ping" => Message::Ping(proto::Ping {
+            id: field(&value, "id")?,
+        }),
+        "done" => Message::Done(proto::Done {}),
+        _ => return Err(Error::Invalid("Unknown message type")),
+    };
+
+    Ok(proto::ServerMessage {
+        message: Some(message),
+    })
+}
+
+/// Get the field called `name` from a message's JSON representation.
+///
+/// Missing fields are treated as `null`.
+///
+/// # Errors
+///
+/// Fails if the field has the wrong type.
+fn field<T: DeserializeOwned>(value: &Value, name: &str) -> Result<T, Error> {
+    let field = value.get(name).cloned().unwrap_or_default();
+    Ok(serde_json::from_value(field)?)
+}
+
+/// Serve the gRPC service on the given listener,
+/// until it receives a shutdown signal over `client_info`.
+///
+/// Messages longer than `max_message_size` bytes are refused.
+pub async fn serve(
+    listener: tokio::net::TcpListener,
+    max_message_size: usize,
+    client_info: ClientState,
+) {
+    let mut shutdown = client_info.get_shutdown_notifier();
+    let incoming = futures::stream::unfold(listener, |listener| async move {
+        loop {
+            // failing to accept one client (e.g. running out of file descriptors) isn't fatal
+            match listener.accept().await {
+                Ok((socket, _)) => return Some((Ok::<_, std::io::Error>(socket), listener)),
+                Err(e) => {
+                    error!("Couldn't accept new gRPC client: {}", e);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    });
+    let server = tonic::transport::Server::builder()
+        .add_service(Service::new(client_info, max_message_size))
+        .serve_with_incoming_shutdown(incoming, async move {
+            shutdown.recv().await;
+        });
+    if let Err(e) = server.await {
+        error!("gRPC server failed: {}", e);
+    }
+    debug!("gRPC server shutting down");
+}
@@ -0,0 +1,58 @@
+//! Invite codes for private games, whose uses are kept track of for as long as the server runs.
+//!
+//! The codes themselves come from each game's [config][crate::game::Config::invites],
+//! which maps each code to the number of players who can use it.
+//! Each arena keeps its own [`Invites`], so codes only let players into the arena listing them,
+//! and the uses aren't refilled when the arena starts its next game.
+
+use std::collections::{BTreeMap, HashMap};
+
+/// How each of an arena's invite codes has been used so far.
+#[derive(Debug, Clone, Default)]
+pub struct Invites {
+    /// The number of players who have joined with each code.
+    uses: HashMap<String, u32>,
+    /// The code each player joined with, by the name they registered under.
+    redeemed: HashMap<String, String>,
+}
+
+impl Invites {
+    /// Whether the player registered as `name` can join a game with the `invite` code,
+    /// given the game's `codes`.
+    ///
+    /// Any code is accepted if the game doesn't use invites,
+    /// or if the player already [redeemed][Invites::redeem] one of its codes.
+    #[must_use]
+    pub fn check(&self, codes: &BTreeMap<String, u32>, name: &str, invite: Option<&str>) -> bool {
+        codes.is_empty() || self.joined(codes, name) || self.usable(codes, invite).is_some()
+    }
+
+    /// Use up the `invite` code for the player registered as `name`,
+    /// if they [needed one][Invites::check] to join a game with the given `codes`.
+    pub fn redeem(&mut self, codes: &BTreeMap<String, u32>, name: &str, invite: Option<&str>) {
+        if codes.is_empty() || self.joined(codes, name) {
+            return;
+        }
+        if let Some(code) = self.usable(codes, invite) {
+            *self.uses.entry(code.to_owned()).or_default() += 1;
+            self.redeemed.insert(name.to_owned(), code.to_owned());
+        }
+    }
+
+    /// Whether the player registered as `name` already joined with one of the `codes`.
+    fn joined(&self, codes: &BTreeMap<String, u32>, name: &str) -> bool {
+        (self.redeemed.get(name)).is_some_and(|code| codes.contains_key(code))
+    }
+
+    /// The `invite` code, if it's one of the `codes` and has uses left.
+    fn usable<'a>(
+        &self,
+        codes: &BTreeMap<String, u32>,
+        invite: Option<&'a str>,
+    ) -> Option<&'a str> {
+        let code = invite?;
+        let allowed = *codes.get(code)?;
+        let used = self.uses.get(code).copied().unwrap_or(0);
+        (used < allowed).then_some(code)
+    }
+}
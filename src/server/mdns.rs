@@ -0,0 +1,119 @@
+//! Advertising the server on the local network with mDNS and DNS-SD.
+//!
+//! Each arena is advertised as a service of type [`SERVICE_TYPE`],
+//! so that clients on the same network can find games
+//! without having to pass IP addresses around the room.
+//! The service's port is the arena's TCP port, or another of its ports if TCP is disabled;
+//! every port the arena listens on, along with the size of the map and the number of player slots,
+//! are given in the service's TXT record.
+
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{anyhow, Result};
+use log::warn;
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+
+use super::Listening;
+
+/// The DNS-SD service type the server is advertised as.
+pub const SERVICE_TYPE: &str = "_beeeees._tcp.local.";
+
+/// How long to wait for each step of withdrawing an advertisement when the server shuts down.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The details of an arena to advertise.
+#[derive(Debug, Clone)]
+pub struct Details {
+    /// The arena's name, used as the service's instance name.
+    pub name: String,
+    /// The addresses the arena is listening on.
+    pub listening: Listening,
+    /// The width and height of the arena's map.
+    pub map: (i32, i32),
+    /// The number of players that can take part in the arena's games.
+    pub slots: usize,
+}
+
+/// An arena being advertised on the local network, until dropped.
+pub struct Advertisement {
+    /// The daemon answering mDNS queries.
+    daemon: ServiceDaemon,
+    /// The full name of the advertised service.
+    fullname: String,
+}
+
+impl Advertisement {
+    /// Start advertising an arena with the given `details`.
+    ///
+    /// An arena listening on an unspecified address (such as `0.0.0.0`)
+    /// is advertised on every address the host has.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the arena isn't listening on anything,
+    /// or if the service couldn't be registered.
+    pub fn start(details: &Details) -> Result<Self> {
+        let Listening { tcp, web, grpc } = details.listening;
+        let addr = tcp
+            .or(web)
+            .or(grpc)
+            .ok_or_else(|| anyhow!("Nothing to advertise"))?;
+
+        let mut properties = HashMap::new();
+        if let Some(tcp) = tcp {
+            properties.insert(String::from("tcp"), tcp.port().to_string());
+        }
+        if let Some(web) = web {
+            properties.insert(String::from("web"), web.port().to_string());
+        }
+        if let Some(grpc) = grpc {
+            properties.insert(String::from("grpc"), grpc.port().to_string());
+        }
+        let (width, height) = details.map;
+        properties.insert(String::from("map"), format!("{}x{}", width, height));
+        properties.insert(String::from("slots"), details.slots.to_string());
+
+        // there's no portable way to find the host's name, so make one up for the service
+        let host = format!("beeeees-{}-{}.local.", std::process::id(), addr.port());
+        // leaving out the addresses lets the daemon fill in (and keep up to date) every address
+        let everywhere = addr.ip().is_unspecified();
+        let ip = if everywhere {
+            String::new()
+        } else {
+            addr.ip().to_string()
+        };
+        let info = ServiceInfo::new(
+            SERVICE_TYPE,
+            &details.name,
+            &host,
+            ip,
+            addr.port(),
+            properties,
+        )?;
+        let info = if everywhere {
+            info.enable_addr_auto()
+        } else {
+            info
+        };
+        let fullname = info.get_fullname().to_owned();
+
+        let daemon = ServiceDaemon::new()?;
+        daemon.register(info)?;
+        Ok(Self { daemon, fullname })
+    }
+}
+
+impl Drop for Advertisement {
+    fn drop(&mut self) {
+        // wait for the goodbye to be sent, so clients stop listing the server straight away
+        match self.daemon.unregister(&self.fullname) {
+            Ok(status) => {
+                let _ = status.recv_timeout(SHUTDOWN_TIMEOUT);
+            }
+            Err(e) => warn!("Could not stop advertising {}: {}", self.fullname, e),
+        }
+        if let Ok(status) = self.daemon.shutdown() {
+            let _ = status.recv_timeout(SHUTDOWN_TIMEOUT);
+        }
+    }
+}
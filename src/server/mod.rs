@@ -1,20 +1,66 @@
 //! The primary game server that interacts with players and observers.
 
+pub mod access;
+pub mod announce;
+pub mod arena;
+#[cfg(feature = "discord")]
+pub mod discord;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod invites;
+#[cfg(feature = "mdns")]
+pub mod mdns;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
 pub mod protocol;
+pub mod relay;
+pub mod results;
+pub mod schedule;
+pub mod spec;
+pub mod tokens;
+pub mod tournament;
+pub mod webhook;
 
 use std::{
-    collections::{HashMap, HashSet},
-    net::SocketAddr,
-    sync::{Arc, Mutex},
-    time::Duration,
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+    net::{IpAddr, SocketAddr},
+    num::NonZeroU64,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use futures::{Future, Sink, SinkExt, Stream, StreamExt};
-use log::{debug, info, trace, warn};
+use hyper::client::HttpConnector;
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use log::{debug, error, info, trace, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tokio::time::{self, Interval, MissedTickBehavior};
+
+use crate::game::{
+    self,
+    achievements::Achievement,
+    events::Event,
+    world::{Position, Rect, World},
+    BeeID, Player, Role, Upgrade,
+};
 
-use crate::game::{self, world::World, Player};
+use self::{
+    access::AccessList,
+    invites::Invites,
+    protocol::{ChatChannel, ErrorCode, RosterEntry},
+    results::{GameResult, PlayerResult, Profile, Results},
+    schedule::Schedule,
+    tokens::Tokens,
+    tournament::Tournament,
+};
 
 /// Used to receive and respond to a shutdown signal.
 #[derive(Debug, Clone)]
@@ -68,10 +114,54 @@ impl Shutdown {
 /// that can be retrieved using [`ClientState::get_shutdown_notifier`].
 #[derive(Debug, Clone)]
 pub struct ClientState {
+    /// The name of the arena, if any.
+    name: Option<Arc<str>>,
     /// Send events to the current game.
     events: mpsc::Sender<GameEvent>,
     /// Map of player names to player IDs.
-    players: Arc<Mutex<HashMap<String, Player>>>,
+    players: Registry,
+    /// Results of previously finished games.
+    results: Results,
+    /// The tournament being played, if any.
+    tournament: Option<Tournament>,
+    /// The number of observers that have connected so far.
+    ///
+    /// Used to give each observer a default name in spectator chat.
+    spectators: Arc<AtomicUsize>,
+    /// The addresses of the observers currently connected.
+    observers: Arc<Mutex<HashSet<SocketAddr>>>,
+    /// The addresses the server is listening on.
+    listening: Arc<Mutex<Listening>>,
+    /// The most observers that can be connected at once, if limited.
+    max_observers: Option<usize>,
+    /// The latency to each connected player.
+    latencies: Latencies,
+    /// The number of connections currently open from each address.
+    connections: Arc<Mutex<HashMap<IpAddr, usize>>>,
+    /// The most connections that can be open from a single address at once, if limited.
+    max_connections_per_ip: Option<usize>,
+    /// The most messages of any kind each player can send per tick, if limited.
+    max_moves_per_tick: Option<u32>,
+    /// The most bee moves a single `moves` message can contain, if limited.
+    max_moves_per_message: Option<usize>,
+    /// How to treat players who lag behind the game's updates.
+    player_lag_policy: LagPolicy,
+    /// How to treat observers who lag behind the game's updates.
+    observer_lag_policy: LagPolicy,
+    /// The number of times a client can lag behind before being disconnected, if the policy says to.
+    max_lags: u32,
+    /// The API tokens players must register with, if required.
+    tokens: Option<Tokens>,
+    /// The token admins must give to manage the server, if admin actions are enabled.
+    admin_token: Option<Arc<str>>,
+    /// The network addresses allowed to connect.
+    access: Arc<AccessList>,
+    /// Whether players can see chat messages sent by observers.
+    public_spectator_chat: bool,
+    /// Used to subscribe to the notable moments of the server's games.
+    milestones: broadcast::Sender<Milestone>,
+    /// Used to watch the live scores of the game being played.
+    scoreboard: Arc<watch::Sender<Scoreboard>>,
     /// Used to receive notifications of impending shutdown.
     signal: Shutdown,
     /// Unused; when dropped signals that shutdown has finished successfully.
@@ -83,6 +173,617 @@ impl ClientState {
     pub fn get_shutdown_notifier(&self) -> Shutdown {
         self.signal.clone()
     }
+
+    /// Get a handle to the results of previously finished games.
+    pub fn results(&self) -> &Results {
+        &self.results
+    }
+
+    /// Get a handle to the tournament being played, if any.
+    pub fn tournament(&self) -> Option<&Tournament> {
+        self.tournament.as_ref()
+    }
+
+    /// Whether a client connecting from `addr` is allowed access to the server.
+    pub fn permits(&self, addr: IpAddr) -> bool {
+        self.access.permits(addr)
+    }
+
+    /// The most bee moves a single `moves` message can contain, if limited.
+    pub fn max_moves_per_message(&self) -> Option<usize> {
+        self.max_moves_per_message
+    }
+
+    /// Whether `token` is the server's admin token.
+    ///
+    /// Always false if the server has no admin token.
+    pub fn is_admin(&self, token: &str) -> bool {
+        let expected = self.admin_token.as_deref();
+        expected.is_some_and(|expected| game::same_secret(token.as_bytes(), expected.as_bytes()))
+    }
+
+    /// Kick the player registered as `name` out of the game for good.
+    ///
+    /// The player is disconnected, removed from the current game like a player
+    /// who stayed disconnected for too long, and their name is freed up.
+    /// Callers should check the request came from an [admin][ClientState::is_admin] first.
+    ///
+    /// # Errors
+    ///
+    /// Fails if no player is registered as `name`, or the server has finished playing.
+    pub async fn kick(&self, name: &str) -> Result<()> {
+        let player = (self.players.find(name).await)
+            .ok_or_else(|| anyhow!("No player is registered as {:?}", name))?;
+        let (response, kicked_rx) = oneshot::channel();
+        let kicked = match self.events.send(GameEvent::Kick { player, response }).await {
+            Ok(()) => kicked_rx.await.is_ok(),
+            Err(_) => false,
+        };
+        if !kicked {
+            bail!("The server has finished playing");
+        }
+        info!("Kicked {} ({:?})", player, name);
+        Ok(())
+    }
+
+    /// Count a new connection from `addr`,
+    /// unless there are already too many connections open from that address.
+    fn claim_connection(&self, addr: IpAddr) -> Option<ConnectionSlot> {
+        ConnectionSlot::claim(&self.connections, self.max_connections_per_ip, addr)
+    }
+
+    /// Reload the tunable parts of the current game's configuration,
+    /// as described by [`game::State::reload`].
+    ///
+    /// If the server is playing the same configuration repeatedly,
+    /// later games use the whole of the new configuration.
+    /// A configuration that fails [validation][game::Config::validate] is logged and ignored.
+    pub async fn reload(&self, config: game::Config) {
+        if let Err(e) = config.validate() {
+            error!("Keeping the current configuration: {:?}", e);
+            return;
+        }
+        let config = Box::new(config);
+        if self
+            .events
+            .send(GameEvent::Reload { config })
+            .await
+            .is_err()
+        {
+            debug!("Failed to reload the configuration, as the game has finished");
+        }
+    }
+
+    /// Record the addresses the server ended up listening on,
+    /// which may differ from those requested if they had a port of zero.
+    pub fn set_listening(&self, listening: Listening) {
+        *self.listening.lock().unwrap() = listening;
+    }
+
+    /// Get public information about the server and its current game,
+    /// for server browsers to list.
+    pub async fn info(&self) -> Info {
+        let (response, info_rx) = oneshot::channel();
+        let game = match self.events.send(GameEvent::Info { response }).await {
+            Ok(()) => info_rx.await.ok(),
+            Err(_) => None,
+        };
+        Info {
+            name: self.name.clone(),
+            listening: *self.listening.lock().unwrap(),
+            game,
+        }
+    }
+
+    /// Get the current state of the game being played,
+    /// or `None` if the server has finished playing.
+    pub async fn snapshot(&self) -> Option<Snapshot> {
+        let (response, snapshot_rx) = oneshot::channel();
+        self.events
+            .send(GameEvent::Snapshot { response })
+            .await
+            .ok()?;
+        snapshot_rx.await.ok()
+    }
+
+    /// Subscribe to the notable moments of the server's games as they happen.
+    ///
+    /// Only milestones sent after subscribing are received.
+    pub fn milestones(&self) -> broadcast::Receiver<Milestone> {
+        self.milestones.subscribe()
+    }
+
+    /// Watch the live scores of the game being played, which change after every tick.
+    ///
+    /// The scores are only kept up to date while someone is watching them.
+    pub fn scoreboard(&self) -> watch::Receiver<Scoreboard> {
+        self.scoreboard.subscribe()
+    }
+
+    /// Get a snapshot of the server's current status.
+    pub async fn status(&self) -> Status {
+        let players = self.players.list().await;
+        Status {
+            listening: *self.listening.lock().unwrap(),
+            observers: self.observers.lock().unwrap().len(),
+            max_observers: self.max_observers,
+            latencies: self.latencies.all(),
+            players,
+        }
+    }
+}
+
+/// A client for posting to other web servers, over either `http://` or `https://`.
+type HttpClient = hyper::Client<HttpsConnector<HttpConnector>>;
+
+/// Make a new [`HttpClient`], which trusts the usual root certificates of the web.
+fn http_client() -> HttpClient {
+    let https = HttpsConnectorBuilder::new()
+        .with_webpki_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    hyper::Client::builder().build(https)
+}
+
+/// A snapshot of the server's current status, as returned by [`ClientState::status`].
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Status {
+    /// The addresses the server is listening on.
+    pub listening: Listening,
+    /// The number of observers currently connected.
+    pub observers: usize,
+    /// The most observers that can be connected at once, if limited.
+    pub max_observers: Option<usize>,
+    /// The latency to each connected player, ordered by player ID.
+    pub latencies: Vec<Latency>,
+    /// The name each player is registered under, ordered by player ID.
+    pub players: Vec<Registration>,
+}
+
+/// The addresses the server is listening on, as listed in a [`Status`].
+#[derive(Debug, Clone, Copy, Default, Serialize, JsonSchema)]
+pub struct Listening {
+    /// The address accepting TCP connections, unless disabled.
+    pub tcp: Option<SocketAddr>,
+    /// The address hosting the website, unless disabled.
+    pub web: Option<SocketAddr>,
+    /// The address accepting gRPC connections, if enabled.
+    pub grpc: Option<SocketAddr>,
+}
+
+/// Public information about the server, as returned by [`ClientState::info`].
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Info {
+    /// The name of the arena, if the server runs several.
+    pub name: Option<Arc<str>>,
+    /// The addresses the server is listening on.
+    pub listening: Listening,
+    /// The game currently being played, or `None` if the server has finished playing.
+    pub game: Option<GameInfo>,
+}
+
+/// Public information about the game currently being played, as listed in an [`Info`].
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct GameInfo {
+    /// The width of the map, in tiles.
+    pub width: i32,
+    /// The height of the map, in tiles.
+    pub height: i32,
+    /// The number of ticks played so far.
+    pub tick: u64,
+    /// The number of players taking part in the game.
+    pub players: usize,
+    /// The number of players currently connected.
+    pub connected: usize,
+    /// The most players that can take part in the game, if limited.
+    pub max_players: Option<usize>,
+}
+
+/// The state of the game currently being played, as returned by [`ClientState::snapshot`].
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// The world map.
+    pub world: Arc<World>,
+    /// The number of ticks played so far.
+    pub tick: u64,
+    /// The game's entities, as seen by observers.
+    pub entities: game::Serializer,
+    /// Each player taking part in the game.
+    pub players: Vec<RosterEntry>,
+}
+
+/// A notable moment in the server's games, as sent by [`ClientState::milestones`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Milestone {
+    /// A new game has started.
+    GameStarted {
+        /// The width of the map, in tiles.
+        width: i32,
+        /// The height of the map, in tiles.
+        height: i32,
+        /// The players carried over from the previous game, if any.
+        players: Vec<Registration>,
+    },
+    /// A player has joined the current game.
+    PlayerJoined {
+        /// The player's ID.
+        player: Player,
+        /// The name the player registered under.
+        name: String,
+    },
+    /// A player has unlocked an achievement.
+    Achievement {
+        /// The player's ID.
+        player: Player,
+        /// The name the player registered under.
+        name: String,
+        /// The achievement that was unlocked.
+        achievement: Achievement,
+    },
+    /// The live scores of the current game, sent every so often.
+    Scores {
+        /// The number of ticks played so far.
+        tick: u64,
+        /// The current standings, ordered by rank.
+        players: Vec<Standing>,
+    },
+    /// The current game has finished.
+    GameOver {
+        /// The number of ticks the game lasted.
+        ticks: u64,
+        /// The final standings, ordered by rank.
+        players: Vec<Standing>,
+    },
+}
+
+/// The live scores of the game currently being played, as sent by [`ClientState::scoreboard`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Scoreboard {
+    /// Which game is being played, counting from 1 for the first game since the server started,
+    /// or 0 before the first game.
+    pub game: u64,
+    /// The number of ticks played so far.
+    pub tick: u64,
+    /// The current standings, ordered by rank.
+    pub players: Vec<Standing>,
+    /// Whether the game has finished, in which case these are the final standings.
+    pub finished: bool,
+}
+
+/// A player's position in a game, as listed in [`Milestone::Scores`] and [`Milestone::GameOver`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Standing {
+    /// The player's ID.
+    pub player: Player,
+    /// The name the player registered under.
+    pub name: String,
+    /// The player's score.
+    pub score: i32,
+    /// The player's position in the game, starting from 1.
+    pub rank: usize,
+    /// The team the player was on, if any.
+    pub team: Option<String>,
+}
+
+/// A name registered to a player, as listed in a [`Status`].
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Registration {
+    /// The player's ID.
+    pub player: Player,
+    /// The name the player registered under.
+    pub name: String,
+}
+
+/// An admin's request to [kick][ClientState::kick] a player, as sent to `/api/kick`.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct KickRequest {
+    /// The name the player registered under.
+    pub name: String,
+}
+
+/// The most recently measured latency to a connected player.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Latency {
+    /// The player's ID.
+    pub player: Player,
+    /// The player's name.
+    pub name: Arc<str>,
+    /// The round-trip time for the player's last reply to a ping, in milliseconds.
+    pub round_trip_ms: u64,
+}
+
+/// The latency to each connected player, shared between their connections.
+#[derive(Debug, Clone, Default)]
+struct Latencies(Arc<Mutex<HashMap<Player, Latency>>>);
+
+impl Latencies {
+    /// Record a new measurement of the round-trip time to `player`.
+    fn record(&self, player: Player, name: Arc<str>, round_trip: Duration) {
+        let round_trip_ms = round_trip.as_millis() as u64;
+        let latency = Latency {
+            player,
+            name,
+            round_trip_ms,
+        };
+        self.0.lock().unwrap().insert(player, latency);
+    }
+
+    /// Forget the latency to `player`, once they've disconnected.
+    fn forget(&self, player: Player) {
+        self.0.lock().unwrap().remove(&player);
+    }
+
+    /// The most recent round-trip time to `player` in milliseconds, if known.
+    fn get(&self, player: Player) -> Option<u64> {
+        let latencies = self.0.lock().unwrap();
+        latencies.get(&player).map(|l| l.round_trip_ms)
+    }
+
+    /// The latency to every connected player, ordered by player ID.
+    fn all(&self) -> Vec<Latency> {
+        let mut all: Vec<_> = self.0.lock().unwrap().values().cloned().collect();
+        all.sort_by_key(|l| l.player);
+        all
+    }
+}
+
+/// The player ID each registered name belongs to, shared between every connection.
+///
+/// Names stay registered across games, so players keep the same ID when they reconnect,
+/// until their player is removed from the game for staying disconnected too long.
+#[derive(Debug, Clone, Default)]
+struct Registry(Arc<tokio::sync::Mutex<HashMap<String, Player>>>);
+
+impl Registry {
+    /// The player registered under `name`, registering a new player if there isn't one.
+    async fn claim(&self, name: &str) -> Player {
+        *self.0.lock().await.entry(name.to_owned()).or_default()
+    }
+
+    /// Register `name` to `player`, returning whether the name was free.
+    async fn reserve(&self, name: &str, player: Player) -> bool {
+        match self.0.lock().await.entry(name.to_owned()) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(player);
+                true
+            }
+        }
+    }
+
+    /// The player registered under `name`, if any.
+    async fn find(&self, name: &str) -> Option<Player> {
+        self.0.lock().await.get(name).copied()
+    }
+
+    /// Free up `name` for anyone else to register under.
+    async fn release(&self, name: &str) {
+        self.0.lock().await.remove(name);
+    }
+
+    /// Free up every name registered to `player`, once they've been removed from the game.
+    async fn expire(&self, player: Player) {
+        self.0.lock().await.retain(|_, &mut p| p != player);
+    }
+
+    /// Every registered name, ordered by player ID.
+    async fn list(&self) -> Vec<Registration> {
+        let names = self.0.lock().await;
+        let mut all: Vec<_> = (names.iter())
+            .map(|(name, &player)| Registration {
+                player,
+                name: name.clone(),
+            })
+            .collect();
+        all.sort_by_key(|r| r.player);
+        all
+    }
+}
+
+/// Keeps an observer's address in [`ClientState::observers`] while it stays connected.
+#[derive(Debug)]
+struct ObserverSlot {
+    /// The connected observers.
+    observers: Arc<Mutex<HashSet<SocketAddr>>>,
+    /// The address of this observer.
+    addr: SocketAddr,
+}
+
+impl ObserverSlot {
+    /// Record a new observer connecting from `addr`,
+    /// unless there are already `max` observers connected.
+    fn claim(
+        observers: &Arc<Mutex<HashSet<SocketAddr>>>,
+        max: Option<usize>,
+        addr: SocketAddr,
+    ) -> Option<Self> {
+        let mut connected = observers.lock().unwrap();
+        if max.is_some_and(|max| connected.len() >= max) {
+            return None;
+        }
+        connected.insert(addr);
+        Some(Self {
+            observers: observers.clone(),
+            addr,
+        })
+    }
+}
+
+impl Drop for ObserverSlot {
+    fn drop(&mut self) {
+        self.observers.lock().unwrap().remove(&self.addr);
+    }
+}
+
+/// Counts a connection towards its address's total in [`ClientState::connections`]
+/// while it stays open.
+#[derive(Debug)]
+struct ConnectionSlot {
+    /// The number of connections open from each address.
+    connections: Arc<Mutex<HashMap<IpAddr, usize>>>,
+    /// The address of this connection.
+    addr: IpAddr,
+}
+
+impl ConnectionSlot {
+    /// Record a new connection from `addr`,
+    /// unless there are already `max` connections open from that address.
+    fn claim(
+        connections: &Arc<Mutex<HashMap<IpAddr, usize>>>,
+        max: Option<usize>,
+        addr: IpAddr,
+    ) -> Option<Self> {
+        let mut open = connections.lock().unwrap();
+        let count = open.entry(addr).or_default();
+        if max.is_some_and(|max| *count >= max) {
+            return None;
+        }
+        *count += 1;
+        Some(Self {
+            connections: connections.clone(),
+            addr,
+        })
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        let mut open = self.connections.lock().unwrap();
+        if let Entry::Occupied(mut count) = open.entry(self.addr) {
+            *count.get_mut() -= 1;
+            if *count.get() == 0 {
+                count.remove();
+            }
+        }
+    }
+}
+
+/// How to treat a client that falls too far behind the game's updates,
+/// and so misses some of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// Send the client a warning each time.
+    Warn,
+    /// Carry on without telling the client.
+    Skip,
+    /// Warn the client, disconnecting them once they've lagged too many times.
+    Disconnect,
+}
+
+impl std::str::FromStr for LagPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warn" => Ok(LagPolicy::Warn),
+            "skip" => Ok(LagPolicy::Skip),
+            "disconnect" => Ok(LagPolicy::Disconnect),
+            _ => Err(format!("unknown lag policy {:?}", s)),
+        }
+    }
+}
+
+/// What to do when the server falls behind on its game ticks,
+/// such as after a tick that took a long time to process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissedTickPolicy {
+    /// Push every later tick back, keeping the usual gap between ticks.
+    Delay,
+    /// Process the missed ticks straight away to catch back up with the original schedule.
+    Burst,
+    /// Give up on the missed ticks and carry on with the original schedule.
+    Skip,
+}
+
+impl std::str::FromStr for MissedTickPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "delay" => Ok(MissedTickPolicy::Delay),
+            "burst" => Ok(MissedTickPolicy::Burst),
+            "skip" => Ok(MissedTickPolicy::Skip),
+            _ => Err(format!("unknown missed tick policy {:?}", s)),
+        }
+    }
+}
+
+impl From<MissedTickPolicy> for MissedTickBehavior {
+    fn from(policy: MissedTickPolicy) -> Self {
+        match policy {
+            MissedTickPolicy::Delay => MissedTickBehavior::Delay,
+            MissedTickPolicy::Burst => MissedTickBehavior::Burst,
+            MissedTickPolicy::Skip => MissedTickBehavior::Skip,
+        }
+    }
+}
+
+/// Options controlling how the server runs games and treats its clients.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// The name of the arena the server is running, if the config file describes several.
+    pub name: Option<String>,
+    /// How long to spend on each game tick.
+    pub tick_rate: Duration,
+    /// Whether to stretch the tick rate when ticks keep taking longer than it to process.
+    pub adaptive_tick_rate: bool,
+    /// What to do about ticks that are missed when the server falls behind.
+    pub missed_tick_policy: MissedTickPolicy,
+    /// The most missed ticks to process in a row under [`MissedTickPolicy::Burst`],
+    /// or `None` for no limit.
+    ///
+    /// Any further missed ticks are skipped.
+    pub max_catch_up: Option<u32>,
+    /// Whether players can see chat messages sent by observers.
+    ///
+    /// By default spectator chat is only visible to other observers.
+    pub public_spectator_chat: bool,
+    /// How long a disconnected player has to reconnect before they're removed from the game.
+    ///
+    /// If `None`, disconnected players keep their hives and bees until the game finishes.
+    pub disconnect_grace: Option<Duration>,
+    /// The number of [`Broadcast`] messages that can be queued for each client
+    /// before they start missing updates.
+    ///
+    /// Each tick may send an update along with other notifications such as achievements,
+    /// so this should leave some room for those to be sent together.
+    /// Queued messages are shared between every client,
+    /// so memory use grows with the capacity and the size of each update,
+    /// but not with the number of clients.
+    pub broadcast_capacity: usize,
+    /// How many ticks apart to send the live scores as a [`Milestone::Scores`],
+    /// or `None` to only send the final scores.
+    pub score_interval: Option<NonZeroU64>,
+    /// The most observers that can be connected at once, or `None` for no limit.
+    pub max_observers: Option<usize>,
+    /// The most connections that can be open from a single address at once,
+    /// or `None` for no limit.
+    pub max_connections_per_ip: Option<usize>,
+    /// The most messages each player can send per tick, or `None` for no limit.
+    ///
+    /// Named for the `moves` messages that make up most of what players send,
+    /// but every kind of message counts, except for replies to pings.
+    pub max_moves_per_tick: Option<u32>,
+    /// The most bee moves a single `moves` message can contain, or `None` for no limit.
+    pub max_moves_per_message: Option<usize>,
+    /// The most paths to a [`Move::target`][game::Move::target] each player can have found per tick.
+    pub max_paths_per_tick: usize,
+    /// How to treat players who lag behind the game's updates.
+    pub player_lag_policy: LagPolicy,
+    /// How to treat observers who lag behind the game's updates.
+    pub observer_lag_policy: LagPolicy,
+    /// The number of times a client can lag behind before being disconnected,
+    /// under [`LagPolicy::Disconnect`].
+    pub max_lags: u32,
+    /// The API tokens players must register with, if required.
+    pub tokens: Option<Tokens>,
+    /// The token admins must give to manage the server, such as to kick players,
+    /// or `None` to disable admin actions.
+    pub admin_token: Option<String>,
+    /// The network addresses allowed to connect.
+    pub access: AccessList,
 }
 
 /// Data representing a game server.
@@ -103,19 +804,66 @@ pub struct GameServer<Server: Future, Shutdown: Future> {
 /// and a future that can be awaited to initiate a clean shutdown.
 ///
 /// After the future completes all clients will have shut down.
+///
+/// The games to play are decided by the `schedule`.
+/// When each game finishes its final scores are recorded into `results`.
+/// Everything else is controlled by the provided `options`.
 pub fn make_game_server(
-    state: game::State,
-    tick_rate: Duration,
+    schedule: Schedule,
+    results: Results,
+    options: Options,
 ) -> GameServer<impl Future<Output = ()>, impl Future<Output = ()>> {
     let (events_tx, events_rx) = mpsc::channel(16);
     let (shutdown_complete_tx, mut shutdown_complete_rx) = mpsc::channel(1);
     let (signal, shutdown_signal_tx) = Shutdown::new();
 
-    let server = play_game(state, tick_rate, events_rx);
+    let tournament = schedule.tournament().cloned();
+    let players = Registry::default();
+    let (milestones, _) = broadcast::channel(MILESTONE_CAPACITY);
+    let scoreboard = Arc::new(watch::channel(Scoreboard::default()).0);
+    let server = play_game(
+        schedule,
+        Pacing {
+            tick_rate: options.tick_rate,
+            adaptive_tick_rate: options.adaptive_tick_rate,
+            missed_tick_policy: options.missed_tick_policy,
+            max_catch_up: options.max_catch_up,
+        },
+        options.disconnect_grace,
+        options.broadcast_capacity,
+        options.score_interval,
+        options.max_paths_per_tick,
+        events_rx,
+        results.clone(),
+        players.clone(),
+        milestones.clone(),
+        scoreboard.clone(),
+    );
 
     let client_info = ClientState {
+        name: options.name.map(Arc::from),
         events: events_tx.clone(),
-        players: Default::default(),
+        players,
+        results,
+        tournament,
+        spectators: Default::default(),
+        observers: Default::default(),
+        max_observers: options.max_observers,
+        listening: Default::default(),
+        latencies: Default::default(),
+        connections: Default::default(),
+        max_connections_per_ip: options.max_connections_per_ip,
+        max_moves_per_tick: options.max_moves_per_tick,
+        max_moves_per_message: options.max_moves_per_message,
+        player_lag_policy: options.player_lag_policy,
+        observer_lag_policy: options.observer_lag_policy,
+        max_lags: options.max_lags,
+        tokens: options.tokens,
+        admin_token: options.admin_token.map(Arc::from),
+        access: Arc::new(options.access),
+        public_spectator_chat: options.public_spectator_chat,
+        milestones,
+        scoreboard,
         signal,
         _shutdown_complete: shutdown_complete_tx,
     };
@@ -136,15 +884,384 @@ pub fn make_game_server(
     }
 }
 
+/// The maximum length of a chat message, in bytes.
+const CHAT_MAX_LENGTH: usize = 256;
+
+/// The maximum length of a name chosen by a client, in bytes.
+const NAME_MAX_LENGTH: usize = 32;
+
+/// Check that a `name` chosen by a client is short enough to be used.
+///
+/// # Errors
+///
+/// Fails if the name is longer than [`NAME_MAX_LENGTH`].
+fn check_name_length(name: &str) -> Result<()> {
+    if name.len() > NAME_MAX_LENGTH {
+        bail!("Name longer than {} bytes", NAME_MAX_LENGTH);
+    }
+    Ok(())
+}
+
+/// The maximum number of chat messages a client may send within [`CHAT_WINDOW`].
+const CHAT_MAX_MESSAGES: usize = 5;
+
+/// The period over which [`CHAT_MAX_MESSAGES`] applies.
+const CHAT_WINDOW: Duration = Duration::from_secs(10);
+
+/// How often to measure each player's latency.
+const PING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The number of ticks in a row a player can go over the [message rate limit][MessageLimiter]
+/// before they're disconnected.
+const MESSAGE_LIMIT_STRIKES: u32 = 5;
+
+/// The chat state for a single client connection.
+///
+/// Also prevents the client from flooding the chat.
+#[derive(Debug)]
+struct Chatter {
+    /// The player sending messages, or an observer.
+    player: Player,
+    /// The name shown alongside messages.
+    name: Arc<str>,
+    /// The channel messages are sent on.
+    channel: ChatChannel,
+    /// When each recently accepted message was sent, oldest first.
+    sent: VecDeque<Instant>,
+}
+
+impl Chatter {
+    /// Create the chat state for a client sending messages as `player` and `name`.
+    fn new(player: Player, name: Arc<str>, channel: ChatChannel) -> Self {
+        Self {
+            player,
+            name,
+            channel,
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// Check whether `msg` may be sent now, recording it if so.
+    ///
+    /// # Errors
+    ///
+    /// Returns a human-readable reason if the message should be dropped.
+    fn check(&mut self, msg: &str) -> Result<(), String> {
+        if msg.len() > CHAT_MAX_LENGTH {
+            return Err(format!(
+                "Chat message longer than {} bytes",
+                CHAT_MAX_LENGTH
+            ));
+        }
+
+        let now = Instant::now();
+        while self.sent.front().is_some_and(|&t| now - t > CHAT_WINDOW) {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= CHAT_MAX_MESSAGES {
+            return Err(String::from("Sending chat messages too quickly"));
+        }
+
+        self.sent.push_back(now);
+        Ok(())
+    }
+
+    /// Send `msg` to the game using the `events` channel.
+    ///
+    /// If the message isn't allowed, it is dropped and a warning is sent via the `sink`.
+    async fn send<S, E>(
+        &mut self,
+        msg: String,
+        sink: &mut S,
+        events: &mpsc::Sender<GameEvent>,
+    ) -> Result<(), E>
+    where
+        S: Sink<protocol::Send, Error = E> + Unpin,
+    {
+        if let Err(msg) = self.check(&msg) {
+            debug!(
+                "Dropping chat from {} ({}): {}",
+                self.name, self.player, msg
+            );
+            return sink.send(protocol::Send::Warning { msg }).await;
+        }
+
+        let event = GameEvent::Chat {
+            player: self.player,
+            name: self.name.clone(),
+            msg,
+            channel: self.channel,
+        };
+        if events.send(event).await.is_err() {
+            debug!("{} failed to send chat event", self.player);
+        }
+        Ok(())
+    }
+}
+
+/// Counts how often a client has lagged behind the game's updates.
+#[derive(Debug)]
+struct LagTracker {
+    /// How to treat the client when they lag.
+    policy: LagPolicy,
+    /// The number of times the client can lag before being disconnected, if the policy says to.
+    limit: u32,
+    /// The number of times the client has lagged so far.
+    lags: u32,
+}
+
+impl LagTracker {
+    /// Create a tracker following `policy`, disconnecting after `limit` lags if it says to.
+    fn new(policy: LagPolicy, limit: u32) -> Self {
+        Self {
+            policy,
+            limit,
+            lags: 0,
+        }
+    }
+
+    /// Record that the client has lagged behind, returning how to respond this time.
+    ///
+    /// Under [`LagPolicy::Disconnect`] the client is warned
+    /// until they've lagged as many times as the limit allows.
+    fn record(&mut self) -> LagPolicy {
+        self.lags += 1;
+        match self.policy {
+            LagPolicy::Disconnect if self.lags < self.limit => LagPolicy::Warn,
+            policy => policy,
+        }
+    }
+}
+
+/// Measures the round-trip latency to a player by sending them pings.
+#[derive(Debug, Default)]
+struct Pinger {
+    /// The ID to give the next ping.
+    next_id: u64,
+    /// The ID of the last ping sent and when it was sent, if it hasn't been answered yet.
+    pending: Option<(u64, Instant)>,
+}
+
+impl Pinger {
+    /// Make a new ping to send, replacing any ping that hasn't been answered.
+    fn ping(&mut self) -> protocol::Send {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending = Some((id, Instant::now()));
+        protocol::Send::Ping { id }
+    }
+
+    /// Handle a reply to the ping `id`, returning the round-trip time if it's the latest ping.
+    fn pong(&mut self, id: u64) -> Option<Duration> {
+        match self.pending {
+            Some((pending, sent)) if pending == id => {
+                self.pending = None;
+                Some(sent.elapsed())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What to do with a message from a player, as decided by a [`MessageLimiter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verdict {
+    /// Handle the message as normal.
+    Accept,
+    /// Drop the message, warning the player that they've gone over the limit.
+    Warn,
+    /// Drop the message; the player has already been warned this tick.
+    Ignore,
+    /// Disconnect the player for going over the limit too many ticks in a row.
+    Disconnect,
+}
+
+/// Limits how many messages a player can send each tick,
+/// counting every kind of message other than replies to the server's pings.
+///
+/// Stops a misbehaving client from flooding the game with events and starving other players.
+#[derive(Debug)]
+struct MessageLimiter {
+    /// The most messages accepted each tick, or `None` for no limit.
+    max: Option<u32>,
+    /// The number of messages sent so far this tick.
+    sent: u32,
+    /// The number of ticks in a row the player has gone over the limit.
+    strikes: u32,
+}
+
+impl MessageLimiter {
+    /// Create a limiter accepting up to `max` messages each tick.
+    fn new(max: Option<u32>) -> Self {
+        Self {
+            max,
+            sent: 0,
+            strikes: 0,
+        }
+    }
+
+    /// Start counting messages for a new tick.
+    fn tick(&mut self) {
+        if self.max.is_none_or(|max| self.sent <= max) {
+            self.strikes = 0;
+        }
+        self.sent = 0;
+    }
+
+    /// Record a new message, deciding what should be done with it.
+    fn check(&mut self) -> Verdict {
+        let max = match self.max {
+            Some(max) => max,
+            None => return Verdict::Accept,
+        };
+        self.sent += 1;
+        if self.sent <= max {
+            Verdict::Accept
+        } else if self.sent > max + 1 {
+            Verdict::Ignore
+        } else {
+            self.strikes += 1;
+            if self.strikes >= MESSAGE_LIMIT_STRIKES {
+                Verdict::Disconnect
+            } else {
+                Verdict::Warn
+            }
+        }
+    }
+}
+
+/// The number of milestones that can be queued for each subscriber.
+const MILESTONE_CAPACITY: usize = 64;
+
+/// The number of private messages that can be queued for each player.
+const PRIVATE_CAPACITY: usize = 16;
+
+/// Channels used by the game to send messages to individual players,
+/// for anything other clients shouldn't see.
+#[derive(Debug, Default)]
+struct PrivateChannels {
+    /// The send end of the channel to each connected player.
+    senders: HashMap<Player, mpsc::Sender<protocol::Send>>,
+}
+
+impl PrivateChannels {
+    /// Open a new channel to `player`, replacing any previous one,
+    /// and return its receive end.
+    fn open(&mut self, player: Player) -> mpsc::Receiver<protocol::Send> {
+        let (tx, rx) = mpsc::channel(PRIVATE_CAPACITY);
+        self.senders.insert(player, tx);
+        rx
+    }
+
+    /// Stop sending messages to `player`.
+    fn close(&mut self, player: Player) {
+        self.senders.remove(&player);
+    }
+
+    /// Send `msg` to `player` alone, if they're connected.
+    ///
+    /// The message is dropped if the player already has too many messages queued up.
+    fn send(&mut self, player: Player, msg: protocol::Send) {
+        if let Some(tx) = self.senders.get(&player) {
+            if let Err(e) = tx.try_send(msg) {
+                debug!("Dropping private message to {}: {}", player, e);
+            }
+        }
+    }
+}
+
+/// Messages broadcast by the game to every connected client.
+#[derive(Debug, Clone)]
+enum Broadcast {
+    /// The current state of the game.
+    Update(game::Serializer),
+    /// The game's tick rate has changed.
+    TickRate(Duration),
+    /// When the next tick will be processed.
+    Schedule {
+        /// The number of the next tick.
+        tick: u64,
+        /// When the next tick is due.
+        deadline: Instant,
+    },
+    /// A random event happened in the world.
+    Event(Event),
+    /// A player has donated pollen to another player.
+    Transfer {
+        /// The player that donated the pollen.
+        from: Player,
+        /// The player that received the pollen.
+        to: Player,
+        /// The amount of pollen transferred.
+        amount: i32,
+    },
+    /// A player has changed their name.
+    Rename(Player, Arc<str>),
+    /// A player was removed from the game after disconnecting, with their final score.
+    Removed(Player, i32),
+    /// A client has sent a chat message.
+    Chat {
+        /// The player that sent the message, or an observer.
+        player: Player,
+        /// The name of the client that sent the message.
+        name: Arc<str>,
+        /// The contents of the message.
+        msg: Arc<str>,
+        /// Which channel the message was sent on.
+        channel: ChatChannel,
+    },
+    /// A new game has started, with a new world map.
+    Registration {
+        /// The world map for the new game.
+        world: Arc<World>,
+        /// The expected tick rate of the game.
+        tick_rate: Duration,
+        /// The statistics from previous games for each player taking part.
+        ///
+        /// Connected players not listed here are not taking part in the game.
+        profiles: Arc<HashMap<Player, Option<Profile>>>,
+    },
+    /// The game has finished; provides the final summary for each player.
+    Summary(Arc<HashMap<Player, game::Summary>>),
+}
+
 /// The information passed back by the game on successful creation.
 #[derive(Debug)]
 struct GameEventResponse {
     /// The receive end of a stream receiving game updates.
-    updates: broadcast::Receiver<game::Serializer>,
+    updates: broadcast::Receiver<Broadcast>,
+    /// The receive end of a channel for messages meant for this player alone.
+    ///
+    /// Observers are never sent private messages, so their channel is already closed.
+    private: mpsc::Receiver<protocol::Send>,
     /// The world map.
     world: Arc<World>,
     /// The expected tick rate of the game.
     tick_rate: Duration,
+    /// The player's statistics from previous games, if any.
+    profile: Option<Profile>,
+    /// The current state of the game,
+    /// so that the client doesn't have to wait for the next tick to see anything.
+    snapshot: game::Serializer,
+}
+
+/// The details provided by a player when registering.
+#[derive(Debug, Clone, Default)]
+struct PlayerDetails {
+    /// The name the player registered with.
+    ///
+    /// Used to identify the player in the game's results.
+    name: String,
+    /// The team the player asked to join, if any.
+    team: Option<String>,
+    /// How the player's hive and bees should be displayed.
+    cosmetics: game::Cosmetics,
+    /// The password the player gave when registering, if any.
+    password: Option<String>,
+    /// The invite code the player gave when registering, if any.
+    invite: Option<String>,
+    /// The API token the player gave when registering, if any.
+    token: Option<String>,
 }
 
 /// An event to be passed to the active game.
@@ -162,6 +1279,8 @@ enum GameEvent {
         /// and as such will have no control over the game.
         /// They will still receive updates, however.
         player: Player,
+        /// The details the player registered with.
+        details: PlayerDetails,
         /// Used to respond back on the status of the request.
         ///
         /// If the player was successfully added,
@@ -169,31 +1288,311 @@ enum GameEvent {
         /// and a reference to the (immutable) tile map for the game.
         response: oneshot::Sender<Result<GameEventResponse>>,
     },
+    /// Kick a player out of the game for good, disconnecting them.
+    Kick {
+        /// The player to kick.
+        player: Player,
+        /// Used to respond once the player has been removed.
+        response: oneshot::Sender<()>,
+    },
     /// Notify that a player has disconnected early from the game.
     ///
-    /// Used to determine whether to admit a reconnecting player.
-    Disconnect {
-        /// The player that disconnected.
-        player: Player,
-    },
-    /// Move the player's bees within the game.
-    Move {
-        /// The player requesting the move.
-        player: Player,
-        /// The bees to be moved.
-        moves: Vec<protocol::Move>,
-    },
-    /// Finish the game.
-    Finish,
+    /// Used to determine whether to admit a reconnecting player.
+    Disconnect {
+        /// The player that disconnected.
+        player: Player,
+    },
+    /// Move the player's bees within the game.
+    Move {
+        /// The player requesting the move.
+        player: Player,
+        /// The bees to be moved.
+        moves: Vec<game::Move>,
+        /// The tick the moves are meant for, or `None` for whichever tick is next.
+        tick: Option<u64>,
+        /// Used to respond with the tick the moves apply to,
+        /// and how many of them were accepted.
+        ///
+        /// Responds with `None` if the moves were meant for a different tick,
+        /// in which case the player is privately warned about it instead.
+        response: oneshot::Sender<Option<(u64, usize)>>,
+    },
+    /// Send a chat message to everyone in the game.
+    Chat {
+        /// The player sending the message, or an observer.
+        player: Player,
+        /// The name of the client sending the message.
+        name: Arc<str>,
+        /// The contents of the message.
+        msg: String,
+        /// Which channel to send the message on.
+        channel: ChatChannel,
+    },
+    /// Donate pollen from one player's hive to another's.
+    Transfer {
+        /// The player donating the pollen.
+        from: Player,
+        /// The player receiving the pollen.
+        to: Player,
+        /// The amount of pollen to transfer.
+        amount: i32,
+        /// Used to respond back on whether the transfer was made.
+        response: oneshot::Sender<Result<()>>,
+    },
+    /// Spend a player's pollen to build a new hive.
+    BuildHive {
+        /// The player building the hive.
+        player: Player,
+        /// The bee marking where to build the hive.
+        bee: BeeID,
+        /// Used to respond back on whether the hive was built.
+        response: oneshot::Sender<Result<()>>,
+    },
+    /// Spend a player's pollen to move one of their hives.
+    RelocateHive {
+        /// The player moving their hive.
+        player: Player,
+        /// The current position of the hive.
+        from: Position,
+        /// Where to move the hive to.
+        to: Position,
+        /// Used to respond back on whether the hive started moving.
+        response: oneshot::Sender<Result<()>>,
+    },
+    /// Spend a player's pollen to upgrade one of their hives.
+    UpgradeHive {
+        /// The player upgrading their hive.
+        player: Player,
+        /// The position of the hive.
+        hive: Position,
+        /// The upgrade to buy.
+        upgrade: Upgrade,
+        /// Used to respond back on whether the upgrade was bought.
+        response: oneshot::Sender<Result<()>>,
+    },
+    /// Change the role of one of a player's bees.
+    AssignRole {
+        /// The player owning the bee.
+        player: Player,
+        /// The bee to change.
+        bee: BeeID,
+        /// The bee's new role.
+        role: Role,
+        /// Used to respond back on whether the role was changed.
+        response: oneshot::Sender<Result<()>>,
+    },
+    /// Change the name a player is registered under.
+    Rename {
+        /// The player changing their name.
+        player: Player,
+        /// The player's new name.
+        name: String,
+        /// Used to respond back on whether the name could be changed.
+        response: oneshot::Sender<Result<()>>,
+    },
+    /// Request information about each player in the current game.
+    Roster {
+        /// Used to respond with the requested information.
+        response: oneshot::Sender<Vec<RosterEntry>>,
+    },
+    /// Request public information about the current game.
+    Info {
+        /// Used to respond with the requested information.
+        response: oneshot::Sender<GameInfo>,
+    },
+    /// Request the current state of the game.
+    Snapshot {
+        /// Used to respond with the game's state.
+        response: oneshot::Sender<Snapshot>,
+    },
+    /// Reload the game's configuration.
+    Reload {
+        /// The new configuration.
+        config: Box<game::Config>,
+    },
+    /// Finish the game.
+    Finish,
+}
+
+/// The number of ticks in a row that must take longer than the tick rate
+/// before an [adaptive tick rate][TickBudget] is stretched.
+const SLOW_TICK_LIMIT: u32 = 5;
+
+/// The number of ticks in a row that must take at most half of a stretched tick rate
+/// before an [adaptive tick rate][TickBudget] is stepped back down.
+const FAST_TICK_LIMIT: u32 = 20;
+
+/// Keeps track of how long each tick takes to process,
+/// stretching the tick rate when ticks keep taking longer than it allows,
+/// and stepping it back down once they keep up again.
+#[derive(Debug)]
+struct TickBudget {
+    /// The tick rate the game is meant to run at, which the budget never drops below.
+    target: Duration,
+    /// The current time allowed for each tick.
+    rate: Duration,
+    /// The number of ticks in a row that have gone over the budget.
+    slow: u32,
+    /// The longest time taken by one of the slow ticks.
+    slowest: Duration,
+    /// The number of ticks in a row that have taken at most half of the budget.
+    fast: u32,
+}
+
+impl TickBudget {
+    /// Start tracking ticks that are meant to take at most `rate`.
+    fn new(rate: Duration) -> Self {
+        Self {
+            target: rate,
+            rate,
+            slow: 0,
+            slowest: Duration::ZERO,
+            fast: 0,
+        }
+    }
+
+    /// Record how long a tick took to process.
+    ///
+    /// Returns the new tick rate if it changes:
+    /// either stretched to leave some room over the slowest recent tick,
+    /// or stepped back a quarter of the way down towards the target.
+    fn record(&mut self, elapsed: Duration) -> Option<Duration> {
+        if elapsed <= self.rate {
+            self.slow = 0;
+            self.slowest = Duration::ZERO;
+            if self.rate == self.target || elapsed > self.rate / 2 {
+                self.fast = 0;
+                return None;
+            }
+            self.fast += 1;
+            if self.fast < FAST_TICK_LIMIT {
+                return None;
+            }
+            self.rate = (self.rate * 3 / 4).max(self.target);
+            self.fast = 0;
+            return Some(self.rate);
+        }
+        self.fast = 0;
+        self.slow += 1;
+        self.slowest = self.slowest.max(elapsed);
+        if self.slow < SLOW_TICK_LIMIT {
+            return None;
+        }
+        self.rate = self.slowest * 5 / 4;
+        self.slow = 0;
+        self.slowest = Duration::ZERO;
+        Some(self.rate)
+    }
+}
+
+/// How quickly [`play_game`] runs through each game's ticks.
+#[derive(Debug, Clone, Copy)]
+struct Pacing {
+    /// How long to spend on each tick.
+    tick_rate: Duration,
+    /// Whether to stretch the tick rate when ticks keep taking longer than it to process.
+    adaptive_tick_rate: bool,
+    /// What to do about ticks that are missed when falling behind.
+    missed_tick_policy: MissedTickPolicy,
+    /// The most missed ticks to process in a row under [`MissedTickPolicy::Burst`].
+    max_catch_up: Option<u32>,
+}
+
+/// Decides when each tick of a game happens.
+#[derive(Debug)]
+struct Clock {
+    /// The schedule of ticks.
+    interval: Interval,
+    /// What to do about missed ticks.
+    policy: MissedTickPolicy,
+    /// The most missed ticks to process in a row, if limited.
+    max_catch_up: Option<u32>,
+    /// The number of ticks in a row that have been processed late.
+    catching_up: u32,
+}
+
+impl Clock {
+    /// Start a new clock, with the first tick straight away.
+    fn new(pacing: Pacing) -> Self {
+        let mut interval = tokio::time::interval(pacing.tick_rate);
+        interval.set_missed_tick_behavior(pacing.missed_tick_policy.into());
+        Self {
+            interval,
+            policy: pacing.missed_tick_policy,
+            max_catch_up: pacing.max_catch_up,
+            catching_up: 0,
+        }
+    }
+
+    /// The current time between each tick.
+    fn rate(&self) -> Duration {
+        self.interval.period()
+    }
+
+    /// Wait for the next tick, returning when it was scheduled for.
+    async fn tick(&mut self) -> time::Instant {
+        self.interval.tick().await
+    }
+
+    /// Restart the clock with a new `rate`, with the next tick one `rate` after the tick `at`.
+    fn set_rate(&mut self, at: time::Instant, rate: Duration) {
+        self.restart(at + rate, rate);
+    }
+
+    /// Check whether the tick scheduled `at` finished late enough that the next tick is overdue.
+    ///
+    /// If too many ticks in a row have been caught up under [`MissedTickPolicy::Burst`],
+    /// the rest are skipped so that the game doesn't stall while they're processed.
+    fn finished(&mut self, at: time::Instant) {
+        let rate = self.rate();
+        let behind = time::Instant::now().saturating_duration_since(at);
+        if behind < rate {
+            self.catching_up = 0;
+            return;
+        }
+        self.catching_up += 1;
+        let limit = self
+            .max_catch_up
+            .filter(|_| self.policy == MissedTickPolicy::Burst);
+        if limit.is_some_and(|max| self.catching_up > max) {
+            let missed = behind.as_nanos().checked_div(rate.as_nanos()).unwrap_or(0);
+            warn!(
+                "Skipping {} missed tick(s) after catching up on too many",
+                missed
+            );
+            self.restart(at + rate * (missed as u32 + 1), rate);
+            self.catching_up = 0;
+        }
+    }
+
+    /// Replace the schedule with one starting at `start`, keeping the missed tick policy.
+    fn restart(&mut self, start: time::Instant, rate: Duration) {
+        self.interval = time::interval_at(start, rate);
+        self.interval.set_missed_tick_behavior(self.policy.into());
+    }
 }
 
-/// Runs an instance of the game.
+/// Runs the games decided by the `schedule`, one after the other.
 ///
-/// Will update the game `state` at a constant rate,
-/// as denoted by `tick_rate`.
+/// Will update the current game at a constant rate,
+/// as denoted by the `pacing`.
 /// User input can be provided via `events`,
 /// and the current game state will be regularly broadcast via `updates`.
-/// If the `events` channel closes the game will finish.
+/// If the `events` channel closes the server will finish.
+///
+/// When a game reaches one of its [finishing conditions][game::State::is_finished]
+/// the final scores are recorded in `results`, and the next game is started.
+/// Any connected players are automatically added to the new game if allowed.
+/// Once there are no more games to play the server finishes.
+///
+/// Missed ticks are handled according to the `pacing`.
+/// If it has an adaptive tick rate and ticks keep taking longer than the tick rate to process,
+/// the tick rate of the current game is stretched to match, and clients are told the new rate.
+///
+/// Players who stay disconnected for longer than `disconnect_grace`
+/// are [removed][game::State::remove_player] from the current game,
+/// and their names are freed up in the `registry`.
+/// Up to `broadcast_capacity` updates are queued for clients that fall behind.
 ///
 /// # TODO
 ///
@@ -201,69 +1600,358 @@ enum GameEvent {
 /// instead of the existing "server-driven" one;
 /// that is, rather than tick at a constant speed and leave players behind,
 /// always tick at the rate of the slowest connection
-/// (with `tick_rate` as a maximum speed).
+/// (with the `pacing` as a maximum speed).
+#[allow(clippy::too_many_arguments)]
 async fn play_game(
-    mut state: game::State,
-    tick_rate: Duration,
+    mut schedule: Schedule,
+    pacing: Pacing,
+    disconnect_grace: Option<Duration>,
+    broadcast_capacity: usize,
+    score_interval: Option<NonZeroU64>,
+    max_paths_per_tick: usize,
     mut events: mpsc::Receiver<GameEvent>,
+    results: Results,
+    registry: Registry,
+    milestones: broadcast::Sender<Milestone>,
+    scoreboard: Arc<watch::Sender<Scoreboard>>,
 ) {
-    let mut next_moves = game::Moves::new();
-    let mut interval = tokio::time::interval(tick_rate);
-    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
     let mut active_players = HashSet::new();
-    let (updates, _) = broadcast::channel(1);
-    let world = Arc::new(state.world().clone());
+    let mut disconnected: HashMap<Player, Instant> = HashMap::new();
+    let mut details = HashMap::new();
+    let (updates, _) = broadcast::channel(broadcast_capacity);
+    let mut private = PrivateChannels::default();
+    // invite codes stay used up from one game to the next
+    let mut invites = Invites::default();
+    let mut games = 0;
 
-    let make_response = |_| GameEventResponse {
-        updates: updates.subscribe(),
-        world: world.clone(),
-        tick_rate,
-    };
+    'games: while let Some(config) = schedule.next_game() {
+        games += 1;
+        let mut state = game::State::new(config);
+        let world = Arc::new(state.world().clone());
+        info!("Starting new game");
 
-    loop {
-        tokio::select! {
-            // handle any events sent in
-            event = events.recv() => match event {
-                Some(GameEvent::AddPlayer{ player, response }) => {
-                    trace!("Adding player {}", player);
-                    let result = if player.is_observer() {
-                        Ok(())
-                    } else {
-                        state.add_player(player).and_then(|_| {
-                            if active_players.insert(player) {
-                                Ok(())
+        // bring across any players still connected from the previous game
+        let mut profiles = HashMap::new();
+        for &player in &active_players {
+            let player_details = &details[&player];
+            match join_game(
+                &mut state,
+                &mut schedule,
+                &mut invites,
+                player,
+                player_details,
+            ) {
+                Ok(()) => {
+                    profiles.insert(player, results.profile(&player_details.name));
+                }
+                Err(e) => info!("{} not taking part in new game: {}", player, e),
+            }
+        }
+        let _ = updates.send(Broadcast::Registration {
+            world: world.clone(),
+            tick_rate: pacing.tick_rate,
+            profiles: Arc::new(profiles),
+        });
+        let _ = milestones.send(Milestone::GameStarted {
+            width: world.width,
+            height: world.height,
+            players: (state.players())
+                .map(|&player| Registration {
+                    player,
+                    name: details[&player].name.clone(),
+                })
+                .collect(),
+        });
+
+        let make_response = |profile, snapshot, private, tick_rate| GameEventResponse {
+            updates: updates.subscribe(),
+            private,
+            world: world.clone(),
+            tick_rate,
+            profile,
+            snapshot,
+        };
+
+        let mut next_moves = game::Moves::new();
+        // finding paths is slow, so each player can only ask for so many each tick
+        let mut paths_left: HashMap<Player, usize> = HashMap::new();
+        // the last score each player was privately told about
+        let mut scores: HashMap<Player, i32> = HashMap::new();
+        let mut budget = TickBudget::new(pacing.tick_rate);
+        let mut clock = Clock::new(pacing);
+
+        loop {
+            tokio::select! {
+                // handle any events sent in
+                event = events.recv() => match event {
+                    Some(GameEvent::AddPlayer{ player, details: new_details, response }) => {
+                        trace!("Adding player {}", player);
+                        let result = if player.is_observer() {
+                            Ok(None)
+                        } else if active_players.contains(&player) {
+                            Err(anyhow!("Duplicate player ID"))
+                        } else {
+                            let returning = state.players().any(|&p| p == player);
+                            join_game(&mut state, &mut schedule, &mut invites, player, &new_details).map(|_| {
+                                active_players.insert(player);
+                                disconnected.remove(&player);
+                                if !returning {
+                                    let _ = milestones.send(Milestone::PlayerJoined {
+                                        player,
+                                        name: new_details.name.clone(),
+                                    });
+                                }
+                                let profile = results.profile(&new_details.name);
+                                details.insert(player, new_details);
+                                profile
+                            })
+                        };
+                        let result = result.map(|profile| {
+                            let channel = if player.is_observer() {
+                                mpsc::channel(1).1
                             } else {
-                                Err(anyhow!("Duplicate player ID"))
+                                // tell them their score again after the next tick
+                                scores.remove(&player);
+                                private.open(player)
+                            };
+                            make_response(profile, state.make_serializer(), channel, clock.rate())
+                        });
+                        response.send(result).unwrap();
+                    },
+                    Some(GameEvent::Disconnect { player }) => {
+                        debug!("Disconnecting {}", player);
+                        if active_players.remove(&player) {
+                            disconnected.insert(player, Instant::now());
+                        } else if details.contains_key(&player) {
+                            warn!("Disconnecting {} that wasn't active?", player);
+                            disconnected.insert(player, Instant::now());
+                        }
+                        private.close(player);
+                    }
+                    Some(GameEvent::Kick { player, response }) => {
+                        let code = ErrorCode::Kicked;
+                        let msg = code.to_string();
+                        private.send(player, protocol::Send::Error { msg, code: Some(code) });
+                        private.close(player);
+                        active_players.remove(&player);
+                        disconnected.remove(&player);
+                        let (moves, names) = (&mut next_moves, &registry);
+                        remove_player(player, &mut state, moves, &mut details, names, &updates).await;
+                        let _ = response.send(());
+                    }
+                    Some(GameEvent::Move { player, moves, tick, response }) => {
+                        assert!(!player.is_observer());
+                        let next_tick = state.ticks() + 1;
+                        let rejection = match tick {
+                            Some(tick) if tick < next_tick => Some(format!(
+                                "Moves for tick {} arrived too late, the next tick is {}",
+                                tick,
+                                next_tick
+                            )),
+                            Some(tick) if tick > next_tick => Some(format!(
+                                "Moves for tick {} arrived too early, the next tick is {}",
+                                tick,
+                                next_tick
+                            )),
+                            _ => None,
+                        };
+                        let result = match rejection {
+                            Some(msg) => {
+                                debug!("{} sent moves for the wrong tick: {}", player, msg);
+                                private.send(player, protocol::Send::Warning { msg });
+                                None
                             }
-                        })
-                    };
-                    response.send(result.map(make_response)).unwrap();
+                            None => {
+                                let paths = paths_left.entry(player).or_insert(max_paths_per_tick);
+                                let accepted =
+                                    state.apply_moves(&mut next_moves, player, moves, paths);
+                                Some((next_tick, accepted))
+                            }
+                        };
+                        let _ = response.send(result);
+                    },
+                    Some(GameEvent::Chat { player, name, msg, channel }) => {
+                        debug!("{} ({}) says {:?} to {:?}", name, player, msg, channel);
+                        let msg = msg.into();
+                        let _ = updates.send(Broadcast::Chat { player, name, msg, channel });
+                    },
+                    Some(GameEvent::Transfer { from, to, amount, response }) => {
+                        let result = state.transfer_pollen(from, to, amount);
+                        if result.is_ok() {
+                            info!("{} donated {} pollen to {}", from, amount, to);
+                            let _ = updates.send(Broadcast::Transfer { from, to, amount });
+                        }
+                        let _ = response.send(result);
+                    },
+                    Some(GameEvent::BuildHive { player, bee, response }) => {
+                        let result = state.build_hive(player, bee);
+                        if result.is_ok() {
+                            info!("{} built a new hive", player);
+                        }
+                        let _ = response.send(result);
+                    },
+                    Some(GameEvent::RelocateHive { player, from, to, response }) => {
+                        let result = state.relocate_hive(player, from, to);
+                        if result.is_ok() {
+                            info!("{} is relocating a hive to {:?}", player, to);
+                        }
+                        let _ = response.send(result);
+                    },
+                    Some(GameEvent::UpgradeHive { player, hive, upgrade, response }) => {
+                        let result = state.upgrade_hive(player, hive, upgrade);
+                        if result.is_ok() {
+                            info!("{} upgraded a hive with {:?}", player, upgrade);
+                        }
+                        let _ = response.send(result);
+                    },
+                    Some(GameEvent::AssignRole { player, bee, role, response }) => {
+                        let _ = response.send(state.assign_role(player, bee, role));
+                    },
+                    Some(GameEvent::Rename { player, name, response }) => {
+                        let result = if schedule.tournament().is_some() {
+                            Err(anyhow!("Cannot change name during a tournament"))
+                        } else {
+                            info!("{} renamed to {:?}", player, name);
+                            let _ = updates.send(Broadcast::Rename(player, name.as_str().into()));
+                            details.entry(player).or_default().name = name;
+                            Ok(())
+                        };
+                        let _ = response.send(result);
+                    },
+                    Some(GameEvent::Roster { response }) => {
+                        let _ = response.send(roster(&state, &details, &active_players));
+                    },
+                    Some(GameEvent::Info { response }) => {
+                        let world = state.world();
+                        let _ = response.send(GameInfo {
+                            width: world.width,
+                            height: world.height,
+                            tick: state.ticks(),
+                            players: state.players().count(),
+                            connected: active_players.len(),
+                            max_players: state.max_players(),
+                        });
+                    },
+                    Some(GameEvent::Snapshot { response }) => {
+                        let _ = response.send(Snapshot {
+                            world: world.clone(),
+                            tick: state.ticks(),
+                            entities: state.make_serializer(),
+                            players: roster(&state, &details, &active_players),
+                        });
+                    },
+                    Some(GameEvent::Reload { config }) => {
+                        info!("Reloading the configuration");
+                        schedule.reload((*config).clone());
+                        state.reload(*config);
+                    },
+                    Some(GameEvent::Finish) | None => break 'games,
                 },
-                Some(GameEvent::Disconnect { player }) => {
-                    debug!("Disconnecting {}", player);
-                    if !active_players.remove(&player) {
-                        warn!("Disconnecting {} that wasn't active?", player);
-                    }
-                }
-                Some(GameEvent::Move { player, moves }) => {
-                    assert!(!player.is_observer());
-                    for protocol::Move { bee, direction } in moves {
-                        if let Some(direction) = direction {
-                            next_moves.insert((player, bee), direction);
+                // go to the next state
+                at = clock.tick() => {
+                    trace!("Server tick: {:?}", next_moves);
+                    let started = Instant::now();
+                    state.tick(&next_moves);
+                    if let Some(rate) = (pacing.adaptive_tick_rate)
+                        .then(|| budget.record(started.elapsed()))
+                        .flatten()
+                    {
+                        if rate > clock.rate() {
+                            warn!("Ticks are taking too long, slowing the tick rate to {:?}", rate);
                         } else {
-                            next_moves.remove(&(player, bee));
+                            info!(
+                                "Ticks are keeping up again, speeding the tick rate up to {:?}",
+                                rate
+                            );
                         }
+                        clock.set_rate(at, rate);
+                        let _ = updates.send(Broadcast::TickRate(rate));
                     }
-                },
-                Some(GameEvent::Finish) | None => break,
-            },
-            // go to the next state
-            _ = interval.tick() => {
-                trace!("Server tick: {:?}", next_moves);
-                state.tick(&next_moves);
-                // ignore errors of nobody connected yet
-                let _ = updates.send(state.make_serializer());
-                next_moves.clear();
+                    // ignore errors of nobody connected yet
+                    let _ = updates.send(Broadcast::Schedule {
+                        tick: state.ticks() + 1,
+                        deadline: at.into_std() + clock.rate(),
+                    });
+                    let _ = updates.send(Broadcast::Update(state.make_serializer()));
+                    for (player, achievement) in state.drain_achievements() {
+                        info!("{} unlocked {:?}", player, achievement);
+                        private.send(player, protocol::Send::Achievement { player, achievement });
+                        if let Some(player_details) = details.get(&player) {
+                            let _ = milestones.send(Milestone::Achievement {
+                                player,
+                                name: player_details.name.clone(),
+                                achievement,
+                            });
+                        }
+                    }
+                    for event in state.drain_events() {
+                        info!("Event: {:?}", event);
+                        let _ = updates.send(Broadcast::Event(event));
+                    }
+                    let mut blocked: HashMap<Player, Vec<BeeID>> = HashMap::new();
+                    for (player, bee) in state.drain_blocked() {
+                        blocked.entry(player).or_default().push(bee);
+                    }
+                    for (player, bees) in blocked {
+                        private.send(player, protocol::Send::Blocked { bees: bees.into() });
+                    }
+                    for (player, score) in state.scores() {
+                        if scores.insert(player, score) != Some(score) {
+                            private.send(player, protocol::Send::Score { score });
+                        }
+                    }
+                    // keep any plans that still have steps left
+                    state.advance_moves(&mut next_moves);
+                    paths_left.clear();
+
+                    // give up on players who haven't come back in time
+                    if let Some(grace) = disconnect_grace {
+                        let expired: Vec<_> = (disconnected.iter())
+                            .filter(|(_, since)| since.elapsed() >= grace)
+                            .map(|(&player, _)| player)
+                            .collect();
+                        for player in expired {
+                            disconnected.remove(&player);
+                            let (moves, names) = (&mut next_moves, &registry);
+                            remove_player(player, &mut state, moves, &mut details, names, &updates)
+                                .await;
+                        }
+                    }
+
+                    clock.finished(at);
+
+                    let tick = state.ticks();
+                    // only work out the standings if anyone is watching them
+                    if scoreboard.receiver_count() > 0 {
+                        scoreboard.send_replace(Scoreboard {
+                            game: games,
+                            tick,
+                            players: standings(&state, &details),
+                            finished: state.is_finished(),
+                        });
+                    }
+                    if score_interval.is_some_and(|n| tick.is_multiple_of(n.get())) && !state.is_finished() {
+                        let _ = milestones.send(Milestone::Scores {
+                            tick,
+                            players: standings(&state, &details),
+                        });
+                    }
+
+                    if state.is_finished() {
+                        info!("Game finished after {} ticks", state.ticks());
+                        let summaries = state.summaries().into_iter().collect();
+                        let _ = updates.send(Broadcast::Summary(Arc::new(summaries)));
+                        let _ = milestones.send(Milestone::GameOver {
+                            ticks: state.ticks(),
+                            players: standings(&state, &details),
+                        });
+
+                        let result = game_result(&state, &details);
+                        schedule.finish_game(&result);
+                        record_results(result, &results).await;
+                        continue 'games;
+                    }
+                }
             }
         }
     }
@@ -271,23 +1959,162 @@ async fn play_game(
     info!("Game server shutting down");
 }
 
-/// Register the given `player` into the game,
+/// Remove `player` from the game `state` for good, along with their `details` and `next_moves`,
+/// and free up their names in the `registry`.
+///
+/// Everyone is told the player's final score via `updates` if they were taking part in the game.
+async fn remove_player(
+    player: Player,
+    state: &mut game::State,
+    next_moves: &mut game::Moves,
+    details: &mut HashMap<Player, PlayerDetails>,
+    registry: &Registry,
+    updates: &broadcast::Sender<Broadcast>,
+) {
+    details.remove(&player);
+    next_moves.retain(|&(p, _), _| p != player);
+    registry.expire(player).await;
+    if let Some(summary) = state.remove_player(player) {
+        info!("Removed {} with a score of {}", player, summary.score);
+        let _ = updates.send(Broadcast::Removed(player, summary.score));
+    }
+}
+
+/// Add `player` to the game `state` with the provided `details`,
+/// if the `schedule` allows them to take part, the game isn't full,
+/// and they gave the game's password and an invite code if it needs them.
+///
+/// Any invite code the player gave is used up in the arena's `invites`.
+///
+/// Teams assigned by the game's configuration take priority
+/// over the team the player asked to join.
+fn join_game(
+    state: &mut game::State,
+    schedule: &mut Schedule,
+    invites: &mut Invites,
+    player: Player,
+    details: &PlayerDetails,
+) -> Result<()> {
+    if !state.check_password(details.password.as_deref()) {
+        return Err(ErrorCode::WrongPassword.into());
+    }
+    let invite = details.invite.as_deref();
+    if !invites.check(state.invites(), &details.name, invite) {
+        return Err(ErrorCode::InvalidInvite.into());
+    }
+    let returning = state.players().any(|&p| p == player);
+    if let Some(max) = state.max_players() {
+        if !returning && state.players().count() >= max {
+            bail!("Could not add player: game is full ({} player limit)", max);
+        }
+    }
+    schedule.admit(&details.name)?;
+    let team = match state.team_for(&details.name) {
+        Some(team) => Some(team.to_owned()),
+        None => details.team.clone(),
+    };
+    state.add_player(player, details.cosmetics.clone(), team)?;
+    schedule.join(&details.name);
+    invites.redeem(state.invites(), &details.name, invite);
+    Ok(())
+}
+
+/// List each player taking part in the game, with their current score.
+fn roster(
+    state: &game::State,
+    details: &HashMap<Player, PlayerDetails>,
+    active_players: &HashSet<Player>,
+) -> Vec<RosterEntry> {
+    state
+        .scores()
+        .map(|(player, score)| RosterEntry {
+            player,
+            name: details
+                .get(&player)
+                .map(|d| d.name.clone())
+                .unwrap_or_default(),
+            score,
+            team: state.team(player).map(String::from),
+            connected: active_players.contains(&player),
+            latency_ms: None,
+        })
+        .collect()
+}
+
+/// Collect the standings of each player in a game, ordered by rank.
+fn standings(state: &game::State, details: &HashMap<Player, PlayerDetails>) -> Vec<Standing> {
+    let mut standings: Vec<_> = state
+        .summaries()
+        .into_iter()
+        .map(|(player, summary)| Standing {
+            player,
+            name: details
+                .get(&player)
+                .map(|d| d.name.clone())
+                .unwrap_or_default(),
+            score: summary.score,
+            rank: summary.rank,
+            team: summary.team,
+        })
+        .collect();
+    standings.sort_by_key(|standing| (standing.rank, standing.player));
+    standings
+}
+
+/// Collect the final scores of each player in a finished game.
+fn game_result(state: &game::State, details: &HashMap<Player, PlayerDetails>) -> GameResult {
+    let players = state
+        .summaries()
+        .into_iter()
+        .filter_map(|(player, summary)| {
+            let name = details.get(&player)?.name.clone();
+            Some(PlayerResult {
+                name,
+                score: summary.score,
+                pollen_delivered: summary.stats.pollen_delivered,
+            })
+        })
+        .collect();
+    GameResult::new(state.ticks(), players)
+}
+
+/// Record the final scores of a finished game.
+///
+/// Games that nobody played in are not recorded.
+/// Errors are logged but otherwise ignored,
+/// since there is nothing more useful to do with them.
+async fn record_results(result: GameResult, results: &Results) {
+    if result.players.is_empty() {
+        return;
+    }
+
+    if let Err(e) = results.record(result).await {
+        error!("Failed to record game results: {:?}", e);
+    }
+}
+
+/// Register the given `player` into the game with the provided `details`,
 /// using the `events` channel.
 ///
 /// Notifies the player of their registration (or any issues)
 /// via the provided `sink`.
 ///
-/// Returns a receiver to be used to monitor any updates to the game state.
+/// Returns a receiver to be used to monitor any updates to the game state,
+/// and a receiver for messages sent privately to the player.
 ///
 /// The `player` can be an [observer][`Player::observer`];
 /// in that case the player is not added to the game,
 /// but we still subscribe to the receiver.
 async fn register<S, E>(
     player: Player,
+    details: PlayerDetails,
     sink: &mut S,
     addr: SocketAddr,
     events: &mpsc::Sender<GameEvent>,
-) -> Result<broadcast::Receiver<game::Serializer>>
+) -> Result<(
+    broadcast::Receiver<Broadcast>,
+    mpsc::Receiver<protocol::Send>,
+)>
 where
     S: Sink<protocol::Send, Error = E> + Unpin,
     E: std::error::Error + Send + Sync + 'static,
@@ -296,9 +2123,14 @@ where
     let finished_msg = "Game already finished";
 
     let (response, register_rx) = oneshot::channel();
-    if let Err(e) = events.send(GameEvent::AddPlayer { player, response }).await {
+    let event = GameEvent::AddPlayer {
+        player,
+        details,
+        response,
+    };
+    if let Err(e) = events.send(event).await {
         let msg = String::from(finished_msg);
-        sink.send(protocol::Send::Error { msg }).await?;
+        sink.send(protocol::Send::Error { msg, code: None }).await?;
         sink.close().await?;
         return Err(anyhow!(e));
     }
@@ -306,53 +2138,214 @@ where
     match register_rx.await.map_err(|_| anyhow!(finished_msg)) {
         Ok(Ok(GameEventResponse {
             updates,
+            private,
             world,
             tick_rate,
+            profile,
+            snapshot,
         })) => {
             info!("Registered {} as {}", addr, player);
             let msg = protocol::Send::Registration {
-                world,
+                world: world.clone(),
                 player,
                 tick_rate,
+                profile,
             };
             sink.send(msg).await?;
-            Ok(updates)
+            send_chunks(sink, &world).await?;
+            let data = snapshot.view(player);
+            sink.send(protocol::Send::Update { data }).await?;
+            Ok((updates, private))
         }
         Ok(Err(e)) | Err(e) => {
             let msg = e.to_string();
-            sink.send(protocol::Send::Error { msg }).await?;
+            let code = e.downcast_ref::<ErrorCode>().copied();
+            sink.send(protocol::Send::Error { msg, code }).await?;
             sink.close().await?;
             Err(e)
         }
     }
 }
 
+/// Send each chunk of a large world map to a client, following its registration.
+///
+/// Does nothing if the world is small enough to have been sent with the registration.
+async fn send_chunks<S, E>(sink: &mut S, world: &World) -> Result<(), E>
+where
+    S: Sink<protocol::Send, Error = E> + Unpin,
+{
+    if world.is_streamed() {
+        for chunk in world.chunks() {
+            let chunk = chunk.clone();
+            sink.send(protocol::Send::Chunk { chunk }).await?;
+        }
+    }
+    Ok(())
+}
+
 /// Manage a single observation socket.
 ///
-/// We only take a sink, since we don't care about input we get.
-/// The `events` is used to subscribe to the associated game.
+/// Observers receive every update to the game, but cannot control any bees.
+/// They can chat with each other on the spectator channel.
 ///
 /// The `_shutdown` channel is used to determine when the client has closed cleanly.
-pub async fn handle_observer<S, E>(
-    mut sink: S,
+pub async fn handle_observer<S, E>(socket: S, addr: SocketAddr, channels: ClientState) -> Result<()>
+where
+    S: Stream<Item = Result<protocol::Receive, E>> + Sink<protocol::Send, Error = E> + Unpin,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let (mut sink, stream) = socket.split();
+    let _connection = match channels.claim_connection(addr.ip()) {
+        Some(slot) => slot,
+        None => {
+            info!(
+                "Turning away {}: too many connections from one address",
+                addr
+            );
+            let msg = String::from("Too many connections from your address");
+            sink.send(protocol::Send::Error { msg, code: None }).await?;
+            sink.close().await?;
+            return Ok(());
+        }
+    };
+    observer_processing_loop(sink, stream, addr, channels).await
+}
+
+/// Implement the main processing loop for an observer connection.
+///
+/// Each observer is given a default name in spectator chat,
+/// which they can change by sending a `register` message.
+///
+/// Finishes when the game shuts down or the observer disconnects.
+async fn observer_processing_loop<T, R, E>(
+    mut sink: T,
+    mut stream: R,
     addr: SocketAddr,
     channels: ClientState,
 ) -> Result<()>
 where
-    S: Sink<protocol::Send, Error = E> + Unpin,
+    T: Sink<protocol::Send, Error = E> + Unpin,
+    R: Stream<Item = Result<protocol::Receive, E>> + Unpin,
     E: std::error::Error + Send + Sync + 'static,
 {
-    let events = channels.events;
-    let mut updates = register(Player::observer(), &mut sink, addr, &events).await?;
+    let ClientState {
+        events,
+        spectators,
+        observers,
+        max_observers,
+        observer_lag_policy,
+        max_lags,
+        latencies,
+        ..
+    } = channels;
+    let _slot = match ObserverSlot::claim(&observers, max_observers, addr) {
+        Some(slot) => slot,
+        None => {
+            info!("Turning away observer {}: too many observers", addr);
+            let msg = String::from("Too many observers, try again later");
+            sink.send(protocol::Send::Error { msg, code: None }).await?;
+            sink.close().await?;
+            return Ok(());
+        }
+    };
+    debug!("{} observer(s) connected", observers.lock().unwrap().len());
+    let details = PlayerDetails::default();
+    let (mut updates, _) = register(Player::observer(), details, &mut sink, addr, &events).await?;
+
+    let number = spectators.fetch_add(1, Ordering::Relaxed) + 1;
+    let name = format!("Spectator {}", number).into();
+    let mut chat = Chatter::new(Player::observer(), name, ChatChannel::Spectators);
+    let mut lag = LagTracker::new(observer_lag_policy, max_lags);
 
     loop {
-        // Note: we don't really care about lagging for observers
-        // but worth logging a warning anyway, just in case
-        use broadcast::error::RecvError::{Closed, Lagged};
-        match updates.recv().await {
-            Ok(data) => sink.send(protocol::Send::Update { data }).await?,
-            Err(Lagged(skipped)) => warn!("{} lagging, skipped {} update(s)", addr, skipped),
-            Err(Closed) => break,
+        tokio::select! {
+            // Note: we don't really care about lagging for observers
+            // but worth logging a warning anyway, just in case
+            res = updates.recv() => match res {
+                Ok(Broadcast::Update(data)) => sink.send(protocol::Send::Update { data }).await?,
+                Ok(Broadcast::Schedule { tick, deadline }) => {
+                    let next_tick_in = deadline.saturating_duration_since(Instant::now());
+                    sink.send(protocol::Send::TickSchedule { tick, next_tick_in }).await?;
+                },
+                Ok(Broadcast::TickRate(tick_rate)) => {
+                    sink.send(protocol::Send::TickRate { tick_rate }).await?;
+                },
+                Ok(Broadcast::Event(event)) => {
+                    sink.send(protocol::Send::Event { event }).await?;
+                },
+                Ok(Broadcast::Chat { player, name, msg, channel }) => {
+                    sink.send(protocol::Send::Chat { player, name, msg, channel }).await?;
+                },
+                Ok(Broadcast::Transfer { from, to, amount }) => {
+                    sink.send(protocol::Send::Transfer { from, to, amount }).await?;
+                },
+                Ok(Broadcast::Rename(player, name)) => {
+                    sink.send(protocol::Send::Rename { player, name }).await?;
+                },
+                Ok(Broadcast::Removed(player, score)) => {
+                    sink.send(protocol::Send::Removed { player, score }).await?;
+                },
+                Ok(Broadcast::Registration { world, tick_rate, .. }) => {
+                    let msg = protocol::Send::Registration {
+                        world: world.clone(),
+                        player: Player::observer(),
+                        tick_rate,
+                        profile: None,
+                    };
+                    sink.send(msg).await?;
+                    send_chunks(&mut sink, &world).await?;
+                },
+                Ok(Broadcast::Summary(_)) => {},
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    let msg = format!("Lagging behind: skipped {} update(s)", skipped);
+                    match lag.record() {
+                        LagPolicy::Warn => {
+                            warn!("Observer {} {}", addr, msg);
+                            sink.send(protocol::Send::Warning { msg }).await?;
+                        },
+                        LagPolicy::Skip => debug!("Observer {} {}", addr, msg),
+                        LagPolicy::Disconnect => {
+                            info!("Disconnecting observer {}: lagged too many times", addr);
+                            let msg = String::from("Disconnected for lagging behind too often");
+                            sink.send(protocol::Send::Error { msg, code: None }).await?;
+                            sink.close().await?;
+                            return Ok(());
+                        },
+                    }
+                },
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            packet = stream.next() => match packet {
+                Some(Ok(protocol::Receive::Chat { msg })) => {
+                    chat.send(msg, &mut sink, &events).await?;
+                },
+                Some(Ok(protocol::Receive::Query { what })) => {
+                    query(what, &mut sink, &events, &latencies).await?;
+                },
+                Some(Ok(protocol::Receive::Register { name, .. })) if !name.is_empty() => {
+                    match check_name_length(&name) {
+                        Ok(()) => {
+                            // keep the prefix, so that observers can't pass as players
+                            let name = format!("Spectator {}", name);
+                            debug!("{} renamed from {} to {}", addr, chat.name, name);
+                            chat.name = name.into();
+                        },
+                        Err(e) => {
+                            let msg = e.to_string();
+                            sink.send(protocol::Send::Warning { msg }).await?;
+                        },
+                    }
+                },
+                Some(packet) => {
+                    debug!("Bad input from observer {}: {:?}", addr, packet);
+                    let msg = String::from("Bad input");
+                    sink.send(protocol::Send::Warning { msg }).await?;
+                },
+                None => {
+                    info!("Observer disconnected ({})", addr);
+                    return Ok(());
+                },
+            },
         }
     }
 
@@ -376,28 +2369,55 @@ where
 {
     let mut shutdown = channels.get_shutdown_notifier();
     let (mut sink, mut stream) = socket.split();
+    let _connection = match channels.claim_connection(addr.ip()) {
+        Some(slot) => slot,
+        None => {
+            info!(
+                "Turning away {}: too many connections from one address",
+                addr
+            );
+            let msg = String::from("Too many connections from your address");
+            sink.send(protocol::Send::Error { msg, code: None }).await?;
+            sink.close().await?;
+            return Ok(());
+        }
+    };
 
     let packet = tokio::select! {
         packet = stream.next() => packet,
         _ = shutdown.recv() => {
             let msg = String::from("Game already finished");
-            sink.send(protocol::Send::Error { msg }).await?;
+            sink.send(protocol::Send::Error { msg, code: None }).await?;
             sink.close().await?;
             return Ok(());
         },
     };
 
-    let name = match packet {
-        Some(Ok(protocol::Receive::Register { name })) => name,
+    let mut details = match packet {
+        Some(Ok(protocol::Receive::Register {
+            name,
+            team,
+            cosmetics,
+            password,
+            invite,
+            token,
+        })) => PlayerDetails {
+            name,
+            team: team.filter(|t| !t.is_empty()),
+            cosmetics,
+            password,
+            invite,
+            token,
+        },
         Some(Ok(other)) => {
             let msg = String::from("Expected registration");
-            sink.send(protocol::Send::Error { msg }).await?;
+            sink.send(protocol::Send::Error { msg, code: None }).await?;
             sink.close().await?;
             return Err(anyhow!(format!("Expected registration, got {:?}", other)));
         }
         Some(Err(e)) => {
             let msg = e.to_string();
-            sink.send(protocol::Send::Error { msg }).await?;
+            sink.send(protocol::Send::Error { msg, code: None }).await?;
             sink.close().await?;
             return Err(anyhow!(e));
         }
@@ -406,25 +2426,56 @@ where
         }
     };
 
-    if name.is_empty() {
+    if details.name.is_empty() {
         warn!("No name provided, downgrading {} to observer", addr);
-        return handle_observer(sink, addr, channels).await;
+        return observer_processing_loop(sink, stream, addr, channels).await;
+    }
+    if let Err(e) = check_name_length(&details.name) {
+        let msg = e.to_string();
+        sink.send(protocol::Send::Error { msg, code: None }).await?;
+        sink.close().await?;
+        return Err(e.context(format!("{} registered with too long a name", addr)));
     }
 
-    let ClientState {
-        events, players, ..
-    } = channels;
+    if let Some(tokens) = &channels.tokens {
+        match details.token.as_deref().and_then(|t| tokens.name(t)) {
+            Some(name) => details.name = name.to_owned(),
+            None => {
+                let code = ErrorCode::InvalidToken;
+                let msg = code.to_string();
+                sink.send(protocol::Send::Error {
+                    msg,
+                    code: Some(code),
+                })
+                .await?;
+                sink.close().await?;
+                bail!("{} gave an invalid token for {:?}", addr, details.name);
+            }
+        }
+    }
+
+    let player = channels.players.claim(&details.name).await;
+
+    let invalid_cosmetics = details.cosmetics.check().err();
+    if invalid_cosmetics.is_some() {
+        details.cosmetics = game::Cosmetics::default();
+    }
 
-    let player = *players
-        .lock()
-        .unwrap()
-        .entry(name)
-        .or_insert_with(Player::new);
+    let chat = Chatter::new(player, details.name.as_str().into(), ChatChannel::Players);
+    let (updates, private) = register(player, details, &mut sink, addr, &channels.events).await?;
 
-    let updates = register(player, &mut sink, addr, &events).await?;
+    if let Some(e) = invalid_cosmetics {
+        debug!("Ignoring cosmetics from {}: {}", player, e);
+        let msg = e.to_string();
+        sink.send(protocol::Send::Warning { msg }).await?;
+    }
 
     // split into separate function so we can catch errors and send disconnection notices
-    match player_processing_loop(player, &mut sink, stream, updates, &events).await {
+    let processing =
+        player_processing_loop(player, &mut sink, stream, updates, private, &channels, chat);
+    let result = processing.await;
+    channels.latencies.forget(player);
+    match result {
         Ok(_) => {
             sink.send(protocol::Send::Done).await?;
             sink.close().await?;
@@ -433,7 +2484,8 @@ where
             Ok(())
         }
         Err(e) => {
-            if events.send(GameEvent::Disconnect { player }).await.is_err() {
+            let event = GameEvent::Disconnect { player };
+            if channels.events.send(event).await.is_err() {
                 debug!("{} failed to send disconnection notice", player);
             }
             Err(e)
@@ -443,37 +2495,133 @@ where
 
 /// Implement the main processing loop for a player connection.
 ///
+/// Passes on both `updates` broadcast to everyone and `private` messages for this player alone.
+/// Chat messages from observers are only passed on
+/// if the server is configured to make spectator chat public.
+///
 /// Only finishes if either an error occurs or if the game shuts down.
 async fn player_processing_loop<T, R, E>(
     player: Player,
     sink: &mut T,
     mut stream: R,
-    mut updates: broadcast::Receiver<game::Serializer>,
-    events: &mpsc::Sender<GameEvent>,
+    mut updates: broadcast::Receiver<Broadcast>,
+    mut private: mpsc::Receiver<protocol::Send>,
+    channels: &ClientState,
+    mut chat: Chatter,
 ) -> Result<()>
 where
     T: Sink<protocol::Send, Error = E> + Unpin,
     R: Stream<Item = Result<protocol::Receive, E>> + Unpin,
     E: std::error::Error + Send + Sync + 'static,
 {
+    let mut viewport = None;
+    let mut limiter = MessageLimiter::new(channels.max_moves_per_tick);
+    let mut lag = LagTracker::new(channels.player_lag_policy, channels.max_lags);
+    let mut pinger = Pinger::default();
+    let mut pings = tokio::time::interval(PING_INTERVAL);
     loop {
         tokio::select! {
             res = updates.recv() => match res {
-                // TODO: filter to only things relevant for this player?
-                Ok(data) => {
+                Ok(Broadcast::Schedule { tick, deadline }) => {
+                    let next_tick_in = deadline.saturating_duration_since(Instant::now());
+                    sink.send(protocol::Send::TickSchedule { tick, next_tick_in }).await?;
+                },
+                Ok(Broadcast::TickRate(tick_rate)) => {
+                    sink.send(protocol::Send::TickRate { tick_rate }).await?;
+                },
+                Ok(Broadcast::Update(data)) => {
+                    limiter.tick();
+                    let data = data.view(player).within(viewport);
                     sink.send(protocol::Send::Update{ data }).await?;
                 },
+                Ok(Broadcast::Event(event)) => {
+                    sink.send(protocol::Send::Event { event }).await?;
+                },
+                Ok(Broadcast::Chat { channel: ChatChannel::Spectators, .. })
+                    if !channels.public_spectator_chat => {},
+                Ok(Broadcast::Chat { player, name, msg, channel }) => {
+                    sink.send(protocol::Send::Chat { player, name, msg, channel }).await?;
+                },
+                Ok(Broadcast::Transfer { from, to, amount }) => {
+                    sink.send(protocol::Send::Transfer { from, to, amount }).await?;
+                },
+                Ok(Broadcast::Rename(player, name)) => {
+                    sink.send(protocol::Send::Rename { player, name }).await?;
+                },
+                Ok(Broadcast::Removed(player, score)) => {
+                    sink.send(protocol::Send::Removed { player, score }).await?;
+                },
+                Ok(Broadcast::Registration { world, tick_rate, profiles }) => {
+                    if let Some(profile) = profiles.get(&player).cloned() {
+                        let msg = protocol::Send::Registration {
+                            world: world.clone(),
+                            player,
+                            tick_rate,
+                            profile,
+                        };
+                        sink.send(msg).await?;
+                        send_chunks(sink, &world).await?;
+                    } else {
+                        let msg = String::from("Not taking part in this game");
+                        sink.send(protocol::Send::Warning { msg }).await?;
+                    }
+                },
+                Ok(Broadcast::Summary(summaries)) => {
+                    if let Some(summary) = summaries.get(&player).cloned() {
+                        sink.send(protocol::Send::Summary { summary }).await?;
+                    }
+                },
                 Err(broadcast::error::RecvError::Lagged(skipped)) => {
                     let msg = format!("Lagging behind: skipped {} update(s)", skipped);
-                    warn!("{} {}", player, msg);
-                    sink.send(protocol::Send::Warning{ msg }).await?;
+                    match lag.record() {
+                        LagPolicy::Warn => {
+                            warn!("{} {}", player, msg);
+                            sink.send(protocol::Send::Warning { msg }).await?;
+                        },
+                        LagPolicy::Skip => debug!("{} {}", player, msg),
+                        LagPolicy::Disconnect => {
+                            let msg = String::from("Disconnected for lagging behind too often");
+                            sink.send(protocol::Send::Error { msg, code: None }).await?;
+                            sink.close().await?;
+                            bail!("{} lagged behind too many times", player);
+                        },
+                    }
                 },
                 Err(broadcast::error::RecvError::Closed) => {
                     return Ok(());
                 },
             },
+            msg = private.recv() => match msg {
+                Some(msg) => sink.send(msg).await?,
+                None => return Ok(()),
+            },
+            _ = pings.tick() => sink.send(pinger.ping()).await?,
             packet = stream.next() => match packet {
-                Some(packet) => process_packet(player, packet, sink, events).await?,
+                // pongs answer the server's own pings, so don't count towards the limit
+                Some(Ok(protocol::Receive::Pong { id })) => {
+                    if let Some(round_trip) = pinger.pong(id) {
+                        trace!("{} has a latency of {:?}", player, round_trip);
+                        channels.latencies.record(player, chat.name.clone(), round_trip);
+                    }
+                },
+                Some(packet) => match limiter.check() {
+                    Verdict::Accept => {
+                        process_packet(player, packet, sink, channels, &mut chat, &mut viewport)
+                            .await?
+                    },
+                    Verdict::Warn => {
+                        let msg = String::from("Sending messages too quickly, ignoring extra messages");
+                        debug!("{}: {}", player, msg);
+                        sink.send(protocol::Send::Warning { msg }).await?;
+                    },
+                    Verdict::Ignore => {},
+                    Verdict::Disconnect => {
+                        let msg = String::from("Disconnected for sending messages too quickly");
+                        sink.send(protocol::Send::Error { msg, code: None }).await?;
+                        sink.close().await?;
+                        bail!("{} kept sending messages too quickly", player);
+                    },
+                },
                 None => return Err(anyhow!("Far side closed when processing packets.")),
             },
         }
@@ -488,20 +2636,151 @@ async fn process_packet<S, E>(
     player: Player,
     packet: Result<protocol::Receive, E>,
     sink: &mut S,
-    events: &mpsc::Sender<GameEvent>,
+    channels: &ClientState,
+    chat: &mut Chatter,
+    viewport: &mut Option<Rect>,
 ) -> Result<(), E>
 where
     S: Sink<protocol::Send, Error = E> + Unpin,
     E: std::error::Error,
 {
+    let events = &channels.events;
     match packet {
-        Ok(protocol::Receive::Moves { moves }) => {
-            trace!("Parsed {}'s message: {:?}", player, moves);
-            let result = events.send(GameEvent::Move { player, moves }).await;
-            if result.is_err() {
+        Ok(protocol::Receive::Moves {
+            moves,
+            groups,
+            tick,
+        }) => {
+            trace!("Parsed {}'s message: {:?} {:?}", player, moves, groups);
+            // JSON messages were already counted while parsing, but gRPC messages arrive whole
+            let count = moves.len() + groups.iter().map(|g| g.bees.len()).sum::<usize>();
+            if let Some(max) = channels.max_moves_per_message.filter(|&max| count > max) {
+                debug!("{} sent {} moves in one message", player, count);
+                let msg = format!("Too many moves in one message (limit {})", max);
+                return sink.send(protocol::Send::Warning { msg }).await;
+            }
+            // expand groups first so that individual moves take priority
+            let groups = groups.iter().flat_map(game::GroupMove::expand);
+            let moves = groups.chain(moves).collect();
+            let (response, ack_rx) = oneshot::channel();
+            let event = GameEvent::Move {
+                player,
+                moves,
+                tick,
+                response,
+            };
+            // rejected moves aren't acknowledged, the game warns the player about them instead
+            if events.send(event).await.is_err() {
                 debug!("{} failed to send move event", player);
+            } else if let Ok(Some((tick, accepted))) = ack_rx.await {
+                let received = count;
+                sink.send(protocol::Send::Ack {
+                    tick,
+                    accepted,
+                    received,
+                })
+                .await?;
+            }
+        }
+        Ok(protocol::Receive::Chat { msg }) => chat.send(msg, sink, events).await?,
+        Ok(protocol::Receive::Query { what }) => {
+            query(what, sink, events, &channels.latencies).await?
+        }
+        Ok(protocol::Receive::Transfer { to, amount }) => {
+            let (response, transfer_rx) = oneshot::channel();
+            let event = GameEvent::Transfer {
+                from: player,
+                to,
+                amount,
+                response,
+            };
+            if events.send(event).await.is_err() {
+                debug!("{} failed to send transfer event", player);
+            } else if let Ok(Err(e)) = transfer_rx.await {
+                debug!("{} failed to transfer pollen to {}: {}", player, to, e);
+                let msg = e.to_string();
+                sink.send(protocol::Send::Warning { msg }).await?;
+            }
+        }
+        Ok(protocol::Receive::BuildHive { bee }) => {
+            let (response, build_rx) = oneshot::channel();
+            let event = GameEvent::BuildHive {
+                player,
+                bee,
+                response,
+            };
+            if events.send(event).await.is_err() {
+                debug!("{} failed to send build event", player);
+            } else if let Ok(Err(e)) = build_rx.await {
+                debug!("{} failed to build a hive: {}", player, e);
+                let msg = e.to_string();
+                sink.send(protocol::Send::Warning { msg }).await?;
+            }
+        }
+        Ok(protocol::Receive::RelocateHive { hive, to }) => {
+            let (response, relocate_rx) = oneshot::channel();
+            let event = GameEvent::RelocateHive {
+                player,
+                from: hive,
+                to,
+                response,
+            };
+            if events.send(event).await.is_err() {
+                debug!("{} failed to send relocate event", player);
+            } else if let Ok(Err(e)) = relocate_rx.await {
+                debug!("{} failed to relocate a hive: {}", player, e);
+                let msg = e.to_string();
+                sink.send(protocol::Send::Warning { msg }).await?;
+            }
+        }
+        Ok(protocol::Receive::UpgradeHive { hive, upgrade }) => {
+            let (response, upgrade_rx) = oneshot::channel();
+            let event = GameEvent::UpgradeHive {
+                player,
+                hive,
+                upgrade,
+                response,
+            };
+            if events.send(event).await.is_err() {
+                debug!("{} failed to send upgrade event", player);
+            } else if let Ok(Err(e)) = upgrade_rx.await {
+                debug!("{} failed to upgrade a hive: {}", player, e);
+                let msg = e.to_string();
+                sink.send(protocol::Send::Warning { msg }).await?;
+            }
+        }
+        Ok(protocol::Receive::AssignRole { bee, role }) => {
+            let (response, role_rx) = oneshot::channel();
+            let event = GameEvent::AssignRole {
+                player,
+                bee,
+                role,
+                response,
+            };
+            if events.send(event).await.is_err() {
+                debug!("{} failed to send role event", player);
+            } else if let Ok(Err(e)) = role_rx.await {
+                debug!("{} failed to change a bee's role: {}", player, e);
+                let msg = e.to_string();
+                sink.send(protocol::Send::Warning { msg }).await?;
+            }
+        }
+        Ok(protocol::Receive::SetViewport { rect }) => {
+            trace!("{} set viewport to {:?}", player, rect);
+            *viewport = rect;
+        }
+        Ok(protocol::Receive::Rename { name }) => {
+            match rename(player, &chat.name, name.clone(), channels).await {
+                Ok(()) => chat.name = name.into(),
+                Err(e) => {
+                    debug!("{} failed to rename to {:?}: {}", player, name, e);
+                    let msg = e.to_string();
+                    sink.send(protocol::Send::Warning { msg }).await?;
+                }
             }
         }
+        // already answered by the processing loop, which keeps track of pings
+        Ok(protocol::Receive::Pong { .. }) => {}
         Ok(protocol::Receive::Register { .. }) => {
             debug!("Bad input from {}: registration", player);
             let msg = String::from("Bad input");
@@ -509,10 +2788,86 @@ where
         }
         Err(e) => {
             debug!("Bad input from {}: {}", player, e);
-            let msg = String::from("Bad input");
+            let msg = format!("Bad input: {}", e);
             sink.send(protocol::Send::Warning { msg }).await?;
         }
     }
 
     Ok(())
 }
+
+/// Change the name that `player` is registered under from `old` to `new`.
+///
+/// The new name is reserved before notifying the game,
+/// so that no other player can take it in the meantime.
+///
+/// # Errors
+///
+/// Fails if the new name is empty, too long or already taken,
+/// or if the game doesn't allow players to change their name.
+/// Players can't change their name if the server uses API tokens,
+/// since they must play under the name their token belongs to.
+async fn rename(player: Player, old: &str, new: String, channels: &ClientState) -> Result<()> {
+    if channels.tokens.is_some() {
+        bail!("Cannot change name when playing with API tokens");
+    }
+    if new.is_empty() {
+        bail!("Name cannot be empty");
+    }
+    check_name_length(&new)?;
+    if new == old {
+        return Ok(());
+    }
+
+    if !channels.players.reserve(&new, player).await {
+        bail!("Name {:?} is already taken", new);
+    }
+
+    let (response, rename_rx) = oneshot::channel();
+    let event = GameEvent::Rename {
+        player,
+        name: new.clone(),
+        response,
+    };
+    let result = match channels.events.send(event).await {
+        Ok(()) => rename_rx.await.unwrap_or_else(|e| Err(anyhow!(e))),
+        Err(e) => Err(anyhow!(e.to_string())),
+    };
+
+    // release whichever name is no longer in use
+    let unused = if result.is_ok() { old } else { new.as_str() };
+    channels.players.release(unused).await;
+    result
+}
+
+/// Answer a query from a client about the game, sending the answer via the `sink`.
+///
+/// Ignores any errors sending the query to the game,
+/// since this means that the game should be entering shutdown anyway.
+async fn query<S, E>(
+    what: protocol::Query,
+    sink: &mut S,
+    events: &mpsc::Sender<GameEvent>,
+    latencies: &Latencies,
+) -> Result<(), E>
+where
+    S: Sink<protocol::Send, Error = E> + Unpin,
+{
+    match what {
+        protocol::Query::Players => {
+            let (response, roster_rx) = oneshot::channel();
+            if events.send(GameEvent::Roster { response }).await.is_err() {
+                debug!("Failed to send roster query");
+                return Ok(());
+            }
+            if let Ok(mut players) = roster_rx.await {
+                for entry in &mut players {
+                    entry.latency_ms = latencies.get(entry.player);
+                }
+                sink.send(protocol::Send::Roster { players }).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
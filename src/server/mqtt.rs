@@ -0,0 +1,216 @@
+//! Publishing the live scores to an MQTT broker, for scoreboards and other displays.
+//!
+//! Every topic starts with a prefix (`beeeees` by default),
+//! followed by the name of the arena if the server runs several.
+//! Under that, the server publishes:
+//! - `game`: the number of the game being played, counting from 1, whenever a game starts;
+//!
+//! and under `<game>/`, the number of each game:
+//! - `tick`: the number of ticks played so far, after every tick;
+//! - `scores`: the current standings as a JSON array, whenever they change;
+//! - `players/<id>/score`: each player's score, whenever it changes.
+//!
+//! Every message is retained, so displays that subscribe later see the latest values straight away.
+//! Once a game finishes its topics are cleared, so retained values don't pile up on the broker.
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use futures::Future;
+use log::debug;
+use rumqttc::{AsyncClient, MqttOptions, QoS};
+use tokio::{sync::watch, time};
+
+use crate::game::Player;
+
+use super::{relay::Outlet, ClientState, Scoreboard};
+
+/// How often to check the connection to the broker is still alive.
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// How long to wait before reconnecting after losing the connection to the broker.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// The number of messages that can be queued to be published.
+///
+/// Messages beyond this are dropped, so a slow broker doesn't hold up the game.
+const QUEUE_CAPACITY: usize = 256;
+
+/// Publish the live scores sent over `client_info` to the broker at `host` and `port`,
+/// under topics starting with `prefix`, until it receives a shutdown signal.
+///
+/// Only changes to the scores after this is called are published.
+/// Losing the connection to the broker is logged, and it is reconnected to every so often.
+pub fn publish(
+    host: String,
+    port: u16,
+    prefix: String,
+    client_info: ClientState,
+) -> impl Future<Output = ()> {
+    let scoreboard = client_info.scoreboard();
+    run(host, port, prefix, scoreboard, client_info)
+}
+
+/// Publish the live scores from `scoreboard`, as described in [`publish`].
+async fn run(
+    host: String,
+    port: u16,
+    prefix: String,
+    mut scoreboard: watch::Receiver<Scoreboard>,
+    client_info: ClientState,
+) {
+    let prefix = match &client_info.name {
+        Some(arena) => format!("{}/{}", prefix, topic_level(arena)),
+        None => prefix,
+    };
+    let client_id = match &client_info.name {
+        Some(arena) => format!("beeeees-{}-{}", std::process::id(), topic_level(arena)),
+        None => format!("beeeees-{}", std::process::id()),
+    };
+    let mut options = MqttOptions::new(client_id, &host, port);
+    options.set_keep_alive(KEEP_ALIVE);
+    let (client, mut connection) = AsyncClient::new(options, QUEUE_CAPACITY);
+
+    let mut shutdown = client_info.get_shutdown_notifier();
+    let mut published = Published::default();
+    let mut outlet = Outlet::new(format!("MQTT broker {}:{}", host, port));
+
+    // unlike other outlets the connection has to be polled all the time, not only when sending
+    loop {
+        tokio::select! {
+            event = connection.poll() => match event {
+                Ok(_) => outlet.record(Ok(())),
+                Err(e) => {
+                    outlet.record(Err(e.into()));
+                    // anything published while disconnected is lost, so send it all again after
+                    published.resend();
+                    time::sleep(RECONNECT_DELAY).await;
+                }
+            },
+            changed = scoreboard.changed() => match changed {
+                Ok(()) => {
+                    let latest = scoreboard.borrow_and_update().clone();
+                    published.update(&client, &prefix, latest);
+                }
+                Err(_) => break,
+            },
+            _ = shutdown.recv() => break,
+        }
+    }
+
+    debug!("Stopped publishing to MQTT broker {}:{}", host, port);
+}
+
+/// What has already been published to the broker,
+/// so that only values that change are published again.
+#[derive(Debug, Default)]
+struct Published {
+    /// The game whose topics have been published, if they haven't been cleared since.
+    game: Option<u64>,
+    /// The players whose score topics have been published for the game.
+    players: HashSet<Player>,
+    /// The most recently published scoreboard.
+    scoreboard: Option<Scoreboard>,
+    /// The most recently published score of each player.
+    scores: HashMap<Player, i32>,
+}
+
+impl Published {
+    /// Publish anything that has changed in the `latest` scoreboard using `client`,
+    /// under topics starting with `prefix`.
+    ///
+    /// The topics of any previous game are cleared first,
+    /// as are the game's own topics once it has finished.
+    /// Messages that can't be queued are dropped, and published again when they next change.
+    fn update(&mut self, client: &AsyncClient, prefix: &str, latest: Scoreboard) {
+        if self.game.is_some_and(|game| game != latest.game) {
+            self.clear(client, prefix);
+        }
+        if latest.finished {
+            if self.game == Some(latest.game) {
+                self.clear(client, prefix);
+            }
+            return;
+        }
+
+        if self.scoreboard.is_none() {
+            let game = latest.game.to_string().into_bytes();
+            publish_retained(client, format!("{}/game", prefix), QoS::AtLeastOnce, game);
+        }
+        self.game = Some(latest.game);
+        let prefix = format!("{}/{}", prefix, latest.game);
+        publish_retained(
+            client,
+            format!("{}/tick", prefix),
+            QoS::AtMostOnce,
+            latest.tick.to_string().into_bytes(),
+        );
+
+        if self.scoreboard.as_ref().map(|s| &s.players) != Some(&latest.players) {
+            let scores = serde_json::to_vec(&latest.players).expect("standings should serialize");
+            if !publish_retained(
+                client,
+                format!("{}/scores", prefix),
+                QoS::AtLeastOnce,
+                scores,
+            ) {
+                return;
+            }
+        }
+        for standing in &latest.players {
+            let player = standing.player;
+            if self.scores.get(&player) == Some(&standing.score) {
+                continue;
+            }
+            let topic = format!("{}/players/{}/score", prefix, player.id());
+            let score = standing.score.to_string().into_bytes();
+            self.players.insert(player);
+            if publish_retained(client, topic, QoS::AtLeastOnce, score) {
+                self.scores.insert(player, standing.score);
+            }
+        }
+        self.scoreboard = Some(latest);
+    }
+
+    /// Publish everything again on the next update, such as after reconnecting to the broker.
+    fn resend(&mut self) {
+        self.scoreboard = None;
+        self.scores.clear();
+    }
+
+    /// Clear the retained messages of the game's topics under `prefix`, if any were published,
+    /// and start afresh.
+    fn clear(&mut self, client: &AsyncClient, prefix: &str) {
+        if let Some(game) = self.game {
+            let prefix = format!("{}/{}", prefix, game);
+            let topics = ["tick", "scores"].iter().map(|topic| topic.to_string());
+            let scores =
+                (self.players.iter()).map(|player| format!("players/{}/score", player.id()));
+            for topic in topics.chain(scores) {
+                let topic = format!("{}/{}", prefix, topic);
+                publish_retained(client, topic, QoS::AtLeastOnce, Vec::new());
+            }
+        }
+        *self = Published::default();
+    }
+}
+
+/// Publish a retained message with the `payload` to the `topic` using `client`,
+/// returning whether it could be queued.
+///
+/// An empty payload clears the topic's retained message.
+fn publish_retained(client: &AsyncClient, topic: String, qos: QoS, payload: Vec<u8>) -> bool {
+    let result = client.try_publish(&topic, qos, true, payload);
+    if let Err(e) = &result {
+        debug!("Could not publish {}: {}", topic, e);
+    }
+    result.is_ok()
+}
+
+/// Make `name` safe to use as a single level of an MQTT topic,
+/// by replacing separators and wildcards with underscores.
+fn topic_level(name: &str) -> String {
+    name.replace(['/', '+', '#'], "_")
+}
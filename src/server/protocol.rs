@@ -2,13 +2,19 @@
 
 use std::{sync::Arc, time::Duration};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::game::{
     self,
-    world::{Direction, World},
+    achievements::Achievement,
+    events::Event,
+    world::{Chunk, Position, Rect, World},
+    GroupMove, Move, Role, Upgrade,
 };
 
+use super::results::Profile;
+
 /// Serialize a duration as a single [`f64`] representing the number of seconds.
 fn serialize_duration_as_f64<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
 where
@@ -17,21 +23,51 @@ where
     duration.as_secs_f64().serialize(serializer)
 }
 
+/// Serialize a world, leaving out the tiles if the world is [streamed][`World::is_streamed`].
+fn serialize_world<S>(world: &Arc<World>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    if world.is_streamed() {
+        world.without_map().serialize(serializer)
+    } else {
+        world.serialize(serializer)
+    }
+}
+
 /// Messages sent from the server.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 #[serde(tag = "type", rename_all = "snake_case")]
+#[schemars(rename = "ServerMessage")]
 pub enum Send {
     /// Sent on initial handshake,
     /// and provides any initial/immutable information
     /// about the game state.
     Registration {
         /// The world map.
+        ///
+        /// Large maps are sent without their tiles,
+        /// which instead follow in [`Send::Chunk`] messages.
+        #[serde(serialize_with = "serialize_world")]
         world: Arc<World>,
         /// A unique integer denoting the client's identifier.
         player: game::Player,
         /// The expected tick rate of the server.
         #[serde(serialize_with = "serialize_duration_as_f64")]
+        #[schemars(with = "f64")]
         tick_rate: Duration,
+        /// The player's cumulative statistics from previously finished games.
+        ///
+        /// `None` for observers and for players who haven't finished a game before.
+        profile: Option<Profile>,
+    },
+    /// Part of a large world map, sent following a [`Send::Registration`].
+    ///
+    /// Every chunk of the map is sent before the first update.
+    Chunk {
+        /// The position and tiles of the chunk.
+        #[serde(flatten)]
+        chunk: Chunk,
     },
     /// Sent regularly, providing an updated view of the current game state.
     ///
@@ -41,6 +77,111 @@ pub enum Send {
         /// The mutable game data.
         data: game::Serializer,
     },
+    /// Sent when the server changes the tick rate of the current game,
+    /// because ticks were taking too long to process.
+    TickRate {
+        /// The new expected tick rate of the server.
+        #[serde(serialize_with = "serialize_duration_as_f64")]
+        #[schemars(with = "f64")]
+        tick_rate: Duration,
+    },
+    /// Sent just before each [`Send::Update`],
+    /// saying when the server will next advance the game.
+    TickSchedule {
+        /// The number of the next tick, which moves sent now will apply to.
+        tick: u64,
+        /// How long until the next tick is processed.
+        #[serde(serialize_with = "serialize_duration_as_f64")]
+        #[schemars(with = "f64")]
+        next_tick_in: Duration,
+    },
+    /// A chat message sent by another client.
+    Chat {
+        /// The player that sent the message.
+        ///
+        /// Always the observer ID for messages on the spectator channel.
+        player: game::Player,
+        /// The name of the client that sent the message.
+        name: Arc<str>,
+        /// The contents of the message.
+        msg: Arc<str>,
+        /// Which channel the message was sent on.
+        channel: ChatChannel,
+    },
+    /// Sent in response to a [`Query::Players`],
+    /// listing every player taking part in the current game.
+    Roster {
+        /// Information about each player.
+        players: Vec<RosterEntry>,
+    },
+    /// Sent to everyone when a player donates pollen to another player.
+    Transfer {
+        /// The player that donated the pollen.
+        from: game::Player,
+        /// The player that received the pollen.
+        to: game::Player,
+        /// The amount of pollen transferred.
+        amount: i32,
+    },
+    /// Sent to everyone when a player changes their name.
+    Rename {
+        /// The player that changed their name.
+        player: game::Player,
+        /// The player's new name.
+        name: Arc<str>,
+    },
+    /// Sent to everyone when a player is removed from the game
+    /// after staying disconnected for too long.
+    Removed {
+        /// The player that was removed.
+        player: game::Player,
+        /// The player's score when they were removed.
+        score: i32,
+    },
+    /// Sent to a player when they unlock an achievement.
+    Achievement {
+        /// The player that unlocked the achievement.
+        player: game::Player,
+        /// The achievement that was unlocked.
+        achievement: Achievement,
+    },
+    /// Sent to everyone when a random event happens in the world.
+    Event {
+        /// What happened.
+        #[serde(flatten)]
+        event: Event,
+    },
+    /// Sent to a player in reply to each [`Receive::Moves`].
+    Ack {
+        /// The tick that the moves will apply to.
+        tick: u64,
+        /// The number of moves that were accepted.
+        accepted: usize,
+        /// The number of moves in the message, including those in groups.
+        received: usize,
+    },
+    /// Sent to a player when some of their bees couldn't move because the tile was full.
+    Blocked {
+        /// The bees that stayed where they were.
+        bees: Arc<[game::BeeID]>,
+    },
+    /// Sent to a player after each tick in which their score changed.
+    ///
+    /// Scores aren't part of each [`Send::Update`],
+    /// so this is how players keep track of their own.
+    Score {
+        /// The player's total score over all of their hives.
+        score: i32,
+    },
+    /// Sent when the game finishes,
+    /// summarising the player's performance over the game.
+    ///
+    /// Not sent to observers.
+    Summary {
+        /// The player's final score and statistics.
+        #[serde(flatten)]
+        summary: game::Summary,
+    },
     /// Sent when an ignorable issue has occurred.
     ///
     /// The client's connection will still be maintained.
@@ -54,6 +195,16 @@ pub enum Send {
     Error {
         /// A human-readable description of the error.
         msg: String,
+        /// What went wrong, for errors that clients may want to handle specially.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        code: Option<ErrorCode>,
+    },
+    /// Sent to players every so often to measure their latency.
+    ///
+    /// Players should reply straight away with a [`Receive::Pong`] with the same `id`.
+    Ping {
+        /// Identifies the ping, so that the reply can be matched up with it.
+        id: u64,
     },
     /// Sent on game shutdown.
     ///
@@ -61,19 +212,110 @@ pub enum Send {
     Done,
 }
 
+/// Machine-readable reasons for a [`Send::Error`].
+///
+/// Can be returned as an error during registration to send the code along with the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The game requires a password, and the player gave the wrong one.
+    WrongPassword,
+    /// The game is invite-only, and the player's invite code was missing, wrong, or used up.
+    InvalidInvite,
+    /// The server requires API tokens, and the player's token was missing or unknown.
+    InvalidToken,
+    /// An admin kicked the player out of the game.
+    Kicked,
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorCode::WrongPassword => write!(f, "Wrong password"),
+            ErrorCode::InvalidInvite => write!(f, "Invalid invite code"),
+            ErrorCode::InvalidToken => write!(f, "Invalid API token"),
+            ErrorCode::Kicked => write!(f, "Kicked from the game by an admin"),
+        }
+    }
+}
+
+impl std::error::Error for ErrorCode {}
+
+/// The audiences that chat messages can be sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatChannel {
+    /// Messages from players, seen by everyone.
+    Players,
+    /// Messages from observers.
+    ///
+    /// Only seen by other observers unless the server is configured otherwise.
+    Spectators,
+}
+
+/// Information about a single player in the current game.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct RosterEntry {
+    /// The player's ID.
+    pub player: game::Player,
+    /// The name the player registered with.
+    pub name: String,
+    /// The player's current score.
+    pub score: i32,
+    /// The team the player is on, if any.
+    pub team: Option<String>,
+    /// Whether the player is currently connected.
+    pub connected: bool,
+    /// The player's most recently measured round-trip latency in milliseconds, if known.
+    pub latency_ms: Option<u64>,
+}
+
+/// Information that clients can request from the server.
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Query {
+    /// The players taking part in the current game, answered with [`Send::Roster`].
+    Players,
+}
+
 /// Messages received from the client.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(tag = "type", rename_all = "snake_case")]
+#[schemars(rename = "ClientMessage")]
 pub enum Receive {
     /// Sent on initial handshake,
     /// registers the player with the server
     /// and provides any appropriate metadata.
+    ///
+    /// Observers may also send this at any time to set their name in spectator chat,
+    /// which is always shown after a `Spectator` prefix.
     Register {
-        /// The player's name.
+        /// The player's name, of at most 32 bytes.
         ///
         /// Should be unique, and is used to allow reconnecting to an existing session
         /// if the player had disconnected earlier for whatever reason.
         name: String,
+        /// The team the player would like to join, if any.
+        ///
+        /// Ignored if the game's configuration assigns the player to a team.
+        #[serde(default)]
+        team: Option<String>,
+        /// How the player's hive and bees should be displayed.
+        ///
+        /// Invalid cosmetics are ignored with a warning.
+        #[serde(flatten)]
+        cosmetics: game::Cosmetics,
+        /// The password needed to join the game, if it has one.
+        #[serde(default)]
+        password: Option<String>,
+        /// The invite code needed to join the game, if it is invite-only.
+        #[serde(default)]
+        invite: Option<String>,
+        /// The player's API token, if the server requires one.
+        ///
+        /// The player always plays under the name the token belongs to.
+        #[serde(default)]
+        token: Option<String>,
     },
     /// A set of bee movements to be made on the next tick.
     ///
@@ -85,17 +327,180 @@ pub enum Receive {
     /// This is equivalent to passing a direction of `None`.
     Moves {
         /// The set of moves to perform.
+        #[serde(default)]
         moves: Vec<Move>,
+        /// Moves applying to several bees at once.
+        ///
+        /// Entries in [`Receive::Moves::moves`] take priority over these.
+        #[serde(default)]
+        groups: Vec<GroupMove>,
+        /// The tick the moves are meant for, if any.
+        ///
+        /// Moves meant for any tick but the next one are rejected with a warning,
+        /// rather than being applied on the wrong tick.
+        #[serde(default)]
+        tick: Option<u64>,
+    },
+    /// A chat message to send to everyone in the game.
+    ///
+    /// Messages from observers are sent on the spectator channel instead.
+    /// Messages that are too long, or sent too quickly, are ignored with a warning.
+    Chat {
+        /// The contents of the message.
+        msg: String,
+    },
+    /// Donate pollen from the player's hive to another player's hive.
+    ///
+    /// Only allowed between allied players,
+    /// while bees from both players are together at one of their hives.
+    /// Fails with a warning if the transfer isn't allowed.
+    Transfer {
+        /// The player receiving the pollen.
+        to: game::Player,
+        /// The amount of pollen to transfer.
+        amount: i32,
+    },
+    /// Spend pollen to build a new hive where one of the player's bees is.
+    ///
+    /// The bee must be on an unused spawn point,
+    /// and the game must allow building hives.
+    /// Fails with a warning if the hive can't be built.
+    BuildHive {
+        /// The bee marking where to build the hive.
+        bee: game::BeeID,
+    },
+    /// Spend pollen to move one of the player's hives to a new position.
+    ///
+    /// The hive is in transit for several ticks before arriving.
+    /// Fails with a warning if the hive can't be moved.
+    RelocateHive {
+        /// The current position of the hive to move.
+        hive: Position,
+        /// The spawn point to move the hive to.
+        to: Position,
+    },
+    /// Spend pollen to upgrade one of the player's hives.
+    ///
+    /// Fails with a warning if the hive can't be upgraded.
+    UpgradeHive {
+        /// The position of the hive to upgrade.
+        hive: Position,
+        /// The upgrade to buy.
+        upgrade: Upgrade,
+    },
+    /// Change the role of one of the player's bees.
+    ///
+    /// The bee must be at one of the player's hives.
+    /// Fails with a warning if the role can't be changed.
+    AssignRole {
+        /// The bee to change.
+        bee: game::BeeID,
+        /// The bee's new role.
+        role: Role,
     },
+    /// Only receive updates about entities within part of the world.
+    ///
+    /// The player's own bees and hives are always included.
+    /// Observers always receive the full game state.
+    SetViewport {
+        /// The area to receive updates about, or `None` for the whole world.
+        #[serde(default)]
+        rect: Option<Rect>,
+    },
+    /// Change the name the player is registered under.
+    ///
+    /// Fails with a warning if the name is already taken by another player.
+    /// Reconnecting players must then use the new name.
+    Rename {
+        /// The player's new name.
+        name: String,
+    },
+    /// Request information about the game.
+    ///
+    /// Can be sent by players and observers at any time.
+    Query {
+        /// The information being requested.
+        what: Query,
+    },
+    /// A reply to a [`Send::Ping`], used to measure the player's latency.
+    Pong {
+        /// The `id` of the ping being replied to.
+        id: u64,
+    },
+}
+
+impl Receive {
+    /// Parse a message from a line of JSON, as sent over TCP or a WebSocket.
+    ///
+    /// If `max_moves` is given, the moves in a `moves` message are counted before they're parsed,
+    /// so a message with too many moves is refused without building any of them.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the line isn't a valid message, or has too many moves.
+    pub fn from_json(line: &str, max_moves: Option<usize>) -> serde_json::Result<Self> {
+        // anything that can't be counted is left for the real parse to complain about
+        let counted = max_moves.map(|max| (max, serde_json::from_str::<MoveCount>(line)));
+        if let Some((max, Ok(counted))) = counted {
+            let count = counted.moves.0 + counted.groups.iter().map(|g| g.bees.0).sum::<usize>();
+            if counted.kind == "moves" && count > max {
+                let msg = format!("Too many moves in one message (limit {})", max);
+                return Err(serde::de::Error::custom(msg));
+            }
+        }
+        serde_json::from_str(line)
+    }
+}
+
+/// The number of moves in a `moves` message, counted without keeping any of them.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct MoveCount {
+    /// The type of message; only `moves` messages are counted.
+    #[serde(rename = "type")]
+    kind: String,
+    /// The number of [moves][Receive::Moves::moves].
+    moves: Count,
+    /// The number of bees in each [group][Receive::Moves::groups].
+    groups: Vec<GroupCount>,
+}
+
+/// The number of bees in a [`GroupMove`].
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct GroupCount {
+    /// The number of bees in the group.
+    bees: Count,
 }
 
-/// A single movement for a bee.
-#[derive(Debug, Deserialize)]
-pub struct Move {
-    /// The bee that is moving.
-    pub bee: game::BeeID,
-    /// The direction the bee should move.
-    /// `None` indicates that no movement should be made.
-    #[serde(default)]
-    pub direction: Option<Direction>,
+/// The length of a JSON array.
+#[derive(Debug, Default)]
+struct Count(usize);
+
+impl<'de> Deserialize<'de> for Count {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        /// Counts each element of an array, skipping over its contents.
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Count;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "an array")
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Count, A::Error> {
+                let mut count = 0;
+                while seq.next_element::<serde::de::IgnoredAny>()?.is_some() {
+                    count += 1;
+                }
+                Ok(Count(count))
+            }
+        }
+
+        deserializer.deserialize_seq(Visitor)
+    }
 }
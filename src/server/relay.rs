@@ -0,0 +1,98 @@
+//! Passing news of the game on to outside services, such as webhooks and chat channels.
+//!
+//! Each service is sent its news one item at a time by a background task,
+//! until the server shuts down.
+//! News that can't be sent is dropped, so a service being unavailable doesn't affect the game.
+//! Failures are logged when a service stops accepting news and again when it recovers,
+//! but not for each item dropped in between.
+
+use anyhow::Result;
+use futures::Future;
+use log::{debug, info, warn};
+use tokio::sync::broadcast::error::RecvError;
+
+use super::{ClientState, Milestone};
+
+/// An outside service that news is sent to, and whether it is currently failing.
+#[derive(Debug)]
+pub struct Outlet {
+    /// Describes the service in the logs, such as `webhook https://example.com/`.
+    name: String,
+    /// Whether the last attempt to send news failed.
+    failing: bool,
+}
+
+impl Outlet {
+    /// Make a new outlet, described in the logs by `name`.
+    pub fn new(name: String) -> Self {
+        Outlet {
+            name,
+            failing: false,
+        }
+    }
+
+    /// Log the `result` of sending news to the service,
+    /// if it has started or stopped failing.
+    pub fn record(&mut self, result: Result<()>) {
+        match &result {
+            Ok(()) if self.failing => info!("Reached {} again", self.name),
+            Ok(()) => {}
+            Err(e) if !self.failing => warn!("Could not reach {}: {:?}", self.name, e),
+            Err(_) => debug!("Still failing to reach {}", self.name),
+        }
+        self.failing = result.is_err();
+    }
+}
+
+/// Send the milestones sent over `client_info` to the `outlet`,
+/// until it receives a shutdown signal.
+///
+/// Each milestone is turned into news with `news`, skipping those it returns `None` for,
+/// and the news sent with `send`.
+/// The milestones are subscribed to as soon as this is called, even before the future is first polled,
+/// so that none sent in the meantime are missed.
+pub fn milestones<T>(
+    outlet: Outlet,
+    client_info: ClientState,
+    mut news: impl FnMut(Milestone) -> Option<T>,
+    send: impl AsyncFnMut(T) -> Result<()>,
+) -> impl Future<Output = ()> {
+    let mut milestones = client_info.milestones();
+    let name = outlet.name.clone();
+    let next = async move || loop {
+        let milestone = match milestones.recv().await {
+            Ok(milestone) => milestone,
+            Err(RecvError::Lagged(missed)) => {
+                warn!("{} is too slow, skipped {} milestones", name, missed);
+                continue;
+            }
+            Err(RecvError::Closed) => return None,
+        };
+        if let Some(news) = news(milestone) {
+            return Some(news);
+        }
+    };
+    relay(outlet, client_info, next, send)
+}
+
+/// Send each item of news returned by `next` to the `outlet` with `send`,
+/// until either `next` returns `None` or `client_info` receives a shutdown signal.
+pub async fn relay<T>(
+    mut outlet: Outlet,
+    client_info: ClientState,
+    mut next: impl AsyncFnMut() -> Option<T>,
+    mut send: impl AsyncFnMut(T) -> Result<()>,
+) {
+    let mut shutdown = client_info.get_shutdown_notifier();
+    loop {
+        let news = tokio::select! {
+            news = next() => match news {
+                Some(news) => news,
+                None => break,
+            },
+            _ = shutdown.recv() => break,
+        };
+        outlet.record(send(news).await);
+    }
+    debug!("Stopped sending news to {}", outlet.name);
+}
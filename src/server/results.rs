@@ -0,0 +1,410 @@
+//! Persistent storage for the results of finished games.
+//!
+//! Results are stored in a file containing one JSON-encoded [`GameResult`] per line.
+//! The file is read in full when the server starts,
+//! and new results are appended to it as games finish.
+//! Aggregate information such as player [profiles][`Results::profile`]
+//! and the [leaderboard][`Results::leaderboard`]
+//! is computed from these stored results.
+//!
+//! Each player also has an ELO-style rating,
+//! which is updated after each game based on how they scored
+//! relative to each other player in that game.
+//! Ratings are recomputed from the stored results in order when the file is loaded,
+//! so they persist along with the results themselves.
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, ErrorKind},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+/// The rating given to players who haven't finished any games.
+const INITIAL_RATING: f64 = 1500.0;
+
+/// The maximum amount a player's rating can change in a single game.
+const RATING_K_FACTOR: f64 = 32.0;
+
+/// The final result for a single player in a game.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PlayerResult {
+    /// The name the player registered with.
+    pub name: String,
+    /// The player's final score.
+    pub score: i32,
+    /// The total amount of pollen the player delivered to their hive.
+    #[serde(default)]
+    pub pollen_delivered: i32,
+}
+
+/// The final results of a finished game.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameResult {
+    /// When the game finished, in seconds since the Unix epoch.
+    pub finished_at: u64,
+    /// The number of ticks the game ran for.
+    pub ticks: u64,
+    /// The results for each player in the game.
+    pub players: Vec<PlayerResult>,
+}
+
+impl GameResult {
+    /// Create a result for a game that has just finished.
+    #[must_use]
+    pub fn new(ticks: u64, players: Vec<PlayerResult>) -> Self {
+        let finished_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Self {
+            finished_at,
+            ticks,
+            players,
+        }
+    }
+
+    /// The highest score achieved by any player in this game.
+    #[must_use]
+    pub fn best_score(&self) -> Option<i32> {
+        self.players.iter().map(|p| p.score).max()
+    }
+
+    /// A finished game where each named player got the given score.
+    #[cfg(test)]
+    pub(crate) fn with_scores(scores: &[(&str, i32)]) -> Self {
+        let players = (scores.iter())
+            .map(|&(name, score)| PlayerResult {
+                name: name.to_owned(),
+                score,
+                pollen_delivered: 0,
+            })
+            .collect();
+        Self::new(100, players)
+    }
+}
+
+/// Cumulative statistics for a player across all finished games.
+#[derive(Debug, Clone, Default, Serialize, JsonSchema)]
+pub struct Profile {
+    /// The number of finished games the player has participated in.
+    pub games_played: usize,
+    /// The number of games where the player had the (possibly shared) highest score.
+    pub wins: usize,
+    /// The sum of the player's scores across all games.
+    pub total_score: i64,
+    /// The player's highest score in a single game.
+    pub best_score: i32,
+    /// The total amount of pollen the player has delivered across all games.
+    pub total_pollen: i64,
+    /// The player's current ELO-style rating.
+    pub rating: f64,
+}
+
+impl Profile {
+    /// Add the player's `result` from a `game` to their profile.
+    fn add(&mut self, game: &GameResult, result: &PlayerResult) {
+        if self.games_played == 0 || result.score > self.best_score {
+            self.best_score = result.score;
+        }
+        self.games_played += 1;
+        self.total_score += i64::from(result.score);
+        self.total_pollen += i64::from(result.pollen_delivered);
+        if Some(result.score) == game.best_score() {
+            self.wins += 1;
+        }
+    }
+}
+
+/// A single row of the [leaderboard][`Results::leaderboard`].
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct LeaderboardEntry {
+    /// The position of this player on the leaderboard, starting from 1.
+    pub rank: usize,
+    /// The player's name.
+    pub name: String,
+    /// The player's cumulative statistics.
+    #[serde(flatten)]
+    pub profile: Profile,
+}
+
+/// Update the `ratings` of each player based on the results of a `game`.
+///
+/// Each pair of players in the game is treated as an individual match,
+/// won by whoever had the higher score.
+/// The total change for each player is scaled by the number of opponents,
+/// so that the size of a game doesn't affect how much ratings can change.
+fn update_ratings(ratings: &mut HashMap<String, f64>, game: &GameResult) {
+    let rating = |name: &str| ratings.get(name).copied().unwrap_or(INITIAL_RATING);
+    let opponents = game.players.len().saturating_sub(1).max(1) as f64;
+
+    let mut changes = vec![0.0; game.players.len()];
+    for (i, this) in game.players.iter().enumerate() {
+        for (_, other) in game.players.iter().enumerate().filter(|&(j, _)| i != j) {
+            let difference = rating(&other.name) - rating(&this.name);
+            let expected = 1.0 / (1.0 + 10_f64.powf(difference / 400.0));
+            let actual = match this.score.cmp(&other.score) {
+                Ordering::Greater => 1.0,
+                Ordering::Equal => 0.5,
+                Ordering::Less => 0.0,
+            };
+            changes[i] += actual - expected;
+        }
+    }
+
+    for (player, change) in game.players.iter().zip(changes) {
+        let rating = ratings.entry(player.name.clone()).or_insert(INITIAL_RATING);
+        *rating += RATING_K_FACTOR * change / opponents;
+    }
+}
+
+/// The data shared between handles to [`Results`].
+#[derive(Debug, Default)]
+struct Stored {
+    /// All results loaded or recorded so far, oldest first.
+    games: Vec<GameResult>,
+    /// The current rating of each player that has finished a game.
+    ratings: HashMap<String, f64>,
+}
+
+impl Stored {
+    /// Add the result of a finished game.
+    fn push(&mut self, game: GameResult) {
+        update_ratings(&mut self.ratings, &game);
+        self.games.push(game);
+    }
+
+    /// Get the current rating for the player registered as `name`.
+    fn rating(&self, name: &str) -> f64 {
+        self.ratings.get(name).copied().unwrap_or(INITIAL_RATING)
+    }
+}
+
+/// A shared handle to the stored results of finished games.
+///
+/// Cloning this creates a new handle to the same storage.
+#[derive(Debug, Clone, Default)]
+pub struct Results {
+    /// Where results are persisted, if anywhere.
+    path: Option<Arc<PathBuf>>,
+    /// The results and ratings loaded or recorded so far.
+    stored: Arc<Mutex<Stored>>,
+}
+
+impl Results {
+    /// Load any existing results from the file at `path`.
+    ///
+    /// The file will be created when the first result is recorded if it doesn't exist.
+    /// If `path` is `None` then results are only kept in memory.
+    ///
+    /// This uses blocking IO, and so should be done before starting any async work.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file exists but could not be read or parsed.
+    pub fn open(path: Option<PathBuf>) -> Result<Self> {
+        let mut stored = Stored::default();
+        if let Some(path) = &path {
+            match File::open(path) {
+                Ok(file) => {
+                    for line in BufReader::new(file).lines() {
+                        let line = line.context("Could not read results file")?;
+                        if !line.trim().is_empty() {
+                            let result = serde_json::from_str(&line)
+                                .context("Could not parse results file")?;
+                            stored.push(result);
+                        }
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e).context("Could not open results file"),
+            }
+        }
+
+        Ok(Self {
+            path: path.map(Arc::new),
+            stored: Arc::new(Mutex::new(stored)),
+        })
+    }
+
+    /// Record the result of a newly finished game.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the result could not be written to the results file.
+    /// The result will still be available in memory regardless.
+    pub async fn record(&self, result: GameResult) -> Result<()> {
+        let mut line = serde_json::to_string(&result)?;
+        line.push('\n');
+        self.stored.lock().unwrap().push(result);
+
+        if let Some(path) = &self.path {
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path.as_ref())
+                .await
+                .context("Could not open results file")?;
+            file.write_all(line.as_bytes())
+                .await
+                .context("Could not write to results file")?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the cumulative statistics for the player registered as `name`.
+    ///
+    /// Returns `None` if the player has not finished any games.
+    #[must_use]
+    pub fn profile(&self, name: &str) -> Option<Profile> {
+        let stored = self.stored.lock().unwrap();
+        let mut profile = None;
+        for game in &stored.games {
+            for result in game.players.iter().filter(|p| p.name == name) {
+                profile
+                    .get_or_insert_with(Profile::default)
+                    .add(game, result);
+            }
+        }
+        profile.map(|profile| Profile {
+            rating: stored.rating(name),
+            ..profile
+        })
+    }
+
+    /// Aggregate all results into per-name totals, ordered from best to worst.
+    ///
+    /// Players are ranked by their total score across all games;
+    /// players with the same total share the same rank.
+    #[must_use]
+    pub fn leaderboard(&self) -> Vec<LeaderboardEntry> {
+        let stored = self.stored.lock().unwrap();
+        let mut profiles = HashMap::new();
+        for game in &stored.games {
+            for result in &game.players {
+                let profile: &mut Profile = profiles.entry(result.name.clone()).or_default();
+                profile.add(game, result);
+            }
+        }
+
+        let mut entries: Vec<_> = profiles
+            .into_iter()
+            .map(|(name, profile)| LeaderboardEntry {
+                rank: 0,
+                profile: Profile {
+                    rating: stored.rating(&name),
+                    ..profile
+                },
+                name,
+            })
+            .collect();
+        entries.sort_by(|a, b| {
+            let by_score = b.profile.total_score.cmp(&a.profile.total_score);
+            by_score.then_with(|| a.name.cmp(&b.name))
+        });
+
+        let mut previous = None;
+        for (index, entry) in entries.iter_mut().enumerate() {
+            let total = entry.profile.total_score;
+            entry.rank = match previous {
+                Some((prev_total, rank)) if prev_total == total => rank,
+                _ => index + 1,
+            };
+            previous = Some((total, entry.rank));
+        }
+
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn winner_takes_half_the_k_factor_from_an_equal_opponent() {
+        let mut ratings = HashMap::new();
+        update_ratings(
+            &mut ratings,
+            &GameResult::with_scores(&[("a", 10), ("b", 5)]),
+        );
+        assert_close(ratings["a"], INITIAL_RATING + RATING_K_FACTOR / 2.0);
+        assert_close(ratings["b"], INITIAL_RATING - RATING_K_FACTOR / 2.0);
+    }
+
+    #[test]
+    fn draw_between_equals_changes_nothing() {
+        let mut ratings = HashMap::new();
+        update_ratings(
+            &mut ratings,
+            &GameResult::with_scores(&[("a", 7), ("b", 7)]),
+        );
+        assert_close(ratings["a"], INITIAL_RATING);
+        assert_close(ratings["b"], INITIAL_RATING);
+    }
+
+    #[test]
+    fn game_size_does_not_change_the_stakes() {
+        let mut ratings = HashMap::new();
+        update_ratings(
+            &mut ratings,
+            &GameResult::with_scores(&[("a", 3), ("b", 2), ("c", 1)]),
+        );
+        assert_close(ratings["a"], INITIAL_RATING + RATING_K_FACTOR / 2.0);
+        assert_close(ratings["b"], INITIAL_RATING);
+        assert_close(ratings["c"], INITIAL_RATING - RATING_K_FACTOR / 2.0);
+    }
+
+    #[test]
+    fn upsets_move_ratings_further() {
+        let mut ratings = HashMap::new();
+        ratings.insert("strong".to_owned(), 1900.0);
+        ratings.insert("weak".to_owned(), 1100.0);
+        update_ratings(
+            &mut ratings,
+            &GameResult::with_scores(&[("strong", 1), ("weak", 2)]),
+        );
+
+        // the weak player was expected to win 1 in 101 matches
+        let gain = ratings["weak"] - 1100.0;
+        assert_close(gain, RATING_K_FACTOR * 100.0 / 101.0);
+        assert_close(ratings["strong"], 1900.0 - gain);
+    }
+
+    #[test]
+    fn ratings_are_only_moved_between_players() {
+        let mut ratings = HashMap::new();
+        ratings.insert("a".to_owned(), 1620.0);
+        ratings.insert("b".to_owned(), 1480.0);
+        update_ratings(
+            &mut ratings,
+            &GameResult::with_scores(&[("a", 4), ("b", 9), ("c", 4), ("d", 0)]),
+        );
+        let total: f64 = ratings.values().sum();
+        assert_close(total, 1620.0 + 1480.0 + 2.0 * INITIAL_RATING);
+    }
+
+    #[test]
+    fn playing_alone_keeps_the_initial_rating() {
+        let mut ratings = HashMap::new();
+        update_ratings(&mut ratings, &GameResult::with_scores(&[("solo", 50)]));
+        assert_close(ratings["solo"], INITIAL_RATING);
+    }
+}
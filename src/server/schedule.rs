@@ -0,0 +1,98 @@
+//! Deciding which games a server plays, and who may take part in them.
+
+use anyhow::Result;
+use rand::thread_rng;
+
+use super::{results::GameResult, tournament::Tournament};
+use crate::game;
+
+/// The sequence of games played by a server.
+///
+/// Each game is played on a map chosen by [`game::Config::select_map`].
+#[derive(Debug)]
+pub enum Schedule {
+    /// Play a single game, then finish.
+    ///
+    /// Stores the configuration for the game until it starts.
+    Single(Option<game::Config>),
+    /// Play games with the same configuration forever,
+    /// starting a new game whenever the previous one finishes.
+    Repeat {
+        /// The configuration for each game.
+        config: game::Config,
+        /// The number of games started so far.
+        games: usize,
+    },
+    /// Play each round of a tournament in turn.
+    Tournament(Tournament),
+}
+
+impl Schedule {
+    /// Get the configuration for the next game to play,
+    /// or `None` if there are no more games.
+    pub(super) fn next_game(&mut self) -> Option<game::Config> {
+        let (mut config, game) = match self {
+            Schedule::Single(config) => (config.take()?, 0),
+            Schedule::Repeat { config, games } => {
+                *games += 1;
+                (config.clone(), *games - 1)
+            }
+            Schedule::Tournament(tournament) => (tournament.next_round()?, 0),
+        };
+        config.select_map(&mut thread_rng(), game);
+        Some(config)
+    }
+
+    /// Play games with the same `config` forever.
+    #[must_use]
+    pub fn repeat(config: game::Config) -> Self {
+        Schedule::Repeat { config, games: 0 }
+    }
+
+    /// Use a reloaded `config` for any later games played with the same configuration.
+    ///
+    /// Tournament rounds keep their own configurations.
+    pub(super) fn reload(&mut self, config: game::Config) {
+        match self {
+            Schedule::Single(_) | Schedule::Tournament(_) => {}
+            Schedule::Repeat { config: old, .. } => *old = config,
+        }
+    }
+
+    /// Check whether the player registered as `name` may join the current game.
+    ///
+    /// # Errors
+    ///
+    /// Fails with a human-readable reason if the player may not join.
+    pub(super) fn admit(&self, name: &str) -> Result<()> {
+        match self {
+            Schedule::Single(_) | Schedule::Repeat { .. } => Ok(()),
+            Schedule::Tournament(tournament) => tournament.admit(name),
+        }
+    }
+
+    /// Note that the player registered as `name` has joined the current game.
+    pub(super) fn join(&mut self, name: &str) {
+        match self {
+            Schedule::Single(_) | Schedule::Repeat { .. } => {}
+            Schedule::Tournament(tournament) => tournament.join(name),
+        }
+    }
+
+    /// Note the `result` of the current game once it has finished.
+    pub(super) fn finish_game(&mut self, result: &GameResult) {
+        match self {
+            Schedule::Single(_) | Schedule::Repeat { .. } => {}
+            Schedule::Tournament(tournament) => tournament.finish_round(result),
+        }
+    }
+
+    /// Get a handle to the tournament being played, if any.
+    #[must_use]
+    pub fn tournament(&self) -> Option<&Tournament> {
+        match self {
+            Schedule::Single(_) | Schedule::Repeat { .. } => None,
+            Schedule::Tournament(tournament) => Some(tournament),
+        }
+    }
+}
@@ -0,0 +1,350 @@
+//! Machine-readable specifications of the server's interfaces,
+//! generated from the types the server actually sends and receives.
+//!
+//! The [AsyncAPI] document describes the game's protocol over TCP and WebSockets,
+//! with a message for each kind of [client](Receive) and [server](Send) message.
+//! The [OpenAPI] document describes the REST endpoints under `/api`.
+//! The schemas in both come from the types' doc comments,
+//! so they stay in step with the code.
+//!
+//! [AsyncAPI]: https://www.asyncapi.com/docs/reference/specification/v2.6.0
+//! [OpenAPI]: https://spec.openapis.org/oas/v3.0.3
+
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    JsonSchema,
+};
+use serde_json::{json, Map, Value};
+
+use super::{
+    protocol::{Receive, Send},
+    results::LeaderboardEntry,
+    tournament::Bracket,
+    Info, KickRequest, Status,
+};
+
+/// Where schemas are kept in both kinds of document.
+const SCHEMAS_PATH: &str = "#/components/schemas/";
+
+/// The HTTP method a REST endpoint answers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// Fetch some information.
+    Get,
+    /// Ask the server to do something.
+    Post,
+}
+
+impl Method {
+    /// The method's name, as used in an OpenAPI document.
+    fn name(self) -> &'static str {
+        match self {
+            Method::Get => "get",
+            Method::Post => "post",
+        }
+    }
+}
+
+/// Every REST endpoint served under `/api`, as the method and path each answers to.
+///
+/// The [OpenAPI document](openapi) is built from this list,
+/// and the website won't serve an endpoint that isn't on it,
+/// so the two can't drift apart.
+/// The GraphQL endpoint is only listed when the server is built with the `graphql` feature.
+pub const API_ROUTES: &[(Method, &str)] = &[
+    (Method::Get, "leaderboard"),
+    (Method::Get, "tournament"),
+    (Method::Get, "status"),
+    (Method::Get, "info"),
+    (Method::Post, "kick"),
+    #[cfg(feature = "graphql")]
+    (Method::Get, "graphql"),
+    #[cfg(feature = "graphql")]
+    (Method::Post, "graphql"),
+];
+
+/// Generate an AsyncAPI document describing the game's protocol.
+pub fn asyncapi() -> Value {
+    let mut gen = SchemaSettings::draft07()
+        .with(|settings| settings.definitions_path = SCHEMAS_PATH.to_owned())
+        .into_generator();
+    gen.subschema_for::<Receive>();
+    gen.subschema_for::<Send>();
+    let mut schemas = definitions(gen);
+
+    // give each kind of message its own entry, so tools can tell them apart
+    let mut messages = Map::new();
+    let client = split_messages(&mut schemas, &Receive::schema_name(), &mut messages);
+    let server = split_messages(&mut schemas, &Send::schema_name(), &mut messages);
+    let operations = json!({
+        "publish": {
+            "summary": "Messages sent by clients to the server.",
+            "message": { "oneOf": client },
+        },
+        "subscribe": {
+            "summary": "Messages sent by the server to clients.",
+            "message": { "oneOf": server },
+        },
+    });
+    let channel = |servers: &[&str], description: &str| {
+        let mut channel = operations.clone();
+        channel["servers"] = json!(servers);
+        channel["description"] = json!(description);
+        channel
+    };
+
+    json!({
+        "asyncapi": "2.6.0",
+        "info": {
+            "title": "Beeeees",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "The protocol bots and observers use to play and watch the game. \
+                See protocol.md for how the messages fit together.",
+        },
+        "defaultContentType": "application/json",
+        "servers": {
+            "tcp": {
+                "url": "127.0.0.1:49998",
+                "protocol": "tcp",
+                "description": "Each message is a single line of JSON.",
+            },
+            "web": {
+                "url": "127.0.0.1:8080",
+                "protocol": "ws",
+                "description": "Each message is a single WebSocket text frame of JSON.",
+            },
+        },
+        "channels": {
+            "/": channel(&["tcp"], "Play the game, or watch it by registering with an empty name."),
+            "/play": channel(&["web"], "Play the game."),
+            "/observe": channel(&["web"], "Watch the game as an observer."),
+        },
+        "components": {
+            "messages": messages,
+            "schemas": schemas,
+        },
+    })
+}
+
+/// Generate an OpenAPI document describing the REST endpoints listed in [`API_ROUTES`].
+///
+/// # Panics
+///
+/// Panics if one of the endpoints hasn't been documented.
+pub fn openapi() -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let leaderboard = gen.subschema_for::<Vec<LeaderboardEntry>>();
+    let bracket = gen.subschema_for::<Bracket>();
+    let status = gen.subschema_for::<Status>();
+    let info = gen.subschema_for::<Info>();
+    let kick_request = gen.subschema_for::<KickRequest>();
+    let get = |summary: &str, description: &str, schema| {
+        json!({
+            "summary": summary,
+            "description": description,
+            "responses": {
+                "200": {
+                    "description": "OK",
+                    "content": { "application/json": { "schema": schema } },
+                },
+            },
+        })
+    };
+    let text = |description: &str| {
+        let content = json!({ "text/plain": { "schema": { "type": "string" } } });
+        json!({ "description": description, "content": content })
+    };
+
+    let mut paths = Map::new();
+    for &(method, route) in API_ROUTES {
+        let operation = match (method, route) {
+            (Method::Get, "leaderboard") => get(
+                "Per-player totals across every recorded game.",
+                "Only games recorded to the server's results file are included.",
+                leaderboard.clone(),
+            ),
+            (Method::Get, "tournament") => {
+                let mut tournament = get(
+                    "The current state of the tournament.",
+                    "Only available while the server is running a tournament.",
+                    bracket.clone(),
+                );
+                tournament["responses"]["404"] = text("No tournament is running.");
+                tournament
+            }
+            (Method::Get, "status") => get(
+                "The server's current status.",
+                "Includes the connected observers, and each player's name and latency.",
+                status.clone(),
+            ),
+            (Method::Get, "info") => get(
+                "Public information about the server and its current game.",
+                "The same information is posted to the server directory, if any.",
+                info.clone(),
+            ),
+            (Method::Post, "kick") => json!({
+                "summary": "Kick a player out of the game for good.",
+                "description": "The player is disconnected and removed from the game, \
+                    and their name is freed up. Only available to admins.",
+                "security": [{ "admin": [] }],
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/json": { "schema": kick_request.clone() } },
+                },
+                "responses": {
+                    "200": text("The player was kicked."),
+                    "401": text("The admin token was missing or wrong."),
+                    "404": text("No player is registered under the name."),
+                },
+            }),
+            #[cfg(feature = "graphql")]
+            (Method::Get, "graphql") => json!({
+                "summary": "GraphiQL, for trying out GraphQL queries in the browser.",
+                "description": "Loads GraphiQL itself from a CDN.",
+                "responses": {
+                    "200": {
+                        "description": "OK",
+                        "content": { "text/html": { "schema": { "type": "string" } } },
+                    },
+                },
+            }),
+            #[cfg(feature = "graphql")]
+            (Method::Post, "graphql") => json!({
+                "summary": "Answer a read-only GraphQL query about the current game.",
+                "description": "The game's entities have the same fields that observers are \
+                    sent in each update. The schema can be fetched with an introspection query.",
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/json": { "schema": graphql_request() } },
+                },
+                "responses": {
+                    "200": {
+                        "description": "The query's results, along with any errors.",
+                        "content": { "application/json": { "schema": graphql_response() } },
+                    },
+                },
+            }),
+            _ => panic!("{} /api/{} should be documented", method.name(), route),
+        };
+        let path = paths
+            .entry(format!("/{}", route))
+            .or_insert_with(|| json!({}));
+        path[method.name()] = operation;
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Beeeees",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Information about the server and its games, served by its website.",
+        },
+        "servers": [{ "url": "http://127.0.0.1:8080/api" }],
+        "paths": paths,
+        "components": {
+            "schemas": definitions(gen),
+            "securitySchemes": {
+                "admin": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "The server's `--admin-token`.",
+                },
+            },
+        },
+    })
+}
+
+/// The schema of a GraphQL query, as sent in the body of a request.
+#[cfg(feature = "graphql")]
+fn graphql_request() -> Value {
+    json!({
+        "type": "object",
+        "required": ["query"],
+        "properties": {
+            "query": { "type": "string", "description": "The query, in GraphQL's syntax." },
+            "operationName": {
+                "type": "string",
+                "nullable": true,
+                "description": "Which operation to run, if the query has several.",
+            },
+            "variables": {
+                "type": "object",
+                "nullable": true,
+                "description": "The values of the query's variables.",
+            },
+        },
+    })
+}
+
+/// The schema of the response to a GraphQL query.
+#[cfg(feature = "graphql")]
+fn graphql_response() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "data": {
+                "type": "object",
+                "nullable": true,
+                "description": "The results, shaped like the query.",
+            },
+            "errors": {
+                "type": "array",
+                "items": { "type": "object" },
+                "description": "Anything that went wrong, if the query failed.",
+            },
+        },
+    })
+}
+
+/// Take the schemas of every type referenced so far out of `gen`.
+fn definitions(mut gen: SchemaGenerator) -> Map<String, Value> {
+    (gen.take_definitions().into_iter())
+        .map(|(name, schema)| {
+            let schema = serde_json::to_value(schema).expect("schemas should serialize");
+            (name, schema)
+        })
+        .collect()
+}
+
+/// Move each variant of the internally tagged enum `name` out of `schemas` into its own message,
+/// named after the enum and the variant's tag, returning references to the messages.
+fn split_messages(
+    schemas: &mut Map<String, Value>,
+    name: &str,
+    messages: &mut Map<String, Value>,
+) -> Vec<Value> {
+    let schema = schemas.remove(name).expect("message types should be enums");
+    let variants = match schema.get("oneOf") {
+        Some(Value::Array(variants)) => variants.clone(),
+        _ => panic!("{} should have a schema for each kind of message", name),
+    };
+    variants
+        .into_iter()
+        .map(|variant| {
+            let tag = tag_of(&variant).expect("message types should be tagged");
+            let message = format!("{}_{}", name, tag);
+            let summary = variant.get("description").and_then(Value::as_str);
+            let summary = summary.and_then(|s| s.split("\n\n").next()).unwrap_or(tag);
+            let entry = json!({
+                "name": tag,
+                "title": tag,
+                "summary": summary,
+                "payload": variant,
+            });
+            messages.insert(message.clone(), entry);
+            json!({ "$ref": format!("#/components/messages/{}", message) })
+        })
+        .collect()
+}
+
+/// Find the value of the `type` field that tags a message's schema,
+/// looking through any schemas it was combined from.
+fn tag_of(schema: &Value) -> Option<&str> {
+    let tag = schema
+        .pointer("/properties/type/enum/0")
+        .and_then(Value::as_str);
+    tag.or_else(|| {
+        let parts = schema.get("allOf")?.as_array()?;
+        parts.iter().find_map(tag_of)
+    })
+}
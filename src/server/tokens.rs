@@ -0,0 +1,27 @@
+//! Pre-provisioned API tokens, used to identify players in competitions.
+//!
+//! The tokens file is a JSON object mapping each token to the name of the player it belongs to.
+//! When the server is given a tokens file, players must give a known token when registering,
+//! and always play under the name their token maps to,
+//! so that entries can't impersonate each other just by guessing names.
+//! For the same reason, players can't rename themselves while tokens are in use.
+
+use std::{collections::HashMap, sync::Arc};
+
+use serde::Deserialize;
+
+/// The API tokens players can register with, and who they belong to.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Tokens {
+    /// Map of tokens to player names.
+    names: Arc<HashMap<String, String>>,
+}
+
+impl Tokens {
+    /// The name of the player that `token` belongs to, if it's a known token.
+    #[must_use]
+    pub fn name(&self, token: &str) -> Option<&str> {
+        self.names.get(token).map(String::as_str)
+    }
+}
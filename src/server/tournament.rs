@@ -0,0 +1,288 @@
+//! Running a sequence of games as a tournament.
+//!
+//! A tournament is made of a number of rounds, each with its own map and rules,
+//! which are played one after the other.
+//! Any player can join the first round;
+//! for later rounds only the best-scoring players from the previous round
+//! are allowed to take part.
+//!
+//! The state of the tournament can be viewed as a [`Bracket`].
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::results::{GameResult, PlayerResult};
+use crate::game;
+
+/// Configuration for a tournament.
+#[derive(Debug, Deserialize)]
+pub struct TournamentConfig {
+    /// The rounds to play, in order.
+    pub rounds: Vec<RoundConfig>,
+}
+
+/// Configuration for a single round of a tournament.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RoundConfig {
+    /// The maximum number of players taking part in this round.
+    ///
+    /// For the first round this limits how many players may join.
+    /// For later rounds this many of the best-scoring players
+    /// from the previous round advance to this one;
+    /// if not provided, every player from the previous round advances.
+    #[serde(default)]
+    pub participants: Option<usize>,
+    /// The map and rules for this round.
+    ///
+    /// Rounds should set [`max_ticks`][game::Config::max_ticks]
+    /// or [`target_score`][game::Config::target_score],
+    /// otherwise the round will never finish.
+    #[serde(flatten)]
+    pub config: game::Config,
+}
+
+/// The progress of a round in the tournament.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundStatus {
+    /// The round hasn't started yet.
+    Pending,
+    /// The round is currently being played.
+    Running,
+    /// The round has finished.
+    Finished,
+}
+
+/// The public state of a single round of the tournament.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Round {
+    /// The progress of this round.
+    pub status: RoundStatus,
+    /// The maximum number of players taking part in this round, if limited.
+    pub participants: Option<usize>,
+    /// The number of ticks the round lasts for, if limited.
+    pub max_ticks: Option<u64>,
+    /// The total score at which the round finishes, if any.
+    pub target_score: Option<i32>,
+    /// The names of the players allowed to join this round.
+    ///
+    /// `None` if anybody may join, as in the first round.
+    /// Empty until the previous round has finished.
+    pub eligible: Option<Vec<String>>,
+    /// The names of the players that have joined this round so far.
+    pub players: Vec<String>,
+    /// The final results of the round, best first, once it has finished.
+    pub results: Option<Vec<PlayerResult>>,
+}
+
+/// The current state of the tournament as a whole.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Bracket {
+    /// The index of the round currently being played, if any.
+    pub current: Option<usize>,
+    /// Every round in the tournament, in order.
+    pub rounds: Vec<Round>,
+}
+
+/// A shared handle to a running tournament.
+///
+/// Cloning this creates a new handle to the same tournament.
+#[derive(Debug, Clone)]
+pub struct Tournament {
+    /// The configuration for each round.
+    config: Arc<[RoundConfig]>,
+    /// The progress of the tournament so far.
+    bracket: Arc<Mutex<Bracket>>,
+}
+
+impl Tournament {
+    /// Create a new tournament that hasn't started yet.
+    #[must_use]
+    pub fn new(config: TournamentConfig) -> Self {
+        let rounds = config
+            .rounds
+            .iter()
+            .enumerate()
+            .map(|(index, round)| Round {
+                status: RoundStatus::Pending,
+                participants: round.participants,
+                max_ticks: round.config.max_ticks,
+                target_score: round.config.target_score,
+                eligible: (index > 0).then(Vec::new),
+                players: Vec::new(),
+                results: None,
+            })
+            .collect();
+
+        Self {
+            config: config.rounds.into(),
+            bracket: Arc::new(Mutex::new(Bracket {
+                current: None,
+                rounds,
+            })),
+        }
+    }
+
+    /// Get a copy of the current state of the tournament.
+    #[must_use]
+    pub fn bracket(&self) -> Bracket {
+        self.bracket.lock().unwrap().clone()
+    }
+
+    /// Start the next round of the tournament.
+    ///
+    /// Returns the configuration for the round,
+    /// or `None` if every round has been played.
+    pub(super) fn next_round(&mut self) -> Option<game::Config> {
+        let mut bracket = self.bracket.lock().unwrap();
+        let next = bracket.current.map_or(0, |current| current + 1);
+        let config = self.config.get(next)?.config.clone();
+        bracket.rounds[next].status = RoundStatus::Running;
+        bracket.current = Some(next);
+        Some(config)
+    }
+
+    /// Check whether the player registered as `name` can join the current round.
+    ///
+    /// Players aren't counted as taking part until they have [joined][Tournament::join].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the player was eliminated in an earlier round,
+    /// or if the round has no space for more players.
+    pub(super) fn admit(&self, name: &str) -> Result<()> {
+        let bracket = self.bracket.lock().unwrap();
+        let current = match bracket.current {
+            Some(current) => current,
+            None => bail!("Tournament has not started"),
+        };
+        let round = &bracket.rounds[current];
+
+        if round.players.iter().any(|p| p == name) {
+            return Ok(());
+        }
+        if let Some(eligible) = &round.eligible {
+            if !eligible.iter().any(|p| p == name) {
+                bail!("Not eligible to play in round {}", current + 1);
+            }
+        }
+        if round
+            .participants
+            .is_some_and(|max| round.players.len() >= max)
+        {
+            bail!("Round {} is full", current + 1);
+        }
+        Ok(())
+    }
+
+    /// Record the player registered as `name` as taking part in the current round,
+    /// once they have been [admitted][Tournament::admit] and added to the game.
+    pub(super) fn join(&mut self, name: &str) {
+        let mut bracket = self.bracket.lock().unwrap();
+        let current = match bracket.current {
+            Some(current) => current,
+            None => return,
+        };
+        let round = &mut bracket.rounds[current];
+        if !round.players.iter().any(|p| p == name) {
+            round.players.push(name.to_owned());
+        }
+    }
+
+    /// Record the `result` of the current round,
+    /// and decide which players advance to the next round.
+    pub(super) fn finish_round(&mut self, result: &GameResult) {
+        let mut bracket = self.bracket.lock().unwrap();
+        let current = match bracket.current {
+            Some(current) => current,
+            None => return,
+        };
+
+        let mut results = result.players.clone();
+        results.sort_by_key(|p| std::cmp::Reverse(p.score));
+
+        let round = &mut bracket.rounds[current];
+        round.status = RoundStatus::Finished;
+        round.results = Some(results.clone());
+
+        if let Some(next) = bracket.rounds.get_mut(current + 1) {
+            let advancing = next.participants.unwrap_or(results.len());
+            let eligible = results.into_iter().take(advancing).map(|p| p.name);
+            next.eligible = Some(eligible.collect());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tournament with a round for each of the given participant limits.
+    fn tournament(participants: &[Option<usize>]) -> Tournament {
+        let rounds = (participants.iter())
+            .map(|&participants| RoundConfig {
+                participants,
+                config: game::Config::default(),
+            })
+            .collect();
+        Tournament::new(TournamentConfig { rounds })
+    }
+
+    #[test]
+    fn nobody_is_admitted_before_the_first_round() {
+        let tournament = tournament(&[None]);
+        assert!(tournament.admit("a").is_err());
+    }
+
+    #[test]
+    fn first_round_admits_anyone_until_full() {
+        let mut tournament = tournament(&[Some(2)]);
+        tournament.next_round();
+        for name in ["a", "b"] {
+            tournament.admit(name).unwrap();
+            tournament.join(name);
+        }
+        assert!(tournament.admit("c").is_err());
+        // players already in the round can rejoin it
+        tournament.admit("a").unwrap();
+    }
+
+    #[test]
+    fn admitting_does_not_take_a_place() {
+        let mut tournament = tournament(&[Some(1)]);
+        tournament.next_round();
+        tournament.admit("a").unwrap();
+        tournament.admit("b").unwrap();
+        tournament.join("b");
+        assert!(tournament.admit("a").is_err());
+    }
+
+    #[test]
+    fn joining_twice_counts_once() {
+        let mut tournament = tournament(&[Some(2)]);
+        tournament.next_round();
+        tournament.join("a");
+        tournament.join("a");
+        assert_eq!(tournament.bracket().rounds[0].players, ["a"]);
+        tournament.admit("b").unwrap();
+    }
+
+    #[test]
+    fn only_the_best_players_advance() {
+        let mut tournament = tournament(&[None, Some(2)]);
+        tournament.next_round();
+        for name in ["a", "b", "c"] {
+            tournament.join(name);
+        }
+        tournament.finish_round(&GameResult::with_scores(&[("a", 1), ("b", 3), ("c", 2)]));
+        tournament.next_round();
+
+        tournament.admit("b").unwrap();
+        tournament.admit("c").unwrap();
+        assert!(tournament.admit("a").is_err());
+        assert!(tournament.admit("d").is_err());
+    }
+}
@@ -0,0 +1,68 @@
+//! Posting the notable moments of each game to webhooks, such as a chat bot or scoreboard.
+//!
+//! Each [milestone][Milestone] is posted as a JSON object with an `event` field naming it,
+//! along with the `arena` it happened in (or `null` for a server with a single arena).
+//! Milestones are posted to each webhook one at a time, in the order they happened.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use futures::Future;
+use hyper::{header, Body, Request, Uri};
+use serde::Serialize;
+use tokio::time;
+
+use super::{
+    http_client,
+    relay::{self, Outlet},
+    ClientState, HttpClient, Milestone,
+};
+
+/// How long to wait for a webhook to respond.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The body posted to a webhook.
+#[derive(Debug, Serialize)]
+struct Payload<'a> {
+    /// The name of the arena the milestone happened in, if the server runs several.
+    arena: Option<&'a str>,
+    /// What happened.
+    #[serde(flatten)]
+    milestone: &'a Milestone,
+}
+
+/// Post each of the milestones sent over `client_info` to the webhook at `url`,
+/// until it receives a shutdown signal.
+///
+/// Only milestones sent after this is called are posted, as described in [`relay::milestones`].
+pub fn notify(url: Uri, client_info: ClientState) -> impl Future<Output = ()> {
+    let client = http_client();
+    let arena = client_info.name.clone();
+    let outlet = Outlet::new(format!("webhook {}", url));
+    relay::milestones(outlet, client_info, Some, async move |milestone| {
+        let payload = Payload {
+            arena: arena.as_deref(),
+            milestone: &milestone,
+        };
+        post(&client, &url, &payload).await
+    })
+}
+
+/// Post the `payload` to the webhook at `url`.
+///
+/// # Errors
+///
+/// Fails if the request couldn't be sent or timed out, or if the webhook didn't accept it.
+async fn post(client: &HttpClient, url: &Uri, payload: &Payload<'_>) -> Result<()> {
+    let body = serde_json::to_vec(payload)?;
+    let request = Request::post(url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))?;
+    let response = time::timeout(WEBHOOK_TIMEOUT, client.request(request))
+        .await
+        .map_err(|_| anyhow!("Timed out waiting for the webhook to respond"))??;
+    if !response.status().is_success() {
+        bail!("The webhook responded with {}", response.status());
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+//! Bindings for running the game from JavaScript, when built for WebAssembly.
+//!
+//! A page playing over a WebSocket can hand the messages it receives to a [`Prediction`],
+//! to show what the next tick will probably look like before the server sends it:
+//!
+//! ```js
+//! const prediction = new Prediction(registrationJson, updateJson);
+//! prediction.apply_moves(movesJson);
+//! prediction.tick();
+//! draw(JSON.parse(prediction.state()));
+//! ```
+//!
+//! Every message is passed as the JSON text sent over the connection.
+
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use crate::game::{world::World, GroupMove, Move, Moves, Player, Snapshot, State};
+
+/// The parts of a `"registration"` message needed to rebuild the game.
+#[derive(Deserialize)]
+struct Registration {
+    /// The world map, which must include its tiles even if they were sent in chunks.
+    world: World,
+    /// The player the messages were sent to.
+    player: Player,
+}
+
+/// An `"update"` message.
+#[derive(Deserialize)]
+struct Update {
+    /// The entities the player could see.
+    data: Snapshot,
+}
+
+/// A `"moves"` message, as it would be sent to the server.
+#[derive(Deserialize)]
+struct MovesMessage {
+    /// The set of moves to perform.
+    #[serde(default)]
+    moves: Vec<Move>,
+    /// Moves applying to several bees at once.
+    #[serde(default)]
+    groups: Vec<GroupMove>,
+}
+
+/// A copy of the game run by the browser, to predict what the server will send next.
+#[wasm_bindgen]
+pub struct Prediction {
+    /// The predicted game.
+    state: State,
+    /// The player whose bees are being moved.
+    player: Player,
+    /// The actions the player's bees will take on the next tick.
+    moves: Moves,
+}
+
+#[wasm_bindgen]
+impl Prediction {
+    /// Rebuild the game from a `"registration"` message and the latest `"update"` message.
+    ///
+    /// # Errors
+    ///
+    /// Fails if either message can't be understood.
+    #[wasm_bindgen(constructor)]
+    pub fn new(registration: &str, update: &str) -> Result<Prediction, JsError> {
+        let Registration { world, player } = serde_json::from_str(registration)?;
+        let Update { data } = serde_json::from_str(update)?;
+        Ok(Prediction {
+            state: State::from_snapshot(world, data),
+            player,
+            moves: Moves::new(),
+        })
+    }
+
+    /// Set the actions of the player's bees from a `"moves"` message,
+    /// returning how many of the moves were accepted.
+    ///
+    /// As on the server, later moves for the same bee replace earlier ones,
+    /// but there is no limit on how many paths to targets can be found.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the message can't be understood.
+    pub fn apply_moves(&mut self, message: &str) -> Result<usize, JsError> {
+        let MovesMessage { moves, groups } = serde_json::from_str(message)?;
+        // as on the server, groups come first so that individual moves take priority
+        let moves = groups.iter().flat_map(GroupMove::expand).chain(moves);
+        let mut paths_left = usize::MAX;
+        let accepted = self
+            .state
+            .apply_moves(&mut self.moves, self.player, moves, &mut paths_left);
+        Ok(accepted)
+    }
+
+    /// Advance the game by one tick, carrying on with any plans that have steps left.
+    pub fn tick(&mut self) {
+        self.state.tick(&self.moves);
+        self.state.advance_moves(&mut self.moves);
+    }
+
+    /// The predicted state of the game, in the same form as the data of an `"update"` message.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the state can't be serialised.
+    pub fn state(&mut self) -> Result<String, JsError> {
+        let view = self.state.make_serializer().view(self.player);
+        Ok(serde_json::to_string(&view)?)
+    }
+}
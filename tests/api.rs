@@ -0,0 +1,59 @@
+//! Checks the REST endpoints under `/api` against the server's OpenAPI document.
+
+mod common;
+
+use hyper::{header, Body, Client, Request};
+use serde_json::Value;
+
+use common::{example, Server, ADMIN_TOKEN};
+
+#[tokio::test]
+async fn every_documented_endpoint_is_served() {
+    let spec: Value = serde_json::from_str(&common::run(&["openapi"])).unwrap();
+    let server = Server::start(&[]);
+    let web = server.listening.web.expect("the website should be served");
+    let client = Client::new();
+
+    let paths = spec["paths"]
+        .as_object()
+        .expect("the document should list paths");
+    assert!(!paths.is_empty());
+    for (path, operations) in paths {
+        for (method, operation) in operations.as_object().unwrap() {
+            let request = Request::builder()
+                .method(method.to_uppercase().as_str())
+                .uri(format!("http://{}/api{}", web, path))
+                .header(header::AUTHORIZATION, format!("Bearer {}", ADMIN_TOKEN));
+            let request =
+                match operation["requestBody"]["content"]["application/json"].get("schema") {
+                    Some(schema) => request
+                        .header(header::CONTENT_TYPE, "application/json")
+                        .body(Body::from(example(&spec, schema).to_string())),
+                    None => request.body(Body::empty()),
+                };
+            let response = client.request(request.unwrap()).await.unwrap();
+
+            // an endpoint that isn't served would fall through to a bare 404
+            let status = response.status();
+            let documented = &operation["responses"][status.as_str()];
+            assert!(
+                documented.is_object(),
+                "{} {} responded with undocumented status {}",
+                method,
+                path,
+                status
+            );
+            if let Some(content) = documented["content"].as_object() {
+                let content_type = response.headers().get(header::CONTENT_TYPE);
+                let content_type = content_type.and_then(|t| t.to_str().ok()).unwrap_or("");
+                assert!(
+                    content.keys().any(|t| content_type.starts_with(t.as_str())),
+                    "{} {} responded with undocumented content type {:?}",
+                    method,
+                    path,
+                    content_type
+                );
+            }
+        }
+    }
+}
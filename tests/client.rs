@@ -0,0 +1,183 @@
+//! Plays a game with the Rust client, and checks its messages against the AsyncAPI document.
+
+mod common;
+
+use std::{collections::BTreeSet, time::Duration};
+
+use serde_json::Value;
+
+use beeeees::{
+    client::{
+        BeeID, Client, ClientMessage, Direction, GroupMove, Move, Player, Position, Query, Rect,
+        Register, Role, ServerMessage, Upgrade,
+    },
+    game::Config,
+};
+use common::{example, resolve, Server};
+
+/// The payload schemas of the AsyncAPI messages whose names start with `prefix`, by type.
+fn messages<'a>(spec: &'a Value, prefix: &str) -> Vec<(&'a str, &'a Value)> {
+    let messages = spec["components"]["messages"]
+        .as_object()
+        .expect("the document should list messages");
+    (messages.iter())
+        .filter(|(name, _)| name.starts_with(prefix))
+        .map(|(name, message)| (&name[prefix.len()..], &message["payload"]))
+        .collect()
+}
+
+#[test]
+fn every_documented_server_message_is_understood() {
+    let spec: Value = serde_json::from_str(&common::run(&["asyncapi"])).unwrap();
+    let messages = messages(&spec, "ServerMessage_");
+    assert!(!messages.is_empty());
+    for (kind, payload) in messages {
+        let mut message = example(&spec, payload);
+        if message["type"] == "registration" {
+            // the example world is empty, which the game can't be played on
+            message["world"] = serde_json::to_value(Config::default().world).unwrap();
+        }
+        let parsed = serde_json::from_value::<ServerMessage>(message.clone());
+        assert!(
+            matches!(parsed, Ok(ref m) if !matches!(m, ServerMessage::Unknown)),
+            "the client couldn't understand the {} message {}: {:?}",
+            kind,
+            message,
+            parsed
+        );
+    }
+}
+
+#[test]
+fn every_client_message_is_documented() {
+    let spec: Value = serde_json::from_str(&common::run(&["asyncapi"])).unwrap();
+    let position = Position::new(1, 2);
+    let (bee, player) = (BeeID::new(), Player::new());
+    let samples = [
+        ClientMessage::Register(Register::new("buzz")),
+        ClientMessage::Moves {
+            moves: vec![Move::new(bee, Direction::North)],
+            groups: vec![GroupMove {
+                bees: vec![bee],
+                direction: None,
+            }],
+            tick: Some(3),
+        },
+        ClientMessage::Chat { msg: "hi".into() },
+        ClientMessage::Transfer {
+            to: player,
+            amount: 5,
+        },
+        ClientMessage::BuildHive { bee },
+        ClientMessage::RelocateHive {
+            hive: position,
+            to: position,
+        },
+        ClientMessage::UpgradeHive {
+            hive: position,
+            upgrade: Upgrade::Speed,
+        },
+        ClientMessage::AssignRole {
+            bee,
+            role: Role::Scout,
+        },
+        ClientMessage::SetViewport {
+            rect: Some(Rect {
+                x: 0,
+                y: 0,
+                width: 10,
+                height: 10,
+            }),
+        },
+        ClientMessage::Rename {
+            name: "bumble".into(),
+        },
+        ClientMessage::Query {
+            what: Query::Players,
+        },
+        ClientMessage::Pong { id: 7 },
+    ];
+
+    let documented = messages(&spec, "ClientMessage_");
+    let mut sent = BTreeSet::new();
+    for sample in &samples {
+        let message = serde_json::to_value(sample).unwrap();
+        let kind = message["type"].as_str().unwrap();
+        let (_, payload) = (documented.iter())
+            .find(|(name, _)| *name == kind)
+            .unwrap_or_else(|| panic!("the {} message isn't documented", kind));
+        let payload = resolve(&spec, payload);
+        let properties = payload["properties"].as_object().unwrap();
+        for field in message.as_object().unwrap().keys() {
+            assert!(
+                properties.contains_key(field),
+                "the {} message's {} field isn't documented",
+                kind,
+                field
+            );
+        }
+        for field in payload["required"].as_array().unwrap() {
+            assert!(
+                message.get(field.as_str().unwrap()).is_some(),
+                "the {} message is missing the required {} field",
+                kind,
+                field
+            );
+        }
+        sent.insert(kind.to_owned());
+    }
+    let documented: BTreeSet<_> = documented
+        .iter()
+        .map(|(kind, _)| kind.to_string())
+        .collect();
+    assert_eq!(
+        sent, documented,
+        "the client should send every documented message"
+    );
+}
+
+#[tokio::test]
+async fn plays_a_game() {
+    let server = Server::start(&["--tick-rate", "0.1"]);
+    let tcp = server.listening.tcp.expect("the TCP port should be open");
+
+    let play = async {
+        let mut client = Client::connect(tcp).await.unwrap();
+        let registration = client.register(Register::new("buzz")).await.unwrap();
+        assert!(registration.world.get(Position::new(0, 0)).is_some());
+
+        let state = loop {
+            match client.recv().await.unwrap() {
+                Some(ServerMessage::Update { data }) => break data,
+                Some(_) => {}
+                None => panic!("the server closed the connection before the game started"),
+            }
+        };
+        let moves: Vec<_> = (state.bees.iter())
+            .filter(|bee| bee.player == registration.player)
+            .map(|bee| Move::new(bee.id, Direction::North))
+            .collect();
+        let sent = moves.len();
+        client.send_moves(moves, None).await.unwrap();
+
+        loop {
+            match client.recv().await.unwrap() {
+                Some(ServerMessage::Ack {
+                    tick,
+                    accepted,
+                    received,
+                }) => {
+                    assert!(tick > 0);
+                    assert_eq!(received, sent);
+                    assert!(accepted <= received);
+                    break;
+                }
+                Some(_) => {}
+                None => panic!("the server closed the connection before acknowledging the moves"),
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(30), play)
+        .await
+        .expect("the game should be played within the time limit");
+}
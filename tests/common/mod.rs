@@ -0,0 +1,136 @@
+//! Running the server in the background for the integration tests,
+//! and making sense of the documents describing its APIs.
+
+// each test uses its own share of these
+#![allow(dead_code)]
+
+use std::{
+    io::{BufRead, BufReader},
+    net::SocketAddr,
+    process::{Child, ChildStdout, Command, Stdio},
+};
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+/// The admin token each server is started with.
+pub const ADMIN_TOKEN: &str = "let-me-in";
+
+/// Where a server is listening, as it prints once it's ready.
+#[derive(Debug, Deserialize)]
+pub struct Listening {
+    /// The address accepting TCP connections.
+    pub tcp: Option<SocketAddr>,
+    /// The address hosting the website.
+    pub web: Option<SocketAddr>,
+}
+
+/// A server running in the background, which is stopped when dropped.
+pub struct Server {
+    /// The server's process.
+    child: Child,
+    /// The server's standard output, kept open so that printing to it doesn't fail.
+    _stdout: BufReader<ChildStdout>,
+    /// Where the server is listening.
+    pub listening: Listening,
+}
+
+impl Server {
+    /// Start a server listening on free ports of the loopback address,
+    /// with any extra command line `args`, and wait until it's ready.
+    pub fn start(args: &[&str]) -> Server {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_beeeees"))
+            .args(["--tcp-addr", "127.0.0.1:0", "--web-addr", "127.0.0.1:0"])
+            .args(["--admin-token", ADMIN_TOKEN])
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("the server should start");
+        let mut stdout = BufReader::new(child.stdout.take().expect("stdout should be piped"));
+        let mut ready = String::new();
+        stdout
+            .read_line(&mut ready)
+            .expect("the server should say it's ready");
+        let listening =
+            serde_json::from_str(&ready).expect("the server should say where it's listening");
+        Server {
+            child,
+            _stdout: stdout,
+            listening,
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Run the server with the given command line `args`, returning what it printed.
+pub fn run(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_beeeees"))
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .expect("the server should run");
+    assert!(output.status.success(), "the server should succeed");
+    String::from_utf8(output.stdout).expect("the server should print UTF-8")
+}
+
+/// Look up the schema that `schema` refers to in the `spec`, if it's a reference.
+pub fn resolve<'a>(spec: &'a Value, schema: &'a Value) -> &'a Value {
+    match schema["$ref"].as_str() {
+        Some(path) => {
+            let pointer = path.trim_start_matches('#');
+            resolve(
+                spec,
+                spec.pointer(pointer).expect("references should be defined"),
+            )
+        }
+        None => schema,
+    }
+}
+
+/// Make up a value for `schema`, filling in just the required properties of objects.
+pub fn example(spec: &Value, schema: &Value) -> Value {
+    let schema = resolve(spec, schema);
+    if let Some(value) = schema["enum"].get(0) {
+        return value.clone();
+    }
+    // optional values are documented as either their type or null
+    let kind = match &schema["type"] {
+        Value::Array(kinds) => kinds.iter().find(|&kind| kind != "null"),
+        kind => Some(kind),
+    };
+    let mut value = match kind.and_then(Value::as_str) {
+        Some("object") => {
+            let required = schema["required"].as_array().cloned().unwrap_or_default();
+            let properties: Map<_, _> = (required.iter().filter_map(Value::as_str))
+                .map(|name| {
+                    let property = &schema["properties"][name];
+                    (name.to_owned(), example(spec, property))
+                })
+                .collect();
+            Value::Object(properties)
+        }
+        Some("string") => json!("nobody"),
+        Some("integer" | "number") => json!(0),
+        Some("boolean") => json!(false),
+        Some("array") => json!([]),
+        _ => Value::Null,
+    };
+    // internally tagged enums put the tag beside the properties of one of their variants
+    for combined in ["allOf", "oneOf", "anyOf"] {
+        if let Some(first) = schema[combined].get(0) {
+            match (&mut value, example(spec, first)) {
+                (Value::Object(value), Value::Object(variant)) => value.extend(variant),
+                (value, variant) => *value = variant,
+            }
+        }
+    }
+    value
+}
@@ -0,0 +1,88 @@
+//! Rebuilds the game from what a client is sent, as browsers do to predict the next ticks.
+
+use beeeees::game::{
+    world::Direction, Action, Config, Cosmetics, Move, Moves, Player, Snapshot, State,
+};
+
+#[test]
+fn rebuilt_game_matches_the_update() {
+    let config = Config::default();
+    let world = config.world.clone();
+    let mut state = State::new(config);
+    let player = Player::new();
+    state
+        .add_player(player, Cosmetics::default(), None)
+        .unwrap();
+    for _ in 0..5 {
+        state.tick(&Moves::new());
+    }
+
+    let update = serde_json::to_value(state.make_serializer().view(player)).unwrap();
+    let snapshot: Snapshot = serde_json::from_value(update.clone()).unwrap();
+    let mut rebuilt = State::from_snapshot(world, snapshot);
+    let view = serde_json::to_value(rebuilt.make_serializer().view(player)).unwrap();
+    assert_eq!(view, update);
+    assert_eq!(rebuilt.players().collect::<Vec<_>>(), [&player]);
+}
+
+#[test]
+fn unplayable_configs_are_rejected() {
+    assert!(Config::default().validate().is_ok());
+
+    let empty: Config =
+        serde_json::from_str(r#"{"flower_initial_pollen":{"start":5,"end":3}}"#).unwrap();
+    assert!(empty.validate().is_err());
+
+    let mut config = Config::default();
+    config.items.spawn_chance = 1.5;
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn later_moves_replace_earlier_ones_and_paths_are_limited() {
+    let mut state = State::new(Config::default());
+    let (player, rival) = (Player::new(), Player::new());
+    for p in [player, rival].iter().copied() {
+        state.add_player(p, Cosmetics::default(), None).unwrap();
+    }
+    state.tick(&Moves::new());
+    let view = serde_json::to_value(state.make_serializer().view(player)).unwrap();
+    let bee_of = |p: Player| {
+        (view["bees"].as_array().unwrap().iter())
+            .find(|bee| bee["player"] == serde_json::to_value(p).unwrap())
+            .map(|bee| bee["id"].clone())
+            .expect("each player should start with a bee")
+    };
+    let step = |bee: &serde_json::Value, direction: &str| -> Move {
+        serde_json::from_value(serde_json::json!({ "bee": bee, "direction": direction })).unwrap()
+    };
+    let (mine, theirs) = (bee_of(player), bee_of(rival));
+
+    let mut moves = Moves::new();
+    let sent = vec![
+        step(&mine, "North"),
+        step(&theirs, "North"),
+        step(&mine, "South"),
+    ];
+    assert_eq!(state.apply_moves(&mut moves, player, sent, &mut 0), 1);
+    let actions: Vec<_> = moves.values().collect();
+    assert_eq!(actions, [&Action::Move(Direction::South)]);
+
+    // only the last of the targets is looked for, and then there are no paths left
+    let travel = |x| -> Move {
+        let target = serde_json::json!({ "x": x, "y": 0 });
+        serde_json::from_value(serde_json::json!({ "bee": mine, "target": target })).unwrap()
+    };
+    let mut paths_left = 1;
+    state.apply_moves(
+        &mut moves,
+        player,
+        vec![travel(0), travel(1)],
+        &mut paths_left,
+    );
+    assert_eq!(paths_left, 0);
+    let before = moves.clone();
+    let accepted = state.apply_moves(&mut moves, player, vec![travel(2)], &mut paths_left);
+    assert_eq!(accepted, 0);
+    assert_eq!(moves, before);
+}
@@ -5,6 +5,12 @@ function write(message) {
   log.insertAdjacentHTML('afterbegin', '<p>' + message + '</p>');
 }
 
+function escapeHtml(text) {
+  const p = document.createElement('p');
+  p.textContent = text;
+  return p.innerHTML;
+}
+
 const websocket = new WebSocket('ws://' + window.location.host + '/observe');
 const canvas = document.getElementById('canvas');
 const ctx = canvas.getContext('2d');
@@ -13,8 +19,12 @@ const world = {
   tile_size: null,
   width: null,
   height: null,
+  grid: 'square',
   map: null,
   at: function (x, y) { return this.map[x + y * this.width] },
+  // the horizontal position (in tiles) to draw the tile at (x, y);
+  // rows of a hex grid are shifted half a tile per row
+  left: function (x, y) { return this.grid === 'hex' ? x + y / 2 : x },
 };
 
 const players = {
@@ -22,6 +32,7 @@ const players = {
   data: new Map(),
 };
 
+const hives = new Map();   // "x,y" -> hive data
 const bees = new Map();    // ID -> bee data
 const flowers = new Map(); // ID -> flower data
 
@@ -31,21 +42,37 @@ let ticks_per_update = null;  // expected number of ticks between each game upda
 
 function resize() {
   const th = canvas.height / world.height;
-  const tw = canvas.width / world.width;
+  const tw = canvas.width / (world.grid === 'hex' ? world.width + (world.height - 1) / 2 : world.width);
   world.tile_size = Math.min(th, tw);
 }
 
 function init(new_world, new_tick_rate) {
-  Object.assign(world, new_world);
+  Object.assign(world, { grid: 'square' }, new_world);
+  if (world.map === null) {
+    // large maps are sent separately in chunks
+    world.map = new Array(world.width * world.height).fill('Block');
+  }
   resize();
 
   players.max = world.map.filter(t => t === 'SpawnPoint').length;
   players.data.clear();
+  hives.clear();
+  bees.clear();
+  flowers.clear();
 
   tick_length = 20;  // 50Hz
   ticks_per_update = (new_tick_rate * 1000) / tick_length;
 }
 
+function add_chunk(chunk) {
+  for (let i = 0; i < chunk.map.length; ++i) {
+    const x = chunk.x + i % chunk.width;
+    const y = chunk.y + Math.floor(i / chunk.width);
+    world.map[x + y * world.width] = chunk.map[i];
+  }
+  players.max = world.map.filter(t => t === 'SpawnPoint').length;
+}
+
 function make_ellipse(a, b, initial_t, t_step) {
   return {
     t: initial_t,
@@ -66,8 +93,19 @@ function make_ellipse(a, b, initial_t, t_step) {
 function update(data) {
   for (const h of data.hives) {
     if (!players.data.has(h.player)) {
-      const colour = 360 * players.data.size / players.max;
-      players.data.set(h.player, Object.assign({ colour, opacity: 0 }, h));
+      const hue = 360 * players.data.size / players.max;
+      const colour = h.color || `hsl(${hue}, 100%, 50%)`;
+      players.data.set(h.player, Object.assign({ colour }, h));
+    }
+    const key = `${h.position.x},${h.position.y}`;
+    if (!hives.has(key)) {
+      hives.set(key, Object.assign({ colour: players.data.get(h.player).colour, opacity: 0 }, h));
+    }
+    Object.assign(hives.get(key), { transit: h.transit, queen: h.queen });
+  }
+  for (const key of hives.keys()) {
+    if (!data.hives.some(h => `${h.position.x},${h.position.y}` === key)) {
+      hives.delete(key);
     }
   }
 
@@ -102,9 +140,9 @@ function animate(num_ticks) {
   const update_step = num_ticks / ticks_per_update;
   const time_step = num_ticks * tick_length / 1000;
 
-  players.data.forEach(p => {
-    if (p.opacity < 1.0) {
-      p.opacity = Math.min(1.0, p.opacity + update_step);
+  hives.forEach(h => {
+    if (h.opacity < 1.0) {
+      h.opacity = Math.min(1.0, h.opacity + update_step);
     }
   });
 
@@ -155,6 +193,9 @@ function tile_colour(tile) {
     case 'Road': return '#AAAAAA';
     case 'Block': return 'brown';
     case 'SpawnPoint': return '#444444';
+    case 'Meadow': return '#CCEE44';
+    case 'Water': return '#3399FF';
+    case 'Crossing': return '#DDDDDD';
   }
 }
 
@@ -164,18 +205,27 @@ function draw_map() {
   for (let row = 0; row < world.width; ++row) {
     for (let col = 0; col < world.height; ++col) {
       ctx.fillStyle = tile_colour(world.at(row, col));
-      ctx.fillRect(row * s, col * s, s, s);
+      ctx.fillRect(world.left(row, col) * s, col * s, s, s);
     }
   }
   ctx.restore();
 }
 
+function flower_colour(species) {
+  switch (species) {
+    case 'clover': return '255, 192, 255';
+    case 'rose': return '255, 32, 64';
+    default: return '255, 255, 0';
+  }
+}
+
 function draw_flowers() {
   ctx.save();
   const s = world.tile_size;
   for (const { curr, _ } of flowers.values()) {
-    const { x, y } = curr.position;
-    ctx.fillStyle = `rgba(255, 255, 0, ${curr.opacity})`
+    const { y } = curr.position;
+    const x = world.left(curr.position.x, y);
+    ctx.fillStyle = `rgba(${flower_colour(curr.species)}, ${curr.opacity})`
     ctx.fillRect(x * s, y * s, s, s);
   }
   ctx.restore();
@@ -184,10 +234,24 @@ function draw_flowers() {
 function draw_hives() {
   ctx.save();
   const s = world.tile_size;
-  for (const p of players.data.values()) {
-    const { x, y } = p.position;
-    ctx.fillStyle = `hsla(${p.colour}, 100%, 50%, ${p.opacity})`;
+  for (const h of hives.values()) {
+    const { y } = h.position;
+    const x = world.left(h.position.x, y);
+    // hives being relocated are drawn faded
+    ctx.globalAlpha = h.transit ? h.opacity / 2 : h.opacity;
+    ctx.fillStyle = h.colour;
     ctx.fillRect(x * s, y * s, s, s);
+    if (!h.queen) {
+      // mark hives that have lost their queen
+      ctx.strokeStyle = 'red';
+      ctx.strokeRect(x * s + 1, y * s + 1, s - 2, s - 2);
+    }
+    if (h.emoji) {
+      ctx.font = `${Math.floor(s * 0.7)}px sans-serif`;
+      ctx.textAlign = 'center';
+      ctx.textBaseline = 'middle';
+      ctx.fillText(h.emoji, (x + 0.5) * s, (y + 0.5) * s);
+    }
   }
   ctx.restore();
 }
@@ -196,12 +260,14 @@ function draw_bees() {
   ctx.save();
   ctx.strokeStyle = 'black';
   for (const { curr, _ } of bees.values()) {
-    const x = (curr.position.x + 0.5) * world.tile_size;
+    const x = (world.left(curr.position.x, curr.position.y) + 0.5) * world.tile_size;
     const y = (curr.position.y + 0.5) * world.tile_size;
-    ctx.fillStyle = `hsla(${curr.colour}, 100%, 50%, 0.7)`;
+    ctx.fillStyle = curr.colour;
     ctx.beginPath();
     ctx.arc(x + curr.jitter.x, y + curr.jitter.y, curr.radius, 0, 2 * Math.PI);
+    ctx.globalAlpha = 0.7;
     ctx.fill();
+    ctx.globalAlpha = 1.0;
     ctx.stroke();
   }
   ctx.restore();
@@ -229,6 +295,20 @@ function main(now) {
   }
 }
 
+document.getElementById('chat').onsubmit = e => {
+  e.preventDefault();
+  const name = document.getElementById('chat-name');
+  const msg = document.getElementById('chat-msg');
+  if (name.value && name.value !== name.dataset.sent) {
+    websocket.send(JSON.stringify({ type: 'register', name: name.value }));
+    name.dataset.sent = name.value;
+  }
+  if (msg.value) {
+    websocket.send(JSON.stringify({ type: 'chat', msg: msg.value }));
+    msg.value = '';
+  }
+};
+
 websocket.onopen = () => write('CONNECTED');
 websocket.onclose = () => write('DISCONNECTED');
 websocket.onerror = e => write('<span class="error">ERROR:</span> ' + e.data);
@@ -239,6 +319,9 @@ websocket.onmessage = e => {
     case 'registration':
       init(packet.world, packet.tick_rate);
       break;
+    case 'chunk':
+      add_chunk(packet);
+      break;
     case 'update':
       update(packet.data);
       if (last_tick === null) {
@@ -246,6 +329,37 @@ websocket.onmessage = e => {
         main(last_tick);
       }
       break;
+    case 'tick_schedule':
+      break;
+    case 'tick_rate':
+      ticks_per_update = (packet.tick_rate * 1000) / tick_length;
+      write(`<span>TICK RATE:</span> ${packet.tick_rate} seconds`);
+      break;
+
+    case 'event':
+      write(`<span>EVENT:</span> ${packet.kind.toUpperCase()}`
+        + (packet.kind === 'migration' ? ` of ${packet.birds} birds heading ${packet.direction}` : ` at (${packet.center.x}, ${packet.center.y})`));
+      break;
+
+    case 'chat':
+      write(`<span>CHAT (${packet.channel}):</span> ${escapeHtml(packet.name)}: ${escapeHtml(packet.msg)}`);
+      break;
+
+    case 'transfer':
+      write(`<span>TRANSFER:</span> Player ${packet.from} gave ${packet.amount} pollen to player ${packet.to}`);
+      break;
+
+    case 'rename':
+      write(`<span>RENAME:</span> Player ${packet.player} is now called ${escapeHtml(packet.name)}`);
+      break;
+
+    case 'roster':
+      for (const p of packet.players) {
+        const status = p.connected ? 'connected' : 'disconnected';
+        const latency = p.latency_ms === null ? '' : `, ${p.latency_ms}ms`;
+        write(`<span>PLAYER:</span> ${p.player} (${escapeHtml(p.name)}): ${p.score}, ${status}${latency}`);
+      }
+      break;
 
     case 'done': write('<span>Received "done"</span>'); break;
     case 'warning': write('<span class="warning">WARNING:</span> ' + packet.msg); break;